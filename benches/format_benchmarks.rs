@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
-use ebook_cli::formats::{EpubHandler, CbzHandler, TxtHandler, PdfHandler};
+use ebook_cli::formats::{EpubHandler, CbzHandler};
 use ebook_cli::traits::{EbookReader, EbookWriter};
 use ebook_cli::Metadata;
 use std::path::PathBuf;
@@ -0,0 +1,227 @@
+//! Convert every ebook in a directory as a resumable job: a JSON manifest records how each
+//! file went, so a re-run only converts what is not done yet
+
+use crate::conversion::{ConversionOptions, Converter};
+use crate::registry::FormatRegistry;
+use crate::{EbookError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Name of the manifest written to the output directory unless another path is given
+pub const DEFAULT_MANIFEST_NAME: &str = "batch-manifest.json";
+
+/// Where a file of a batch run stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+/// One file of a batch run; `error` says why it failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchFile {
+    /// Path of the source relative to the input directory
+    pub input: String,
+    /// Path of the converted file relative to the output directory
+    pub output: String,
+    pub status: BatchStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// State of a batch run, saved after every file so an interrupted run can be resumed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchManifest {
+    pub target_format: String,
+    pub files: Vec<BatchFile>,
+    /// Set once the run has gone through every file
+    pub finished: bool,
+}
+
+impl BatchManifest {
+    /// The manifest at `path`, or `None` if there is none yet
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map(Some)
+                .map_err(|e| EbookError::Parse(format!("batch manifest {}: {e}", path.display()))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self).unwrap() + "\n")?;
+        Ok(())
+    }
+
+    /// Files that failed in this run
+    pub fn failures(&self) -> Vec<&BatchFile> {
+        self.files.iter().filter(|file| file.status == BatchStatus::Failed).collect()
+    }
+}
+
+/// How a batch run picks and treats its files
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /// Include subdirectories of the input directory
+    pub recursive: bool,
+    /// Convert files the manifest already records as done
+    pub force: bool,
+    /// Record a file that fails to convert and carry on with the rest instead of stopping
+    pub keep_going: bool,
+    /// Manifest path; `batch-manifest.json` in the output directory by default
+    pub manifest_path: Option<PathBuf>,
+    /// Output file name template (see [`crate::utils::render_name_template`]); `{chapter}` is
+    /// the source file name without its extension. By default the source name with the target
+    /// extension.
+    pub name_template: Option<String>,
+    pub conversion: ConversionOptions,
+}
+
+/// What happened to one file of a run, as it is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchEvent {
+    Converted,
+    /// Done in an earlier run and its output is still there
+    Skipped,
+    Failed,
+}
+
+/// Convert every file in `input_dir` that can be converted to `target_format` into the same
+/// relative path under `output_dir`, named after the source or by `name_template`. Files of
+/// other types are skipped. Files the manifest
+/// records as done, whose output still exists, are not converted again unless `force` is set.
+/// The first file that fails to convert stops the run, unless `keep_going` is set; either
+/// way the manifest records it as failed.
+pub fn run_batch(
+    input_dir: &Path,
+    output_dir: &Path,
+    target_format: &str,
+    options: &BatchOptions,
+    mut on_file: impl FnMut(&BatchFile, BatchEvent),
+) -> Result<BatchManifest> {
+    let manifest_path = options.manifest_path.clone().unwrap_or_else(|| output_dir.join(DEFAULT_MANIFEST_NAME));
+    // A manifest left by a run to another format says nothing about this one
+    let previous = BatchManifest::load(&manifest_path)?.filter(|manifest| manifest.target_format == target_format);
+    let done_before = |input: &str| {
+        previous.as_ref().is_some_and(|manifest| {
+            manifest.files.iter().any(|file| file.input == input && file.status == BatchStatus::Done)
+        })
+    };
+
+    let mut manifest = BatchManifest { target_format: target_format.to_string(), files: Vec::new(), finished: false };
+    let mut taken = std::collections::HashSet::new();
+    for (idx, input) in batch_inputs(input_dir, target_format, options.recursive)?.into_iter().enumerate() {
+        let relative = input.strip_prefix(input_dir).unwrap_or(&input);
+        let output = match &options.name_template {
+            Some(template) => templated_output(&input, relative, idx, target_format, template, &mut taken)?,
+            None => relative.with_extension(target_format),
+        };
+        let input = relative.to_string_lossy().into_owned();
+        let status = if !options.force && done_before(&input) && output_dir.join(&output).exists() {
+            BatchStatus::Done
+        } else {
+            BatchStatus::Pending
+        };
+        manifest.files.push(BatchFile { input, output: output.to_string_lossy().into_owned(), status, error: None });
+    }
+    manifest.save(&manifest_path)?;
+
+    for idx in 0..manifest.files.len() {
+        let file = &manifest.files[idx];
+        if file.status == BatchStatus::Done {
+            on_file(file, BatchEvent::Skipped);
+            continue;
+        }
+
+        let (input, output) = (input_dir.join(&file.input), output_dir.join(&file.output));
+        let result = Converter::convert_with_options(&input, &output, target_format, &options.conversion, None);
+        let file = &mut manifest.files[idx];
+        match result {
+            Ok(()) => {
+                file.status = BatchStatus::Done;
+                on_file(file, BatchEvent::Converted);
+            }
+            Err(e) => {
+                file.status = BatchStatus::Failed;
+                file.error = Some(e.to_string().lines().map(str::trim).collect::<Vec<_>>().join(" "));
+                on_file(file, BatchEvent::Failed);
+                if !options.keep_going {
+                    manifest.save(&manifest_path)?;
+                    return Err(EbookError::InFile { path: input, source: Box::new(e) });
+                }
+            }
+        }
+        manifest.save(&manifest_path)?;
+    }
+
+    manifest.finished = true;
+    manifest.save(&manifest_path)?;
+    Ok(manifest)
+}
+
+/// Output path of the `idx`th (0-based) input from the name template, in the input's own
+/// subdirectory; a name an earlier input already took gets a `-2`, `-3`, ... suffix
+fn templated_output(
+    input: &Path,
+    relative: &Path,
+    idx: usize,
+    target_format: &str,
+    template: &str,
+    taken: &mut std::collections::HashSet<PathBuf>,
+) -> Result<PathBuf> {
+    use crate::utils::{render_name_template, safe_relative_path, suffixed_path, NameTemplateContext};
+
+    // Only open the book when the template needs its metadata; an unreadable one fails when converted
+    let metadata = if template.contains("{title") || template.contains("{author") {
+        FormatRegistry::default().open(input).and_then(|book| book.get_metadata()).ok()
+    } else {
+        None
+    };
+    let context = NameTemplateContext {
+        title: metadata.as_ref().and_then(|metadata| metadata.title.clone()),
+        author: metadata.as_ref().and_then(|metadata| metadata.all_authors().first().cloned()),
+        index: idx + 1,
+        ext: target_format.to_string(),
+        chapter: relative.file_stem().map(|stem| stem.to_string_lossy().into_owned()),
+    };
+    let name = safe_relative_path(&render_name_template(template, &context)?);
+    let output = relative.parent().unwrap_or(Path::new("")).join(name);
+    let mut candidate = output.clone();
+    let mut n = 1;
+    while taken.contains(&candidate) {
+        n += 1;
+        candidate = suffixed_path(&output, n);
+    }
+    taken.insert(candidate.clone());
+    Ok(candidate)
+}
+
+/// Files under `dir`, in path order, whose format converts to `target_format`
+fn batch_inputs(dir: &Path, target_format: &str, recursive: bool) -> Result<Vec<PathBuf>> {
+    let registry = FormatRegistry::default();
+    let walker = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(if recursive { usize::MAX } else { 1 })
+        .sort_by_file_name();
+
+    let mut inputs = Vec::new();
+    for dir_entry in walker {
+        let dir_entry = dir_entry.map_err(std::io::Error::from)?;
+        if !dir_entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(format) = registry.format_for_path(dir_entry.path()) else { continue };
+        if Converter::supported_targets(&format).contains(&target_format) {
+            inputs.push(dir_entry.into_path());
+        }
+    }
+    Ok(inputs)
+}
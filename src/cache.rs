@@ -0,0 +1,98 @@
+//! On-disk cache of conversion outputs, so re-converting an unchanged file is a copy
+
+use crate::{ConversionOptions, Converter, Result};
+use std::fs::File;
+use std::hash::{DefaultHasher, Hasher};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that turns the cache on and sets where it lives
+pub const CACHE_DIR_ENV: &str = "EBOOK_CACHE_DIR";
+
+/// Converted outputs stored by (input content, target format, options)
+#[derive(Debug, Clone)]
+pub struct ConversionCache {
+    dir: PathBuf,
+}
+
+impl ConversionCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `$EBOOK_CACHE_DIR` when set, else `ebook` under `$XDG_CACHE_HOME` or `~/.cache`
+    pub fn default_dir() -> PathBuf {
+        let non_empty = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+        non_empty(CACHE_DIR_ENV)
+            .or_else(|| non_empty("XDG_CACHE_HOME").map(|dir| dir.join("ebook")))
+            .or_else(|| non_empty("HOME").map(|home| home.join(".cache").join("ebook")))
+            .unwrap_or_else(|| std::env::temp_dir().join("ebook-cache"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Cache key for converting `input` to `target_format` with `options`. Any change to the
+    /// input bytes, the options or the tool version gives a different key.
+    pub fn key(input: &Path, target_format: &str, options: &ConversionOptions) -> Result<String> {
+        // Two differently seeded 64-bit hashes, so a stale hit needs a 128-bit collision
+        let mut hashers = [DefaultHasher::new(), DefaultHasher::new()];
+        for (seed, hasher) in hashers.iter_mut().enumerate() {
+            hasher.write_usize(seed);
+            hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+            hasher.write(target_format.as_bytes());
+            hasher.write(format!("{options:?}").as_bytes());
+        }
+
+        let mut file = File::open(input)?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            for hasher in &mut hashers {
+                hasher.write(&buffer[..read]);
+            }
+        }
+        Ok(format!("{:016x}{:016x}", hashers[0].finish(), hashers[1].finish()))
+    }
+
+    fn entry_path(&self, key: &str, target_format: &str) -> PathBuf {
+        self.dir.join(format!("{key}.{target_format}"))
+    }
+
+    /// Convert like `Converter::convert_with_options`, copying the output from the cache when
+    /// the same input was converted with the same options before. Returns whether it was a hit.
+    pub fn convert(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        target_format: &str,
+        options: &ConversionOptions,
+        progress_name: Option<String>,
+    ) -> Result<bool> {
+        let key = Self::key(input_path, target_format, options)?;
+        let entry = self.entry_path(&key, target_format);
+        if entry.is_file() {
+            log::debug!("Conversion cache hit: {entry:?}");
+            // A conversion would create the output directory; so must a cached copy
+            if let Some(parent) = output_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(&entry, output_path)?;
+            return Ok(true);
+        }
+
+        Converter::convert_with_options(input_path, output_path, target_format, options, progress_name)?;
+
+        // Copy under a temporary name and rename, so an interrupted run never leaves a partial entry
+        std::fs::create_dir_all(&self.dir)?;
+        let partial = self.dir.join(format!("{key}.{}.partial", std::process::id()));
+        std::fs::copy(output_path, &partial)?;
+        std::fs::rename(&partial, &entry)?;
+        log::debug!("Stored conversion in cache: {entry:?}");
+        Ok(false)
+    }
+}
@@ -0,0 +1,88 @@
+//! Metadata report for every ebook in a directory
+
+use crate::registry::FormatRegistry;
+use crate::Result;
+use serde::Serialize;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// One catalogued file; `error` is set (and the metadata empty) when it could not be read
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CatalogEntry {
+    /// Path relative to the catalogued directory
+    pub filename: String,
+    pub format: String,
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub language: Option<String>,
+    pub isbn: Option<String>,
+    pub error: Option<String>,
+}
+
+const CSV_HEADER: &str = "filename,format,title,authors,language,isbn,error";
+
+/// Read the metadata of every file in `dir` whose format the registry knows, in path order.
+/// Files of other types are skipped; a book that fails to open still gets an entry.
+pub fn build_catalog(dir: &Path, recursive: bool, registry: &FormatRegistry) -> Result<Vec<CatalogEntry>> {
+    let walker = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(if recursive { usize::MAX } else { 1 })
+        .sort_by_file_name();
+
+    let mut entries = Vec::new();
+    for dir_entry in walker {
+        let dir_entry = dir_entry.map_err(std::io::Error::from)?;
+        if !dir_entry.file_type().is_file() {
+            continue;
+        }
+        let path = dir_entry.path();
+        let Ok(format) = registry.format_for_path(path) else { continue };
+
+        let mut entry = CatalogEntry {
+            filename: path.strip_prefix(dir).unwrap_or(path).to_string_lossy().into_owned(),
+            format,
+            ..Default::default()
+        };
+        match registry.open(path).and_then(|handler| handler.get_metadata()) {
+            Ok(metadata) => {
+                entry.authors = metadata.all_authors();
+                entry.title = metadata.title;
+                entry.language = metadata.language;
+                entry.isbn = metadata.isbn;
+            }
+            // Keep each entry on one line; some errors carry a hint on a line of its own
+            Err(e) => entry.error = Some(e.to_string().lines().map(str::trim).collect::<Vec<_>>().join(" ")),
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Render entries as CSV with a header row; multiple authors are joined with `; `
+pub fn to_csv(entries: &[CatalogEntry]) -> String {
+    let mut csv = format!("{CSV_HEADER}\n");
+    for entry in entries {
+        let fields = [
+            entry.filename.as_str(),
+            entry.format.as_str(),
+            entry.title.as_deref().unwrap_or(""),
+            &entry.authors.join("; "),
+            entry.language.as_deref().unwrap_or(""),
+            entry.isbn.as_deref().unwrap_or(""),
+            entry.error.as_deref().unwrap_or(""),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quote a field if it contains a separator, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -1,8 +1,308 @@
-use crate::{EbookError, Result, Progress};
+use crate::{EbookError, Metadata, Result, Progress};
 use crate::traits::{EbookReader, EbookWriter};
-use crate::formats::{EpubHandler, TxtHandler, MobiHandler, Fb2Handler, PdfHandler};
+use crate::formats::{EpubHandler, EpubVersion, TxtHandler, MobiHandler, AzwHandler, Fb2Handler, PdfHandler, CbzHandler, NcxMode, PageOrder, PageProgression, TextEncoding};
+use crate::image_optimizer::OptimizationOptions;
 use std::path::Path;
 
+/// Options controlling how a conversion is performed
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOptions {
+    /// Prepend a `Title:`/`Author:` header block when writing TXT output
+    pub include_metadata_header: bool,
+    /// Optimize (e.g. downscale) images before writing image-based output
+    pub image_optimization: Option<OptimizationOptions>,
+    /// Separator for joining multiple authors in flat formats (TXT header, PDF Info)
+    pub author_separator: Option<String>,
+    /// Join words hyphenated across line breaks in extracted PDF text
+    pub dehyphenate: bool,
+    /// Mark where images are drawn in extracted PDF text with `[Figure: page N, WxH]` lines
+    pub figure_markers: bool,
+    /// Split heading-only sources into chapters at `Chapter N` lines so the output gets a TOC
+    pub toc_from_headings: bool,
+    /// Reading order of CBZ source pages
+    pub page_order: PageOrder,
+    /// Write EPUB output from a CBZ with a right-to-left `page-progression-direction` (manga)
+    pub right_to_left: bool,
+    /// Title template for generated chapters (see `utils::render_chapter_title`);
+    /// `Chapter {n}` when unset
+    pub chapter_title_template: Option<String>,
+    /// Open EPUB and PDF output with a generated title page, unless the source already opens
+    /// with its title or a cover (comics always open with their cover)
+    pub add_title_page: bool,
+    /// Write a sample edition: only the first N chapters (pages for PDF and CBZ sources),
+    /// with "(Sample)" appended to the title
+    pub sample: Option<usize>,
+    /// OCR image-only PDF pages and CBZ pages with this Tesseract language (e.g. `eng`);
+    /// needs a build with the `ocr` feature
+    pub ocr_language: Option<String>,
+    /// Split TXT chapters larger than this many bytes into `Title (part k)` chapters at
+    /// paragraph breaks
+    pub max_chapter_bytes: Option<usize>,
+    /// Navigation documents of EPUB output; `NcxMode::Only` writes EPUB 2
+    pub ncx_mode: NcxMode,
+    /// Character encoding of TXT output
+    pub output_encoding: TextEncoding,
+    /// Format of the input, overriding detection from its extension
+    pub source_format: Option<String>,
+    /// Title replacing the source's
+    pub title: Option<String>,
+    /// Authors replacing the source's, when not empty
+    pub authors: Vec<String>,
+    /// Language replacing the source's, e.g. `en`
+    pub language: Option<String>,
+    /// Detect the language from the text when neither the source nor the options give one
+    pub detect_language: bool,
+}
+
+impl ConversionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_metadata_header(mut self, include: bool) -> Self {
+        self.include_metadata_header = include;
+        self
+    }
+
+    pub fn with_image_optimization(mut self, options: OptimizationOptions) -> Self {
+        self.image_optimization = Some(options);
+        self
+    }
+
+    pub fn with_author_separator(mut self, separator: impl Into<String>) -> Self {
+        self.author_separator = Some(separator.into());
+        self
+    }
+
+    pub fn with_source_format(mut self, format: impl Into<String>) -> Self {
+        self.source_format = Some(format.into());
+        self
+    }
+
+    pub fn with_dehyphenation(mut self, dehyphenate: bool) -> Self {
+        self.dehyphenate = dehyphenate;
+        self
+    }
+
+    pub fn with_figure_markers(mut self, enabled: bool) -> Self {
+        self.figure_markers = enabled;
+        self
+    }
+
+    pub fn with_toc_from_headings(mut self, enabled: bool) -> Self {
+        self.toc_from_headings = enabled;
+        self
+    }
+
+    pub fn with_page_order(mut self, page_order: PageOrder) -> Self {
+        self.page_order = page_order;
+        self
+    }
+
+    pub fn with_right_to_left(mut self, right_to_left: bool) -> Self {
+        self.right_to_left = right_to_left;
+        self
+    }
+
+    pub fn with_chapter_title_template(mut self, template: impl Into<String>) -> Self {
+        self.chapter_title_template = Some(template.into());
+        self
+    }
+
+    pub fn with_title_page(mut self, add: bool) -> Self {
+        self.add_title_page = add;
+        self
+    }
+
+    pub fn with_sample(mut self, chapters: usize) -> Self {
+        self.sample = Some(chapters);
+        self
+    }
+
+    pub fn with_ocr(mut self, language: impl Into<String>) -> Self {
+        self.ocr_language = Some(language.into());
+        self
+    }
+
+    pub fn with_max_chapter_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_chapter_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_ncx_mode(mut self, mode: NcxMode) -> Self {
+        self.ncx_mode = mode;
+        self
+    }
+
+    pub fn with_output_encoding(mut self, encoding: TextEncoding) -> Self {
+        self.output_encoding = encoding;
+        self
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_authors<I, S>(mut self, authors: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.authors = authors.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    pub fn with_language_detection(mut self, detect: bool) -> Self {
+        self.detect_language = detect;
+        self
+    }
+
+    /// Apply the TXT output settings to the handler writing it
+    fn configure_txt(&self, handler: &mut TxtHandler) {
+        handler.set_include_metadata_header(self.include_metadata_header);
+        if let Some(separator) = &self.author_separator {
+            handler.set_author_separator(separator.clone());
+        }
+        handler.set_output_encoding(self.output_encoding);
+    }
+
+    /// Apply the EPUB output settings to the handler writing it
+    fn configure_epub(&self, handler: &mut EpubHandler) {
+        handler.set_ncx_mode(self.ncx_mode);
+        if self.ncx_mode == NcxMode::Only {
+            handler.set_epub_version(EpubVersion::V2);
+        }
+    }
+
+    /// `(title, text)` chapters with each one over `max_chapter_bytes` split at paragraph
+    /// breaks into parts titled `Title (part k)`
+    fn cap_chapter_size(&self, chapters: Vec<(String, String)>) -> Vec<(String, String)> {
+        let Some(max_bytes) = self.max_chapter_bytes else { return chapters };
+        let mut capped = Vec::new();
+        for (title, text) in chapters {
+            let parts = crate::utils::split_at_paragraphs(&text, max_bytes);
+            if parts.len() <= 1 {
+                capped.push((title, text));
+                continue;
+            }
+            for (k, part) in parts.into_iter().enumerate() {
+                capped.push((format!("{title} (part {})", k + 1), part));
+            }
+        }
+        capped
+    }
+
+    /// How many of a book's `total` chapters or pages go into the output; all of them, with
+    /// a warning, when the book is shorter than the sample
+    fn sample_len(&self, total: usize, unit: &str) -> usize {
+        match self.sample {
+            Some(n) if n > total => {
+                log::warn!("Sample of {n} {unit} covers the whole book ({total} {unit})");
+                total
+            }
+            Some(n) => n,
+            None => total,
+        }
+    }
+
+    /// The first `sample` chapters or pages of `items`
+    fn sample<T>(&self, mut items: Vec<T>, unit: &str) -> Vec<T> {
+        items.truncate(self.sample_len(items.len(), unit));
+        items
+    }
+
+    /// Text of the first `sample` chapters (pages for PDF) of `book` joined by `separator`,
+    /// or its whole `content` when not sampling
+    fn sample_content(&self, book: &(impl EbookReader + ?Sized), content: String, separator: &str, unit: &str) -> Result<String> {
+        let Some(n) = self.sample else { return Ok(content) };
+        let mut chapters = Vec::new();
+        while chapters.len() <= n {
+            match book.get_chapter(chapters.len())? {
+                Some(chapter) => chapters.push(chapter),
+                None => break,
+            }
+        }
+        Ok(self.sample(chapters, unit).join(separator))
+    }
+
+    /// Text of the first `sample` chapter-marker sections of TXT `content`
+    fn sample_text(&self, content: String) -> String {
+        if self.sample.is_none() {
+            return content;
+        }
+        let sections: Vec<&str> = content.split(TXT_CHAPTER_MARKER).collect();
+        self.sample(sections, "chapters").join(TXT_CHAPTER_MARKER)
+    }
+
+    /// The metadata of `book` with the title, authors and language given in the options in
+    /// place of its own, for everything generated from it (title pages included). With
+    /// language detection on, a book still without a language gets the one its text is
+    /// confidently written in.
+    fn source_metadata(&self, book: &(impl EbookReader + ?Sized)) -> Result<Metadata> {
+        let mut metadata = book.get_metadata()?;
+        if let Some(title) = &self.title {
+            metadata.title = Some(title.clone());
+        }
+        if !self.authors.is_empty() {
+            metadata = metadata.with_authors(self.authors.iter().cloned());
+        }
+        if let Some(language) = &self.language {
+            metadata.language = Some(language.clone());
+        }
+        if self.detect_language && metadata.language.is_none() {
+            metadata.detect_language(&book.get_content()?);
+        }
+        Ok(metadata)
+    }
+
+    /// Metadata for the output; a sample edition gets "(Sample)" after its title
+    fn output_metadata(&self, mut metadata: Metadata) -> Metadata {
+        if self.sample.is_some() {
+            let title = metadata.title.as_deref().unwrap_or("Untitled");
+            metadata.title = Some(format!("{title} (Sample)"));
+        }
+        metadata
+    }
+
+    /// Title for the 1-based chapter `n` of a source that has no heading for it
+    fn chapter_title(&self, n: usize) -> Result<String> {
+        let template = self.chapter_title_template.as_deref().unwrap_or("Chapter {n}");
+        crate::utils::render_chapter_title(template, n)
+    }
+}
+
+/// Separator between chapters in TXT sources
+const TXT_CHAPTER_MARKER: &str = "\n\n---\n\n";
+
+/// Canonical lower-case name of a format given by the user, e.g. `AZW3` as `azw`
+fn normalized_format(format: &str) -> String {
+    crate::utils::format_name(format).map_or_else(|| format.to_lowercase(), String::from)
+}
+
+/// Every supported `(source, target)` conversion, the single source of truth for
+/// dispatch and for the listings shown by the CLI and MCP server
+pub const SUPPORTED_CONVERSIONS: &[(&str, &str)] = &[
+    ("txt", "epub"),
+    ("txt", "pdf"),
+    ("txt", "mobi"),
+    ("txt", "fb2"),
+    ("epub", "txt"),
+    ("epub", "pdf"),
+    ("epub", "epub"),
+    ("mobi", "txt"),
+    ("mobi", "epub"),
+    ("azw", "epub"),
+    ("fb2", "txt"),
+    ("pdf", "txt"),
+    ("cbz", "epub"),
+];
+
 /// Conversion utility for converting between ebook formats
 pub struct Converter {
     // Placeholder for future conversion options
@@ -29,7 +329,51 @@ impl Converter {
         target_format: &str,
         progress_name: Option<String>,
     ) -> Result<()> {
-        let input_format = crate::utils::detect_format(input_path)?;
+        Self::convert_with_options(input_path, output_path, target_format, &ConversionOptions::default(), progress_name)
+    }
+
+    /// Target formats a source format can be converted to
+    pub fn supported_targets(source_format: &str) -> Vec<&'static str> {
+        let source_format = source_format.to_lowercase();
+        SUPPORTED_CONVERSIONS
+            .iter()
+            .filter(|(source, _)| *source == source_format)
+            .map(|(_, target)| *target)
+            .collect()
+    }
+
+    /// Fail unless `input_format` converts to `target_format`
+    fn check_supported(input_format: &str, target_format: &str) -> Result<()> {
+        if Self::supported_targets(input_format).contains(&target_format) {
+            Ok(())
+        } else {
+            Err(EbookError::NotSupported(format!(
+                "Conversion from {input_format} to {target_format} is not supported"
+            )))
+        }
+    }
+
+    /// Every format that at least one conversion produces, sorted
+    pub fn all_targets() -> Vec<&'static str> {
+        let mut targets: Vec<&'static str> = SUPPORTED_CONVERSIONS.iter().map(|(_, target)| *target).collect();
+        targets.sort_unstable();
+        targets.dedup();
+        targets
+    }
+
+    /// Convert an ebook with explicit conversion options and optional progress reporting
+    pub fn convert_with_options(
+        input_path: &Path,
+        output_path: &Path,
+        target_format: &str,
+        options: &ConversionOptions,
+        progress_name: Option<String>,
+    ) -> Result<()> {
+        let input_format = match &options.source_format {
+            Some(format) => normalized_format(format),
+            None => crate::utils::detect_format(input_path)?,
+        };
+        Self::check_supported(&input_format, target_format)?;
         let progress = progress_name.map(|name| Progress::new(name, 3));
 
         if let Some(ref p) = progress {
@@ -40,60 +384,84 @@ impl Converter {
         let result = match (input_format.as_str(), target_format) {
             ("txt", "epub") => {
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting to EPUB"); }
-                let r = Self::txt_to_epub(input_path, output_path);
+                let r = Self::txt_to_epub(input_path, output_path, options, progress.as_ref());
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing EPUB"); }
                 r
             }
             ("txt", "pdf") => {
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting to PDF"); }
-                let r = Self::txt_to_pdf(input_path, output_path);
+                let r = Self::txt_to_pdf(input_path, output_path, options, progress.as_ref());
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing PDF"); }
                 r
             }
             ("txt", "mobi") => {
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting to MOBI"); }
-                let r = Self::txt_to_mobi(input_path, output_path);
+                let r = Self::txt_to_mobi(input_path, output_path, options, progress.as_ref());
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing MOBI"); }
                 r
             }
             ("epub", "txt") => {
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting to TXT"); }
-                let r = Self::epub_to_txt(input_path, output_path);
+                let r = Self::epub_to_txt(input_path, output_path, options);
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing TXT"); }
                 r
             }
             ("epub", "pdf") => {
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting EPUB to PDF"); }
-                let r = Self::epub_to_pdf(input_path, output_path);
+                let r = Self::epub_to_pdf(input_path, output_path, options);
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing PDF"); }
                 r
             }
+            ("epub", "epub") => {
+                if let Some(ref p) = progress { p.increment(1); p.print_with_message("Rewriting EPUB"); }
+                let r = Self::epub_to_epub(input_path, output_path, options);
+                if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing EPUB"); }
+                r
+            }
             ("mobi", "txt") => {
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting MOBI to TXT"); }
-                let r = Self::mobi_to_txt(input_path, output_path);
+                let r = Self::mobi_to_txt(input_path, output_path, options);
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing TXT"); }
                 r
             }
+            ("mobi", "epub") => {
+                if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting MOBI to EPUB"); }
+                let r = Self::mobi_to_epub(input_path, output_path, options);
+                if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing EPUB"); }
+                r
+            }
+            ("azw", "epub") => {
+                if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting AZW to EPUB"); }
+                let r = Self::azw_to_epub(input_path, output_path, options);
+                if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing EPUB"); }
+                r
+            }
             ("fb2", "txt") => {
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting FB2 to TXT"); }
-                let r = Self::fb2_to_txt(input_path, output_path);
+                let r = Self::fb2_to_txt(input_path, output_path, options);
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing TXT"); }
                 r
             }
             ("pdf", "txt") => {
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting PDF to TXT"); }
-                let r = Self::pdf_to_txt(input_path, output_path);
+                let r = Self::pdf_to_txt(input_path, output_path, options);
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing TXT"); }
                 r
             }
+            ("cbz", "epub") => {
+                if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting CBZ to EPUB"); }
+                let r = Self::cbz_to_epub(input_path, output_path, options);
+                if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing EPUB"); }
+                r
+            }
             ("txt", "fb2") => {
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Converting to FB2"); }
-                let r = Self::txt_to_fb2(input_path, output_path);
+                let r = Self::txt_to_fb2(input_path, output_path, options, progress.as_ref());
                 if let Some(ref p) = progress { p.increment(1); p.print_with_message("Writing FB2"); }
                 r
             }
             _ => Err(EbookError::NotSupported(format!(
-                "Conversion from {input_format} to {target_format} is not supported"
+                "Conversion from {input_format} to {target_format} is listed but not implemented"
             ))),
         };
 
@@ -104,194 +472,588 @@ impl Converter {
         result
     }
 
-    fn txt_to_epub(input_path: &Path, output_path: &Path) -> Result<()> {
+    /// Write an already-read `source` book as `target_format` (`txt` or `pdf`), so pipelines
+    /// that convert one book to several formats read it once. `options.source_format` must name
+    /// the format the source was read from. The output is what converting the source file
+    /// would write; checks that need the concrete format, such as refusing fixed-layout EPUBs,
+    /// are left to the caller.
+    pub fn convert_handler(
+        source: &dyn EbookReader,
+        output_path: &Path,
+        target_format: &str,
+        options: &ConversionOptions,
+    ) -> Result<()> {
+        let source_format = options.source_format.as_deref().map(normalized_format).ok_or_else(|| {
+            EbookError::NotSupported("Converting a loaded book needs its source format".to_string())
+        })?;
+        Self::check_supported(&source_format, target_format)?;
+        let leg = Leg::new(&source_format, target_format);
+        match (source_format.as_str(), target_format) {
+            ("pdf", "txt") => Self::write_txt(source, &leg, output_path, options, "\n\n", "pages"),
+            ("mobi", "txt") => Self::write_txt(source, &leg, output_path, options, TXT_CHAPTER_MARKER, "chapters"),
+            (_, "txt") => Self::write_txt(source, &leg, output_path, options, "\n", "chapters"),
+            (_, "pdf") => Self::write_pdf(source, &leg, output_path, options),
+            _ => Err(EbookError::NotSupported(format!(
+                "Conversion of a loaded book to {target_format} is not supported"
+            ))),
+        }
+    }
+
+    /// Write the text and metadata of a read `source` as TXT; a sample joins its first
+    /// chapters (pages for PDF) with `separator`
+    fn write_txt(
+        source: &(impl EbookReader + ?Sized),
+        leg: &Leg,
+        output_path: &Path,
+        options: &ConversionOptions,
+        separator: &str,
+        unit: &str,
+    ) -> Result<()> {
         // Ensure parent directory exists
         if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            leg.write(std::fs::create_dir_all(parent))?;
         }
 
+        let content = leg.read(source.get_content())?;
+        let content = leg.read(options.sample_content(source, content, separator, unit))?;
+        let metadata = leg.read(options.source_metadata(source))?;
+
         let mut txt_handler = TxtHandler::new();
-        txt_handler.read_from_file(input_path)?;
+        options.configure_txt(&mut txt_handler);
+        leg.transform(txt_handler.set_metadata(options.output_metadata(metadata)))?;
+        leg.transform(txt_handler.set_content(&content))?;
+        leg.write(txt_handler.write_to_file(output_path))?;
+        Ok(())
+    }
+
+    /// Write the text and metadata of a read `source` as PDF; the title page is only added
+    /// when the source has no cover or title page of its own
+    fn write_pdf(source: &(impl EbookReader + ?Sized), leg: &Leg, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+        // Ensure parent directory exists
+        if let Some(parent) = output_path.parent() {
+            leg.write(std::fs::create_dir_all(parent))?;
+        }
+
+        let content = leg.read(source.get_content())?;
+        let content = leg.read(options.sample_content(source, content, "\n", "chapters"))?;
+        let metadata = leg.read(options.source_metadata(source))?;
+        let has_front_page = leg.read(has_front_page(source))?;
+
+        let mut pdf_handler = PdfHandler::new();
+        if let Some(separator) = &options.author_separator {
+            pdf_handler.set_author_separator(separator.clone());
+        }
+        if options.add_title_page && !has_front_page && !opens_with_title(&metadata, &content) {
+            pdf_handler.set_title_page(title_page_lines(&metadata));
+        }
+        leg.transform(pdf_handler.set_metadata(options.output_metadata(metadata)))?;
+        leg.transform(pdf_handler.set_content(&content))?;
+        leg.write(pdf_handler.write_to_file(output_path))?;
+        Ok(())
+    }
+
+    fn txt_to_epub(input_path: &Path, output_path: &Path, options: &ConversionOptions, progress: Option<&Progress>) -> Result<()> {
+        let leg = Leg::new("txt", "epub");
+        // Ensure parent directory exists
+        if let Some(parent) = output_path.parent() {
+            leg.write(std::fs::create_dir_all(parent))?;
+        }
+
+        let txt_handler = leg.read(read_txt(input_path, progress))?;
 
-        let content = txt_handler.get_content()?;
-        let metadata = txt_handler.get_metadata()?;
+        let content = leg.read(txt_handler.get_content())?;
+        let metadata = leg.read(options.source_metadata(&txt_handler))?;
 
         let mut epub_handler = EpubHandler::new();
-        epub_handler.set_metadata(metadata)?;
-        epub_handler.set_content(&content)?;
+        if options.add_title_page && !opens_with_title(&metadata, &content) {
+            leg.transform(epub_handler.add_chapter("Title Page", &title_page_xhtml(&metadata, None)))?;
+        }
+        leg.transform(epub_handler.set_metadata(options.output_metadata(metadata)))?;
+        leg.transform(epub_handler.set_content(&content))?;
 
         // Split content into chapters
-        let chapters: Vec<&str> = content.split("\n\n---\n\n")
+        let chapters: Vec<&str> = content.split(TXT_CHAPTER_MARKER)
             .map(|s| s.trim())
             .filter(|s| !s.is_empty())
             .collect();
 
-        if chapters.is_empty() {
+        let sections = if options.toc_from_headings && chapters.len() <= 1 {
+            crate::utils::split_at_headings(&content)
+        } else {
+            Vec::new()
+        };
+
+        let titled: Vec<(String, String)> = if !sections.is_empty() {
+            // No chapter markers: one chapter per heading, so the nav lists the headings
+            options.sample(sections, "chapters")
+        } else if chapters.is_empty() {
             // If no chapter markers, treat entire content as one chapter
-            epub_handler.add_chapter("Chapter 1", &content)?;
+            vec![(leg.transform(options.chapter_title(1))?, content.clone())]
         } else {
-            for (idx, chapter) in chapters.iter().enumerate() {
-                epub_handler.add_chapter(&format!("Chapter {}", idx + 1), chapter)?;
+            let mut titled = Vec::new();
+            for (idx, chapter) in options.sample(chapters, "chapters").iter().enumerate() {
+                // A chapter that opens with its own heading keeps it
+                let heading = chapter.lines().next().filter(|line| crate::utils::is_heading_line(line));
+                let title = match heading {
+                    Some(heading) => heading.trim().to_string(),
+                    None => leg.transform(options.chapter_title(idx + 1))?,
+                };
+                titled.push((title, chapter.to_string()));
             }
+            titled
+        };
+
+        for (title, chapter) in options.cap_chapter_size(titled) {
+            leg.transform(epub_handler.add_chapter(&title, &chapter))?;
         }
 
-        epub_handler.write_to_file(output_path)?;
+        options.configure_epub(&mut epub_handler);
+        leg.write(epub_handler.write_to_file(output_path))?;
         Ok(())
     }
 
-    fn txt_to_pdf(input_path: &Path, output_path: &Path) -> Result<()> {
+    fn txt_to_pdf(input_path: &Path, output_path: &Path, options: &ConversionOptions, progress: Option<&Progress>) -> Result<()> {
+        let leg = Leg::new("txt", "pdf");
         // Ensure parent directory exists
         if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            leg.write(std::fs::create_dir_all(parent))?;
         }
 
-        let mut txt_handler = TxtHandler::new();
-        txt_handler.read_from_file(input_path)?;
+        let txt_handler = leg.read(read_txt(input_path, progress))?;
 
-        let content = txt_handler.get_content()?;
-        let metadata = txt_handler.get_metadata()?;
+        let content = options.sample_text(leg.read(txt_handler.get_content())?);
+        let metadata = leg.read(options.source_metadata(&txt_handler))?;
 
         let mut pdf_handler = PdfHandler::new();
-        pdf_handler.set_metadata(metadata)?;
-        pdf_handler.set_content(&content)?;
-        pdf_handler.write_to_file(output_path)?;
+        if let Some(separator) = &options.author_separator {
+            pdf_handler.set_author_separator(separator.clone());
+        }
+        if options.add_title_page && !opens_with_title(&metadata, &content) {
+            pdf_handler.set_title_page(title_page_lines(&metadata));
+        }
+        leg.transform(pdf_handler.set_metadata(options.output_metadata(metadata)))?;
+        let sections = if options.toc_from_headings {
+            crate::utils::split_at_headings(&content)
+        } else {
+            Vec::new()
+        };
+        if sections.is_empty() {
+            leg.transform(pdf_handler.set_content(&content))?;
+        } else {
+            // Each heading becomes an outline entry
+            for (title, section) in &sections {
+                leg.transform(pdf_handler.add_chapter(title, section))?;
+            }
+        }
+        leg.write(pdf_handler.write_to_file(output_path))?;
         Ok(())
     }
 
-    fn txt_to_mobi(input_path: &Path, output_path: &Path) -> Result<()> {
+    fn txt_to_mobi(input_path: &Path, output_path: &Path, options: &ConversionOptions, progress: Option<&Progress>) -> Result<()> {
+        let leg = Leg::new("txt", "mobi");
         // Ensure parent directory exists
         if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            leg.write(std::fs::create_dir_all(parent))?;
         }
 
-        let mut txt_handler = TxtHandler::new();
-        txt_handler.read_from_file(input_path)?;
+        let txt_handler = leg.read(read_txt(input_path, progress))?;
 
-        let content = txt_handler.get_content()?;
-        let metadata = txt_handler.get_metadata()?;
+        let content = options.sample_text(leg.read(txt_handler.get_content())?);
+        let metadata = leg.read(options.source_metadata(&txt_handler))?;
 
         let mut mobi_handler = MobiHandler::new();
-        mobi_handler.set_metadata(metadata)?;
-        mobi_handler.set_content(&content)?;
-        mobi_handler.write_to_file(output_path)?;
+        leg.transform(mobi_handler.set_metadata(options.output_metadata(metadata)))?;
+        leg.transform(mobi_handler.set_content(&content))?;
+        leg.write(mobi_handler.write_to_file(output_path))?;
+        Ok(())
+    }
+
+    /// Reflowing a fixed-layout book loses its page geometry, so refuse rather than emit a mangled copy
+    fn ensure_reflowable(epub_handler: &EpubHandler, target: &str) -> Result<()> {
+        if epub_handler.is_fixed_layout() {
+            return Err(EbookError::NotSupported(format!(
+                "source is a fixed-layout EPUB (rendition:layout pre-paginated); converting to {target} would reflow its pages"
+            )));
+        }
         Ok(())
     }
 
-    fn epub_to_txt(input_path: &Path, output_path: &Path) -> Result<()> {
+    fn epub_to_txt(input_path: &Path, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+        let leg = Leg::new("epub", "txt");
         // Ensure parent directory exists
         if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            leg.write(std::fs::create_dir_all(parent))?;
         }
 
         let mut epub_handler = EpubHandler::new();
-        epub_handler.read_from_file(input_path)?;
+        leg.read(epub_handler.read_from_file(input_path))?;
+        leg.transform(Self::ensure_reflowable(&epub_handler, "TXT"))?;
+        Self::write_txt(&epub_handler, &leg, output_path, options, "\n", "chapters")
+    }
 
-        let content = epub_handler.get_content()?;
-        let metadata = epub_handler.get_metadata()?;
+    fn epub_to_pdf(input_path: &Path, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+        let leg = Leg::new("epub", "pdf");
+        // Ensure parent directory exists
+        if let Some(parent) = output_path.parent() {
+            leg.write(std::fs::create_dir_all(parent))?;
+        }
 
-        let mut txt_handler = TxtHandler::new();
-        txt_handler.set_metadata(metadata)?;
-        txt_handler.set_content(&content)?;
-        txt_handler.write_to_file(output_path)?;
-        Ok(())
+        let mut epub_handler = EpubHandler::new();
+        leg.read(epub_handler.read_from_file(input_path))?;
+        leg.transform(Self::ensure_reflowable(&epub_handler, "PDF"))?;
+        Self::write_pdf(&epub_handler, &leg, output_path, options)
     }
 
-    fn epub_to_pdf(input_path: &Path, output_path: &Path) -> Result<()> {
+    /// Rewrite an EPUB, e.g. as a sample edition of its first chapters
+    fn epub_to_epub(input_path: &Path, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+        let leg = Leg::new("epub", "epub");
         // Ensure parent directory exists
         if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            leg.write(std::fs::create_dir_all(parent))?;
         }
 
         let mut epub_handler = EpubHandler::new();
-        epub_handler.read_from_file(input_path)?;
+        leg.read(epub_handler.read_from_file(input_path))?;
 
-        let content = epub_handler.get_content()?;
-        let metadata = epub_handler.get_metadata()?;
+        let mut chapter_count = 0;
+        while leg.read(epub_handler.get_chapter(chapter_count))?.is_some() {
+            chapter_count += 1;
+        }
+        epub_handler.truncate_chapters(options.sample_len(chapter_count, "chapters"));
 
-        let mut pdf_handler = PdfHandler::new();
-        pdf_handler.set_metadata(metadata)?;
-        pdf_handler.set_content(&content)?;
-        pdf_handler.write_to_file(output_path)?;
+        let metadata = leg.read(options.source_metadata(&epub_handler))?;
+        leg.transform(epub_handler.set_metadata(options.output_metadata(metadata)))?;
+        options.configure_epub(&mut epub_handler);
+        leg.write(epub_handler.write_to_file(output_path))?;
         Ok(())
     }
 
-    fn mobi_to_txt(input_path: &Path, output_path: &Path) -> Result<()> {
+    fn mobi_to_txt(input_path: &Path, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+        let leg = Leg::new("mobi", "txt");
         // Ensure parent directory exists
         if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            leg.write(std::fs::create_dir_all(parent))?;
         }
 
         let mut mobi_handler = MobiHandler::new();
-        mobi_handler.read_from_file(input_path)?;
+        leg.read(mobi_handler.read_from_file(input_path))?;
+        Self::write_txt(&mobi_handler, &leg, output_path, options, TXT_CHAPTER_MARKER, "chapters")
+    }
 
-        let content = mobi_handler.get_content()?;
-        let metadata = mobi_handler.get_metadata()?;
+    fn mobi_to_epub(input_path: &Path, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+        Self::kindle_to_epub(MobiHandler::new(), &Leg::new("mobi", "epub"), input_path, output_path, options)
+    }
 
-        let mut txt_handler = TxtHandler::new();
-        txt_handler.set_metadata(metadata)?;
-        txt_handler.set_content(&content)?;
-        txt_handler.write_to_file(output_path)?;
-        Ok(())
+    fn azw_to_epub(input_path: &Path, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+        Self::kindle_to_epub(AzwHandler::new(), &Leg::new("azw", "epub"), input_path, output_path, options)
     }
 
-    fn fb2_to_txt(input_path: &Path, output_path: &Path) -> Result<()> {
+    /// Rebuild a MOBI or AZW book as an EPUB with one chapter per page-break section, keeping
+    /// its metadata and embedded images (the EXTH cover stays the cover)
+    fn kindle_to_epub(
+        mut handler: impl EbookReader,
+        leg: &Leg,
+        input_path: &Path,
+        output_path: &Path,
+        options: &ConversionOptions,
+    ) -> Result<()> {
         // Ensure parent directory exists
         if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            leg.write(std::fs::create_dir_all(parent))?;
         }
 
-        let mut fb2_handler = Fb2Handler::new();
-        fb2_handler.read_from_file(input_path)?;
+        leg.read(handler.read_from_file(input_path))?;
 
-        let content = fb2_handler.get_content()?;
-        let metadata = fb2_handler.get_metadata()?;
+        let content = leg.read(handler.get_content())?;
+        let metadata = leg.read(options.source_metadata(&handler))?;
+        let images = leg.read(handler.extract_images())?;
+        let mut sections = Vec::new();
+        while let Some(section) = leg.read(handler.get_chapter(sections.len()))? {
+            sections.push(section);
+        }
+        let sections = options.sample(sections, "chapters");
 
-        let mut txt_handler = TxtHandler::new();
-        txt_handler.set_metadata(metadata)?;
-        txt_handler.set_content(&content)?;
-        txt_handler.write_to_file(output_path)?;
+        let mut epub_handler = EpubHandler::new();
+        if options.add_title_page && !sections.first().is_some_and(|first| opens_with_title(&metadata, first)) {
+            let cover = metadata.cover_image_path.as_deref()
+                .filter(|cover| images.iter().any(|image| image.name == *cover));
+            leg.transform(epub_handler.add_chapter("Title Page", &title_page_xhtml(&metadata, cover)))?;
+        }
+        leg.transform(epub_handler.set_metadata(options.output_metadata(metadata)))?;
+        leg.transform(epub_handler.set_content(&content))?;
+
+        for image in images {
+            leg.transform(epub_handler.add_image(&image.name, image.data))?;
+        }
+
+        for (idx, section) in sections.iter().enumerate() {
+            // A section that opens with its own heading keeps it
+            let heading = section.lines().next().filter(|line| crate::utils::is_heading_line(line));
+            let title = match heading {
+                Some(heading) => heading.trim().to_string(),
+                None => leg.transform(options.chapter_title(idx + 1))?,
+            };
+            leg.transform(epub_handler.add_chapter(&title, &chapter_xhtml(&title, section)))?;
+        }
+
+        options.configure_epub(&mut epub_handler);
+        leg.write(epub_handler.write_to_file(output_path))?;
         Ok(())
     }
 
-    fn pdf_to_txt(input_path: &Path, output_path: &Path) -> Result<()> {
+    fn fb2_to_txt(input_path: &Path, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+        let leg = Leg::new("fb2", "txt");
         // Ensure parent directory exists
         if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            leg.write(std::fs::create_dir_all(parent))?;
+        }
+
+        let mut fb2_handler = Fb2Handler::new();
+        leg.read(fb2_handler.read_from_file(input_path))?;
+        Self::write_txt(&fb2_handler, &leg, output_path, options, "\n", "chapters")
+    }
+
+    fn pdf_to_txt(input_path: &Path, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+        let leg = Leg::new("pdf", "txt");
+        // Ensure parent directory exists
+        if let Some(parent) = output_path.parent() {
+            leg.write(std::fs::create_dir_all(parent))?;
         }
 
         let mut pdf_handler = PdfHandler::new();
-        pdf_handler.read_from_file(input_path)?;
+        if let Some(separator) = &options.author_separator {
+            pdf_handler.set_author_separator(separator.clone());
+        }
+        pdf_handler.set_dehyphenate(options.dehyphenate);
+        pdf_handler.set_figure_markers(options.figure_markers);
+        leg.read(pdf_handler.read_from_file(input_path))?;
+        if let Some(language) = &options.ocr_language {
+            let backend = leg.read(crate::ocr::default_backend())?;
+            if leg.read(pdf_handler.ocr_if_image_only(backend.as_ref(), language))? {
+                log::info!("{} has no text layer; used OCR ({language})", input_path.display());
+            }
+        }
 
-        let content = pdf_handler.get_content()?;
-        let metadata = pdf_handler.get_metadata()?;
+        Self::write_txt(&pdf_handler, &leg, output_path, options, "\n\n", "pages")
+    }
 
-        let mut txt_handler = TxtHandler::new();
-        txt_handler.set_metadata(metadata)?;
-        txt_handler.set_content(&content)?;
-        txt_handler.write_to_file(output_path)?;
+    fn txt_to_fb2(input_path: &Path, output_path: &Path, options: &ConversionOptions, progress: Option<&Progress>) -> Result<()> {
+        let leg = Leg::new("txt", "fb2");
+        // Ensure parent directory exists
+        if let Some(parent) = output_path.parent() {
+            leg.write(std::fs::create_dir_all(parent))?;
+        }
+
+        let txt_handler = leg.read(read_txt(input_path, progress))?;
+
+        let content = options.sample_text(leg.read(txt_handler.get_content())?);
+        let metadata = leg.read(options.source_metadata(&txt_handler))?;
+
+        let mut fb2_handler = Fb2Handler::new();
+        leg.transform(fb2_handler.set_metadata(options.output_metadata(metadata)))?;
+        leg.transform(fb2_handler.set_content(&content))?;
+        leg.write(fb2_handler.write_to_file(output_path))?;
         Ok(())
     }
 
-    fn txt_to_fb2(input_path: &Path, output_path: &Path) -> Result<()> {
+    /// Build an image-based EPUB with one full-bleed XHTML page per comic page
+    fn cbz_to_epub(input_path: &Path, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+        let leg = Leg::new("cbz", "epub");
         // Ensure parent directory exists
         if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            leg.write(std::fs::create_dir_all(parent))?;
         }
 
-        let mut txt_handler = TxtHandler::new();
-        txt_handler.read_from_file(input_path)?;
+        let mut cbz_handler = CbzHandler::new();
+        cbz_handler.set_page_order(options.page_order);
+        leg.read(cbz_handler.read_from_file(input_path))?;
 
-        let content = txt_handler.get_content()?;
-        let metadata = txt_handler.get_metadata()?;
+        if let Some(optimization) = options.image_optimization {
+            leg.transform(cbz_handler.optimize_images(optimization))?;
+        }
 
-        let mut fb2_handler = Fb2Handler::new();
-        fb2_handler.set_metadata(metadata)?;
-        fb2_handler.set_content(&content)?;
-        fb2_handler.write_to_file(output_path)?;
+        let metadata = leg.read(options.source_metadata(&cbz_handler))?;
+        let pages = options.sample(leg.read(cbz_handler.extract_images())?, "pages");
+        // Recognized lettering becomes the alt text of each page
+        let alt_texts = match &options.ocr_language {
+            Some(language) => {
+                let backend = leg.read(crate::ocr::default_backend())?;
+                leg.read(crate::ocr::recognize_images(backend.as_ref(), &pages, language))?
+            }
+            None => Vec::new(),
+        };
+
+        let mut epub_handler = EpubHandler::new();
+        if options.right_to_left {
+            epub_handler.set_page_progression(Some(PageProgression::RightToLeft));
+        }
+        leg.transform(epub_handler.set_metadata(options.output_metadata(metadata)))?;
+
+        for (idx, page) in pages.iter().enumerate() {
+            let page_number = idx + 1;
+            let extension = Path::new(&page.name)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("jpg")
+                .to_lowercase();
+            let image_name = format!("images/page{page_number:04}.{extension}");
+            let alt = match alt_texts.get(idx).filter(|text| !text.is_empty()) {
+                Some(text) => quick_xml::escape::escape(text.as_str()).into_owned(),
+                None => format!("Page {page_number}"),
+            };
+
+            let xhtml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+    <title>Page {page_number}</title>
+    <style>body {{ margin: 0; padding: 0; }} img {{ display: block; width: 100%; height: auto; }}</style>
+</head>
+<body>
+    <img src="{image_name}" alt="{alt}"/>
+</body>
+</html>"#);
+
+            leg.transform(epub_handler.add_image(&image_name, page.data.clone()))?;
+            leg.transform(epub_handler.add_chapter(&format!("Page {page_number}"), &xhtml))?;
+        }
+
+        options.configure_epub(&mut epub_handler);
+        leg.write(epub_handler.write_to_file(output_path))?;
         Ok(())
     }
 }
 
+/// Read a TXT source; with `progress`, the file is streamed and how much has been read shown
+fn read_txt(input_path: &Path, progress: Option<&Progress>) -> Result<TxtHandler> {
+    let mut txt_handler = TxtHandler::new();
+    match progress {
+        Some(progress) => {
+            let handler = progress.step_handler("Reading input file");
+            txt_handler.read_from_file_streaming(input_path, Some(&handler))?;
+        }
+        None => txt_handler.read_from_file(input_path)?,
+    }
+    Ok(txt_handler)
+}
+
+/// Whether `book` opens with its own cover image or has a cover or title page landmark
+fn has_front_page(book: &(impl EbookReader + ?Sized)) -> Result<bool> {
+    let has_cover = match book.get_metadata()?.cover_image_path {
+        Some(cover) => book.extract_images()?.iter().any(|image| image.name == cover),
+        None => false,
+    };
+    // Formats without landmarks have no title page of their own
+    let landmarks = book.get_annotations().unwrap_or_default();
+    Ok(has_cover || landmarks.iter().any(|landmark| matches!(landmark.kind.as_str(), "cover" | "title-page")))
+}
+
+/// Title, authors and date shown on a generated title page; the date falls back to today's
+fn title_page_lines(metadata: &Metadata) -> Vec<String> {
+    let mut lines = vec![metadata.title.clone().unwrap_or_else(|| "Untitled".to_string())];
+    let authors = metadata.all_authors();
+    if !authors.is_empty() {
+        lines.push(authors.join(", "));
+    }
+    lines.push(metadata.publication_date.clone().unwrap_or_else(crate::utils::today_iso_date));
+    lines
+}
+
+/// Whether a book's text already opens with a title page, i.e. its first line is the title
+fn opens_with_title(metadata: &Metadata, text: &str) -> bool {
+    let Some(title) = metadata.title.as_deref() else { return false };
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .is_some_and(|line| line.eq_ignore_ascii_case(title.trim()))
+}
+
+/// XHTML title page with the cover image (if any) above the title, authors and date
+fn title_page_xhtml(metadata: &Metadata, cover_href: Option<&str>) -> String {
+    let lines = title_page_lines(metadata);
+    let mut body = String::new();
+    if let Some(cover) = cover_href {
+        body.push_str(&format!("    <img src=\"{}\" alt=\"Cover\"/>\n", quick_xml::escape::escape(cover)));
+    }
+    body.push_str(&format!("    <h1>{}</h1>", quick_xml::escape::escape(lines[0].as_str())));
+    for line in &lines[1..] {
+        body.push_str(&format!("\n    <p>{}</p>", quick_xml::escape::escape(line.as_str())));
+    }
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+    <title>Title Page</title>
+    <style>body {{ text-align: center; }} img {{ max-width: 100%; }}</style>
+</head>
+<body>
+{body}
+</body>
+</html>"#)
+}
+
+/// XHTML page for a chapter; a section that is already markup goes into the body as is,
+/// plain text becomes one paragraph per blank-line separated block
+pub(crate) fn chapter_xhtml(title: &str, section: &str) -> String {
+    let body = if section.trim_start().starts_with('<') {
+        section.to_string()
+    } else {
+        section
+            .split("\n\n")
+            .map(str::trim)
+            .filter(|paragraph| !paragraph.is_empty())
+            .map(|paragraph| format!("    <p>{}</p>", quick_xml::escape::escape(paragraph)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+    <title>{}</title>
+</head>
+<body>
+{body}
+</body>
+</html>"#, quick_xml::escape::escape(title))
+}
+
+/// One conversion (source and target format), for attaching context to its errors
+struct Leg<'a> {
+    from: &'a str,
+    to: &'a str,
+}
+
+impl<'a> Leg<'a> {
+    fn new(from: &'a str, to: &'a str) -> Self {
+        Self { from, to }
+    }
+
+    /// Errors from reading the source book
+    fn read<T, E: Into<EbookError>>(&self, result: std::result::Result<T, E>) -> Result<T> {
+        self.context(result, format!("reading {}", self.from.to_uppercase()))
+    }
+
+    /// Errors from building the target book in memory
+    fn transform<T, E: Into<EbookError>>(&self, result: std::result::Result<T, E>) -> Result<T> {
+        self.context(result, "converting".to_string())
+    }
+
+    /// Errors from writing the target file
+    fn write<T, E: Into<EbookError>>(&self, result: std::result::Result<T, E>) -> Result<T> {
+        self.context(result, format!("writing {}", self.to.to_uppercase()))
+    }
+
+    fn context<T, E: Into<EbookError>>(&self, result: std::result::Result<T, E>, stage: String) -> Result<T> {
+        result.map_err(|e| EbookError::ConversionError {
+            from: self.from.to_string(),
+            to: self.to.to_string(),
+            stage,
+            source: Box::new(e.into()),
+        })
+    }
+}
+
 impl Default for Converter {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,169 @@
+//! Compare two ebooks by metadata, chapter count and normalized text
+
+use crate::traits::EbookOperator;
+use crate::{Metadata, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Above this many line pairs only a similarity percentage is computed, not a line diff
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// A metadata field whose values differ between the two books
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldDiff {
+    pub field: String,
+    pub a: Option<String>,
+    pub b: Option<String>,
+}
+
+/// Differences between two books
+#[derive(Debug, Clone, Serialize)]
+pub struct BookDiff {
+    pub metadata: Vec<FieldDiff>,
+    /// Chapter counts, `None` for formats without chapter access
+    pub chapters_a: Option<usize>,
+    pub chapters_b: Option<usize>,
+    /// Share of normalized text lines the two books have in common, in percent
+    pub similarity: f64,
+    /// Removed (`- `) and added (`+ `) lines, `None` when the texts are too large to diff
+    pub text_diff: Option<Vec<String>>,
+}
+
+impl BookDiff {
+    /// Compare the metadata, chapters and text of two books
+    pub fn compare(a: &dyn EbookOperator, b: &dyn EbookOperator) -> Result<Self> {
+        let metadata = diff_metadata(&a.get_metadata()?, &b.get_metadata()?);
+        let chapters_a = crate::utils::collect_chapters(a)?.map(|c| c.len());
+        let chapters_b = crate::utils::collect_chapters(b)?.map(|c| c.len());
+
+        let text_a = normalize_text(&a.get_content()?);
+        let text_b = normalize_text(&b.get_content()?);
+        let (similarity, text_diff) = if text_a.len().saturating_mul(text_b.len()) <= MAX_DIFF_CELLS {
+            let (common, diff) = line_diff(&text_a, &text_b);
+            (similarity(common, text_a.len() + text_b.len()), Some(diff))
+        } else {
+            (similarity(common_line_count(&text_a, &text_b), text_a.len() + text_b.len()), None)
+        };
+
+        Ok(Self {
+            metadata,
+            chapters_a,
+            chapters_b,
+            similarity,
+            text_diff,
+        })
+    }
+
+    /// Whether the books match in metadata, chapter count and text
+    pub fn is_identical(&self) -> bool {
+        self.metadata.is_empty()
+            && self.chapters_a == self.chapters_b
+            && self.text_diff.as_ref().is_some_and(Vec::is_empty)
+            && self.similarity >= 100.0
+    }
+}
+
+fn diff_metadata(a: &Metadata, b: &Metadata) -> Vec<FieldDiff> {
+    let fields = |m: &Metadata| -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("title", m.title.clone()),
+            ("authors", m.joined_authors(crate::metadata::DEFAULT_AUTHOR_SEPARATOR)),
+            ("publisher", m.publisher.clone()),
+            ("description", m.description.clone()),
+            ("language", m.language.clone()),
+            ("isbn", m.isbn.clone()),
+            ("publication_date", m.publication_date.clone()),
+            ("tags", m.tags.as_ref().filter(|t| !t.is_empty()).map(|t| t.join(", "))),
+        ]
+    };
+
+    fields(a)
+        .into_iter()
+        .zip(fields(b))
+        .filter(|((_, a), (_, b))| a != b)
+        .map(|((field, a), (_, b))| FieldDiff {
+            field: field.to_string(),
+            a,
+            b,
+        })
+        .collect()
+}
+
+/// Strip markup and collapse whitespace so only the readable text lines are compared
+fn normalize_text(content: &str) -> Vec<String> {
+    let mut text = String::with_capacity(content.len());
+    let mut in_tag = false;
+    for c in content.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push('\n');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn similarity(common: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        (2 * common) as f64 * 100.0 / total as f64
+    }
+}
+
+/// Longest-common-subsequence line diff: number of common lines and the `-`/`+` lines
+fn line_diff(a: &[String], b: &[String]) -> (usize, Vec<String>) {
+    // lcs[i][j] = LCS length of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(format!("- {}", a[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+ {}", b[j]));
+            j += 1;
+        }
+    }
+
+    (lcs[0][0], diff)
+}
+
+/// Lines shared by both texts regardless of order (multiset intersection)
+fn common_line_count(a: &[String], b: &[String]) -> usize {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for line in a {
+        *counts.entry(line).or_default() += 1;
+    }
+    b.iter()
+        .filter(|line| match counts.get_mut(line.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        })
+        .count()
+}
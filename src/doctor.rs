@@ -0,0 +1,197 @@
+//! Diagnose a book that won't open: format detection, reading, validation and the command
+//! that fixes what was found
+
+use crate::formats::EpubHandler;
+use crate::registry::FormatRegistry;
+use crate::{EbookError, Metadata};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Something wrong with the book, and the command that fixes it when there is one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub problem: String,
+    pub fix: Option<String>,
+}
+
+/// Everything `ebook doctor` found out about a file
+#[derive(Debug, Clone)]
+pub struct DoctorReport {
+    pub path: PathBuf,
+    /// Format named by the file extension
+    pub extension_format: Option<String>,
+    /// Format recognized from the file's bytes
+    pub content_format: Option<String>,
+    /// Metadata of the book, `None` when it could not be read
+    pub metadata: Option<Metadata>,
+    /// Chapter count, for formats with chapter access
+    pub chapters: Option<usize>,
+    /// Findings of the strict EPUB checks, e.g. `error: Spine has no itemref elements`
+    pub validation: Vec<String>,
+    pub findings: Vec<Finding>,
+}
+
+impl DoctorReport {
+    /// Run every check on the file at `path`; failures become findings instead of errors
+    pub fn diagnose(path: &Path) -> Self {
+        let registry = FormatRegistry::default();
+        let mut report = Self {
+            path: path.to_path_buf(),
+            extension_format: registry.format_for_path(path).ok(),
+            content_format: None,
+            metadata: None,
+            chapters: None,
+            validation: Vec::new(),
+            findings: Vec::new(),
+        };
+
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) => {
+                report.add(format!("The file cannot be opened: {e}"), None);
+                return report;
+            }
+        };
+        report.content_format = crate::utils::detect_format_from_content(&data);
+        report.check_format();
+
+        let Some(format) = report.content_format.clone().or_else(|| report.extension_format.clone()) else {
+            report.add("The format is not recognized from the extension or the content".to_string(), None);
+            return report;
+        };
+        report.check_book(&registry, &format);
+        if format == "epub" {
+            report.check_epub();
+        }
+        report
+    }
+
+    /// Whether nothing was found that needs fixing
+    pub fn is_healthy(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    fn add(&mut self, problem: String, fix: Option<String>) {
+        self.findings.push(Finding { problem, fix });
+    }
+
+    fn repair_command(&self) -> String {
+        format!("ebook repair {}", quote(&self.path))
+    }
+
+    /// The extension and the content should name the same format
+    fn check_format(&mut self) {
+        let (Some(extension), Some(content)) = (&self.extension_format, &self.content_format) else { return };
+        // AZW files are MOBI files with another name
+        if extension == content || (extension == "azw" && content == "mobi") {
+            return;
+        }
+        let renamed = self.path.with_extension(content);
+        let problem = format!("The extension says {extension} but the content is {content}");
+        self.add(problem, Some(format!("mv {} {}", quote(&self.path), quote(&renamed))));
+    }
+
+    /// Read the book as `format` and look for the gaps `repair` fills
+    fn check_book(&mut self, registry: &FormatRegistry, format: &str) {
+        let read = registry.create(format).and_then(|mut handler| {
+            handler.read_from_file(&self.path)?;
+            Ok(handler)
+        });
+        let handler = match read {
+            Ok(handler) => handler,
+            Err(e) => {
+                // `repair` rebuilds archives whose index is damaged
+                let fix = matches!(e, EbookError::Zip(_)).then(|| self.repair_command());
+                self.add(format!("Reading it as {format} failed: {e}"), fix);
+                return;
+            }
+        };
+
+        let metadata = handler.get_metadata().unwrap_or_default();
+        if !metadata.has_title() {
+            self.add("The metadata has no title".to_string(), Some(self.repair_command()));
+        }
+        if metadata.language.is_none() {
+            self.add("The metadata has no language".to_string(), Some(self.repair_command()));
+        }
+        self.chapters = crate::utils::collect_chapters(handler.as_ref()).ok().flatten().map(|chapters| chapters.len());
+        self.metadata = Some(metadata);
+    }
+
+    /// Run the strict EPUB checks; errors are fixed by rewriting the book
+    fn check_epub(&mut self) {
+        let report = match EpubHandler::validate_detailed(&self.path) {
+            Ok(report) => report,
+            Err(e) => {
+                self.add(format!("The EPUB structure could not be checked: {e}"), Some(self.repair_command()));
+                return;
+            }
+        };
+        self.validation = report.issues.iter().map(ToString::to_string).collect();
+        let errors = report.errors().count();
+        if errors > 0 {
+            self.add(format!("Strict validation found {errors} error(s)"), Some(self.repair_command()));
+        }
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = "unknown".to_string();
+        writeln!(f, "File: {}", self.path.display())?;
+        writeln!(
+            f,
+            "Format: {} (extension), {} (content)",
+            self.extension_format.as_ref().unwrap_or(&unknown),
+            self.content_format.as_ref().unwrap_or(&unknown)
+        )?;
+        match &self.metadata {
+            Some(metadata) => {
+                let title = metadata.title.as_ref().filter(|_| metadata.has_title()).unwrap_or(&unknown);
+                write!(f, "Read: ok, \"{title}\"")?;
+                if let Some(chapters) = self.chapters {
+                    write!(f, ", {chapters} chapter(s)")?;
+                }
+                writeln!(f)?;
+            }
+            None => writeln!(f, "Read: failed")?,
+        }
+        if !self.validation.is_empty() {
+            writeln!(f, "Validation:")?;
+            for issue in &self.validation {
+                writeln!(f, "  {issue}")?;
+            }
+        }
+
+        if self.is_healthy() {
+            return write!(f, "✓ No problems found");
+        }
+        writeln!(f, "Problems:")?;
+        for finding in &self.findings {
+            writeln!(f, "  ✗ {}", finding.problem)?;
+        }
+        let mut fixes: Vec<&str> = Vec::new();
+        for fix in self.findings.iter().filter_map(|finding| finding.fix.as_deref()) {
+            if !fixes.contains(&fix) {
+                fixes.push(fix);
+            }
+        }
+        if !fixes.is_empty() {
+            writeln!(f, "Suggested fix:")?;
+            for fix in &fixes {
+                writeln!(f, "  {fix}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A path as a shell argument, quoted when it has spaces or quotes
+fn quote(path: &Path) -> String {
+    let path = path.display().to_string();
+    if path.contains([' ', '\'', '"']) {
+        format!("'{}'", path.replace('\'', r"'\''"))
+    } else {
+        path
+    }
+}
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, EbookError>;
@@ -40,11 +41,94 @@ pub enum EbookError {
     #[error("Image processing error: {0}\nHint: Ensure the image is in a supported format (JPEG, PNG, GIF, WebP)")]
     ImageError(String),
 
-    #[error("Conversion error: {0}\nHint: Not all format conversions are supported. Check documentation for supported conversions")]
-    ConversionError(String),
+    #[error("OCR error: {0}\nHint: Check that Tesseract and the data for the requested language are installed")]
+    Ocr(String),
+
+    #[error("Render error: {0}\nHint: Check that the pdfium library is installed or set PDFIUM_DYNAMIC_LIB_PATH")]
+    Render(String),
+
+    #[error("Conversion from {from} to {to} failed while {stage}: {source}")]
+    ConversionError {
+        from: String,
+        to: String,
+        /// What was being done when it failed, e.g. "reading EPUB" or "writing PDF"
+        stage: String,
+        source: Box<EbookError>,
+    },
+
+    #[error("Archive too large: {0}\nHint: The archive may be a zip bomb; if you trust it, raise the limit with EBOOK_MAX_UNCOMPRESSED_SIZE (e.g. 16GB)")]
+    ArchiveTooLarge(String),
 
     #[error("Validation error: {0}\nHint: Use the 'repair' command to fix common issues")]
     ValidationError(String),
+
+    #[error("{}: {source}", path.display())]
+    InFile { path: std::path::PathBuf, source: Box<EbookError> },
+
+    #[error("{failed} of {total} files failed")]
+    BatchFailed { failed: usize, total: usize },
+}
+
+/// Broad cause of an error, for callers (such as MCP clients) that branch on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    NotFound,
+    UnsupportedFormat,
+    ParseError,
+    IoError,
+    Validation,
+    /// The caller's request was malformed, e.g. a missing argument
+    InvalidArguments,
+    Internal,
+}
+
+impl EbookError {
+    /// Machine-readable name of the variant behind the error, e.g. `zip` or `not_found`;
+    /// errors wrapped with a path or conversion stage report the wrapped one
+    pub fn code(&self) -> &'static str {
+        match self {
+            EbookError::Io(_) => "io",
+            EbookError::Zip(_) => "zip",
+            EbookError::Xml(_) => "xml",
+            EbookError::Pdf(_) => "pdf",
+            EbookError::UnsupportedFormat(_) => "unsupported_format",
+            EbookError::InvalidMetadata(_) => "invalid_metadata",
+            EbookError::Parse(_) => "parse",
+            EbookError::Encoding(_) => "encoding",
+            EbookError::NotFound(_) => "not_found",
+            EbookError::InvalidStructure(_) => "invalid_structure",
+            EbookError::NotSupported(_) => "not_supported",
+            EbookError::ImageError(_) => "image",
+            EbookError::Ocr(_) => "ocr",
+            EbookError::Render(_) => "render",
+            EbookError::ArchiveTooLarge(_) => "archive_too_large",
+            EbookError::ValidationError(_) => "validation",
+            EbookError::BatchFailed { .. } => "batch_failed",
+            EbookError::ConversionError { source, .. } | EbookError::InFile { source, .. } => source.code(),
+        }
+    }
+
+    /// Broad cause of the error, looking through path and conversion-stage wrappers
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            EbookError::ConversionError { source, .. } | EbookError::InFile { source, .. } => source.category(),
+            EbookError::Io(e) if e.kind() == std::io::ErrorKind::NotFound => ErrorCategory::NotFound,
+            EbookError::Io(_) | EbookError::Zip(zip::result::ZipError::Io(_)) => ErrorCategory::IoError,
+            EbookError::Zip(zip::result::ZipError::FileNotFound) | EbookError::NotFound(_) => ErrorCategory::NotFound,
+            EbookError::Zip(_) | EbookError::Xml(_) | EbookError::Pdf(_) | EbookError::Parse(_) | EbookError::Encoding(_) => {
+                ErrorCategory::ParseError
+            }
+            EbookError::UnsupportedFormat(_) | EbookError::NotSupported(_) => ErrorCategory::UnsupportedFormat,
+            EbookError::InvalidMetadata(_)
+            | EbookError::InvalidStructure(_)
+            | EbookError::ValidationError(_)
+            | EbookError::ArchiveTooLarge(_) => {
+                ErrorCategory::Validation
+            }
+            _ => ErrorCategory::Internal,
+        }
+    }
 }
 
 impl From<xml::reader::Error> for EbookError {
@@ -58,3 +142,9 @@ impl From<quick_xml::Error> for EbookError {
         EbookError::Xml(err.to_string())
     }
 }
+
+impl From<notify::Error> for EbookError {
+    fn from(err: notify::Error) -> Self {
+        EbookError::Io(std::io::Error::other(err))
+    }
+}
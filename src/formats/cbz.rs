@@ -1,19 +1,54 @@
 use crate::{EbookError, Metadata, Result};
-use crate::traits::{EbookReader, EbookWriter, EbookOperator, TocEntry, ImageData};
+use crate::ocr::OcrBackend;
+use crate::traits::{ArchiveEntry, EbookReader, EbookWriter, EbookOperator, TocEntry, ImageData};
+use crate::utils::ExtractionBudget;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{Cursor, Seek, Write};
 use std::path::Path;
 use zip::ZipArchive;
 use zip::write::{ZipWriter, FileOptions};
 
 mod comic_info;
-use comic_info::ComicInfo;
+use comic_info::ComicPage;
+pub use comic_info::{ComicInfo, ComicInfoVersion};
 
 #[derive(Default)]
 pub struct CbzHandler {
     metadata: Metadata,
     images: Vec<ImageData>,
     comic_info: Option<ComicInfo>,
+    page_order: PageOrder,
+    /// Zip index of the archive the comic was read from
+    entries: Vec<ArchiveEntry>,
+    extraction_limits: crate::utils::ExtractionLimits,
+    /// Leave ComicInfo.xml out of written archives
+    omit_comic_info: bool,
+    comic_info_version: ComicInfoVersion,
+    /// Recognized text of each page once `recognize_pages` has run
+    ocr_pages: Vec<String>,
+}
+
+/// How the pages of a CBZ are put in reading order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageOrder {
+    /// Natural sort of the image file names
+    #[default]
+    Filename,
+    /// The order of ComicInfo's `<Pages>` list (front cover first), falling back to
+    /// file names when the archive has none
+    ComicInfo,
+}
+
+impl std::str::FromStr for PageOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "filename" => Ok(Self::Filename),
+            "comicinfo" => Ok(Self::ComicInfo),
+            other => Err(format!("unknown page order '{other}' (expected filename or comicinfo)")),
+        }
+    }
 }
 
 impl CbzHandler {
@@ -21,21 +56,161 @@ impl CbzHandler {
         Self::default()
     }
 
+    pub fn set_page_order(&mut self, page_order: PageOrder) {
+        self.page_order = page_order;
+    }
+
+    /// OCR every page so `get_content` and `get_chapter` return the comic's lettering
+    pub fn recognize_pages(&mut self, backend: &dyn OcrBackend, language: &str) -> Result<()> {
+        self.ocr_pages = crate::ocr::recognize_images(backend, &self.images, language)?;
+        Ok(())
+    }
+
+    /// Bounds on what reading an archive, nested ones included, may decompress
+    pub fn set_extraction_limits(&mut self, limits: crate::utils::ExtractionLimits) {
+        self.extraction_limits = limits;
+    }
+
+    /// Whether written archives include a ComicInfo.xml (the default)
+    pub fn set_write_comic_info(&mut self, write: bool) {
+        self.omit_comic_info = !write;
+    }
+
+    /// Schema of the ComicInfo.xml written with the archive
+    pub fn set_comic_info_version(&mut self, version: ComicInfoVersion) {
+        self.comic_info_version = version;
+    }
+
+    /// ComicInfo.xml read from the archive or set with [`Self::set_comic_info`]
+    pub fn get_comic_info(&self) -> Option<&ComicInfo> {
+        self.comic_info.as_ref()
+    }
+
+    /// Write `comic_info` instead of one derived from the metadata
+    pub fn set_comic_info(&mut self, comic_info: ComicInfo) {
+        self.comic_info = Some(comic_info);
+    }
+
+    /// Number of page images read from the archive
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Files of the archive the comic was read from, with their sizes; empty for a new comic
+    pub fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        Ok(self.entries.clone())
+    }
+
+    /// Total size of the page images in bytes
+    pub fn image_bytes(&self) -> u64 {
+        self.images.iter().map(|image| image.data.len() as u64).sum()
+    }
+
+    /// Pages whose bytes repeat an earlier page, e.g. scanned filler pages
+    pub fn find_duplicate_pages(&self) -> crate::image_optimizer::DuplicateImages {
+        crate::image_optimizer::DuplicateImages::find(&self.images)
+    }
+
+    /// Drop pages that repeat an earlier page exactly, returning what was removed
+    pub fn remove_duplicate_pages(&mut self) -> crate::image_optimizer::DuplicateImages {
+        let found = self.find_duplicate_pages();
+        let is_duplicate = |name: &str| found.duplicates.iter().any(|(duplicate, _)| duplicate == name);
+
+        // `<Pages>` refers to images by their index in file name order; drop the removed ones
+        // and shift the rest down
+        let mut names: Vec<&str> = self.images.iter().map(|image| image.name.as_str()).collect();
+        names.sort_by(|a, b| crate::utils::natural_cmp(a, b));
+        let removed: Vec<usize> = (0..names.len()).filter(|&idx| is_duplicate(names[idx])).collect();
+        if let Some(comic_info) = &mut self.comic_info {
+            comic_info.pages.retain(|page| !removed.contains(&page.image));
+            for page in &mut comic_info.pages {
+                page.image -= removed.iter().filter(|&&idx| idx < page.image).count();
+            }
+        }
+
+        self.images.retain(|image| !is_duplicate(&image.name));
+        if let Some(comic_info) = &mut self.comic_info {
+            comic_info.page_count = Some(self.images.len() as u32);
+        }
+        found
+    }
+
+    /// Page count recorded in ComicInfo.xml, if the archive has one
+    pub fn page_count(&self) -> Option<u32> {
+        self.comic_info.as_ref().and_then(|comic_info| comic_info.page_count)
+    }
+
     pub fn optimize_images(&mut self, options: crate::image_optimizer::OptimizationOptions) -> Result<usize> {
+        Ok(Self::optimize_image_list(&mut self.images, options))
+    }
+
+    /// Lower JPEG quality (binary search between `min_quality` and `options.quality`) until
+    /// the archive fits in `max_bytes`, falling back to `min_quality` if it never does
+    pub fn optimize_to_size(
+        &mut self,
+        options: crate::image_optimizer::OptimizationOptions,
+        max_bytes: u64,
+        min_quality: u8,
+    ) -> Result<crate::image_optimizer::TargetSizeResult> {
+        use crate::image_optimizer::TargetSizeResult;
+
+        let min_quality = min_quality.clamp(1, options.quality.clamp(1, 100));
+        let pass = |quality: u8| -> Result<(u64, Vec<ImageData>)> {
+            let mut images = self.images.clone();
+            Self::optimize_image_list(&mut images, options.with_quality(quality));
+            let size = self.write_archive(&images, Cursor::new(Vec::new()))?.into_inner().len() as u64;
+            Ok((size, images))
+        };
+
+        // Find the highest quality that fits; the floor is always tried when nothing does.
+        // Only the pages of the best pass so far are kept.
+        let (mut low, mut high) = (min_quality, options.quality.clamp(min_quality, 100));
+        let mut result = TargetSizeResult { quality: min_quality, size: u64::MAX, reached: false };
+        let mut best = None;
+        while low <= high {
+            let quality = low + (high - low) / 2;
+            let (size, images) = pass(quality)?;
+            if size <= max_bytes {
+                result = TargetSizeResult { quality, size, reached: true };
+                best = Some(images);
+                low = quality + 1;
+            } else {
+                if quality == min_quality {
+                    result.size = size;
+                    best = Some(images);
+                    break;
+                }
+                high = quality - 1;
+            }
+        }
+
+        if let Some(images) = best {
+            self.images = images;
+        }
+        Ok(result)
+    }
+
+    fn optimize_image_list(images: &mut [ImageData], options: crate::image_optimizer::OptimizationOptions) -> usize {
         use crate::image_optimizer::ImageOptimizer;
         
         let optimizer = ImageOptimizer::new(options);
         let mut total_savings = 0usize;
         
-        for image in &mut self.images {
+        let results = optimizer.optimize_all(images);
+        for (image, result) in images.iter_mut().zip(results) {
             let original_size = image.data.len();
-            
-            match optimizer.optimize(&image.data, &image.mime_type) {
-                Ok(optimized_data) => {
-                    let new_size = optimized_data.len();
+
+            match result {
+                Ok(optimized) if optimized.mime_type != image.mime_type => {
+                    // Transcoded pages are always kept, renamed to the new extension
+                    total_savings += original_size.saturating_sub(optimized.data.len());
+                    *image = optimized;
+                }
+                Ok(optimized) => {
+                    let new_size = optimized.data.len();
                     if new_size < original_size {
                         total_savings += original_size - new_size;
-                        image.data = optimized_data;
+                        *image = optimized;
                     }
                 }
                 Err(_) => {
@@ -45,19 +220,112 @@ impl CbzHandler {
             }
         }
         
-        Ok(total_savings)
+        total_savings
+    }
+
+    /// Write ComicInfo.xml (unless omitted) followed by `images` as a zip archive
+    fn write_archive<W: Write + Seek>(&self, images: &[ImageData], writer: W) -> Result<W> {
+        let mut zip = ZipWriter::new(writer);
+        // A fixed entry timestamp keeps repeated writes of the same pages byte-identical
+        let options = FileOptions::<()>::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .last_modified_time(zip::DateTime::default());
+
+        if !self.omit_comic_info {
+            // Generate and write ComicInfo.xml
+            let mut comic_info = if let Some(ref ci) = self.comic_info {
+                ci.clone()
+            } else {
+                ComicInfo::from_metadata(&self.metadata)
+            };
+
+            // Update page count
+            comic_info.page_count = Some(images.len() as u32);
+
+            let xml_content = comic_info.to_xml_versioned(self.comic_info_version)?;
+            zip.start_file("ComicInfo.xml", options)?;
+            zip.write_all(xml_content.as_bytes())?;
+        }
+
+        // Write all images
+        for image in images {
+            zip.start_file(&image.name, options)?;
+            zip.write_all(&image.data)?;
+        }
+
+        Ok(zip.finish()?)
     }
 }
 
+/// Whether an archive entry is one of the page images the handler reads
+fn is_page_image(name: &str) -> bool {
+    [".jpg", ".jpeg", ".png", ".gif", ".webp"].iter().any(|ext| name.ends_with(ext))
+}
+
+/// Whether an archive entry is a comic nested inside the archive, e.g. one per issue
+fn is_nested_archive(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.ends_with(".cbz") || name.ends_with(".zip")
+}
+
+/// Page images of a nested archive, named under its path (`issue1.cbz/page1.jpg`) so they
+/// sort and group together
+fn nested_pages(archive_name: &str, data: Vec<u8>, budget: &mut ExtractionBudget) -> Result<Vec<ImageData>> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    budget.check_declared(&mut archive)?;
+    let mut pages = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if !is_page_image(file.name()) {
+            continue;
+        }
+        let name = format!("{archive_name}/{}", crate::utils::normalize_entry_name(file.name()));
+        let data = budget.read(&mut file)?;
+        let mime_type = crate::utils::guess_mime_type(&name);
+        pages.push(ImageData::new(name, mime_type, data));
+    }
+    Ok(pages)
+}
+
+/// Folder or nested archive a page belongs to; empty for pages at the archive root
+fn page_group(name: &str) -> &str {
+    name.rsplit_once('/').map_or("", |(group, _)| group)
+}
+
+/// Image indices (into the filename-sorted images) in `<Pages>` order with the front cover
+/// first; images the list leaves out follow in filename order
+fn comic_info_order(pages: &[ComicPage], image_count: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = Vec::with_capacity(image_count);
+    for page in pages {
+        if page.image < image_count && !order.contains(&page.image) {
+            order.push(page.image);
+        }
+    }
+    let cover = pages
+        .iter()
+        .find(|page| page.page_type.as_deref() == Some("FrontCover"))
+        .and_then(|cover| order.iter().position(|&idx| idx == cover.image));
+    if let Some(position) = cover {
+        let idx = order.remove(position);
+        order.insert(0, idx);
+    }
+    let unlisted: Vec<usize> = (0..image_count).filter(|idx| !order.contains(idx)).collect();
+    order.extend(unlisted);
+    order
+}
+
 impl EbookReader for CbzHandler {
     fn read_from_file(&mut self, path: &Path) -> Result<()> {
         let file = File::open(path)?;
         let mut archive = ZipArchive::new(file)?;
+        self.entries = ArchiveEntry::list(&mut archive, is_page_image)?;
+        self.ocr_pages.clear();
+        let mut budget = ExtractionBudget::new(self.extraction_limits);
+        budget.check_declared(&mut archive)?;
 
         // Try to read ComicInfo.xml first
-        if let Ok(mut comic_info_file) = archive.by_name("ComicInfo.xml") {
-            let mut xml_content = String::new();
-            comic_info_file.read_to_string(&mut xml_content)?;
+        if let Ok(mut comic_info_file) = crate::utils::zip_entry(&mut archive, "ComicInfo.xml") {
+            let xml_content = budget.read_string(&mut comic_info_file)?;
             
             if let Ok(comic_info) = ComicInfo::parse_xml(&xml_content) {
                 self.metadata = comic_info.to_metadata();
@@ -77,24 +345,37 @@ impl EbookReader for CbzHandler {
         // Extract all images
         for i in 0..archive.len() {
             let mut file = archive.by_index(i)?;
-            let name = file.name().to_string();
+            let name = crate::utils::normalize_entry_name(file.name());
             
             // Skip ComicInfo.xml
             if name == "ComicInfo.xml" {
                 continue;
             }
             
-            if name.ends_with(".jpg") || name.ends_with(".jpeg") || 
-               name.ends_with(".png") || name.ends_with(".gif") ||
-               name.ends_with(".webp") {
-                let mut data = Vec::new();
-                file.read_to_end(&mut data)?;
+            if is_page_image(&name) {
+                let data = budget.read(&mut file)?;
                 let mime_type = crate::utils::guess_mime_type(&name);
                 self.images.push(ImageData::new(name, mime_type, data));
+            } else if is_nested_archive(&name) {
+                let data = budget.read(&mut file)?;
+                match nested_pages(&name, data, &mut budget) {
+                    Ok(pages) => self.images.extend(pages),
+                    Err(e @ EbookError::ArchiveTooLarge(_)) => return Err(e),
+                    Err(e) => log::warn!("Skipping unreadable nested archive {name}: {e}"),
+                }
             }
         }
 
-        self.images.sort_by(|a, b| a.name.cmp(&b.name));
+        self.images.sort_by(|a, b| crate::utils::natural_cmp(&a.name, &b.name));
+        if self.page_order == PageOrder::ComicInfo
+            && let Some(comic_info) = &self.comic_info
+            && !comic_info.pages.is_empty()
+        {
+            let mut images: Vec<Option<ImageData>> = std::mem::take(&mut self.images).into_iter().map(Some).collect();
+            for idx in comic_info_order(&comic_info.pages, images.len()) {
+                self.images.extend(images[idx].take());
+            }
+        }
         
         // Update page count in comic_info if present
         if let Some(ref mut comic_info) = self.comic_info {
@@ -109,11 +390,47 @@ impl EbookReader for CbzHandler {
     }
 
     fn get_content(&self) -> Result<String> {
+        if !self.ocr_pages.is_empty() {
+            return Ok(self.ocr_pages.join("\n\n"));
+        }
         Ok(format!("CBZ archive with {} images", self.images.len()))
     }
 
+    /// Recognized text of a page; comics only have text after `recognize_pages`
+    fn get_chapter(&self, index: usize) -> Result<Option<String>> {
+        if self.ocr_pages.is_empty() {
+            return Err(EbookError::NotSupported("CBZ pages have no text; read them with OCR".to_string()));
+        }
+        Ok(self.ocr_pages.get(index).cloned())
+    }
+
+    /// One entry per subfolder or nested archive, pointing at its first page; pages at the
+    /// archive root are not listed
     fn get_toc(&self) -> Result<Vec<TocEntry>> {
-        Ok(Vec::new())
+        let mut toc: Vec<TocEntry> = Vec::new();
+        let mut current = "";
+        for image in &self.images {
+            let group = page_group(&image.name);
+            if group.is_empty() || group == current {
+                current = group;
+                continue;
+            }
+            current = group;
+            let folder = group.rsplit('/').next().unwrap_or(group);
+            let title = if is_nested_archive(folder) {
+                folder.rsplit_once('.').map_or(folder, |(stem, _)| stem)
+            } else {
+                folder
+            };
+            toc.push(TocEntry {
+                id: toc.len() as u32,
+                title: title.to_string(),
+                level: 0,
+                href: Some(image.name.clone()),
+                children: Vec::new(),
+            });
+        }
+        Ok(toc)
     }
 
     fn extract_images(&self) -> Result<Vec<ImageData>> {
@@ -142,31 +459,7 @@ impl EbookWriter for CbzHandler {
     }
 
     fn write_to_file(&self, path: &Path) -> Result<()> {
-        let file = File::create(path)?;
-        let mut zip = ZipWriter::new(file);
-        let options = FileOptions::<()>::default().compression_method(zip::CompressionMethod::Deflated);
-
-        // Generate and write ComicInfo.xml
-        let mut comic_info = if let Some(ref ci) = self.comic_info {
-            ci.clone()
-        } else {
-            ComicInfo::from_metadata(&self.metadata)
-        };
-        
-        // Update page count
-        comic_info.page_count = Some(self.images.len() as u32);
-        
-        let xml_content = comic_info.to_xml()?;
-        zip.start_file("ComicInfo.xml", options)?;
-        zip.write_all(xml_content.as_bytes())?;
-
-        // Write all images
-        for image in &self.images {
-            zip.start_file(&image.name, options)?;
-            zip.write_all(&image.data)?;
-        }
-
-        zip.finish()?;
+        self.write_archive(&self.images, File::create(path)?)?;
         Ok(())
     }
 }
@@ -184,6 +477,15 @@ impl EbookOperator for CbzHandler {
         if self.metadata.title.is_none() {
             self.metadata.title = Some("Untitled Comic".to_string());
         }
+        // Pages carry no text to guess a language from
+        self.metadata.infer_defaults("");
+
+        // Written archives use ComicInfo over the metadata, so keep it complete too
+        let comic_info = self.comic_info.get_or_insert_with(|| ComicInfo::from_metadata(&self.metadata));
+        if comic_info.title.is_none() {
+            comic_info.title.clone_from(&self.metadata.title);
+        }
+        comic_info.page_count = Some(self.images.len() as u32);
         Ok(())
     }
 }
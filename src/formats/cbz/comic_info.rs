@@ -26,6 +26,48 @@ pub struct ComicInfo {
     pub genre: Option<String>,
     pub tags: Vec<String>,
     pub web: Option<String>,
+    /// `<Pages>` entries in the order they are listed
+    pub pages: Vec<ComicPage>,
+    /// Publication format such as `TPB` or `Web` (v2.0 schema)
+    pub format: Option<String>,
+    /// Age rating such as `Teen` or `Everyone` (v2.0 schema)
+    pub age_rating: Option<String>,
+    /// Story arc the issue belongs to (v2.0 schema)
+    pub story_arc: Option<String>,
+}
+
+/// Schema the written ComicInfo.xml follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComicInfoVersion {
+    /// The original unversioned element set
+    #[default]
+    Legacy,
+    /// The v2.0 schema: elements in schema order, plus `Format`, `AgeRating` and `StoryArc`
+    V2,
+}
+
+impl std::str::FromStr for ComicInfoVersion {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "legacy" => Ok(Self::Legacy),
+            "2.0" | "v2" => Ok(Self::V2),
+            other => Err(format!("unknown ComicInfo version '{other}' (expected legacy or 2.0)")),
+        }
+    }
+}
+
+const V2_SCHEMA_LOCATION: &str =
+    "https://raw.githubusercontent.com/anansi-project/comicinfo/main/schema/v2.0/ComicInfo.xsd";
+
+/// A `<Page>` of ComicInfo's `<Pages>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComicPage {
+    /// Index of the image among the archive's images in filename order
+    pub image: usize,
+    /// Page type such as `FrontCover`, `Story` or `Advertisement`
+    pub page_type: Option<String>,
 }
 
 impl ComicInfo {
@@ -41,9 +83,8 @@ impl ComicInfo {
         comic_info.summary = metadata.description.clone();
         comic_info.language_iso = metadata.language.clone();
         
-        if let Some(author) = &metadata.author {
-            comic_info.writer = Some(author.clone());
-        }
+        // ComicInfo lists multiple creators in one comma-separated field
+        comic_info.writer = metadata.joined_authors(", ");
         
         if let Some(tags) = &metadata.tags {
             comic_info.tags = tags.clone();
@@ -59,7 +100,9 @@ impl ComicInfo {
         metadata.publisher = self.publisher.clone();
         metadata.description = self.summary.clone();
         metadata.language = self.language_iso.clone();
-        metadata.author = self.writer.clone();
+        if let Some(writer) = &self.writer {
+            metadata.set_authors_from_joined(writer, ",");
+        }
         metadata.format = Some("CBZ".to_string());
         
         if !self.tags.is_empty() {
@@ -79,6 +122,21 @@ impl ComicInfo {
         
         loop {
             match reader.read_event_into(&mut buf) {
+                Ok(Event::Empty(e)) | Ok(Event::Start(e)) if e.name().as_ref() == b"Page" => {
+                    let mut image = None;
+                    let mut page_type = None;
+                    for attr in e.attributes().flatten() {
+                        let value = String::from_utf8_lossy(&attr.value).trim().to_string();
+                        match attr.key.as_ref() {
+                            b"Image" => image = value.parse().ok(),
+                            b"Type" if !value.is_empty() => page_type = Some(value),
+                            _ => {}
+                        }
+                    }
+                    if let Some(image) = image {
+                        comic_info.pages.push(ComicPage { image, page_type });
+                    }
+                }
                 Ok(Event::Start(e)) => {
                     current_tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
                 }
@@ -117,6 +175,9 @@ impl ComicInfo {
                             }
                         }
                         "Web" => comic_info.web = Some(text),
+                        "Format" => comic_info.format = Some(text),
+                        "AgeRating" => comic_info.age_rating = Some(text),
+                        "StoryArc" => comic_info.story_arc = Some(text),
                         _ => {}
                     }
                 }
@@ -131,6 +192,10 @@ impl ComicInfo {
     }
 
     pub fn to_xml(&self) -> Result<String> {
+        self.to_xml_versioned(ComicInfoVersion::default())
+    }
+
+    pub fn to_xml_versioned(&self, version: ComicInfoVersion) -> Result<String> {
         let mut writer = Writer::new(Cursor::new(Vec::new()));
         
         writer.write_event(Event::Decl(quick_xml::events::BytesDecl::new("1.0", Some("utf-8"), None)))?;
@@ -138,44 +203,105 @@ impl ComicInfo {
         let mut comic_info_elem = BytesStart::new("ComicInfo");
         comic_info_elem.push_attribute(("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance"));
         comic_info_elem.push_attribute(("xmlns:xsd", "http://www.w3.org/2001/XMLSchema"));
+        if version == ComicInfoVersion::V2 {
+            comic_info_elem.push_attribute(("xsi:noNamespaceSchemaLocation", V2_SCHEMA_LOCATION));
+        }
         writer.write_event(Event::Start(comic_info_elem))?;
+
+        match version {
+            ComicInfoVersion::Legacy => self.write_legacy_elements(&mut writer)?,
+            ComicInfoVersion::V2 => self.write_v2_elements(&mut writer)?,
+        }
+        self.write_pages(&mut writer)?;
         
-        self.write_element(&mut writer, "Title", &self.title)?;
-        self.write_element(&mut writer, "Series", &self.series)?;
-        self.write_element(&mut writer, "Number", &self.number)?;
-        self.write_element(&mut writer, "Volume", &self.volume)?;
-        self.write_element(&mut writer, "Summary", &self.summary)?;
-        self.write_element(&mut writer, "Publisher", &self.publisher)?;
-        self.write_element(&mut writer, "Writer", &self.writer)?;
-        self.write_element(&mut writer, "Penciller", &self.penciller)?;
-        self.write_element(&mut writer, "Inker", &self.inker)?;
-        self.write_element(&mut writer, "Colorist", &self.colorist)?;
-        self.write_element(&mut writer, "Letterer", &self.letterer)?;
-        self.write_element(&mut writer, "CoverArtist", &self.cover_artist)?;
-        self.write_element(&mut writer, "Editor", &self.editor)?;
-        self.write_element(&mut writer, "Year", &self.year)?;
-        self.write_element(&mut writer, "Month", &self.month)?;
-        self.write_element(&mut writer, "Day", &self.day)?;
-        self.write_element(&mut writer, "LanguageISO", &self.language_iso)?;
+        writer.write_event(Event::End(BytesEnd::new("ComicInfo")))?;
+        
+        let result = writer.into_inner().into_inner();
+        String::from_utf8(result)
+            .map_err(|e| EbookError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+    }
+
+    fn write_legacy_elements<W: std::io::Write>(&self, writer: &mut Writer<W>) -> Result<()> {
+        self.write_element(writer, "Title", &self.title)?;
+        self.write_element(writer, "Series", &self.series)?;
+        self.write_element(writer, "Number", &self.number)?;
+        self.write_element(writer, "Volume", &self.volume)?;
+        self.write_element(writer, "Summary", &self.summary)?;
+        self.write_element(writer, "Publisher", &self.publisher)?;
+        self.write_element(writer, "Writer", &self.writer)?;
+        self.write_element(writer, "Penciller", &self.penciller)?;
+        self.write_element(writer, "Inker", &self.inker)?;
+        self.write_element(writer, "Colorist", &self.colorist)?;
+        self.write_element(writer, "Letterer", &self.letterer)?;
+        self.write_element(writer, "CoverArtist", &self.cover_artist)?;
+        self.write_element(writer, "Editor", &self.editor)?;
+        self.write_element(writer, "Year", &self.year)?;
+        self.write_element(writer, "Month", &self.month)?;
+        self.write_element(writer, "Day", &self.day)?;
+        self.write_element(writer, "LanguageISO", &self.language_iso)?;
         
         if let Some(page_count) = self.page_count {
-            self.write_element(&mut writer, "PageCount", &Some(page_count.to_string()))?;
+            self.write_element(writer, "PageCount", &Some(page_count.to_string()))?;
         }
         
-        self.write_element(&mut writer, "Genre", &self.genre)?;
+        self.write_element(writer, "Genre", &self.genre)?;
         
         if !self.tags.is_empty() {
             let tags_str = self.tags.join(", ");
-            self.write_element(&mut writer, "Tags", &Some(tags_str))?;
+            self.write_element(writer, "Tags", &Some(tags_str))?;
         }
         
-        self.write_element(&mut writer, "Web", &self.web)?;
-        
-        writer.write_event(Event::End(BytesEnd::new("ComicInfo")))?;
-        
-        let result = writer.into_inner().into_inner();
-        String::from_utf8(result)
-            .map_err(|e| EbookError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+        self.write_element(writer, "Web", &self.web)?;
+        Ok(())
+    }
+
+    /// The v2.0 schema is an `xs:sequence`, so elements follow its order
+    fn write_v2_elements<W: std::io::Write>(&self, writer: &mut Writer<W>) -> Result<()> {
+        self.write_element(writer, "Title", &self.title)?;
+        self.write_element(writer, "Series", &self.series)?;
+        self.write_element(writer, "Number", &self.number)?;
+        self.write_element(writer, "Volume", &self.volume)?;
+        self.write_element(writer, "Summary", &self.summary)?;
+        self.write_element(writer, "Year", &self.year)?;
+        self.write_element(writer, "Month", &self.month)?;
+        self.write_element(writer, "Day", &self.day)?;
+        self.write_element(writer, "Writer", &self.writer)?;
+        self.write_element(writer, "Penciller", &self.penciller)?;
+        self.write_element(writer, "Inker", &self.inker)?;
+        self.write_element(writer, "Colorist", &self.colorist)?;
+        self.write_element(writer, "Letterer", &self.letterer)?;
+        self.write_element(writer, "CoverArtist", &self.cover_artist)?;
+        self.write_element(writer, "Editor", &self.editor)?;
+        self.write_element(writer, "Publisher", &self.publisher)?;
+        self.write_element(writer, "Genre", &self.genre)?;
+        if !self.tags.is_empty() {
+            self.write_element(writer, "Tags", &Some(self.tags.join(", ")))?;
+        }
+        self.write_element(writer, "Web", &self.web)?;
+        if let Some(page_count) = self.page_count {
+            self.write_element(writer, "PageCount", &Some(page_count.to_string()))?;
+        }
+        self.write_element(writer, "LanguageISO", &self.language_iso)?;
+        self.write_element(writer, "Format", &self.format)?;
+        self.write_element(writer, "StoryArc", &self.story_arc)?;
+        self.write_element(writer, "AgeRating", &self.age_rating)?;
+        Ok(())
+    }
+
+    fn write_pages<W: std::io::Write>(&self, writer: &mut Writer<W>) -> Result<()> {
+        if !self.pages.is_empty() {
+            writer.write_event(Event::Start(BytesStart::new("Pages")))?;
+            for page in &self.pages {
+                let mut page_elem = BytesStart::new("Page");
+                page_elem.push_attribute(("Image", page.image.to_string().as_str()));
+                if let Some(page_type) = &page.page_type {
+                    page_elem.push_attribute(("Type", page_type.as_str()));
+                }
+                writer.write_event(Event::Empty(page_elem))?;
+            }
+            writer.write_event(Event::End(BytesEnd::new("Pages")))?;
+        }
+        Ok(())
     }
     
     fn write_element<W: std::io::Write>(
@@ -1,20 +1,119 @@
+use crate::metadata::Contributor;
 use crate::{EbookError, Metadata, Result};
-use crate::traits::{EbookReader, EbookWriter, EbookOperator, TocEntry, ImageData};
+use crate::traits::{Annotation, AnnotationLocation, ArchiveEntry, ChapterView, EbookReader, EbookWriter, EbookOperator, TocEntry, ImageData};
 use std::fs::File;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 use zip::ZipArchive;
 use zip::write::{ZipWriter, FileOptions};
 
-#[derive(Default)]
+mod validation;
+
+pub use validation::{Severity, ValidationIssue, ValidationReport};
+
+#[derive(Default, Clone)]
 pub struct EpubHandler {
     metadata: Metadata,
     content: String,
     chapters: Vec<Chapter>,
     images: Vec<ImageData>,
     toc: Vec<TocEntry>,
+    page_list: Vec<PageMarker>,
+    guide: Vec<GuideReference>,
     epub_version: EpubVersion,
+    chapters_from_toc: bool,
+    /// Spine chapters split at TOC anchors; only filled when `chapters_from_toc` is set
+    sections: Vec<Chapter>,
+    chapter_title_source: ChapterTitleSource,
+    /// Package-level `rendition:*` meta properties (layout, orientation, spread) in source order
+    rendition: Vec<(String, String)>,
+    /// Spine `page-progression-direction`; `None` leaves it to the reading system
+    page_progression: Option<PageProgression>,
+    /// Archive directory of the OPF the book was read from. When set, chapter and image
+    /// names are archive paths and a rewrite keeps them, so links between files still resolve.
+    package_dir: Option<String>,
+    /// Zip index of the archive the book was read from
+    entries: Vec<ArchiveEntry>,
+    /// The file the book was read from, for `get_resource` to read single entries of
+    source_path: Option<PathBuf>,
+    /// Manifest media-type of each archive path the OPF lists
+    manifest_types: HashMap<String, String>,
+    ncx_mode: NcxMode,
+    /// SMIL media overlays and audio of a read-aloud book, kept so a rewrite still narrates
+    media: Vec<MediaItem>,
+    /// Manifest id of the media overlay of each chapter that has one, keyed by chapter filename
+    media_overlays: HashMap<String, String>,
+    /// `media:*` meta properties (duration, active class, narrator) as (refined manifest id,
+    /// property, value); the id is `None` for the whole book
+    media_metadata: Vec<(Option<String>, String, String)>,
+    extraction_limits: crate::utils::ExtractionLimits,
+}
+
+/// A manifest item the handler doesn't interpret but carries through a rewrite
+#[derive(Debug, Clone)]
+struct MediaItem {
+    /// Manifest id in the source package, which overlays and refinements point at
+    id: String,
+    /// Archive path
+    name: String,
+    media_type: String,
+    data: Vec<u8>,
+}
+
+/// A print page marker from the EPUB 3 `page-list` navigation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageMarker {
+    /// Print page label, e.g. "42" or "xii"
+    pub label: String,
+    /// Location of the page break, relative to the navigation document
+    pub href: String,
+}
+
+impl PageMarker {
+    pub fn new(label: impl Into<String>, href: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            href: href.into(),
+        }
+    }
+}
+
+/// A key-location reference (cover, toc, start of text) from the EPUB 2 `<guide>`
+/// or the EPUB 3 `landmarks` nav
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuideReference {
+    /// Guide type in EPUB 2 vocabulary, e.g. "cover", "toc" or "text"
+    pub kind: String,
+    pub title: String,
+    /// Target document (optionally with a fragment), in the same form as chapter filenames
+    pub href: String,
+}
+
+impl GuideReference {
+    pub fn new(kind: impl Into<String>, title: impl Into<String>, href: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            title: title.into(),
+            href: href.into(),
+        }
+    }
+
+    /// The equivalent EPUB 3 landmark `epub:type`
+    pub fn landmark_type(&self) -> &str {
+        match self.kind.as_str() {
+            "text" => "bodymatter",
+            kind => kind,
+        }
+    }
+
+    fn from_landmark(landmark_type: &str, title: impl Into<String>, href: impl Into<String>) -> Self {
+        let kind = match landmark_type {
+            "bodymatter" => "text",
+            kind => kind,
+        };
+        Self::new(kind, title, href)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -25,12 +124,95 @@ pub enum EpubVersion {
     V3,
 }
 
+/// Reading direction of the spine, its `page-progression-direction`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageProgression {
+    LeftToRight,
+    /// Manga, Arabic and Hebrew books: pages turn from right to left
+    RightToLeft,
+}
+
+impl PageProgression {
+    /// The attribute value, `ltr` or `rtl`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::LeftToRight => "ltr",
+            Self::RightToLeft => "rtl",
+        }
+    }
+
+    /// The direction an attribute value declares; `None` for `default` or anything unknown
+    fn from_attribute(value: &str) -> Option<Self> {
+        match value.trim() {
+            "ltr" => Some(Self::LeftToRight),
+            "rtl" => Some(Self::RightToLeft),
+            _ => None,
+        }
+    }
+}
+
+/// Which navigation documents an EPUB is written with: the EPUB 2 NCX, the EPUB 3 nav or both
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NcxMode {
+    /// The nav for EPUB 3, plus an NCX for EPUB 2-only reading systems; EPUB 2 gets the NCX
+    #[default]
+    Both,
+    /// Nav only (pure EPUB 3); EPUB 2 can't be written without an NCX
+    Never,
+    /// NCX only; EPUB 3 requires a nav, so this is for EPUB 2
+    Only,
+}
+
+impl std::str::FromStr for NcxMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "both" => Ok(Self::Both),
+            "never" => Ok(Self::Never),
+            "only" => Ok(Self::Only),
+            other => Err(format!("unknown NCX mode '{other}' (expected both, never or only)")),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 struct Chapter {
     title: String,
     content: String,
     filename: String,
+    /// Spine itemref `properties`, e.g. `page-spread-left rendition:layout-pre-paginated`
+    properties: Option<String>,
+    /// Manifest media-type of a spine item that is not XHTML (an SVG or image page). Such a
+    /// page has no text; its bytes are kept with the images under the same path.
+    media_type: Option<String>,
+    /// Title and section id of each chapter merged into this one by `flatten_spine`
+    merged: Vec<(String, String)>,
+}
+
+/// Navigation read from an EPUB 2 NCX
+#[derive(Debug, Default)]
+struct NcxNavigation {
+    toc: Vec<TocEntry>,
+    page_list: Vec<PageMarker>,
+}
+
+/// A spine itemref's `idref` and `properties`
+type SpineItem = (String, Option<String>);
+
+/// A manifest item's `href`, `media-type` and `media-overlay`
+struct ManifestItem {
+    href: String,
+    media_type: String,
+    media_overlay: Option<String>,
+}
+
+/// An `<a>` entry of a navigation list
+#[derive(Debug, Default)]
+struct NavLink {
+    href: String,
+    label: String,
+    epub_type: Option<String>,
 }
 
 const STREAMING_THRESHOLD: u64 = 50 * 1024 * 1024; // 50 MB
@@ -48,12 +230,291 @@ impl EpubHandler {
         self.epub_version
     }
 
+    /// Whether the book has SMIL media overlays (read-aloud narration)
+    pub fn has_media_overlays(&self) -> bool {
+        self.media.iter().any(|item| item.media_type == "application/smil+xml")
+    }
+
+    /// Bounds on what reading an archive may decompress; see [`crate::utils::ExtractionLimits`]
+    pub fn set_extraction_limits(&mut self, limits: crate::utils::ExtractionLimits) {
+        self.extraction_limits = limits;
+    }
+
+    /// Write the NCX alongside the nav (the default), never, or instead of it (EPUB 2 only)
+    pub fn set_ncx_mode(&mut self, mode: NcxMode) {
+        self.ncx_mode = mode;
+    }
+
+    /// Print page markers parsed from (or to be written to) the `page-list` nav
+    pub fn get_page_list(&self) -> Vec<PageMarker> {
+        self.page_list.clone()
+    }
+
+    pub fn set_page_list(&mut self, page_list: Vec<PageMarker>) {
+        self.page_list = page_list;
+    }
+
+    pub fn add_page_marker(&mut self, label: impl Into<String>, href: impl Into<String>) {
+        self.page_list.push(PageMarker::new(label, href));
+    }
+
+    /// Guide references, written as `<guide>` for EPUB 2 and as the `landmarks` nav for EPUB 3
+    pub fn get_guide(&self) -> Vec<GuideReference> {
+        self.guide.clone()
+    }
+
+    pub fn set_guide(&mut self, guide: Vec<GuideReference>) {
+        self.guide = guide;
+    }
+
+    pub fn add_guide_reference(&mut self, kind: impl Into<String>, title: impl Into<String>, href: impl Into<String>) {
+        self.guide.push(GuideReference::new(kind, title, href));
+    }
+
+    /// Whether the book declares `rendition:layout` `pre-paginated` (comics, picture books)
+    pub fn is_fixed_layout(&self) -> bool {
+        self.rendition_property("rendition:layout") == Some("pre-paginated")
+    }
+
+    /// Value of a package-level rendition property such as `rendition:layout`
+    pub fn rendition_property(&self, property: &str) -> Option<&str> {
+        self.rendition
+            .iter()
+            .find(|(name, _)| name == property)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_rendition_property(&mut self, property: impl Into<String>, value: impl Into<String>) {
+        let property = property.into();
+        let value = value.into();
+        match self.rendition.iter_mut().find(|(name, _)| *name == property) {
+            Some(existing) => existing.1 = value,
+            None => self.rendition.push((property, value)),
+        }
+    }
+
+    /// The spine's `page-progression-direction`, when the book declares one
+    pub fn get_page_progression(&self) -> Option<PageProgression> {
+        self.page_progression
+    }
+
+    /// Declare the direction pages turn in, e.g. `RightToLeft` for manga; `None` drops it
+    pub fn set_page_progression(&mut self, direction: Option<PageProgression>) {
+        self.page_progression = direction;
+    }
+
+    /// Run epubcheck-style structural checks on the EPUB at `path`.
+    ///
+    /// Unlike `validate`, this inspects the raw archive: the mimetype entry, `container.xml`,
+    /// manifest ids and hrefs, spine idrefs, `dc:language` and EPUB 3 `dcterms:modified`.
+    pub fn validate_detailed(path: &Path) -> Result<ValidationReport> {
+        validation::validate(path)
+    }
+
+    /// Spine itemref `properties` of each chapter, in reading order
+    pub fn get_spine_properties(&self) -> Vec<Option<String>> {
+        self.chapters.iter().map(|chapter| chapter.properties.clone()).collect()
+    }
+
+    pub fn set_chapter_title_source(&mut self, source: ChapterTitleSource) {
+        self.chapter_title_source = source;
+    }
+
+    /// Split spine files at the TOC anchors pointing inside them when reading, so chapters
+    /// and TOC entries follow the book's own TOC rather than its file layout
+    pub fn set_chapters_from_toc(&mut self, chapters_from_toc: bool) {
+        self.chapters_from_toc = chapters_from_toc;
+    }
+
     /// Check if file should use streaming based on size
     pub fn should_use_streaming(path: &Path) -> Result<bool> {
         let metadata = std::fs::metadata(path)?;
         Ok(metadata.len() > STREAMING_THRESHOLD)
     }
 
+    /// Archive directory the OPF, NCX and nav document are written to
+    fn package_dir(&self) -> &str {
+        self.package_dir.as_deref().unwrap_or("OEBPS")
+    }
+
+    /// Archive path of a chapter or image name
+    fn archive_path(&self, name: &str) -> String {
+        match &self.package_dir {
+            Some(_) => name.to_string(),
+            None => format!("OEBPS/{name}"),
+        }
+    }
+
+    /// Href of a chapter, image or guide target relative to the package directory
+    fn package_href(&self, name: &str) -> String {
+        relative_href(self.package_dir(), &self.archive_path(name))
+    }
+
+    /// `file` in the package directory, as an archive path
+    fn package_file(&self, file: &str) -> String {
+        match self.package_dir() {
+            "" => file.to_string(),
+            dir => format!("{dir}/{file}"),
+        }
+    }
+
+    /// Main title (refined as `main`, else the first `dc:title`), its sort name and the subtitle
+    fn apply_title_refinements(&mut self, titles: Vec<(String, Option<String>)>, refinements: &[(String, String, String)]) {
+        let title_type = |id: &Option<String>| id.as_deref().and_then(|id| refined_value(refinements, id, "title-type"));
+        let main = titles
+            .iter()
+            .position(|(_, id)| title_type(id) == Some("main"))
+            .or_else(|| titles.iter().position(|(_, id)| title_type(id) != Some("subtitle")))
+            .or((!titles.is_empty()).then_some(0));
+        if let Some((title, id)) = main.map(|idx| &titles[idx]) {
+            self.metadata.title = Some(title.clone());
+            self.metadata.title_sort = id
+                .as_deref()
+                .and_then(|id| refined_value(refinements, id, "file-as"))
+                .map(str::to_string);
+        }
+        if let Some((subtitle, _)) = titles.iter().find(|(_, id)| title_type(id) == Some("subtitle")) {
+            self.metadata.subtitle = Some(subtitle.clone());
+        }
+    }
+
+    /// Fill the series from the first `belongs-to-collection` that is a series (or untyped),
+    /// unless a `calibre:series` entry already gave one
+    fn apply_collections(&mut self, collections: Vec<(String, String)>, refinements: &[(String, String, String)]) {
+        if self.metadata.series.is_some() {
+            return;
+        }
+        let series = collections.into_iter().find(|(id, _)| {
+            refined_value(refinements, id, "collection-type").is_none_or(|kind| kind.trim() == "series")
+        });
+        if let Some((id, name)) = series {
+            self.metadata.series = Some(name.trim().to_string());
+            if self.metadata.series_index.is_none() {
+                self.metadata.series_index =
+                    refined_value(refinements, &id, "group-position").and_then(|position| position.trim().parse().ok());
+            }
+        }
+    }
+
+    /// The `dc:title` element, with its title type, sort name and a subtitle as EPUB 3
+    /// refinements
+    fn title_elements(&self, title: &str) -> String {
+        use quick_xml::escape::escape;
+
+        let title = escape(title);
+        let (subtitle, title_sort) = (&self.metadata.subtitle, &self.metadata.title_sort);
+        if self.epub_version == EpubVersion::V2 || (subtitle.is_none() && title_sort.is_none()) {
+            return format!("    <dc:title>{title}</dc:title>");
+        }
+        let mut xml = format!(r##"    <dc:title id="title">{title}</dc:title>
+    <meta refines="#title" property="title-type">main</meta>"##);
+        if let Some(title_sort) = title_sort {
+            xml.push_str(&format!(r##"
+    <meta refines="#title" property="file-as">{}</meta>"##, escape(title_sort.as_str())));
+        }
+        if let Some(subtitle) = subtitle {
+            xml.push_str(&format!(r##"
+    <dc:title id="subtitle">{}</dc:title>
+    <meta refines="#subtitle" property="title-type">subtitle</meta>"##, escape(subtitle.as_str())));
+        }
+        xml
+    }
+
+    /// A `dc:creator` (authors) or `dc:contributor` element; EPUB 2 carries the role and sort
+    /// name as `opf:` attributes, EPUB 3 as `<meta refines>` elements
+    fn credit_element(&self, idx: usize, credit: &Contributor) -> String {
+        use quick_xml::escape::escape;
+
+        let element = if credit.is_author() { "dc:creator" } else { "dc:contributor" };
+        let name = escape(credit.name.as_str());
+        if self.epub_version == EpubVersion::V2 {
+            let mut attributes = String::new();
+            if let Some(role) = &credit.role {
+                attributes.push_str(&format!(r#" opf:role="{}""#, escape(role.as_str())));
+            }
+            if let Some(file_as) = &credit.file_as {
+                attributes.push_str(&format!(r#" opf:file-as="{}""#, escape(file_as.as_str())));
+            }
+            return format!("    <{element}{attributes}>{name}</{element}>");
+        }
+
+        let id = format!("credit{}", idx + 1);
+        let mut xml = format!(r#"    <{element} id="{id}">{name}</{element}>"#);
+        if let Some(role) = &credit.role {
+            xml.push_str(&format!(
+                r##"
+    <meta refines="#{id}" property="role" scheme="marc:relators">{}</meta>"##,
+                escape(role.as_str())
+            ));
+        }
+        if let Some(file_as) = &credit.file_as {
+            xml.push_str(&format!(r##"
+    <meta refines="#{id}" property="file-as">{}</meta>"##, escape(file_as.as_str())));
+        }
+        xml
+    }
+
+    /// Name for a generated package file that no chapter or image of the book already uses
+    fn unused_package_file(&self, stem: &str, extension: &str) -> String {
+        let taken = |file: &str| {
+            let path = self.package_file(file);
+            self.chapters.iter().any(|chapter| self.archive_path(&chapter.filename) == path)
+                || self.images.iter().any(|image| self.archive_path(&image.name) == path)
+        };
+        let mut file = format!("{stem}.{extension}");
+        let mut n = 1;
+        while taken(&file) {
+            file = format!("{stem}-{n}.{extension}");
+            n += 1;
+        }
+        file
+    }
+
+    /// Title and package-relative href of each TOC entry written: every chapter, then the
+    /// chapters merged into it
+    fn nav_points(&self) -> Vec<(String, String)> {
+        let mut points = Vec::new();
+        for chapter in &self.chapters {
+            let href = self.package_href(&chapter.filename);
+            points.push((chapter.title.clone(), href.clone()));
+            for (title, id) in &chapter.merged {
+                points.push((title.clone(), format!("{href}#{id}")));
+            }
+        }
+        points
+    }
+
+    /// The EPUB 2 NCX with a navPoint per TOC entry
+    fn generate_ncx(&self, title: &str, identifier: &str) -> String {
+        let mut ncx_content = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <head>
+    <meta name="dtb:uid" content="{}"/>
+    <meta name="dtb:depth" content="1"/>
+    <meta name="dtb:totalPageCount" content="0"/>
+    <meta name="dtb:maxPageNumber" content="0"/>
+  </head>
+  <docTitle>
+    <text>{}</text>
+  </docTitle>
+  <navMap>"#, identifier, title);
+
+        for (idx, (title, href)) in self.nav_points().into_iter().enumerate() {
+            ncx_content.push_str(&format!(r#"
+    <navPoint id="navPoint-{}" playOrder="{}">
+      <navLabel>
+        <text>{}</text>
+      </navLabel>
+      <content src="{}"/>
+    </navPoint>"#, idx, idx + 1, title, href));
+        }
+
+        ncx_content.push_str(r#"
+  </navMap>
+</ncx>"#);
+        ncx_content
+    }
+
     fn generate_nav_xhtml(&self) -> String {
         let mut nav = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>
 <!DOCTYPE html>
@@ -67,17 +528,48 @@ impl EpubHandler {
         <ol>
 "#);
 
-        for chapter in self.chapters.iter() {
-            nav.push_str(&format!(
-                "            <li><a href=\"{}\">{}</a></li>\n",
-                chapter.filename,
-                chapter.title
-            ));
+        for (title, href) in self.nav_points() {
+            nav.push_str(&format!("            <li><a href=\"{href}\">{title}</a></li>\n"));
         }
 
         nav.push_str(r#"        </ol>
     </nav>
-</body>
+"#);
+
+        if !self.page_list.is_empty() {
+            nav.push_str(r#"    <nav epub:type="page-list" id="page-list" hidden="">
+        <ol>
+"#);
+            for marker in &self.page_list {
+                nav.push_str(&format!(
+                    "            <li><a href=\"{}\">{}</a></li>\n",
+                    quick_xml::escape::escape(marker.href.as_str()),
+                    quick_xml::escape::escape(marker.label.as_str())
+                ));
+            }
+            nav.push_str(r#"        </ol>
+    </nav>
+"#);
+        }
+
+        if !self.guide.is_empty() {
+            nav.push_str(r#"    <nav epub:type="landmarks" id="landmarks" hidden="">
+        <ol>
+"#);
+            for reference in &self.guide {
+                nav.push_str(&format!(
+                    "            <li><a epub:type=\"{}\" href=\"{}\">{}</a></li>\n",
+                    quick_xml::escape::escape(reference.landmark_type()),
+                    quick_xml::escape::escape(self.package_href(&reference.href).as_str()),
+                    quick_xml::escape::escape(reference.title.as_str())
+                ));
+            }
+            nav.push_str(r#"        </ol>
+    </nav>
+"#);
+        }
+
+        nav.push_str(r#"</body>
 </html>"#);
 
         nav
@@ -94,51 +586,136 @@ impl EpubHandler {
         let mut in_metadata = false;
         let mut in_manifest = false;
         let mut in_spine = false;
+        let mut in_guide = false;
         let mut current_tag = String::new();
         let mut manifest_items: HashMap<String, String> = HashMap::new();
         let mut spine_items: Vec<String> = Vec::new();
         let mut cover_id: Option<String> = None;
+        let mut rendition_property: Option<String> = None;
+        // Credits are added once the whole package is read: EPUB 3 refines their role and
+        // sort name with `<meta refines="#id">` elements further down
+        let mut credits: Vec<Contributor> = Vec::new();
+        let mut credit_ids: HashMap<String, usize> = HashMap::new();
+        let mut pending_credit: Option<(Contributor, Option<String>)> = None;
+        let mut refinement: Option<(String, String)> = None;
+        // EPUB 3 `<meta refines="#id" property>` values as (id, property, value), resolved
+        // against the titles, credits and collections they point at once the package is read
+        let mut refinements: Vec<(String, String, String)> = Vec::new();
+        let mut titles: Vec<(String, Option<String>)> = Vec::new();
+        let mut pending_title_id: Option<String> = None;
+        let mut collections: Vec<(String, String)> = Vec::new();
+        let mut collection_id: Option<String> = None;
+        let mut media_property: Option<(Option<String>, String)> = None;
 
         loop {
             match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(e)) => {
+                // Manifest items, itemrefs and meta tags are usually self-closing
+                Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
                     let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                    if name == "metadata" {
+                    if name == "package" {
+                        // Keep the source version so a rewrite uses the same guide/landmarks form
+                        let version = e.attributes().flatten().find(|attr| attr.key.as_ref() == b"version");
+                        if let Some(version) = version {
+                            self.epub_version = if version.value.starts_with(b"2") {
+                                EpubVersion::V2
+                            } else {
+                                EpubVersion::V3
+                            };
+                        }
+                    } else if name == "metadata" {
                         in_metadata = true;
                     } else if name == "manifest" {
                         in_manifest = true;
                     } else if name == "spine" {
                         in_spine = true;
+                    } else if name == "guide" {
+                        in_guide = true;
+                    }
+
+                    if in_guide && name == "reference" {
+                        let mut reference = GuideReference::new("", "", "");
+                        for attr in e.attributes().flatten() {
+                            let value = attr.unescape_value().unwrap_or_default().to_string();
+                            match attr.key.as_ref() {
+                                b"type" => reference.kind = value,
+                                b"title" => reference.title = value,
+                                b"href" => reference.href = value,
+                                _ => {}
+                            }
+                        }
+                        if !reference.kind.is_empty() && !reference.href.is_empty() {
+                            self.guide.push(reference);
+                        }
+                    }
+
+                    // opf:role and opf:file-as (EPUB 2) travel on the element itself
+                    if in_metadata && (name == "dc:creator" || name == "dc:contributor") {
+                        let default_role = (name == "dc:contributor").then_some("ctb");
+                        let mut credit = Contributor::new("", default_role);
+                        let mut id = None;
+                        for attr in e.attributes().flatten() {
+                            let value = attr.unescape_value().unwrap_or_default().to_string();
+                            match attr.key.local_name().as_ref() {
+                                b"role" if !value.trim().is_empty() => credit.role = Some(value.trim().to_string()),
+                                b"file-as" if !value.trim().is_empty() => credit.file_as = Some(value),
+                                b"id" => id = Some(value),
+                                _ => {}
+                            }
+                        }
+                        pending_credit = Some((credit, id));
+                    }
+
+                    if in_metadata && name == "dc:title" {
+                        pending_title_id = e
+                            .attributes()
+                            .flatten()
+                            .find(|attr| attr.key.as_ref() == b"id")
+                            .map(|attr| attr.unescape_value().unwrap_or_default().to_string());
                     }
 
                     // Check for cover image in metadata
                     if in_metadata && name == "meta" {
-                        for attr in e.attributes() {
-                            if let Ok(attr) = attr {
-                                let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                                let value = String::from_utf8_lossy(&attr.value).to_string();
-                                if key == "name" && value == "cover" {
-                                    cover_id = Some(String::new()); // Will be filled by content attribute
-                                } else if key == "content" && cover_id.is_some() {
-                                    cover_id = Some(value);
-                                }
-                            }
+                        let attr = |key: &[u8]| {
+                            e.attributes()
+                                .flatten()
+                                .find(|attr| attr.key.as_ref() == key)
+                                .map(|attr| attr.unescape_value().unwrap_or_default().to_string())
+                        };
+                        // Attributes may come in any order, so look both up before deciding
+                        if attr(b"name").as_deref() == Some("cover")
+                            && let Some(content) = attr(b"content")
+                        {
+                            cover_id = Some(content);
+                        }
+                        if let Some(property) = attr(b"property")
+                            && property.starts_with("rendition:")
+                        {
+                            rendition_property = Some(property);
+                        }
+                        media_property = attr(b"property")
+                            .filter(|property| property.starts_with("media:"))
+                            .map(|property| (attr(b"refines").map(|id| id.trim_start_matches('#').to_string()), property));
+                        if let (Some(meta_name), Some(content)) = (attr(b"name"), attr(b"content")) {
+                            self.metadata.set_calibre_field(&meta_name, &content);
                         }
+                        refinement = attr(b"refines")
+                            .zip(attr(b"property"))
+                            .map(|(refines, property)| (refines.trim_start_matches('#').to_string(), property));
+                        collection_id = (refinement.is_none() && attr(b"property").as_deref() == Some("belongs-to-collection"))
+                            .then(|| attr(b"id").unwrap_or_default());
                     }
 
                     // Parse manifest items
                     if in_manifest && name == "item" {
                         let mut id = String::new();
                         let mut href = String::new();
-                        for attr in e.attributes() {
-                            if let Ok(attr) = attr {
-                                let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                                let value = String::from_utf8_lossy(&attr.value).to_string();
-                                if key == "id" {
-                                    id = value;
-                                } else if key == "href" {
-                                    href = value;
-                                }
+                        for attr in e.attributes().flatten() {
+                            let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                            let value = String::from_utf8_lossy(&attr.value).to_string();
+                            if key == "id" {
+                                id = value;
+                            } else if key == "href" {
+                                href = value;
                             }
                         }
                         if !id.is_empty() && !href.is_empty() {
@@ -148,43 +725,57 @@ impl EpubHandler {
 
                     // Parse spine items
                     if in_spine && name == "itemref" {
-                        for attr in e.attributes() {
-                            if let Ok(attr) = attr {
-                                let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                                if key == "idref" {
-                                    spine_items.push(String::from_utf8_lossy(&attr.value).to_string());
-                                }
+                        for attr in e.attributes().flatten() {
+                            let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                            if key == "idref" {
+                                spine_items.push(String::from_utf8_lossy(&attr.value).to_string());
                             }
                         }
                     }
 
                     current_tag = name;
                 }
-                Ok(Event::Text(e)) => {
-                    if in_metadata {
-                        let text = e.unescape().unwrap_or_default().to_string();
-                        match current_tag.as_str() {
-                            "dc:title" => self.metadata.title = Some(text),
-                            "dc:creator" => self.metadata.author = Some(text),
-                            "dc:publisher" => self.metadata.publisher = Some(text),
-                            "dc:description" => self.metadata.description = Some(text),
-                            "dc:language" => self.metadata.language = Some(text),
-                            "dc:identifier" => {
-                                if self.metadata.isbn.is_none() {
-                                    self.metadata.isbn = Some(text);
+                Ok(Event::Text(e)) if in_metadata => {
+                    let text = e.unescape().unwrap_or_default().to_string();
+                    match current_tag.as_str() {
+                        "dc:title" => titles.push((text, pending_title_id.take())),
+                        "dc:creator" | "dc:contributor" => {
+                            if let Some((mut credit, id)) = pending_credit.take() {
+                                credit.name = text;
+                                if let Some(id) = id {
+                                    credit_ids.insert(id, credits.len());
                                 }
+                                credits.push(credit);
                             }
-                            "dc:date" => self.metadata.publication_date = Some(text),
-                            "dc:subject" => {
-                                if self.metadata.tags.is_none() {
-                                    self.metadata.tags = Some(Vec::new());
-                                }
-                                if let Some(tags) = &mut self.metadata.tags {
-                                    tags.push(text);
-                                }
+                        }
+                        "dc:publisher" => self.metadata.publisher = Some(text),
+                        "dc:description" => self.metadata.description = Some(text),
+                        "dc:language" => self.metadata.language = Some(text),
+                        "dc:identifier" if self.metadata.isbn.is_none() => {
+                            self.metadata.isbn = Some(text);
+                        }
+                        "dc:date" => self.metadata.publication_date = Some(text),
+                        "dc:rights" => self.metadata.rights = Some(text),
+                        "meta" => {
+                            if let Some(property) = rendition_property.take() {
+                                self.set_rendition_property(property, text);
+                            } else if let Some((id, property)) = media_property.take() {
+                                self.media_metadata.push((id, property, text));
+                            } else if let Some((id, property)) = refinement.take() {
+                                refinements.push((id, property, text));
+                            } else if let Some(id) = collection_id.take() {
+                                collections.push((id, text));
                             }
-                            _ => {}
                         }
+                        "dc:subject" => {
+                            if self.metadata.tags.is_none() {
+                                self.metadata.tags = Some(Vec::new());
+                            }
+                            if let Some(tags) = &mut self.metadata.tags {
+                                tags.push(text);
+                            }
+                        }
+                        _ => {}
                     }
                 }
                 Ok(Event::End(e)) => {
@@ -195,6 +786,8 @@ impl EpubHandler {
                         in_manifest = false;
                     } else if name == "spine" {
                         in_spine = false;
+                    } else if name == "guide" {
+                        in_guide = false;
                     }
                 }
                 Ok(Event::Eof) => break,
@@ -204,19 +797,34 @@ impl EpubHandler {
             buf.clear();
         }
 
-        // Store cover image path if found
-        if let Some(cover) = cover_id {
-            if let Some(cover_path) = manifest_items.get(&cover) {
-                self.metadata.cover_image_path = Some(cover_path.clone());
+        for (id, property, value) in &refinements {
+            if let Some(credit) = credit_ids.get(id).and_then(|&idx| credits.get_mut(idx)) {
+                match property.as_str() {
+                    "role" => credit.role = Some(value.clone()),
+                    "file-as" => credit.file_as = Some(value.clone()),
+                    _ => {}
+                }
             }
         }
+        for credit in credits {
+            self.metadata.add_contributor(credit);
+        }
+        self.apply_title_refinements(titles, &refinements);
+        self.apply_collections(collections, &refinements);
+
+        // Store cover image path if found
+        if let Some(cover) = cover_id
+            && let Some(cover_path) = manifest_items.get(&cover)
+        {
+            self.metadata.cover_image_path = Some(cover_path.clone());
+        }
 
         self.metadata.format = Some("EPUB".to_string());
         Ok(())
     }
 
     fn find_opf_path(archive: &mut ZipArchive<File>) -> Result<String> {
-        let container = archive.by_name("META-INF/container.xml")?;
+        let container = crate::utils::zip_entry(archive, "META-INF/container.xml")?;
         let mut content = String::new();
         std::io::BufReader::new(container).read_to_string(&mut content)?;
 
@@ -226,150 +834,1322 @@ impl EpubHandler {
         let mut reader = Reader::from_str(&content);
         let mut buf = Vec::new();
 
-        loop {
-            match reader.read_event_into(&mut buf) {
-                Ok(Event::Empty(e)) | Ok(Event::Start(e)) => {
-                    if e.name().as_ref() == b"rootfile" {
-                        for attr in e.attributes() {
-                            if let Ok(attr) = attr {
-                                if attr.key.as_ref() == b"full-path" {
-                                    return Ok(String::from_utf8_lossy(&attr.value).to_string());
-                                }
-                            }
-                        }
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Empty(e)) | Ok(Event::Start(e)) if e.name().as_ref() == b"rootfile" => {
+                    for attr in e.attributes().flatten() {
+                        if attr.key.as_ref() == b"full-path" {
+                            return Ok(crate::utils::normalize_entry_name(&String::from_utf8_lossy(&attr.value)));
+                        }
+                    }
+                }
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(EbookError::Xml(e.to_string())),
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Err(EbookError::NotFound("OPF path not found".to_string()))
+    }
+
+    /// Href of the manifest item flagged as the EPUB 3 navigation document
+    fn find_nav_href(opf_content: &str) -> Option<String> {
+        Self::find_manifest_href(opf_content, |_, properties| {
+            properties.split_whitespace().any(|p| p == "nav")
+        })
+    }
+
+    /// Href of the NCX manifest item
+    fn find_ncx_href(opf_content: &str) -> Option<String> {
+        Self::find_manifest_href(opf_content, |media_type, _| media_type == "application/x-dtbncx+xml")
+    }
+
+    /// Href of the first manifest item whose `(media-type, properties)` satisfy `matches`
+    fn find_manifest_href(opf_content: &str, matches: impl Fn(&str, &str) -> bool) -> Option<String> {
+        use quick_xml::Reader;
+        use quick_xml::events::Event;
+
+        let mut reader = Reader::from_str(opf_content);
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Empty(e)) | Ok(Event::Start(e)) if e.name().as_ref() == b"item" => {
+                    let mut href = None;
+                    let mut media_type = String::new();
+                    let mut properties = String::new();
+                    for attr in e.attributes().flatten() {
+                        let value = String::from_utf8_lossy(&attr.value).to_string();
+                        match attr.key.as_ref() {
+                            b"href" => href = Some(value),
+                            b"media-type" => media_type = value,
+                            b"properties" => properties = value,
+                            _ => {}
+                        }
+                    }
+                    if matches(&media_type, &properties) {
+                        return href;
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => return None,
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+
+    /// Collect the `<a>` entries of the `nav epub:type="page-list"` element
+    fn parse_page_list(nav_content: &str) -> Vec<PageMarker> {
+        Self::parse_nav_links(nav_content, "page-list")
+            .into_iter()
+            .map(|link| PageMarker::new(link.label, link.href))
+            .collect()
+    }
+
+    /// Collect the `<a>` entries of the `nav epub:type="landmarks"` element
+    fn parse_landmarks(nav_content: &str) -> Vec<GuideReference> {
+        Self::parse_nav_links(nav_content, "landmarks")
+            .into_iter()
+            .filter_map(|link| {
+                let landmark_type = link.epub_type?;
+                Some(GuideReference::from_landmark(&landmark_type, link.label, link.href))
+            })
+            .collect()
+    }
+
+    /// Collect the `<a>` entries inside the first `nav` whose `epub:type` includes `nav_type`
+    fn parse_nav_links(nav_content: &str, nav_type: &str) -> Vec<NavLink> {
+        use quick_xml::Reader;
+        use quick_xml::events::Event;
+
+        let mut reader = Reader::from_str(nav_content);
+        reader.config_mut().trim_text(true);
+
+        let mut buf = Vec::new();
+        let mut links = Vec::new();
+        let mut nav_depth = 0usize;
+        let mut target_depth = None;
+        let mut current: Option<NavLink> = None;
+
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(e)) => match e.name().as_ref() {
+                    b"nav" => {
+                        nav_depth += 1;
+                        let is_target = e.attributes().flatten().any(|attr| {
+                            attr.key.as_ref() == b"epub:type"
+                                && String::from_utf8_lossy(&attr.value)
+                                    .split_whitespace()
+                                    .any(|t| t == nav_type)
+                        });
+                        if is_target && target_depth.is_none() {
+                            target_depth = Some(nav_depth);
+                        }
+                    }
+                    b"a" if target_depth.is_some() => {
+                        let mut link = NavLink::default();
+                        let mut has_href = false;
+                        for attr in e.attributes().flatten() {
+                            let value = attr.unescape_value().unwrap_or_default().to_string();
+                            match attr.key.as_ref() {
+                                b"href" => {
+                                    link.href = value;
+                                    has_href = true;
+                                }
+                                b"epub:type" => link.epub_type = Some(value),
+                                _ => {}
+                            }
+                        }
+                        current = has_href.then_some(link);
+                    }
+                    _ => {}
+                },
+                Ok(Event::Text(e)) => {
+                    if let Some(link) = current.as_mut() {
+                        link.label.push_str(&e.unescape().unwrap_or_default());
+                    }
+                }
+                Ok(Event::End(e)) => match e.name().as_ref() {
+                    b"a" => {
+                        if let Some(mut link) = current.take() {
+                            link.label = link.label.trim().to_string();
+                            links.push(link);
+                        }
+                    }
+                    b"nav" => {
+                        if target_depth == Some(nav_depth) {
+                            target_depth = None;
+                        }
+                        nav_depth = nav_depth.saturating_sub(1);
+                    }
+                    _ => {}
+                },
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        links
+    }
+
+    /// Collect the `navPoint` targets of an NCX `navMap`, in document order
+    fn parse_ncx_links(ncx_content: &str) -> Vec<NavLink> {
+        fn flatten(entries: Vec<TocEntry>, links: &mut Vec<NavLink>) {
+            for entry in entries {
+                if let Some(href) = entry.href {
+                    links.push(NavLink { href, label: entry.title, epub_type: None });
+                }
+                flatten(entry.children, links);
+            }
+        }
+
+        let mut links = Vec::new();
+        flatten(Self::parse_ncx(ncx_content).toc, &mut links);
+        links
+    }
+
+    /// Parse an NCX: the nested `navMap` (ids from `playOrder`, siblings in play order) and the
+    /// `pageList`. Hrefs are left relative to the NCX. `navList`s (lists of illustrations,
+    /// tables, ...) are not part of the TOC and are skipped.
+    fn parse_ncx(ncx_content: &str) -> NcxNavigation {
+        use quick_xml::Reader;
+        use quick_xml::events::Event;
+
+        let mut reader = Reader::from_str(ncx_content);
+        reader.config_mut().trim_text(true);
+
+        let mut buf = Vec::new();
+        let mut navigation = NcxNavigation::default();
+        let mut in_nav_map = false;
+        let mut in_page_list = false;
+        let mut in_label = false;
+        let mut label = String::new();
+        // Open navPoints, innermost last
+        let mut open: Vec<TocEntry> = Vec::new();
+        let mut page: Option<PageMarker> = None;
+        let mut next_id = 1;
+
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(e)) => match e.local_name().as_ref() {
+                    b"navMap" => in_nav_map = true,
+                    b"pageList" => in_page_list = true,
+                    b"navPoint" if in_nav_map => {
+                        let play_order = e
+                            .attributes()
+                            .flatten()
+                            .find(|attr| attr.key.as_ref() == b"playOrder")
+                            .and_then(|attr| String::from_utf8_lossy(&attr.value).trim().parse().ok());
+                        open.push(TocEntry::new(String::new(), open.len()).with_id(play_order.unwrap_or(next_id)));
+                        next_id += 1;
+                    }
+                    b"pageTarget" if in_page_list => page = Some(PageMarker::new("", "")),
+                    b"navLabel" => {
+                        in_label = true;
+                        label.clear();
+                    }
+                    _ => {}
+                },
+                Ok(Event::Empty(e)) if e.local_name().as_ref() == b"content" => {
+                    let src = e
+                        .attributes()
+                        .flatten()
+                        .find(|attr| attr.key.as_ref() == b"src")
+                        .map(|attr| String::from_utf8_lossy(&attr.value).into_owned());
+                    if let Some(src) = src {
+                        if let Some(page) = page.as_mut() {
+                            page.href = src;
+                        } else if let Some(entry) = open.last_mut().filter(|entry| entry.href.is_none()) {
+                            entry.href = Some(src);
+                        }
+                    }
+                }
+                Ok(Event::Text(e)) if in_label => {
+                    label.push_str(&e.unescape().unwrap_or_default());
+                }
+                Ok(Event::End(e)) => match e.local_name().as_ref() {
+                    b"navMap" => in_nav_map = false,
+                    b"pageList" => in_page_list = false,
+                    b"navLabel" => {
+                        in_label = false;
+                        let text = label.trim().to_string();
+                        if let Some(page) = page.as_mut() {
+                            page.label = text;
+                        } else if let Some(entry) = open.last_mut().filter(|entry| entry.title.is_empty()) {
+                            entry.title = text;
+                        }
+                    }
+                    b"navPoint" => {
+                        if let Some(mut entry) = open.pop() {
+                            entry.children.sort_by_key(|child| child.id);
+                            match open.last_mut() {
+                                Some(parent) => parent.children.push(entry),
+                                None => navigation.toc.push(entry),
+                            }
+                        }
+                    }
+                    b"pageTarget" => {
+                        if let Some(page) = page.take().filter(|page| !page.href.is_empty()) {
+                            navigation.page_list.push(page);
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        navigation.toc.sort_by_key(|entry| entry.id);
+        navigation
+    }
+
+    /// The NCX named in the manifest, with TOC hrefs resolved against the NCX's directory
+    fn read_ncx(archive: &mut ZipArchive<File>, opf_content: &str, opf_dir: &str) -> Result<Option<NcxNavigation>> {
+        fn resolve_all(entries: &mut [TocEntry], dir: &str) {
+            for entry in entries {
+                if let Some(href) = &entry.href {
+                    entry.href = Some(resolve_href(dir, href));
+                }
+                resolve_all(&mut entry.children, dir);
+            }
+        }
+
+        let Some(href) = Self::find_ncx_href(opf_content) else { return Ok(None) };
+        let path = resolve_href(opf_dir, &href);
+        let Ok(mut file) = crate::utils::zip_entry(archive, &path) else { return Ok(None) };
+
+        let mut document = String::new();
+        file.read_to_string(&mut document)?;
+        let mut navigation = Self::parse_ncx(&document);
+        resolve_all(&mut navigation.toc, path.rsplit_once('/').map_or("", |(dir, _)| dir));
+        Ok(Some(navigation))
+    }
+
+    /// Byte offset of the start tag of every element with an `id`, keyed by id
+    fn element_id_offsets(content: &str) -> HashMap<String, usize> {
+        use quick_xml::Reader;
+        use quick_xml::events::Event;
+
+        let mut reader = Reader::from_str(content);
+        reader.config_mut().check_end_names = false;
+
+        let mut offsets = HashMap::new();
+        loop {
+            // Text events stop at '<', so this is where the next tag starts
+            let offset = reader.buffer_position() as usize;
+            match reader.read_event() {
+                Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                    for attr in e.attributes().flatten() {
+                        if attr.key.local_name().as_ref() == b"id" {
+                            let id = String::from_utf8_lossy(&attr.value).to_string();
+                            offsets.entry(id).or_insert(offset);
+                        }
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+
+        offsets
+    }
+
+    /// Split each spine chapter at the TOC links pointing into it. Text before the first
+    /// anchor stays with the first section; chapters no link points into are kept whole.
+    fn split_at_toc_anchors(chapters: &[Chapter], links: &[NavLink]) -> Vec<Chapter> {
+        let mut sections = Vec::new();
+
+        for chapter in chapters {
+            let ids = Self::element_id_offsets(&chapter.content);
+            let mut starts: Vec<(usize, &NavLink)> = links
+                .iter()
+                .filter_map(|link| {
+                    let (path, fragment) = match link.href.split_once('#') {
+                        Some((path, fragment)) => (path, Some(fragment)),
+                        None => (link.href.as_str(), None),
+                    };
+                    if path != chapter.filename {
+                        return None;
+                    }
+                    match fragment {
+                        Some(id) => ids.get(id).map(|&offset| (offset, link)),
+                        None => Some((0, link)),
+                    }
+                })
+                .collect();
+
+            if starts.is_empty() {
+                sections.push(chapter.clone());
+                continue;
+            }
+            starts.sort_by_key(|&(offset, _)| offset);
+            starts.dedup_by_key(|&mut (offset, _)| offset);
+            starts[0].0 = 0;
+
+            for (idx, &(start, link)) in starts.iter().enumerate() {
+                let end = starts.get(idx + 1).map_or(chapter.content.len(), |&(next, _)| next);
+                sections.push(Chapter {
+                    title: if link.label.is_empty() { chapter.title.clone() } else { link.label.clone() },
+                    content: chapter.content[start..end].to_string(),
+                    filename: link.href.clone(),
+                    properties: chapter.properties.clone(),
+                    media_type: chapter.media_type.clone(),
+                    merged: Vec::new(),
+                });
+            }
+        }
+
+        sections
+    }
+
+    /// TOC links from the EPUB 3 `toc` nav, or the NCX for EPUB 2, with hrefs resolved to
+    /// archive paths
+    fn read_toc_links(archive: &mut ZipArchive<File>, opf_content: &str, opf_dir: &str) -> Result<Vec<NavLink>> {
+        let nav_href = Self::find_nav_href(opf_content);
+        let links = Self::read_nav_document_links(archive, opf_dir, nav_href, |nav| Self::parse_nav_links(nav, "toc"))?;
+        if !links.is_empty() {
+            return Ok(links);
+        }
+        Self::read_nav_document_links(archive, opf_dir, Self::find_ncx_href(opf_content), Self::parse_ncx_links)
+    }
+
+    /// Parse the navigation document at `href` (relative to the OPF) and resolve its link
+    /// hrefs against the document's own directory
+    fn read_nav_document_links(
+        archive: &mut ZipArchive<File>,
+        opf_dir: &str,
+        href: Option<String>,
+        parse: impl Fn(&str) -> Vec<NavLink>,
+    ) -> Result<Vec<NavLink>> {
+        let Some(href) = href else { return Ok(Vec::new()) };
+        let path = resolve_href(opf_dir, &href);
+        let Ok(mut file) = crate::utils::zip_entry(archive, &path) else { return Ok(Vec::new()) };
+
+        let mut document = String::new();
+        file.read_to_string(&mut document)?;
+        let document_dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        Ok(parse(&document)
+            .into_iter()
+            .map(|link| NavLink { href: resolve_href(document_dir, &link.href), ..link })
+            .collect())
+    }
+
+    /// Chapters in reading order: the TOC-split sections when there are any
+    fn reading_chapters(&self) -> &[Chapter] {
+        if self.sections.is_empty() {
+            &self.chapters
+        } else {
+            &self.sections
+        }
+    }
+}
+
+/// Href from `base_dir` to the archive path `target`, the inverse of `resolve_href`
+fn relative_href(base_dir: &str, target: &str) -> String {
+    let base: Vec<&str> = base_dir.split('/').filter(|part| !part.is_empty()).collect();
+    let target: Vec<&str> = target.split('/').collect();
+    let common = base
+        .iter()
+        .zip(&target[..target.len() - 1])
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut parts = vec![".."; base.len() - common];
+    parts.extend(&target[common..]);
+    parts.join("/")
+}
+
+/// Join a relative `href` onto an archive directory, resolving `.` and `..` segments
+fn resolve_href(base_dir: &str, href: &str) -> String {
+    let mut parts: Vec<&str> = base_dir.split('/').filter(|part| !part.is_empty()).collect();
+    for part in href.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// The children of a document's `<body>`, or the whole text of a bare fragment
+fn body_inner(content: &str) -> &str {
+    let lower = content.to_ascii_lowercase();
+    let Some(open) = lower.find("<body") else { return content };
+    let Some(start) = lower[open..].find('>').map(|end| open + end + 1) else { return content };
+    let end = lower.rfind("</body>").filter(|&end| end >= start).unwrap_or(content.len());
+    &content[start..end]
+}
+
+/// Whether a content document's body shows anything: text other than whitespace and
+/// non-breaking spaces, or an image, SVG, media or embedded object
+fn has_visible_content(content: &str) -> bool {
+    use quick_xml::Reader;
+    use quick_xml::events::Event;
+
+    const MEDIA: [&[u8]; 9] = [b"img", b"image", b"svg", b"video", b"audio", b"object", b"embed", b"iframe", b"math"];
+    let mut reader = Reader::from_str(body_inner(content));
+    reader.config_mut().check_end_names = false;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if MEDIA.contains(&e.local_name().as_ref()) => return true,
+            Ok(Event::Text(e)) => {
+                let text = String::from_utf8_lossy(&e);
+                if !text.replace("&nbsp;", " ").replace("&#160;", " ").replace("&#xa0;", " ").trim().is_empty() {
+                    return true;
+                }
+            }
+            Ok(Event::CData(e)) if !e.iter().all(u8::is_ascii_whitespace) => return true,
+            Ok(Event::Eof) => return false,
+            // Unparseable markup: keep the chapter rather than guess
+            Err(_) => return true,
+            _ => {}
+        }
+    }
+}
+
+/// Replace each `href` attribute value for which `rewrite` returns a new one
+fn rewrite_hrefs(content: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    rewrite_attribute_values(content, "href", rewrite)
+}
+
+/// Replace each value of the attributes matching `names` (a regex alternation such as
+/// `href|src`) for which `rewrite` returns a new one
+fn rewrite_attribute_values(content: &str, names: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    let pattern = regex::Regex::new(&format!(r#"(\b(?:{names})\s*=\s*)(?:"([^"]*)"|'([^']*)')"#)).unwrap();
+    pattern
+        .replace_all(content, |captures: &regex::Captures| {
+            let (value, quote) = match captures.get(2) {
+                Some(value) => (value.as_str(), '"'),
+                None => (captures.get(3).map_or("", |value| value.as_str()), '\''),
+            };
+            match rewrite(value) {
+                Some(value) => format!("{}{quote}{value}{quote}", &captures[1]),
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Replace each CSS `url(...)` (in `<style>` blocks and `style` attributes) whose target
+/// `rewrite` returns a new one for, keeping its quoting
+fn rewrite_css_urls(content: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    let pattern = regex::Regex::new(r#"url\(\s*(?:"([^"]*)"|'([^']*)'|([^"')\s]*))\s*\)"#).unwrap();
+    pattern
+        .replace_all(content, |captures: &regex::Captures| {
+            let (value, quote) = match (captures.get(1), captures.get(2)) {
+                (Some(value), _) => (value.as_str(), "\""),
+                (_, Some(value)) => (value.as_str(), "'"),
+                _ => (captures.get(3).map_or("", |value| value.as_str()), ""),
+            };
+            match rewrite(value) {
+                Some(value) => format!("url({quote}{value}{quote})"),
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// The last value a `<meta refines="#id">` gives `property`
+fn refined_value<'a>(refinements: &'a [(String, String, String)], id: &str, property: &str) -> Option<&'a str> {
+    refinements
+        .iter()
+        .rev()
+        .find(|(target, name, _)| target == id && name == property)
+        .map(|(_, _, value)| value.as_str())
+}
+
+/// Whether a spine item's media-type makes it a text content document
+fn is_xhtml_media_type(media_type: &str) -> bool {
+    matches!(media_type, "" | "application/xhtml+xml" | "text/html")
+}
+
+/// Whether a manifest item is part of a read-aloud book's narration: a SMIL media overlay or audio
+fn is_media_overlay_type(media_type: &str) -> bool {
+    media_type == "application/smil+xml" || media_type.starts_with("audio/")
+}
+
+/// Whether an archive entry is one of the images the handler extracts
+fn is_image_entry(name: &str) -> bool {
+    [".jpg", ".jpeg", ".png", ".gif", ".svg", ".webp"].iter().any(|ext| name.ends_with(ext))
+}
+
+/// Whether the entry's format is compressed already, so deflating it again gains nothing
+fn is_compressed_entry(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    [".jpg", ".jpeg", ".png", ".gif", ".webp", ".mp3", ".m4a", ".mp4", ".aac", ".ogg", ".opus", ".woff", ".woff2"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+impl EbookReader for EpubHandler {
+    fn read_from_file(&mut self, path: &Path) -> Result<()> {
+        log::info!("Reading EPUB file: {path:?}");
+        let file = File::open(path)?;
+        let mut archive = ZipArchive::new(file)?;
+        log::debug!("EPUB archive opened with {} files", archive.len());
+        self.entries = ArchiveEntry::list(&mut archive, is_image_entry)?;
+        let mut budget = crate::utils::ExtractionBudget::new(self.extraction_limits);
+        budget.check_declared(&mut archive)?;
+
+        let opf_path = Self::find_opf_path(&mut archive)?;
+
+        let opf_content = budget.read_string(&mut crate::utils::zip_entry(&mut archive, &opf_path)?)?;
+
+        self.parse_opf(&opf_content)?;
+
+        // Guide hrefs are relative to the OPF; store them in the same form as chapter filenames
+        let opf_dir_prefix = opf_path.rsplit_once('/').map(|(dir, _)| format!("{dir}/")).unwrap_or_default();
+        for reference in &mut self.guide {
+            reference.href = format!("{opf_dir_prefix}{}", reference.href);
+        }
+
+        // Parse spine and manifest to get ordered chapter list
+        let opf_dir = opf_path.rsplit_once('/').map_or("", |(dir, _)| dir).to_string();
+        self.package_dir = Some(opf_dir.clone());
+        let (spine_items, manifest_items) = self.parse_spine_and_manifest(&opf_content)?;
+        self.source_path = Some(path.to_path_buf());
+        self.manifest_types = manifest_items
+            .values()
+            .map(|item| (resolve_href(&opf_dir, &item.href), item.media_type.clone()))
+            .collect();
+
+        // Read content files in spine order
+        for (idx, (itemref, properties)) in spine_items.iter().enumerate() {
+            if let Some(item) = manifest_items.get(itemref) {
+                let full_path = crate::utils::normalize_entry_name(&if opf_dir.is_empty() {
+                    item.href.clone()
+                } else {
+                    format!("{opf_dir}/{}", item.href)
+                });
+                if let Some(overlay) = &item.media_overlay {
+                    self.media_overlays.insert(full_path.clone(), overlay.clone());
+                }
+
+                if let Ok(mut file) = crate::utils::zip_entry(&mut archive, &full_path) {
+                    let bytes = budget.read(&mut file)?;
+
+                    // SVG and image pages have no text to extract; keep their bytes for rewriting
+                    if !is_xhtml_media_type(&item.media_type) {
+                        if !is_image_entry(&full_path) && !self.images.iter().any(|image| image.name == full_path) {
+                            self.images.push(ImageData::new(full_path.clone(), item.media_type.clone(), bytes));
+                        }
+                        let title = format!("Page {}", idx + 1);
+                        self.toc.push(TocEntry {
+                            id: idx as u32,
+                            level: 0,
+                            title: title.clone(),
+                            href: Some(full_path.clone()),
+                            children: Vec::new(),
+                        });
+                        self.chapters.push(Chapter {
+                            title,
+                            content: String::new(),
+                            filename: full_path,
+                            properties: properties.clone(),
+                            media_type: Some(item.media_type.clone()),
+                            merged: Vec::new(),
+                        });
+                        continue;
+                    }
+
+                    let content = Self::decode_content_document(&bytes);
+
+                    // Extract title from content
+                    let title = self.extract_chapter_title(&content)
+                        .unwrap_or_else(|| format!("Chapter {}", idx + 1));
+
+                    self.chapters.push(Chapter {
+                        title,
+                        content: content.clone(),
+                        filename: full_path.clone(),
+                        properties: properties.clone(),
+                        media_type: None,
+                        merged: Vec::new(),
+                    });
+
+                    self.content.push_str(&content);
+                    self.content.push('\n');
+
+                    // Add to TOC
+                    self.toc.push(TocEntry {
+                        id: idx as u32,
+                        level: 0,
+                        title: self.chapters.last().map(|c| c.title.clone()).unwrap_or_default(),
+                        href: Some(full_path.clone()),
+                        children: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        // Media overlays and their audio aren't interpreted, only carried along
+        let mut media_items: Vec<(&String, &ManifestItem)> =
+            manifest_items.iter().filter(|(_, item)| is_media_overlay_type(&item.media_type)).collect();
+        media_items.sort_by(|a, b| a.1.href.cmp(&b.1.href));
+        for (id, item) in media_items {
+            let name = resolve_href(&opf_dir, &item.href);
+            let Ok(mut file) = crate::utils::zip_entry(&mut archive, &name) else { continue };
+            let data = budget.read(&mut file)?;
+            self.media.push(MediaItem { id: id.clone(), name, media_type: item.media_type.clone(), data });
+        }
+
+        if self.chapters_from_toc {
+            let links = Self::read_toc_links(&mut archive, &opf_content, &opf_dir)?;
+            self.sections = Self::split_at_toc_anchors(&self.chapters, &links);
+            self.toc = self
+                .sections
+                .iter()
+                .enumerate()
+                .map(|(idx, section)| {
+                    TocEntry::new(section.title.clone(), 0)
+                        .with_id(idx as u32)
+                        .with_href(section.filename.clone())
+                })
+                .collect();
+        } else if self.epub_version == EpubVersion::V2 {
+            // EPUB 2 has no nav document; the NCX carries the nested TOC and print pages
+            if let Some(navigation) = Self::read_ncx(&mut archive, &opf_content, &opf_dir)? {
+                if !navigation.toc.is_empty() {
+                    self.toc = navigation.toc;
+                }
+                self.page_list = navigation.page_list;
+            }
+        }
+
+        // EPUB 3 print page markers live in the navigation document
+        if let Some(nav_href) = Self::find_nav_href(&opf_content) {
+            let nav_path = crate::utils::normalize_entry_name(&if opf_dir.is_empty() {
+                nav_href
+            } else {
+                format!("{opf_dir}/{nav_href}")
+            });
+            if let Ok(mut file) = crate::utils::zip_entry(&mut archive, &nav_path) {
+                let nav_content = budget.read_string(&mut file)?;
+                self.page_list = Self::parse_page_list(&nav_content);
+
+                // EPUB 3 books may carry landmarks instead of (or alongside) a guide
+                if self.guide.is_empty() {
+                    let nav_dir_prefix = nav_path.rsplit_once('/').map(|(dir, _)| format!("{dir}/")).unwrap_or_default();
+                    self.guide = Self::parse_landmarks(&nav_content)
+                        .into_iter()
+                        .map(|reference| {
+                            let href = format!("{nav_dir_prefix}{}", reference.href);
+                            GuideReference { href, ..reference }
+                        })
+                        .collect();
+                }
+            }
+        }
+
+        // Extract images
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            let name = crate::utils::normalize_entry_name(file.name());
+
+            if is_image_entry(&name) {
+                let data = budget.read(&mut file)?;
+                let mime_type = crate::utils::guess_mime_type(&name);
+                self.images.push(ImageData::new(name, mime_type, data));
+            }
+        }
+
+        // A declared cover is relative to the OPF; store it as an archive path like the images.
+        // Without one, a first spine page showing a single image (or being one) is the cover page.
+        self.metadata.cover_image_path = match self.metadata.cover_image_path.take() {
+            Some(cover) => Some(resolve_href(&opf_dir, &cover)),
+            None => self.chapters.first().and_then(|chapter| {
+                if let Some(media_type) = &chapter.media_type {
+                    let raster = media_type.starts_with("image/") && media_type != "image/svg+xml";
+                    return raster.then(|| chapter.filename.clone());
+                }
+                let sources = image_sources(&chapter.content);
+                let [source] = sources.as_slice() else { return None };
+                let chapter_dir = chapter.filename.rsplit_once('/').map_or("", |(dir, _)| dir);
+                let path = resolve_href(chapter_dir, source);
+                self.images.iter().any(|image| image.name == path).then_some(path)
+            }),
+        };
+
+        Ok(())
+    }
+
+    fn get_metadata(&self) -> Result<Metadata> {
+        Ok(self.metadata.clone())
+    }
+
+    fn get_content(&self) -> Result<String> {
+        Ok(self.content.clone())
+    }
+
+    fn get_toc(&self) -> Result<Vec<TocEntry>> {
+        Ok(self.toc.clone())
+    }
+
+    fn extract_images(&self) -> Result<Vec<ImageData>> {
+        Ok(self.images.clone())
+    }
+
+    /// Content of the spine item at `index`, in reading order
+    fn get_chapter(&self, index: usize) -> Result<Option<String>> {
+        Ok(self.reading_chapters().get(index).map(|chapter| chapter.content.clone()))
+    }
+
+    /// One chapter per spine item (or TOC section with `chapters_from_toc`), keyed by archive path
+    fn get_chapters(&self) -> Result<Vec<ChapterView>> {
+        Ok(self
+            .reading_chapters()
+            .iter()
+            .map(|chapter| ChapterView::new(&chapter.title, &chapter.content).with_href(&chapter.filename))
+            .collect())
+    }
+
+    /// Landmarks from the EPUB 3 `landmarks` nav or the EPUB 2 `<guide>`
+    fn get_annotations(&self) -> Result<Vec<Annotation>> {
+        Ok(self
+            .guide
+            .iter()
+            .map(|reference| {
+                Annotation::new(
+                    reference.landmark_type(),
+                    AnnotationLocation::Href(reference.href.clone()),
+                    reference.title.clone(),
+                )
+            })
+            .collect())
+    }
+}
+
+impl EpubHandler {
+    pub fn optimize_images(&mut self, options: crate::image_optimizer::OptimizationOptions) -> Result<usize> {
+        use crate::image_optimizer::ImageOptimizer;
+
+        let optimizer = ImageOptimizer::new(options);
+        let mut total_savings = 0usize;
+        
+        let mut moves = HashMap::new();
+        let results = optimizer.optimize_all(&self.images);
+        for (image, result) in self.images.iter_mut().zip(results) {
+            let original_size = image.data.len();
+
+            match result {
+                Ok(optimized) if optimized.mime_type != image.mime_type => {
+                    // Transcoded (or rasterized SVG) images get a name matching their new
+                    // format, and the manifest their new media-type; point the chapters at it
+                    moves.insert(image.name.clone(), optimized.name.clone());
+                    total_savings += original_size.saturating_sub(optimized.data.len());
+                    *image = optimized;
+                }
+                Ok(optimized) => {
+                    let new_size = optimized.data.len();
+                    if new_size < original_size {
+                        total_savings += original_size - new_size;
+                        *image = optimized;
+                    }
+                }
+                Err(_) => {
+                    // Skip images that fail to optimize
+                    continue;
+                }
+            }
+        }
+        self.rewrite_image_references(&moves);
+
+        Ok(total_savings)
+    }
+
+    /// Store each distinct image once: duplicates are dropped and the chapters that showed
+    /// them point at the first image with the same bytes instead
+    pub fn dedupe_images(&mut self) -> crate::image_optimizer::DuplicateImages {
+        let found = crate::image_optimizer::DuplicateImages::find(&self.images);
+        self.rewrite_image_references(&found.duplicates.iter().cloned().collect());
+        self.images.retain(|image| !found.duplicates.iter().any(|(duplicate, _)| *duplicate == image.name));
+        found
+    }
+
+    /// Move every image into one `images/` folder of the package directory, numbering names
+    /// that would collide (`a.png`, `a-2.png`), and point the chapters' `src` and `href`
+    /// references and the cover at the new paths. Images that are spine pages themselves stay
+    /// where they are. Returns how many images moved.
+    pub fn collapse_images(&mut self) -> usize {
+        let images_dir = match &self.package_dir {
+            Some(dir) if !dir.is_empty() => format!("{dir}/images"),
+            _ => "images".to_string(),
+        };
+        let in_spine = |name: &str| self.chapters.iter().any(|chapter| chapter.filename == name);
+        let in_images_dir = |name: &str| name.rsplit_once('/').is_some_and(|(dir, _)| dir == images_dir);
+
+        // Images already in the folder keep their names; the others take the next free one
+        let mut taken: std::collections::HashSet<String> =
+            self.images.iter().filter(|image| in_images_dir(&image.name)).map(|image| image.name.clone()).collect();
+        let mut moves: HashMap<String, String> = HashMap::new();
+        for image in &self.images {
+            if in_images_dir(&image.name) || in_spine(&image.name) {
+                continue;
+            }
+            let file = image.name.rsplit('/').next().unwrap_or(&image.name);
+            let (stem, extension) = match file.rsplit_once('.') {
+                Some((stem, extension)) => (stem, format!(".{extension}")),
+                None => (file, String::new()),
+            };
+            let mut target = format!("{images_dir}/{file}");
+            let mut n = 1;
+            while taken.contains(&target) {
+                n += 1;
+                target = format!("{images_dir}/{stem}-{n}{extension}");
+            }
+            taken.insert(target.clone());
+            moves.insert(image.name.clone(), target);
+        }
+        if moves.is_empty() {
+            return 0;
+        }
+
+        self.rewrite_image_references(&moves);
+        for image in &mut self.images {
+            if let Some(target) = moves.get(&image.name) {
+                image.name = target.clone();
+            }
+        }
+        moves.len()
+    }
+
+    /// Point the chapters' `src` and `href` attributes and CSS `url()`s, and the cover, at the
+    /// new archive paths of images in `moves` (old path to new)
+    fn rewrite_image_references(&mut self, moves: &HashMap<String, String>) {
+        for chapter in self.chapters.iter_mut().filter(|chapter| chapter.media_type.is_none()) {
+            let chapter_dir = chapter.filename.rsplit_once('/').map_or("", |(dir, _)| dir).to_string();
+            let rewrite = |value: &str| {
+                let (path, fragment) = match value.split_once('#') {
+                    Some((path, fragment)) => (path, Some(fragment)),
+                    None => (value, None),
+                };
+                if path.is_empty() || path.contains(':') {
+                    return None;
+                }
+                let target = moves.get(&resolve_href(&chapter_dir, path))?;
+                let href = relative_href(&chapter_dir, target);
+                Some(match fragment {
+                    Some(fragment) => format!("{href}#{fragment}"),
+                    None => href,
+                })
+            };
+            chapter.content = rewrite_attribute_values(&chapter.content, "href|src", rewrite);
+            chapter.content = rewrite_css_urls(&chapter.content, rewrite);
+        }
+        if let Some(target) = self.metadata.cover_image_path.as_ref().and_then(|cover| moves.get(cover)) {
+            self.metadata.cover_image_path = Some(target.clone());
+        }
+    }
+
+    /// Keep only the first `count` spine chapters, dropping TOC entries, page markers and
+    /// guide references that point into the removed ones
+    pub fn truncate_chapters(&mut self, count: usize) {
+        self.chapters.truncate(count);
+        self.sections.clear();
+
+        let kept: Vec<String> = self.chapters.iter().map(|chapter| chapter.filename.clone()).collect();
+        let kept_hrefs: Vec<String> = kept.iter().map(|filename| self.package_href(filename)).collect();
+        let file = |href: &str| href.split('#').next().unwrap_or_default().to_string();
+        self.toc.retain(|entry| entry.href.as_deref().is_none_or(|href| kept.contains(&file(href))));
+        self.guide.retain(|reference| kept.contains(&file(&reference.href)));
+        self.page_list.retain(|marker| kept_hrefs.contains(&file(&marker.href)));
+    }
+
+    /// Merge consecutive XHTML chapters into fewer spine files: all of them, or
+    /// `chapters_per_file` at a time. Each merged chapter's body becomes a `<section>` of the
+    /// first file of its group (its `<head>` is dropped), ids that would clash are prefixed, and
+    /// links, TOC entries, guide references and page markers into merged chapters are pointed
+    /// at their new place. Chapters in different directories and SVG/image pages are never
+    /// merged. Returns how many spine files were removed.
+    pub fn flatten_spine(&mut self, chapters_per_file: Option<usize>) -> usize {
+        let limit = chapters_per_file.unwrap_or(usize::MAX).max(1);
+        let dir = |filename: &str| filename.rsplit_once('/').map_or("", |(dir, _)| dir).to_string();
+
+        // Group consecutive mergeable chapters; narrated ones stay whole, since their media
+        // overlays point into the file by name
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for (idx, chapter) in self.chapters.iter().enumerate() {
+            let joins = groups.last().is_some_and(|group| {
+                let first = &self.chapters[group[0]];
+                let narrated = |chapter: &Chapter| self.media_overlays.contains_key(&chapter.filename);
+                chapter.media_type.is_none()
+                    && first.media_type.is_none()
+                    && !narrated(chapter)
+                    && !narrated(first)
+                    && group.len() < limit
+                    && dir(&first.filename) == dir(&chapter.filename)
+            });
+            match groups.last_mut() {
+                Some(group) if joins => group.push(idx),
+                _ => groups.push(vec![idx]),
+            }
+        }
+
+        // Where each merged chapter goes: the group's first file, a section id, renamed ids
+        struct Move {
+            target: String,
+            section: String,
+            ids: HashMap<String, String>,
+        }
+        let mut moves: HashMap<String, Move> = HashMap::new();
+        for group in groups.iter().filter(|group| group.len() > 1) {
+            let target = self.chapters[group[0]].filename.clone();
+            let mut taken: std::collections::HashSet<String> =
+                Self::element_id_offsets(&self.chapters[group[0]].content).into_keys().collect();
+            for &idx in &group[1..] {
+                let chapter = &self.chapters[idx];
+                let stem = Path::new(&chapter.filename).file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+                let stem: String = stem.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' }).collect();
+                let mut section = format!("merged-{stem}");
+                let mut n = 1;
+                while taken.contains(&section) {
+                    n += 1;
+                    section = format!("merged-{stem}-{n}");
+                }
+                taken.insert(section.clone());
+
+                let mut ids = HashMap::new();
+                for id in Self::element_id_offsets(&chapter.content).into_keys() {
+                    if taken.contains(&id) {
+                        let mut renamed = format!("{section}-{id}");
+                        while taken.contains(&renamed) {
+                            renamed.push('_');
+                        }
+                        taken.insert(renamed.clone());
+                        ids.insert(id, renamed);
+                    } else {
+                        taken.insert(id);
+                    }
+                }
+                moves.insert(chapter.filename.clone(), Move { target: target.clone(), section, ids });
+            }
+        }
+        if moves.is_empty() {
+            return 0;
+        }
+
+        // New location of `file` (a chapter filename) and `fragment`
+        let relocate = |file: &str, fragment: Option<&str>| -> Option<(String, String)> {
+            let moved = moves.get(file)?;
+            let fragment = match fragment {
+                Some(fragment) => moved.ids.get(fragment).cloned().unwrap_or_else(|| fragment.to_string()),
+                None => moved.section.clone(),
+            };
+            Some((moved.target.clone(), fragment))
+        };
+
+        // Point links at the merged files, and same-document links of merged chapters at renamed ids
+        for chapter in self.chapters.iter_mut().filter(|chapter| chapter.media_type.is_none()) {
+            let chapter_dir = dir(&chapter.filename);
+            let own_ids = moves.get(&chapter.filename).map(|moved| &moved.ids);
+            chapter.content = rewrite_hrefs(&chapter.content, |href| {
+                let (path, fragment) = match href.split_once('#') {
+                    Some((path, fragment)) => (path, Some(fragment)),
+                    None => (href, None),
+                };
+                if path.is_empty() {
+                    let renamed = own_ids?.get(fragment?)?;
+                    return Some(format!("#{renamed}"));
+                }
+                if path.contains(':') {
+                    return None;
+                }
+                let (target, fragment) = relocate(&resolve_href(&chapter_dir, path), fragment)?;
+                Some(format!("{}#{fragment}", relative_href(&chapter_dir, &target)))
+            });
+        }
+
+        let relocate_href = |href: &str| {
+            let (path, fragment) = match href.split_once('#') {
+                Some((path, fragment)) => (path, Some(fragment)),
+                None => (href, None),
+            };
+            relocate(path, fragment).map(|(target, fragment)| format!("{target}#{fragment}"))
+        };
+        fn relocate_toc(entries: &mut [TocEntry], relocate_href: &dyn Fn(&str) -> Option<String>) {
+            for entry in entries {
+                if let Some(href) = entry.href.as_deref().and_then(relocate_href) {
+                    entry.href = Some(href);
+                }
+                relocate_toc(&mut entry.children, relocate_href);
+            }
+        }
+        relocate_toc(&mut self.toc, &relocate_href);
+        for reference in &mut self.guide {
+            if let Some(href) = relocate_href(&reference.href) {
+                reference.href = href;
+            }
+        }
+        // Page markers are relative to the nav document in the package directory
+        let package_dir = self.package_dir().to_string();
+        let archive_names: HashMap<String, String> = self
+            .chapters
+            .iter()
+            .map(|chapter| (self.archive_path(&chapter.filename), chapter.filename.clone()))
+            .collect();
+        let package_hrefs: HashMap<String, String> = self
+            .chapters
+            .iter()
+            .map(|chapter| (chapter.filename.clone(), self.package_href(&chapter.filename)))
+            .collect();
+        for marker in &mut self.page_list {
+            let (path, fragment) = match marker.href.split_once('#') {
+                Some((path, fragment)) => (path, Some(fragment)),
+                None => (marker.href.as_str(), None),
+            };
+            let relocated = archive_names
+                .get(&resolve_href(&package_dir, path))
+                .and_then(|filename| relocate(filename, fragment));
+            if let Some((target, fragment)) = relocated {
+                marker.href = format!("{}#{fragment}", package_hrefs[&target]);
+            }
+        }
+
+        // Move each merged chapter's body into a section of its group's first file
+        let mut removed = 0;
+        for group in groups.iter().rev().filter(|group| group.len() > 1) {
+            let mut sections = String::new();
+            let mut merged = Vec::new();
+            for chapter in self.chapters.drain(group[1]..=group[group.len() - 1]) {
+                let moved = &moves[&chapter.filename];
+                let mut body = body_inner(&chapter.content).to_string();
+                for (from, to) in &moved.ids {
+                    for quote in ['"', '\''] {
+                        body = body.replace(&format!("id={quote}{from}{quote}"), &format!("id={quote}{to}{quote}"));
                     }
                 }
-                Ok(Event::Eof) => break,
-                Err(e) => return Err(EbookError::Xml(e.to_string())),
-                _ => {}
+                sections.push_str(&format!("\n<section id=\"{}\">\n{}\n</section>", moved.section, body.trim()));
+                merged.push((chapter.title, moved.section.clone()));
+                removed += 1;
             }
-            buf.clear();
+            let first = &mut self.chapters[group[0]];
+            match first.content.rfind("</body>") {
+                Some(end) => first.content.insert_str(end, &format!("{sections}\n")),
+                None => first.content.push_str(&sections),
+            }
+            first.merged.extend(merged);
         }
-
-        Err(EbookError::NotFound("OPF path not found".to_string()))
+        self.sections.clear();
+        removed
     }
-}
-
-impl EbookReader for EpubHandler {
-    fn read_from_file(&mut self, path: &Path) -> Result<()> {
-        log::info!("Reading EPUB file: {path:?}");
-        let file = File::open(path)?;
-        let mut archive = ZipArchive::new(file)?;
-        log::debug!("EPUB archive opened with {} files", archive.len());
 
-        let opf_path = Self::find_opf_path(&mut archive)?;
-
-        let mut opf_content = String::new();
-        archive.by_name(&opf_path)?.read_to_string(&mut opf_content)?;
+    /// Drop XHTML chapters whose body has no text and no images or media (blank pages),
+    /// along with the TOC entries, guide references and page markers pointing into them.
+    /// Fixed-layout books are left alone, since a blank page there is deliberate. Returns how
+    /// many chapters were dropped.
+    pub fn drop_empty_chapters(&mut self) -> usize {
+        if self.is_fixed_layout() {
+            return 0;
+        }
+        let empty: Vec<String> = self
+            .chapters
+            .iter()
+            .filter(|chapter| chapter.media_type.is_none() && !has_visible_content(&chapter.content))
+            .map(|chapter| chapter.filename.clone())
+            .collect();
+        if empty.is_empty() {
+            return 0;
+        }
 
-        self.parse_opf(&opf_content)?;
+        let file = |href: &str| href.split('#').next().unwrap_or_default().to_string();
+        let empty_hrefs: Vec<String> = empty.iter().map(|filename| self.package_href(filename)).collect();
+        self.chapters.retain(|chapter| !empty.contains(&chapter.filename));
+        self.sections.retain(|section| !empty.contains(&file(&section.filename)));
+        // An entry into a dropped chapter goes; one with children points at its first child
+        fn retain_toc(entries: &mut Vec<TocEntry>, dropped: &dyn Fn(&str) -> bool) {
+            for entry in entries.iter_mut() {
+                retain_toc(&mut entry.children, dropped);
+            }
+            entries.retain(|entry| !entry.href.as_deref().is_some_and(dropped) || !entry.children.is_empty());
+            for entry in entries.iter_mut() {
+                if entry.href.as_deref().is_some_and(dropped) {
+                    entry.href = entry.children[0].href.clone();
+                }
+            }
+        }
+        retain_toc(&mut self.toc, &|href| empty.contains(&file(href)));
+        self.guide.retain(|reference| !empty.contains(&file(&reference.href)));
+        self.page_list.retain(|marker| !empty_hrefs.contains(&file(&marker.href)));
+        empty.len()
+    }
 
-        // Parse spine and manifest to get ordered chapter list
-        let opf_dir = opf_path.rsplit('/').skip(1).collect::<Vec<&str>>().join("/");
-        let (spine_items, manifest_items) = self.parse_spine_and_manifest(&opf_content)?;
+    /// The cover image: the one the OPF declares, or else the lone image on the first
+    /// spine page
+    pub fn get_cover_image(&self) -> Option<ImageData> {
+        let path = self.metadata.cover_image_path.as_deref()?;
+        self.images.iter().find(|image| image.name == path).cloned()
+    }
 
-        // Read content files in spine order
-        for (idx, itemref) in spine_items.iter().enumerate() {
-            if let Some(href) = manifest_items.get(itemref) {
-                let full_path = if opf_dir.is_empty() {
-                    href.clone()
-                } else {
-                    format!("{opf_dir}/{href}")
-                };
+    /// Files of the archive the book was read from, with their sizes; empty for a new book
+    pub fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        Ok(self.entries.clone())
+    }
 
-                if let Ok(mut file) = archive.by_name(&full_path) {
-                    let mut content = String::new();
-                    file.read_to_string(&mut content)?;
+    /// Bytes and media-type of one file of the archive the book was read from (a chapter, a
+    /// stylesheet, an image), read without extracting the rest. `href` is an archive path, as
+    /// chapter and image names are, or else relative to the OPF; a `#fragment` is ignored.
+    /// `None` when there is no such file or the book wasn't read from one.
+    pub fn get_resource(&self, href: &str) -> Result<Option<(String, Vec<u8>)>> {
+        let Some(source_path) = &self.source_path else { return Ok(None) };
+        let path = href.split('#').next().unwrap_or_default();
+        let package_dir = self.package_dir.as_deref().unwrap_or_default();
+        let mut archive = ZipArchive::new(File::open(source_path)?)?;
+        for name in [resolve_href("", path), resolve_href(package_dir, path)] {
+            let Ok(mut file) = crate::utils::zip_entry(&mut archive, &name) else { continue };
+            let data = crate::utils::ExtractionBudget::new(self.extraction_limits).read(&mut file)?;
+            let media_type = self
+                .manifest_types
+                .get(&name)
+                .cloned()
+                .unwrap_or_else(|| crate::utils::guess_mime_type(&name));
+            return Ok(Some((media_type, data)));
+        }
+        Ok(None)
+    }
 
-                    // Extract title from content
-                    let title = self.extract_chapter_title(&content)
-                        .unwrap_or_else(|| format!("Chapter {}", idx + 1));
+    /// Number of images read from the book
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
 
-                    self.chapters.push(Chapter {
-                        title,
-                        content: content.clone(),
-                        filename: full_path.clone(),
-                    });
+    /// Total size of the book's images in bytes
+    pub fn image_bytes(&self) -> u64 {
+        self.images.iter().map(|image| image.data.len() as u64).sum()
+    }
 
-                    self.content.push_str(&content);
-                    self.content.push('\n');
+    /// Number of image entries in the EPUB at `path`, from the zip index alone
+    pub fn count_archive_images(path: &Path) -> Result<usize> {
+        let archive = ZipArchive::new(File::open(path)?)?;
+        Ok(archive.file_names().filter(|name| is_image_entry(name)).count())
+    }
 
-                    // Add to TOC
-                    self.toc.push(TocEntry {
-                        id: idx as u32,
-                        level: 0,
-                        title: self.chapters.last().map(|c| c.title.clone()).unwrap_or_default(),
-                        href: Some(full_path.clone()),
-                        children: Vec::new(),
-                    });
-                }
+    /// Uncompressed size of the image entries in the EPUB at `path`, from the zip index alone
+    pub fn archive_image_bytes(path: &Path) -> Result<u64> {
+        let mut archive = ZipArchive::new(File::open(path)?)?;
+        let mut total = 0;
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if is_image_entry(file.name()) {
+                total += file.size();
             }
         }
+        Ok(total)
+    }
 
-        // Extract images
+    /// Optimize the images of the EPUB at `input` without rebuilding it: every other entry is
+    /// copied to `output` byte for byte, in its original order and compression, and each image
+    /// is replaced only when its optimized form is smaller. Reading the images is bounded by the
+    /// default [`crate::utils::ExtractionLimits`]. Returns the bytes saved.
+    pub fn optimize_preserving_structure(
+        input: &Path,
+        output: &Path,
+        options: crate::image_optimizer::OptimizationOptions,
+    ) -> Result<usize> {
+        use crate::image_optimizer::{ImageOptimizer, OptimizationOptions};
+
+        // Read everything up front so `output` may be the input file
+        let mut archive = ZipArchive::new(std::io::Cursor::new(std::fs::read(input)?))?;
+        let mut budget = crate::utils::ExtractionBudget::new(crate::utils::ExtractionLimits::default());
+        budget.check_declared(&mut archive)?;
+        let optimizer = ImageOptimizer::new(OptimizationOptions { convert_to: None, ..options });
+
+        let mut images = Vec::new();
         for i in 0..archive.len() {
             let mut file = archive.by_index(i)?;
             let name = file.name().to_string();
-
-            if name.ends_with(".jpg") || name.ends_with(".jpeg") ||
-               name.ends_with(".png") || name.ends_with(".gif") ||
-               name.ends_with(".svg") {
-                let mut data = Vec::new();
-                file.read_to_end(&mut data)?;
+            if is_image_entry(&name) {
+                let data = budget.read(&mut file)?;
                 let mime_type = crate::utils::guess_mime_type(&name);
-                self.images.push(ImageData::new(name, mime_type, data));
+                images.push(ImageData::new(name, mime_type, data));
             }
         }
 
-        Ok(())
-    }
-
-    fn get_metadata(&self) -> Result<Metadata> {
-        Ok(self.metadata.clone())
-    }
-
-    fn get_content(&self) -> Result<String> {
-        Ok(self.content.clone())
-    }
+        let mut replacements = HashMap::new();
+        let mut total_savings = 0usize;
+        for (image, result) in images.iter().zip(optimizer.optimize_all(&images)) {
+            // A changed format would need the name and every reference changed with it
+            if optimizer.output_mime_type(&image.mime_type) != image.mime_type {
+                continue;
+            }
+            if let Ok(optimized) = result
+                && optimized.data.len() < image.data.len()
+            {
+                total_savings += image.data.len() - optimized.data.len();
+                replacements.insert(image.name.clone(), optimized.data);
+            }
+        }
 
-    fn get_toc(&self) -> Result<Vec<TocEntry>> {
-        Ok(self.toc.clone())
-    }
+        let mut zip = ZipWriter::new(File::create(output)?);
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            match replacements.get(file.name()) {
+                Some(data) => {
+                    let options = FileOptions::<()>::default()
+                        .compression_method(file.compression())
+                        .last_modified_time(zip::DateTime::default());
+                    zip.start_file(file.name(), options)?;
+                    zip.write_all(data)?;
+                }
+                None => zip.raw_copy_file(file)?,
+            }
+        }
+        zip.finish()?;
 
-    fn extract_images(&self) -> Result<Vec<ImageData>> {
-        Ok(self.images.clone())
+        Ok(total_savings)
     }
-}
 
-impl EpubHandler {
-    pub fn optimize_images(&mut self, options: crate::image_optimizer::OptimizationOptions) -> Result<usize> {
-        use crate::image_optimizer::ImageOptimizer;
-        
-        let optimizer = ImageOptimizer::new(options);
-        let mut total_savings = 0usize;
-        
-        for image in &mut self.images {
-            let original_size = image.data.len();
-            
-            match optimizer.optimize(&image.data, &image.mime_type) {
-                Ok(optimized_data) => {
-                    let new_size = optimized_data.len();
-                    if new_size < original_size {
-                        total_savings += original_size - new_size;
-                        image.data = optimized_data;
-                    }
-                }
-                Err(_) => {
-                    // Skip images that fail to optimize
-                    continue;
-                }
+    /// Rewrite the zip packaging of the EPUB at `input` to `output`: text entries are deflated at
+    /// the highest level, already-compressed entries (raster images, audio, fonts) are stored
+    /// unless the archive already shrank them, and `mimetype` stays stored. Entries are decompressed
+    /// within the default [`crate::utils::ExtractionLimits`]. Returns the bytes saved.
+    pub fn recompress_archive(input: &Path, output: &Path) -> Result<u64> {
+        // Read everything up front so `output` may be the input file
+        let original = std::fs::read(input)?;
+        let original_size = original.len() as u64;
+        let mut archive = ZipArchive::new(std::io::Cursor::new(original))?;
+        let mut budget = crate::utils::ExtractionBudget::new(crate::utils::ExtractionLimits::default());
+        budget.check_declared(&mut archive)?;
+
+        let stored = FileOptions::<()>::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .last_modified_time(zip::DateTime::default());
+        let deflated = stored
+            .compression_method(zip::CompressionMethod::Deflated)
+            .compression_level(Some(9));
+
+        let mut zip = ZipWriter::new(File::create(output)?);
+        for i in 0..archive.len() {
+            let raw = archive.by_index_raw(i)?;
+            let name = raw.name().to_string();
+            let already_compressed = is_compressed_entry(&name);
+            if already_compressed && raw.compressed_size() < raw.size() {
+                zip.raw_copy_file(raw)?;
+                continue;
+            }
+            drop(raw);
+            let mut file = archive.by_index(i)?;
+            if file.is_dir() {
+                zip.add_directory(name, stored)?;
+                continue;
             }
+            let data = budget.read(&mut file)?;
+            let options = if already_compressed || name == "mimetype" { stored } else { deflated };
+            zip.start_file(name, options)?;
+            zip.write_all(&data)?;
         }
-        
-        Ok(total_savings)
+        zip.finish()?;
+
+        Ok(original_size.saturating_sub(std::fs::metadata(output)?.len()))
     }
 
-    fn parse_spine_and_manifest(&self, opf_content: &str) -> Result<(Vec<String>, HashMap<String, String>)> {
+    /// Spine items in reading order, and the manifest's id → href map
+    fn parse_spine_and_manifest(&mut self, opf_content: &str) -> Result<(Vec<SpineItem>, HashMap<String, ManifestItem>)> {
         use quick_xml::Reader;
         use quick_xml::events::Event;
 
@@ -379,47 +2159,61 @@ impl EpubHandler {
         let mut buf = Vec::new();
         let mut in_manifest = false;
         let mut in_spine = false;
-        let mut manifest_items: HashMap<String, String> = HashMap::new();
-        let mut spine_items: Vec<String> = Vec::new();
+        let mut manifest_items: HashMap<String, ManifestItem> = HashMap::new();
+        let mut spine_items: Vec<SpineItem> = Vec::new();
 
         loop {
             match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(e)) => {
+                Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
                     let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
                     if name == "manifest" {
                         in_manifest = true;
                     } else if name == "spine" {
                         in_spine = true;
+                        self.page_progression = e
+                            .try_get_attribute("page-progression-direction")
+                            .ok()
+                            .flatten()
+                            .and_then(|attr| PageProgression::from_attribute(&String::from_utf8_lossy(&attr.value)));
                     }
 
                     if in_manifest && name == "item" {
                         let mut id = String::new();
                         let mut href = String::new();
-                        for attr in e.attributes() {
-                            if let Ok(attr) = attr {
-                                let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                                let value = String::from_utf8_lossy(&attr.value).to_string();
-                                if key == "id" {
-                                    id = value;
-                                } else if key == "href" {
-                                    href = value;
-                                }
+                        let mut media_type = String::new();
+                        let mut media_overlay = None;
+                        for attr in e.attributes().flatten() {
+                            let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                            let value = String::from_utf8_lossy(&attr.value).to_string();
+                            if key == "id" {
+                                id = value;
+                            } else if key == "href" {
+                                href = value;
+                            } else if key == "media-type" {
+                                media_type = value;
+                            } else if key == "media-overlay" {
+                                media_overlay = Some(value);
                             }
                         }
                         if !id.is_empty() {
-                            manifest_items.insert(id, href);
+                            manifest_items.insert(id, ManifestItem { href, media_type, media_overlay });
                         }
                     }
 
                     if in_spine && name == "itemref" {
-                        for attr in e.attributes() {
-                            if let Ok(attr) = attr {
-                                let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                                if key == "idref" {
-                                    spine_items.push(String::from_utf8_lossy(&attr.value).to_string());
-                                }
+                        let mut idref = None;
+                        let mut properties = None;
+                        for attr in e.attributes().flatten() {
+                            let value = String::from_utf8_lossy(&attr.value).to_string();
+                            match attr.key.as_ref() {
+                                b"idref" => idref = Some(value),
+                                b"properties" if !value.trim().is_empty() => properties = Some(value),
+                                _ => {}
                             }
                         }
+                        if let Some(idref) = idref {
+                            spine_items.push((idref, properties));
+                        }
                     }
                 }
                 Ok(Event::End(e)) => {
@@ -440,41 +2234,190 @@ impl EpubHandler {
         Ok((spine_items, manifest_items))
     }
 
+    /// Decode a content document using its BOM, XML declaration or `<meta charset>`,
+    /// falling back to lossy UTF-8 so one badly encoded chapter can't fail the whole read
+    fn decode_content_document(bytes: &[u8]) -> String {
+        use encoding_rs::{Encoding, UTF_8};
+
+        let encoding = Encoding::for_bom(bytes)
+            .map(|(encoding, _)| encoding)
+            .or_else(|| {
+                // Declarations are ASCII and sit near the top of the file
+                let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+                let declared = regex::Regex::new(r#"(?i)(?:encoding|charset)\s*=\s*["']?([\w.:-]+)"#).unwrap();
+                declared
+                    .captures(&head)
+                    .and_then(|caps| Encoding::for_label(caps[1].as_bytes()))
+            })
+            .unwrap_or(UTF_8);
+
+        let (decoded, _, _) = encoding.decode(bytes);
+        decoded.into_owned()
+    }
+
+    /// Chapter title per the configured [`ChapterTitleSource`], skipping elements with no text;
+    /// tolerates bare `<body>` fragments, HTML-style unclosed tags and unknown entities
     fn extract_chapter_title(&self, content: &str) -> Option<String> {
-        use quick_xml::Reader;
-        use quick_xml::events::Event;
+        let title = || first_element_text(content, |name, _| name == b"title");
+        let heading = || first_element_text(content, |name, _| matches!(name, b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6"));
+        match &self.chapter_title_source {
+            ChapterTitleSource::Title => title().or_else(heading),
+            ChapterTitleSource::Heading => heading().or_else(title),
+            ChapterTitleSource::Selector(selector) => {
+                let selector = SimpleSelector::parse(selector);
+                first_element_text(content, |name, element| selector.matches(name, element))
+                    .or_else(title)
+                    .or_else(heading)
+            }
+        }
+    }
+}
 
-        let mut reader = Reader::from_str(content);
-        reader.config_mut().trim_text(true);
+/// Where EPUB chapter titles (and so TOC labels) are taken from
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ChapterTitleSource {
+    /// The document `<title>`, then the first heading
+    #[default]
+    Title,
+    /// The first `<h1>`–`<h6>`, then the document `<title>`
+    Heading,
+    /// The first element matching a simple selector (`tag`, `.class`, `#id`, `tag.class`),
+    /// then the `<title>` and first heading
+    Selector(String),
+}
 
-        let mut buf = Vec::new();
-        let mut in_title = false;
+impl std::str::FromStr for ChapterTitleSource {
+    type Err = String;
 
-        loop {
-            match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(e)) => {
-                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                    if name == "h1" || name == "h2" || name == "title" {
-                        in_title = true;
-                    }
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.trim() {
+            "" => Err("chapter title source cannot be empty".to_string()),
+            "title" => Ok(Self::Title),
+            "heading" => Ok(Self::Heading),
+            selector => Ok(Self::Selector(selector.to_string())),
+        }
+    }
+}
+
+/// `tag`, `.class`, `#id` or a combination such as `p.chapter-title`
+struct SimpleSelector {
+    tag: Option<String>,
+    class: Option<String>,
+    id: Option<String>,
+}
+
+impl SimpleSelector {
+    fn parse(selector: &str) -> Self {
+        let (rest, id) = match selector.split_once('#') {
+            Some((rest, id)) => (rest, Some(id.to_string())),
+            None => (selector, None),
+        };
+        let (tag, class) = match rest.split_once('.') {
+            Some((tag, class)) => (tag, Some(class.to_string())),
+            None => (rest, None),
+        };
+        let tag = (!tag.is_empty()).then(|| tag.to_string());
+        Self { tag, class, id }
+    }
+
+    fn matches(&self, name: &[u8], element: &quick_xml::events::BytesStart) -> bool {
+        if self.tag.as_ref().is_some_and(|tag| tag.as_bytes() != name) {
+            return false;
+        }
+        let attribute = |key: &[u8]| {
+            element
+                .attributes()
+                .flatten()
+                .find(|attr| attr.key.local_name().as_ref() == key)
+                .map(|attr| String::from_utf8_lossy(&attr.value).into_owned())
+        };
+        let class_matches = self.class.as_ref().is_none_or(|class| {
+            attribute(b"class").is_some_and(|classes| classes.split_whitespace().any(|c| c == class))
+        });
+        let id_matches = self.id.as_ref().is_none_or(|id| attribute(b"id").as_ref() == Some(id));
+        class_matches && id_matches
+    }
+}
+
+/// Sources of the `<img>` elements and SVG `<image>` elements of a content document
+fn image_sources(content: &str) -> Vec<String> {
+    use quick_xml::Reader;
+    use quick_xml::events::Event;
+
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().check_end_names = false;
+
+    let mut sources = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let attribute: &[u8] = match e.local_name().as_ref() {
+                    b"img" => b"src",
+                    b"image" => b"href",
+                    _ => continue,
+                };
+                let source = e
+                    .attributes()
+                    .flatten()
+                    .find(|attr| attr.key.local_name().as_ref() == attribute);
+                if let Some(source) = source {
+                    sources.push(String::from_utf8_lossy(&source.value).into_owned());
                 }
-                Ok(Event::Text(e)) if in_title => {
-                    return Some(e.unescape().unwrap_or_default().to_string());
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    sources
+}
+
+/// Text of the first element accepted by `matches` (by local name) whose text, with whitespace
+/// collapsed, is not empty. Text of nested inline elements is included.
+fn first_element_text(content: &str, matches: impl Fn(&[u8], &quick_xml::events::BytesStart) -> bool) -> Option<String> {
+    use quick_xml::Reader;
+    use quick_xml::events::Event;
+
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().check_end_names = false;
+
+    let mut buf = Vec::new();
+    // Local name of the element being collected, and its text so far
+    let mut current: Option<(Vec<u8>, String)> = None;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) if current.is_none() && matches(e.local_name().as_ref(), &e) => {
+                current = Some((e.local_name().as_ref().to_vec(), String::new()));
+            }
+            Ok(Event::Text(e)) => {
+                if let Some((_, text)) = &mut current {
+                    let unescaped = e
+                        .unescape()
+                        .map(|text| text.into_owned())
+                        .unwrap_or_else(|_| String::from_utf8_lossy(&e).into_owned());
+                    text.push_str(&unescaped);
                 }
-                Ok(Event::End(e)) => {
-                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                    if name == "h1" || name == "h2" || name == "title" {
-                        in_title = false;
-                    }
+            }
+            // A `<br/>` inside a heading separates words
+            Ok(Event::Empty(_)) => {
+                if let Some((_, text)) = &mut current {
+                    text.push(' ');
                 }
-                Ok(Event::Eof) => break,
-                _ => {}
             }
-            buf.clear();
+            Ok(Event::End(e)) if current.as_ref().is_some_and(|(name, _)| name.as_slice() == e.local_name().as_ref()) => {
+                let (_, text) = current.take()?;
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !text.is_empty() {
+                    return Some(text);
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
         }
-
-        None
+        buf.clear();
     }
+
+    None
 }
 
 impl EbookWriter for EpubHandler {
@@ -489,23 +2432,58 @@ impl EbookWriter for EpubHandler {
     }
 
     fn add_chapter(&mut self, title: &str, content: &str) -> Result<()> {
-        let filename = format!("chapter{}.xhtml", self.chapters.len() + 1);
+        // Chapters of a read book are named by archive path; don't clash with its files
+        let mut n = self.chapters.len() + 1;
+        let filename = loop {
+            let filename = match &self.package_dir {
+                Some(_) => self.package_file(&format!("chapter{n}.xhtml")),
+                None => format!("chapter{n}.xhtml"),
+            };
+            if !self.chapters.iter().any(|chapter| chapter.filename == filename) {
+                break filename;
+            }
+            n += 1;
+        };
         self.chapters.push(Chapter {
             title: title.to_string(),
             content: content.to_string(),
             filename,
+            properties: None,
+            media_type: None,
+            merged: Vec::new(),
         });
         Ok(())
     }
 
     fn add_image(&mut self, name: &str, data: Vec<u8>) -> Result<()> {
         let mime_type = crate::utils::guess_mime_type(name);
-        self.images.push(ImageData::new(name.to_string(), mime_type, data));
+        let name = match &self.package_dir {
+            Some(_) => self.package_file(name),
+            None => name.to_string(),
+        };
+        self.images.push(ImageData::new(name, mime_type, data));
         Ok(())
     }
 
     fn write_to_file(&self, path: &Path) -> Result<()> {
+        // A book given only its whole text (`set_content`, as `ebook write` does) gets it as its one chapter
+        if self.chapters.is_empty() && !self.content.trim().is_empty() {
+            let title = self.metadata.title.clone().unwrap_or_else(|| "Chapter 1".to_string());
+            let mut book = self.clone();
+            book.add_chapter(&title, &crate::conversion::chapter_xhtml(&title, &self.content))?;
+            return book.write_to_file(path);
+        }
         log::info!("Writing EPUB file: {:?} (version: {:?})", path, self.epub_version);
+        let (write_ncx, write_nav) = match (self.epub_version, self.ncx_mode) {
+            (EpubVersion::V2, NcxMode::Never) => {
+                return Err(EbookError::InvalidStructure("EPUB 2 requires an NCX; only EPUB 3 can be written without one".to_string()));
+            }
+            (EpubVersion::V3, NcxMode::Only) => {
+                return Err(EbookError::InvalidStructure("EPUB 3 requires a nav document; write EPUB 2 for an NCX-only book".to_string()));
+            }
+            (EpubVersion::V2, _) => (true, false),
+            (EpubVersion::V3, mode) => (mode == NcxMode::Both, true),
+        };
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
@@ -514,131 +2492,264 @@ impl EbookWriter for EpubHandler {
         let file = File::create(path)?;
         let mut zip = ZipWriter::new(file);
         log::debug!("Writing {} chapters and {} images", self.chapters.len(), self.images.len());
-        let options = FileOptions::<()>::default().compression_method(zip::CompressionMethod::Deflated);
+        // A fixed entry timestamp keeps repeated writes of the same book byte-identical
+        let options = FileOptions::<()>::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .last_modified_time(zip::DateTime::default());
 
-        zip.start_file("mimetype", FileOptions::<()>::default().compression_method(zip::CompressionMethod::Stored))?;
+        zip.start_file("mimetype", options.compression_method(zip::CompressionMethod::Stored))?;
         zip.write_all(b"application/epub+zip")?;
 
+        let opf_path = self.package_file("content.opf");
+        let ncx_file = self.unused_package_file("toc", "ncx");
+        let nav_file = self.unused_package_file("nav", "xhtml");
+
         zip.start_file("META-INF/container.xml", options)?;
-        zip.write_all(br#"<?xml version="1.0"?>
+        zip.write_all(format!(r#"<?xml version="1.0"?>
 <container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
   <rootfiles>
-    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+    <rootfile full-path="{opf_path}" media-type="application/oebps-package+xml"/>
   </rootfiles>
-</container>"#)?;
+</container>"#).as_bytes())?;
 
-        let title = self.metadata.title.as_deref().unwrap_or("Untitled");
-        let author = self.metadata.author.as_deref().unwrap_or("Unknown");
+        // The OPF requires a title, identifier and language; creators are optional
+        let title = match &self.metadata.title {
+            Some(title) if self.metadata.has_title() => title.as_str(),
+            _ => "Untitled",
+        };
+        let creators = self
+            .metadata
+            .credits()
+            .iter()
+            .enumerate()
+            .map(|(idx, credit)| self.credit_element(idx, credit))
+            .collect::<Vec<_>>()
+            .join("\n");
         let language = self.metadata.language.as_deref().unwrap_or("en");
+        // A rewritten book keeps its identifier; a new one gets a random UUID
+        let identifier = match &self.metadata.isbn {
+            Some(identifier) => quick_xml::escape::escape(identifier.as_str()).into_owned(),
+            None => format!("urn:uuid:{}", uuid::Uuid::new_v4()),
+        };
+
+        // Optional Dublin Core fields and the cover, after the identifier
+        let mut extra_meta = String::new();
+        let optional = [
+            ("dc:publisher", &self.metadata.publisher),
+            ("dc:description", &self.metadata.description),
+            ("dc:date", &self.metadata.publication_date),
+            ("dc:rights", &self.metadata.rights),
+        ];
+        for (element, value) in optional {
+            if let Some(value) = value {
+                extra_meta.push_str(&format!("\n    <{element}>{}</{element}>", quick_xml::escape::escape(value.as_str())));
+            }
+        }
+        for subject in self.metadata.tags.iter().flatten() {
+            extra_meta.push_str(&format!("\n    <dc:subject>{}</dc:subject>", quick_xml::escape::escape(subject.as_str())));
+        }
+        for (name, content) in self.metadata.calibre_fields() {
+            extra_meta.push_str(&format!(
+                r#"
+    <meta name="{}" content="{}"/>"#,
+                quick_xml::escape::escape(name.as_str()),
+                quick_xml::escape::escape(content.as_str())
+            ));
+        }
+
+        // EPUB 3 also states the series as a collection, which readers other than Calibre know
+        if self.epub_version == EpubVersion::V3
+            && let Some(series) = &self.metadata.series
+        {
+            extra_meta.push_str(&format!(
+                r##"
+    <meta property="belongs-to-collection" id="series">{}</meta>
+    <meta refines="#series" property="collection-type">series</meta>"##,
+                quick_xml::escape::escape(series.as_str())
+            ));
+            if let Some(index) = self.metadata.series_index {
+                extra_meta.push_str(&format!(r##"
+    <meta refines="#series" property="group-position">{index}</meta>"##));
+            }
+        }
 
         // Build manifest items list
         let mut manifest_items = String::new();
         
-        // Add navigation items based on EPUB version
-        if self.epub_version == EpubVersion::V3 {
-            manifest_items.push_str(r#"    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#);
-            manifest_items.push('\n');
+        // Add navigation items based on EPUB version and NCX mode
+        let mut navigation_items = Vec::new();
+        if write_nav {
+            navigation_items.push(format!(r#"    <item id="nav" href="{nav_file}" media-type="application/xhtml+xml" properties="nav"/>"#));
+        }
+        if write_ncx {
+            navigation_items.push(format!(r#"    <item id="ncx" href="{ncx_file}" media-type="application/x-dtbncx+xml"/>"#));
+        }
+        manifest_items.push_str(&navigation_items.join("\n"));
+
+        // A document the spine shows more than once is declared and stored once, under the id
+        // of its first spine position
+        let mut first_position: HashMap<&str, usize> = HashMap::new();
+        for (idx, chapter) in self.chapters.iter().enumerate() {
+            first_position.entry(chapter.filename.as_str()).or_insert(idx);
         }
-        manifest_items.push_str(r#"    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>"#);
+        let is_first = |idx: usize, chapter: &Chapter| first_position[chapter.filename.as_str()] == idx;
 
-        // Add chapter items to manifest
+        // Add chapter items to manifest; SVG and image pages are declared with the images.
+        // Media overlays are EPUB 3; kept media items get a prefix so their ids can't clash.
         for (idx, chapter) in self.chapters.iter().enumerate() {
+            if chapter.media_type.is_some() || !is_first(idx, chapter) {
+                continue;
+            }
+            let media_overlay = match self.media_overlays.get(&chapter.filename) {
+                Some(overlay) if self.epub_version == EpubVersion::V3 && self.media.iter().any(|item| &item.id == overlay) => {
+                    format!(r#" media-overlay="media-{overlay}""#)
+                }
+                _ => String::new(),
+            };
             manifest_items.push_str(&format!(
                 r#"
-    <item id="ch{}" href="{}" media-type="application/xhtml+xml"/>"#,
-                idx, chapter.filename
+    <item id="ch{}" href="{}" media-type="application/xhtml+xml"{}/>"#,
+                idx, self.package_href(&chapter.filename), media_overlay
+            ));
+        }
+        for item in &self.media {
+            manifest_items.push_str(&format!(
+                r#"
+    <item id="media-{}" href="{}" media-type="{}"/>"#,
+                item.id, self.package_href(&item.name), item.media_type
             ));
         }
 
-        // Add image items to manifest
+        // Add image items to manifest; the cover is declared for both EPUB 2 and 3 readers
+        let cover_idx = self.metadata.cover_image_path.as_deref()
+            .and_then(|cover| self.images.iter().position(|image| image.name == cover));
         for (idx, image) in self.images.iter().enumerate() {
             let media_type = &image.mime_type;
+            let properties = if Some(idx) == cover_idx && self.epub_version == EpubVersion::V3 {
+                r#" properties="cover-image""#
+            } else {
+                ""
+            };
             manifest_items.push_str(&format!(
                 r#"
-    <item id="img{}" href="{}" media-type="{}"/>"#,
-                idx, image.name, media_type
+    <item id="img{}" href="{}" media-type="{}"{}/>"#,
+                idx, self.package_href(&image.name), media_type, properties
             ));
         }
 
         // Build spine items list
         let mut spine_items = String::new();
-        for (idx, _chapter) in self.chapters.iter().enumerate() {
-            spine_items.push_str(&format!(r#"    <itemref idref="ch{idx}"/>"#));
+        for chapter in &self.chapters {
+            let idref = match chapter.media_type {
+                None => format!("ch{}", first_position[chapter.filename.as_str()]),
+                Some(_) => match self.images.iter().position(|image| image.name == chapter.filename) {
+                    Some(image_idx) => format!("img{image_idx}"),
+                    None => continue,
+                },
+            };
+            match &chapter.properties {
+                Some(properties) => spine_items.push_str(&format!(r#"    <itemref idref="{idref}" properties="{properties}"/>"#)),
+                None => spine_items.push_str(&format!(r#"    <itemref idref="{idref}"/>"#)),
+            }
+        }
+
+        if let Some(idx) = cover_idx {
+            extra_meta.push_str(&format!(r#"
+    <meta name="cover" content="img{idx}"/>"#));
+        }
+
+        // dcterms:modified and the rendition vocabulary are EPUB 3 only
+        let mut rendition_meta = String::new();
+        if self.epub_version == EpubVersion::V3 {
+            rendition_meta.push_str(&format!("\n    <meta property=\"dcterms:modified\">{}</meta>", crate::utils::now_iso_timestamp()));
+            for (property, value) in &self.rendition {
+                rendition_meta.push_str(&format!("\n    <meta property=\"{property}\">{value}</meta>"));
+            }
+            for (id, property, value) in &self.media_metadata {
+                let value = quick_xml::escape::escape(value.as_str());
+                match id {
+                    Some(id) if self.media.iter().any(|item| &item.id == id) => rendition_meta
+                        .push_str(&format!("\n    <meta refines=\"#media-{id}\" property=\"{property}\">{value}</meta>")),
+                    Some(_) => {}
+                    None => rendition_meta.push_str(&format!("\n    <meta property=\"{property}\">{value}</meta>")),
+                }
+            }
+        }
+
+        zip.start_file(&opf_path, options)?;
+        // EPUB 3 carries these as the landmarks nav instead
+        let mut guide = String::new();
+        if self.epub_version == EpubVersion::V2 && !self.guide.is_empty() {
+            guide.push_str("\n  <guide>");
+            for reference in &self.guide {
+                guide.push_str(&format!(
+                    r#"
+    <reference type="{}" title="{}" href="{}"/>"#,
+                    quick_xml::escape::escape(reference.kind.as_str()),
+                    quick_xml::escape::escape(reference.title.as_str()),
+                    quick_xml::escape::escape(self.package_href(&reference.href).as_str())
+                ));
+            }
+            guide.push_str("\n  </guide>");
         }
 
-        zip.start_file("OEBPS/content.opf", options)?;
         let version_str = match self.epub_version {
             EpubVersion::V2 => "2.0",
             EpubVersion::V3 => "3.0",
         };
         let opf = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
 <package xmlns="http://www.idpf.org/2007/opf" version="{}" unique-identifier="BookID">
-  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
-    <dc:title>{}</dc:title>
-    <dc:creator>{}</dc:creator>
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+{}
+{}
     <dc:language>{}</dc:language>
-    <dc:identifier id="BookID">urn:uuid:{}</dc:identifier>
+    <dc:identifier id="BookID">{}</dc:identifier>{}{}
   </metadata>
   <manifest>
 {}
   </manifest>
-  <spine toc="ncx">
+  <spine{}{}>
 {}
-  </spine>
-</package>"#, version_str, title, author, language, uuid::Uuid::new_v4(), manifest_items, spine_items);
+  </spine>{}
+</package>"#, version_str, self.title_elements(title), creators, language, identifier, extra_meta, rendition_meta, manifest_items,
+            if write_ncx { r#" toc="ncx""# } else { "" },
+            self.page_progression.map(|direction| format!(r#" page-progression-direction="{}""#, direction.as_str())).unwrap_or_default(),
+            spine_items, guide);
         zip.write_all(opf.as_bytes())?;
 
         // Write TOC
-        zip.start_file("OEBPS/toc.ncx", options)?;
-        let mut ncx_content = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
-<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
-  <head>
-    <meta name="dtb:uid" content="{}"/>
-    <meta name="dtb:depth" content="1"/>
-    <meta name="dtb:totalPageCount" content="0"/>
-    <meta name="dtb:maxPageNumber" content="0"/>
-  </head>
-  <docTitle>
-    <text>{}</text>
-  </docTitle>
-  <navMap>"#, uuid::Uuid::new_v4(), title);
-
-        for (idx, chapter) in self.chapters.iter().enumerate() {
-            ncx_content.push_str(&format!(r#"
-    <navPoint id="navPoint-{}" playOrder="{}">
-      <navLabel>
-        <text>{}</text>
-      </navLabel>
-      <content src="{}"/>
-    </navPoint>"#, idx, idx + 1, chapter.title, chapter.filename));
+        if write_ncx {
+            zip.start_file(self.package_file(&ncx_file), options)?;
+            zip.write_all(self.generate_ncx(title, &identifier).as_bytes())?;
         }
 
-        ncx_content.push_str(r#"
-  </navMap>
-</ncx>"#);
-        zip.write_all(ncx_content.as_bytes())?;
-
         // Write nav.xhtml for EPUB 3.0
-        if self.epub_version == EpubVersion::V3 {
-            zip.start_file("OEBPS/nav.xhtml", options)?;
+        if write_nav {
+            zip.start_file(self.package_file(&nav_file), options)?;
             let nav_content = self.generate_nav_xhtml();
             zip.write_all(nav_content.as_bytes())?;
         }
 
-        // Write chapters
-        for chapter in &self.chapters {
-            let filename = format!("OEBPS/{}", chapter.filename);
-            zip.start_file(&filename, options)?;
+        // Write chapters; SVG and image pages are written with the images
+        for (idx, chapter) in self.chapters.iter().enumerate() {
+            if chapter.media_type.is_some() || !is_first(idx, chapter) {
+                continue;
+            }
+            zip.start_file(self.archive_path(&chapter.filename), options)?;
             zip.write_all(chapter.content.as_bytes())?;
         }
 
         // Write images
         for image in &self.images {
-            let filename = format!("OEBPS/{}", image.name);
-            zip.start_file(&filename, options)?;
+            zip.start_file(self.archive_path(&image.name), options)?;
             zip.write_all(&image.data)?;
         }
 
+        for item in &self.media {
+            zip.start_file(self.archive_path(&item.name), options)?;
+            zip.write_all(&item.data)?;
+        }
+
         zip.finish()?;
         Ok(())
     }
@@ -657,6 +2768,7 @@ impl EbookOperator for EpubHandler {
         if self.metadata.title.is_none() {
             self.metadata.title = Some("Untitled".to_string());
         }
+        self.metadata.infer_defaults(&self.content);
         Ok(())
     }
 }
@@ -0,0 +1,329 @@
+use super::resolve_href;
+use crate::Result;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zip::{CompressionMethod, ZipArchive};
+
+const MIMETYPE: &[u8] = b"application/epub+zip";
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+
+/// How serious a validation finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Reading systems may cope, but the book should be fixed before shipping
+    Warning,
+    /// The book breaks the EPUB specification
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single finding of `EpubHandler::validate_detailed`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Everything `EpubHandler::validate_detailed` found, in the order it was checked
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    /// Whether no errors were found; warnings do not make a book invalid
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        self.issues.push(ValidationIssue { severity: Severity::Error, message: message.into() });
+    }
+
+    fn warning(&mut self, message: impl Into<String>) {
+        self.issues.push(ValidationIssue { severity: Severity::Warning, message: message.into() });
+    }
+}
+
+/// What the checks need from the OPF package document
+#[derive(Default)]
+struct Package {
+    version: String,
+    /// Manifest `(id, href)` pairs in document order
+    items: Vec<(String, String)>,
+    spine: Vec<String>,
+    has_language: bool,
+    modified: Option<String>,
+}
+
+pub(super) fn validate(path: &Path) -> Result<ValidationReport> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut report = ValidationReport::default();
+
+    check_mimetype(&mut archive, &mut report)?;
+
+    let Some(opf_path) = check_container(&mut archive, &mut report)? else {
+        return Ok(report);
+    };
+    let mut opf_content = String::new();
+    if archive.by_name(&opf_path)?.read_to_string(&mut opf_content).is_err() {
+        report.error(format!("Package document {opf_path} is not valid UTF-8"));
+        return Ok(report);
+    }
+    if let Err(message) = check_well_formed(&opf_content) {
+        report.error(format!("Package document {opf_path} is not well-formed: {message}"));
+        return Ok(report);
+    }
+    let package = parse_package(&opf_content);
+    let opf_dir = opf_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+
+    let mut ids = HashSet::new();
+    for (id, _) in &package.items {
+        if !ids.insert(id.as_str()) {
+            report.error(format!("Manifest id \"{id}\" is used more than once"));
+        }
+    }
+
+    if package.spine.is_empty() {
+        report.error("Spine has no itemref elements");
+    }
+    for idref in &package.spine {
+        if !ids.contains(idref.as_str()) {
+            report.error(format!("Spine itemref \"{idref}\" does not match any manifest item"));
+        }
+    }
+
+    let archive_names: HashSet<String> = archive.file_names().map(str::to_string).collect();
+    let mut declared = HashSet::new();
+    for (id, href) in &package.items {
+        if href.contains("://") {
+            continue;
+        }
+        let file = href.split('#').next().unwrap_or_default();
+        let target = resolve_href(opf_dir, file);
+        if !archive_names.contains(&target) {
+            report.error(format!("Manifest item \"{id}\" points to {target}, which is not in the archive"));
+        }
+        declared.insert(target);
+    }
+    let mut undeclared: Vec<&String> = archive_names
+        .iter()
+        .filter(|name| !name.ends_with('/') && *name != "mimetype" && !name.starts_with("META-INF/"))
+        .filter(|name| **name != opf_path && !declared.contains(*name))
+        .collect();
+    undeclared.sort();
+    for name in undeclared {
+        report.warning(format!("{name} is in the archive but not declared in the manifest"));
+    }
+
+    if !package.has_language {
+        report.error("Package metadata has no dc:language");
+    }
+    if package.version.starts_with('3') {
+        match package.modified.as_deref() {
+            None => report.error("EPUB 3 package metadata has no dcterms:modified"),
+            Some(modified) if !is_utc_timestamp(modified) => report.warning(format!(
+                "dcterms:modified \"{modified}\" should have the form CCYY-MM-DDThh:mm:ssZ"
+            )),
+            Some(_) => {}
+        }
+    }
+
+    Ok(report)
+}
+
+/// `mimetype` must be the first entry, stored uncompressed, and hold exactly `application/epub+zip`
+fn check_mimetype(archive: &mut ZipArchive<File>, report: &mut ValidationReport) -> Result<()> {
+    let Some(index) = archive.index_for_name("mimetype") else {
+        report.error("The mimetype file is missing");
+        return Ok(());
+    };
+    if index != 0 {
+        report.error("The mimetype file must be the first entry in the archive");
+    }
+    let mut entry = archive.by_index(index)?;
+    if entry.compression() != CompressionMethod::Stored {
+        report.error("The mimetype file must be stored without compression");
+    }
+    let mut content = Vec::new();
+    entry.read_to_end(&mut content)?;
+    if content != MIMETYPE {
+        report.error(format!(
+            "The mimetype file must contain exactly \"application/epub+zip\", found \"{}\"",
+            String::from_utf8_lossy(&content)
+        ));
+    }
+    Ok(())
+}
+
+/// Path of the OPF `container.xml` points to, when it exists in the archive
+fn check_container(archive: &mut ZipArchive<File>, report: &mut ValidationReport) -> Result<Option<String>> {
+    let mut content = String::new();
+    match archive.by_name(CONTAINER_PATH) {
+        Ok(mut entry) => {
+            if entry.read_to_string(&mut content).is_err() {
+                report.error(format!("{CONTAINER_PATH} is not valid UTF-8"));
+                return Ok(None);
+            }
+        }
+        Err(zip::result::ZipError::FileNotFound) => {
+            report.error(format!("{CONTAINER_PATH} is missing"));
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    }
+    if let Err(message) = check_well_formed(&content) {
+        report.error(format!("{CONTAINER_PATH} is not well-formed: {message}"));
+        return Ok(None);
+    }
+
+    let mut reader = Reader::from_str(&content);
+    let mut opf_path = None;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name().as_ref() == b"rootfile" => {
+                opf_path = e
+                    .attributes()
+                    .flatten()
+                    .find(|attr| attr.key.as_ref() == b"full-path")
+                    .map(|attr| String::from_utf8_lossy(&attr.value).into_owned());
+                break;
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    match opf_path {
+        None => {
+            report.error(format!("{CONTAINER_PATH} has no rootfile with a full-path"));
+            Ok(None)
+        }
+        Some(path) if archive.index_for_name(&path).is_none() => {
+            report.error(format!("{CONTAINER_PATH} points to {path}, which is not in the archive"));
+            Ok(None)
+        }
+        Some(path) => Ok(Some(path)),
+    }
+}
+
+/// Parse errors and unclosed elements, which quick-xml alone does not report at end of input
+fn check_well_formed(content: &str) -> std::result::Result<(), String> {
+    let mut reader = Reader::from_str(content);
+    let mut open = Vec::new();
+    let mut has_root = false;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                has_root = true;
+                open.push(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+            }
+            Ok(Event::Empty(_)) => has_root = true,
+            Ok(Event::End(_)) => {
+                open.pop();
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("{e} at byte {}", reader.error_position())),
+            _ => {}
+        }
+    }
+    match open.last() {
+        Some(element) => Err(format!("<{element}> is never closed")),
+        None if !has_root => Err("no root element".to_string()),
+        None => Ok(()),
+    }
+}
+
+fn parse_package(content: &str) -> Package {
+    let mut package = Package::default();
+    let mut reader = Reader::from_str(content);
+    let mut in_manifest = false;
+    let mut in_spine = false;
+    let mut text_of: Option<&'static str> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let attr = |name: &[u8]| {
+                    e.attributes()
+                        .flatten()
+                        .find(|attr| attr.key.as_ref() == name)
+                        .map(|attr| String::from_utf8_lossy(&attr.value).into_owned())
+                };
+                match e.local_name().as_ref() {
+                    b"package" => package.version = attr(b"version").unwrap_or_default(),
+                    b"manifest" => in_manifest = true,
+                    b"spine" => in_spine = true,
+                    b"item" if in_manifest => {
+                        package.items.push((attr(b"id").unwrap_or_default(), attr(b"href").unwrap_or_default()));
+                    }
+                    b"itemref" if in_spine => package.spine.push(attr(b"idref").unwrap_or_default()),
+                    b"language" => text_of = Some("language"),
+                    b"meta" if attr(b"property").as_deref() == Some("dcterms:modified") => {
+                        text_of = Some("modified");
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::Text(e)) => {
+                let text = e.unescape().map(|text| text.trim().to_string()).unwrap_or_default();
+                match text_of.take() {
+                    Some("language") => package.has_language |= !text.is_empty(),
+                    Some(_) if !text.is_empty() => package.modified = Some(text),
+                    _ => {}
+                }
+            }
+            Ok(Event::End(e)) => {
+                match e.local_name().as_ref() {
+                    b"manifest" => in_manifest = false,
+                    b"spine" => in_spine = false,
+                    _ => {}
+                }
+                text_of = None;
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    package
+}
+
+/// Whether `value` has the `CCYY-MM-DDThh:mm:ssZ` shape EPUB 3 requires
+fn is_utc_timestamp(value: &str) -> bool {
+    value.len() == 20
+        && value.bytes().enumerate().all(|(i, b)| match i {
+            4 | 7 => b == b'-',
+            10 => b == b'T',
+            13 | 16 => b == b':',
+            19 => b == b'Z',
+            _ => b.is_ascii_digit(),
+        })
+}
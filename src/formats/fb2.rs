@@ -8,8 +8,10 @@ use std::path::Path;
 pub struct Fb2Handler {
     metadata: Metadata,
     content: String,
-    chapters: Vec<String>,
+    /// `(title, text)` of each top-level body section when read; chapters added for writing
+    chapters: Vec<(String, String)>,
     images: Vec<ImageData>,
+    toc: Vec<TocEntry>,
 }
 
 impl Fb2Handler {
@@ -17,6 +19,37 @@ impl Fb2Handler {
         Self::default()
     }
 
+    /// Render one `<author>` element, splitting the last word off as the last name
+    fn author_xml(name: &str) -> String {
+        use quick_xml::escape::escape;
+
+        match name.trim().rsplit_once(' ') {
+            Some((first, last)) => format!(
+                "      <author>\n        <first-name>{}</first-name>\n        <last-name>{}</last-name>\n      </author>",
+                escape(first.trim()), escape(last)
+            ),
+            None => format!("      <author>\n        <first-name>{}</first-name>\n      </author>", escape(name.trim())),
+        }
+    }
+
+    /// One `<section>`: an optional `<title>`, then a `<p>` per non-blank line of `text`
+    fn section_xml(title: &str, text: &str) -> String {
+        use quick_xml::escape::escape;
+
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty()).peekable();
+        let mut xml = String::from("    <section>\n");
+        if !title.trim().is_empty() {
+            xml.push_str(&format!("      <title><p>{}</p></title>\n", escape(title.trim())));
+            // Chapters read from FB2 start with their section title
+            lines.next_if_eq(&title.trim());
+        }
+        for line in lines {
+            xml.push_str(&format!("      <p>{}</p>\n", escape(line)));
+        }
+        xml.push_str("    </section>");
+        xml
+    }
+
     fn parse_fb2(&mut self, xml_content: &str) -> Result<()> {
         use quick_xml::Reader;
         use quick_xml::events::Event;
@@ -27,8 +60,17 @@ impl Fb2Handler {
         let mut buf = Vec::new();
         let mut in_title_info = false;
         let mut in_body = false;
+        let mut in_author = false;
+        let mut author_name = String::new();
         let mut current_tag = String::new();
         let mut current_text = String::new();
+        // One entry per open <section>; titled sections become (nested) TOC entries
+        let mut sections: Vec<TocEntry> = Vec::new();
+        let mut in_section_title = false;
+        let mut section_title = String::new();
+        let mut toc: Vec<TocEntry> = Vec::new();
+        // Where the text of the open top-level section starts in `current_text`
+        let mut chapter_start = 0;
 
         loop {
             match reader.read_event_into(&mut buf) {
@@ -38,6 +80,17 @@ impl Fb2Handler {
                         in_title_info = true;
                     } else if name == "body" {
                         in_body = true;
+                    } else if name == "author" && in_title_info {
+                        in_author = true;
+                        author_name.clear();
+                    } else if name == "section" && in_body {
+                        if sections.is_empty() {
+                            chapter_start = current_text.len();
+                        }
+                        sections.push(TocEntry::new(String::new(), sections.len() + 1));
+                    } else if name == "title" && in_body && !sections.is_empty() {
+                        in_section_title = true;
+                        section_title.clear();
                     }
                     current_tag = name;
                 }
@@ -47,18 +100,24 @@ impl Fb2Handler {
                     if in_title_info {
                         match current_tag.as_str() {
                             "book-title" => self.metadata.title = Some(text.clone()),
-                            "first-name" | "last-name" => {
-                                let author = self.metadata.author.get_or_insert_with(String::new);
-                                if !author.is_empty() {
-                                    author.push(' ');
+                            "first-name" | "middle-name" | "last-name" | "nickname" if in_author => {
+                                if !author_name.is_empty() {
+                                    author_name.push(' ');
                                 }
-                                author.push_str(&text);
+                                author_name.push_str(&text);
                             }
                             "lang" => self.metadata.language = Some(text.clone()),
                             _ => {}
                         }
                     }
                     
+                    if in_section_title {
+                        if !section_title.is_empty() {
+                            section_title.push(' ');
+                        }
+                        section_title.push_str(&text);
+                    }
+
                     if in_body {
                         current_text.push_str(&text);
                         current_text.push(' ');
@@ -70,6 +129,34 @@ impl Fb2Handler {
                         in_title_info = false;
                     } else if name == "body" {
                         in_body = false;
+                    } else if name == "author" && in_author {
+                        in_author = false;
+                        if !author_name.is_empty() {
+                            self.metadata.add_author(std::mem::take(&mut author_name));
+                        }
+                    } else if name == "title" && in_section_title {
+                        in_section_title = false;
+                        if let Some(section) = sections.last_mut() {
+                            section.title = section_title.trim().to_string();
+                        }
+                    } else if name == "section" && in_body {
+                        if let Some(section) = sections.pop() {
+                            let chapter = current_text[chapter_start..].trim();
+                            if sections.is_empty() && !chapter.is_empty() {
+                                self.chapters.push((section.title.clone(), chapter.to_string()));
+                            }
+                            let siblings = match sections.last_mut() {
+                                Some(parent) => &mut parent.children,
+                                None => &mut toc,
+                            };
+                            if section.title.is_empty() {
+                                // Untitled wrapper sections contribute only their children
+                                siblings.extend(section.children);
+                            } else {
+                                let id = siblings.len() as u32;
+                                siblings.push(section.with_id(id));
+                            }
+                        }
                     } else if name == "p" && in_body {
                         current_text.push('\n');
                     }
@@ -82,6 +169,7 @@ impl Fb2Handler {
         }
 
         self.content = current_text;
+        self.toc = toc;
         self.metadata.format = Some("FB2".to_string());
         Ok(())
     }
@@ -106,12 +194,17 @@ impl EbookReader for Fb2Handler {
     }
 
     fn get_toc(&self) -> Result<Vec<TocEntry>> {
-        Ok(Vec::new())
+        Ok(self.toc.clone())
     }
 
     fn extract_images(&self) -> Result<Vec<ImageData>> {
         Ok(self.images.clone())
     }
+
+    /// Text of the top-level body section at `index`
+    fn get_chapter(&self, index: usize) -> Result<Option<String>> {
+        Ok(self.chapters.get(index).map(|(_, text)| text.clone()))
+    }
 }
 
 impl EbookWriter for Fb2Handler {
@@ -125,8 +218,8 @@ impl EbookWriter for Fb2Handler {
         Ok(())
     }
 
-    fn add_chapter(&mut self, _title: &str, content: &str) -> Result<()> {
-        self.chapters.push(content.to_string());
+    fn add_chapter(&mut self, title: &str, content: &str) -> Result<()> {
+        self.chapters.push((title.to_string(), content.to_string()));
         Ok(())
     }
 
@@ -144,27 +237,44 @@ impl EbookWriter for Fb2Handler {
 
         let mut file = File::create(path)?;
         
-        let title = self.metadata.title.as_deref().unwrap_or("Untitled");
-        let author = self.metadata.author.as_deref().unwrap_or("Unknown");
+        // The FB2 schema requires a book title and at least one author in <title-info>
+        let title = match &self.metadata.title {
+            Some(title) if self.metadata.has_title() => title.as_str(),
+            _ => "Untitled",
+        };
+        let mut authors = self.metadata.all_authors();
+        if !self.metadata.has_author() {
+            authors = vec!["Unknown".to_string()];
+        }
+        let authors_xml = authors
+            .iter()
+            .map(|author| Self::author_xml(author))
+            .collect::<Vec<_>>()
+            .join("\n");
         let lang = self.metadata.language.as_deref().unwrap_or("en");
+        let sections_xml = if self.chapters.is_empty() {
+            Self::section_xml("", &self.content)
+        } else {
+            self.chapters
+                .iter()
+                .map(|(title, text)| Self::section_xml(title, text))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
 
         let fb2_content = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
 <FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0">
   <description>
     <title-info>
       <book-title>{}</book-title>
-      <author>
-        <first-name>{}</first-name>
-      </author>
+{}
       <lang>{}</lang>
     </title-info>
   </description>
   <body>
-    <section>
-      <p>{}</p>
-    </section>
+{}
   </body>
-</FictionBook>"#, title, author, lang, self.content.replace('\n', "</p>\n      <p>"));
+</FictionBook>"#, quick_xml::escape::escape(title), authors_xml, quick_xml::escape::escape(lang), sections_xml);
 
         file.write_all(fb2_content.as_bytes())?;
         Ok(())
@@ -184,6 +294,7 @@ impl EbookOperator for Fb2Handler {
         if self.metadata.title.is_none() {
             self.metadata.title = Some("Untitled".to_string());
         }
+        self.metadata.infer_defaults(&self.content);
         Ok(())
     }
 }
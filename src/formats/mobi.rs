@@ -1,9 +1,11 @@
 use crate::{EbookError, Metadata, Result};
-use crate::traits::{EbookReader, EbookWriter, EbookOperator, TocEntry, ImageData};
+use crate::traits::{ChapterView, EbookReader, EbookWriter, EbookOperator, TocEntry, ImageData};
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 
+use super::palm_db::{self, PalmDatabase};
+
 #[derive(Default)]
 pub struct MobiHandler {
     metadata: Metadata,
@@ -12,6 +14,8 @@ pub struct MobiHandler {
     raw_data: Vec<u8>,
     mobi_header: Option<MobiHeader>,
     toc: Vec<TocEntry>,
+    /// Read from a file without a usable Palm database and MOBI header, or cut short
+    damaged: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -119,34 +123,13 @@ impl MobiHandler {
                 self.raw_data[pos + 108],
                 self.raw_data[pos + 109],
             ]);
-            self.metadata.language = Some(self.language_id_to_code(lang_id));
+            self.metadata.language = Some(palm_db::language_code(lang_id));
         }
 
         self.metadata.format = Some("MOBI".to_string());
         Ok(())
     }
 
-    fn language_id_to_code(&self, id: u16) -> String {
-        // Common language IDs from MOBI/PalmDOC spec
-        match id {
-            0 => "en".to_string(),
-            1 => "fr".to_string(),
-            2 => "de".to_string(),
-            3 => "it".to_string(),
-            4 => "es".to_string(),
-            5 => "nl".to_string(),
-            6 => "sv".to_string(),
-            7 => "nb".to_string(),
-            8 => "da".to_string(),
-            9 => "fi".to_string(),
-            10 => "ja".to_string(),
-            11 => "zh".to_string(),
-            12 => "ko".to_string(),
-            13 => "ar".to_string(),
-            _ => "en".to_string(),
-        }
-    }
-
     fn extract_text(&mut self) -> Result<()> {
         // Text content starts after the headers
         let text_start = if let Some(header) = &self.mobi_header {
@@ -162,16 +145,16 @@ impl MobiHandler {
             // Try to detect UTF-16 encoding first
             if text_data.len() >= 2 {
                 let bom = u16::from_be_bytes([text_data[0], text_data[1]]);
-                if bom == 0xFEFF || bom == 0xFFFE {
-                    if let Ok(text) = String::from_utf16(
+                if (bom == 0xFEFF || bom == 0xFFFE)
+                    && let Ok(text) = String::from_utf16(
                         &text_data[2..]
                             .chunks(2)
                             .map(|c| u16::from_be_bytes([c[0], c[1]]))
                             .collect::<Vec<_>>()
-                    ) {
-                        self.content = text;
-                        return Ok(());
-                    }
+                    )
+                {
+                    self.content = text;
+                    return Ok(());
                 }
             }
 
@@ -185,44 +168,21 @@ impl MobiHandler {
             }
         }
 
-        // Clean up common MOBI formatting artifacts
-        self.content = self.content
-            .replace("<mbp:pagebreak>", "\n\n---\n\n")
-            .replace("</mbp:pagebreak>", "")
-            .replace("&amp;", "&")
-            .replace("&lt;", "<")
-            .replace("&gt;", ">")
-            .replace("&quot;", "\"")
-            .replace("&apos;", "'");
-
+        self.content = palm_db::clean_text(&self.content);
         Ok(())
     }
 
-    fn extract_toc(&mut self) -> Result<()> {
-        // Basic TOC extraction - look for chapter patterns
-        let mut toc = Vec::new();
-        let lines: Vec<&str> = self.content.lines().collect();
-
-        for (idx, line) in lines.iter().enumerate() {
-            let trimmed = line.trim();
-            // Look for potential chapter headings
-            if trimmed.starts_with("Chapter ")
-                || trimmed.starts_with("CHAPTER ")
-                || trimmed.starts_with("# ")
-                || (trimmed.len() < 100 && trimmed.chars().all(|c| c.is_uppercase() || c == ' '))
-            {
-                toc.push(TocEntry {
-                    id: idx as u32,
-                    level: 0,
-                    title: trimmed.to_string(),
-                    href: None,
-                    children: Vec::new(),
-                });
-            }
-        }
-
-        self.toc = toc;
-        Ok(())
+    /// Read the title and text from the Palm database's MOBI header and text records; `false`
+    /// when there is no usable header (or the text is compressed in a way we can't read), for
+    /// the fallback parsing to try. Text records missing from a file cut short mark it damaged.
+    fn read_palm_database(&mut self) -> bool {
+        let Some(database) = PalmDatabase::parse(&self.raw_data) else { return false };
+        let Some((title, content, complete)) = database.book_text() else { return false };
+        self.damaged = !complete;
+        self.metadata.title = title;
+        self.metadata.format = Some("MOBI".to_string());
+        self.content = content;
+        true
     }
 }
 
@@ -231,9 +191,15 @@ impl EbookReader for MobiHandler {
         let mut file = File::open(path)?;
         file.read_to_end(&mut self.raw_data)?;
 
-        self.parse_mobi_header()?;
-        self.extract_text()?;
-        self.extract_toc()?;
+        if !self.read_palm_database() {
+            self.damaged = true;
+            self.parse_mobi_header()?;
+            self.extract_text()?;
+        }
+        self.toc = palm_db::heading_toc(&self.content);
+        if let Some(database) = PalmDatabase::parse(&self.raw_data) {
+            self.images = database.read_images(&mut self.metadata);
+        }
 
         Ok(())
     }
@@ -253,6 +219,16 @@ impl EbookReader for MobiHandler {
     fn extract_images(&self) -> Result<Vec<ImageData>> {
         Ok(self.images.clone())
     }
+
+    /// Text of the section at `index`, sections being separated by page breaks
+    fn get_chapter(&self, index: usize) -> Result<Option<String>> {
+        Ok(palm_db::sections(&self.content).nth(index).map(str::to_string))
+    }
+
+    /// The page-break sections, titled by their opening "Chapter N" line when they have one
+    fn get_chapters(&self) -> Result<Vec<ChapterView>> {
+        Ok(palm_db::chapters(&self.content))
+    }
 }
 
 impl EbookWriter for MobiHandler {
@@ -278,25 +254,15 @@ impl EbookWriter for MobiHandler {
         Ok(())
     }
 
+    /// Write a Palm database with a MOBI header, EXTH metadata, the text uncompressed and the
+    /// images
     fn write_to_file(&self, path: &Path) -> Result<()> {
-        use std::io::Write;
-
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
-        let mut file = File::create(path)?;
-        
-        let mut header = vec![0u8; 78];
-        let title = self.metadata.title.as_deref().unwrap_or("Untitled");
-        let title_bytes = title.as_bytes();
-        let copy_len = title_bytes.len().min(32);
-        header[0..copy_len].copy_from_slice(&title_bytes[0..copy_len]);
-        
-        file.write_all(&header)?;
-        file.write_all(self.content.as_bytes())?;
-        
+        std::fs::write(path, palm_db::build_mobi(&self.metadata, &self.content, &self.images)?)?;
         Ok(())
     }
 }
@@ -307,13 +273,21 @@ impl EbookOperator for MobiHandler {
     }
 
     fn validate(&self) -> Result<bool> {
-        Ok(!self.raw_data.is_empty())
+        Ok(!self.raw_data.is_empty() && !self.damaged)
     }
 
+    /// Fill in a missing title and inferred metadata. Text recovered from a damaged file is
+    /// kept, less the NUL padding of header bytes read as text; writing it out afterwards
+    /// rebuilds the Palm database and MOBI header.
     fn repair(&mut self) -> Result<()> {
+        if self.damaged {
+            self.content = self.content.replace('\0', "");
+            self.damaged = false;
+        }
         if self.metadata.title.is_none() {
             self.metadata.title = Some("Untitled".to_string());
         }
+        self.metadata.infer_defaults(&self.content);
         Ok(())
     }
 }
@@ -5,11 +5,12 @@ pub mod cbz;
 pub mod txt;
 pub mod pdf;
 pub mod azw;
+mod palm_db;
 
-pub use epub::{EpubHandler, EpubVersion};
+pub use epub::{ChapterTitleSource, EpubHandler, EpubVersion, GuideReference, NcxMode, PageMarker, PageProgression, Severity, ValidationIssue, ValidationReport};
 pub use mobi::MobiHandler;
 pub use fb2::Fb2Handler;
-pub use cbz::CbzHandler;
-pub use txt::TxtHandler;
+pub use cbz::{CbzHandler, ComicInfo, ComicInfoVersion, PageOrder};
+pub use txt::{TextEncoding, TxtHandler};
 pub use pdf::PdfHandler;
 pub use azw::AzwHandler;
@@ -0,0 +1,542 @@
+//! Palm database (PDB) container shared by MOBI and AZW: record table, EXTH header, the
+//! text and embedded image records, and a writer for minimal uncompressed MOBI books.
+
+use crate::{EbookError, Metadata, Result};
+use crate::traits::{ChapterView, ImageData, TocEntry};
+
+/// Marker that `<mbp:pagebreak>` tags are replaced with in extracted text
+pub(crate) const PAGE_BREAK: &str = "\n\n---\n\n";
+
+const PDB_HEADER_LEN: usize = 78;
+const RECORD_INFO_LEN: usize = 8;
+/// The MOBI header follows the 16-byte PalmDOC header in record 0
+const MOBI_HEADER_OFFSET: usize = 16;
+const EXTH_FLAG: u32 = 0x40;
+/// Length of the MOBI header the writer emits (the common version 6 layout)
+const MOBI_HEADER_LEN: usize = 232;
+
+// Fields of record 0, from its start: PalmDOC header, then the MOBI header
+const COMPRESSION: usize = 0;
+const TEXT_LENGTH: usize = 4;
+const TEXT_RECORD_COUNT: usize = 8;
+const TEXT_RECORD_SIZE_FIELD: usize = 10;
+const ENCRYPTION: usize = 12;
+const TEXT_ENCODING: usize = 0x1C;
+const FULL_NAME_OFFSET: usize = 0x54;
+const FULL_NAME_LENGTH: usize = 0x58;
+const FIRST_IMAGE_INDEX: usize = 0x6C;
+const EXTH_FLAGS: usize = 0x80;
+const EXTRA_DATA_FLAGS: usize = 0xF2;
+
+const NO_COMPRESSION: u16 = 1;
+const PALMDOC_COMPRESSION: u16 = 2;
+const WINDOWS_1252: u32 = 1252;
+const UTF8: u32 = 65001;
+/// Text records hold at most this many bytes of the book
+const TEXT_RECORD_SIZE: usize = 4096;
+/// Marks the last record of a Kindle book
+const EOF_RECORD: &[u8] = b"\xe9\x8e\r\n";
+
+const EXTH_AUTHOR: u32 = 100;
+const EXTH_PUBLISHER: u32 = 101;
+const EXTH_DESCRIPTION: u32 = 103;
+const EXTH_ISBN: u32 = 104;
+const EXTH_SUBJECT: u32 = 105;
+const EXTH_PUBLISHING_DATE: u32 = 106;
+const EXTH_RIGHTS: u32 = 109;
+const EXTH_COVER_OFFSET: u32 = 201;
+const EXTH_THUMB_OFFSET: u32 = 202;
+const EXTH_UPDATED_TITLE: u32 = 503;
+const EXTH_LANGUAGE: u32 = 524;
+
+/// Record table of a Palm database
+pub(crate) struct PalmDatabase<'a> {
+    data: &'a [u8],
+    offsets: Vec<usize>,
+}
+
+impl<'a> PalmDatabase<'a> {
+    pub(crate) fn parse(data: &'a [u8]) -> Option<Self> {
+        let count = read_u16(data, 76)? as usize;
+        let offsets = (0..count)
+            .map(|i| read_u32(data, PDB_HEADER_LEN + i * RECORD_INFO_LEN).map(|offset| offset as usize))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { data, offsets })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Bytes of record `index`, which run up to the next record (or the end of the file)
+    pub(crate) fn record(&self, index: usize) -> Option<&'a [u8]> {
+        let start = *self.offsets.get(index)?;
+        let end = self.offsets.get(index + 1).copied().unwrap_or(self.data.len());
+        self.data.get(start..end.max(start))
+    }
+
+    /// Record 0, if it holds a MOBI header
+    fn mobi_record0(&self) -> Option<&'a [u8]> {
+        self.record(0).filter(|record0| record0.get(MOBI_HEADER_OFFSET..MOBI_HEADER_OFFSET + 4) == Some(b"MOBI"))
+    }
+
+    /// Name from the database header, the title cut to 31 bytes
+    pub(crate) fn name(&self) -> Option<String> {
+        let name = String::from_utf8_lossy(self.data.get(..32)?).trim_end_matches('\0').trim().to_string();
+        (!name.is_empty()).then_some(name)
+    }
+
+    /// Full title from the MOBI header
+    pub(crate) fn full_name(&self) -> Option<String> {
+        let record0 = self.mobi_record0()?;
+        let offset = read_u32(record0, FULL_NAME_OFFSET)? as usize;
+        let length = read_u32(record0, FULL_NAME_LENGTH)? as usize;
+        let name = String::from_utf8_lossy(record0.get(offset..offset.checked_add(length)?)?).trim().to_string();
+        (!name.is_empty()).then_some(name)
+    }
+
+    /// Whether the PalmDOC header marks the text as encrypted (DRM)
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.mobi_record0().and_then(|record0| read_u16(record0, ENCRYPTION)).is_some_and(|encryption| encryption != 0)
+    }
+
+    /// Bytes of the book's text: the records after record 0 the PalmDOC header counts, without
+    /// their trailing entries, decompressed and cut to the recorded text length, and whether all
+    /// of it was there. `None` without a MOBI header or for text compressed other than PalmDOC.
+    pub(crate) fn text(&self) -> Option<(Vec<u8>, bool)> {
+        let record0 = self.mobi_record0()?;
+        let compression = read_u16(record0, COMPRESSION)?;
+        if compression != NO_COMPRESSION && compression != PALMDOC_COMPRESSION {
+            return None;
+        }
+        let length = read_u32(record0, TEXT_LENGTH)? as usize;
+        let count = read_u16(record0, TEXT_RECORD_COUNT)? as usize;
+        let header_end = MOBI_HEADER_OFFSET + read_u32(record0, MOBI_HEADER_OFFSET + 4)? as usize;
+        let flags = if header_end >= EXTRA_DATA_FLAGS + 2 { read_u16(record0, EXTRA_DATA_FLAGS)? } else { 0 };
+
+        let mut text = Vec::with_capacity(length.min(self.data.len() * 8));
+        let mut complete = true;
+        for index in 1..=count {
+            let Some(record) = self.record(index) else {
+                complete = false;
+                break;
+            };
+            let record = strip_trailing_entries(record, flags);
+            match compression {
+                NO_COMPRESSION => text.extend_from_slice(record),
+                _ => text.extend(palmdoc_decompress(record)),
+            }
+        }
+        complete &= text.len() >= length;
+        text.truncate(length);
+        Some((text, complete))
+    }
+
+    /// `text` decoded with the MOBI header's encoding: Windows-1252 or UTF-8
+    pub(crate) fn decode_text(&self, text: &[u8]) -> String {
+        let encoding = self.mobi_record0().and_then(|record0| read_u32(record0, TEXT_ENCODING));
+        if encoding == Some(WINDOWS_1252) {
+            return encoding_rs::WINDOWS_1252.decode(text).0.into_owned();
+        }
+        String::from_utf8_lossy(text).into_owned()
+    }
+
+    /// `(type, data)` pairs of the EXTH header in record 0, if the MOBI header flags one
+    pub(crate) fn exth_records(&self) -> Vec<(u32, &'a [u8])> {
+        let Some(record0) = self.mobi_record0() else { return Vec::new() };
+        let has_exth = read_u32(record0, EXTH_FLAGS).is_some_and(|flags| flags & EXTH_FLAG != 0);
+        let Some(header_len) = read_u32(record0, MOBI_HEADER_OFFSET + 4) else { return Vec::new() };
+        let exth = MOBI_HEADER_OFFSET + header_len as usize;
+        if !has_exth || record0.get(exth..exth + 4) != Some(b"EXTH") {
+            return Vec::new();
+        }
+
+        let count = read_u32(record0, exth + 8).unwrap_or(0);
+        let mut records = Vec::new();
+        let mut pos = exth + 12;
+        for _ in 0..count {
+            let (Some(kind), Some(len)) = (read_u32(record0, pos), read_u32(record0, pos + 4)) else { break };
+            let len = len as usize;
+            let Some(value) = record0.get(pos + 8..pos + len.max(8)) else { break };
+            records.push((kind, value));
+            pos += len.max(8);
+        }
+        records
+    }
+
+    /// Copy the EXTH text records (authors, publisher, description, ISBN, subjects, date,
+    /// rights, title and language) over the fields parsed from the headers
+    pub(crate) fn apply_exth_metadata(&self, metadata: &mut Metadata) {
+        let mut authors = Vec::new();
+        let mut subjects = Vec::new();
+        for (kind, value) in self.exth_records() {
+            let text = String::from_utf8_lossy(value).trim_end_matches('\0').trim().to_string();
+            if text.is_empty() {
+                continue;
+            }
+            match kind {
+                EXTH_AUTHOR => authors.push(text),
+                EXTH_PUBLISHER => metadata.publisher = Some(text),
+                EXTH_DESCRIPTION => metadata.description = Some(text),
+                EXTH_ISBN => metadata.isbn = Some(text),
+                EXTH_SUBJECT => subjects.push(text),
+                EXTH_PUBLISHING_DATE => metadata.publication_date = Some(text),
+                EXTH_RIGHTS => metadata.rights = Some(text),
+                EXTH_UPDATED_TITLE => metadata.title = Some(text),
+                EXTH_LANGUAGE => metadata.language = Some(text),
+                _ => {}
+            }
+        }
+        if !authors.is_empty() {
+            metadata.set_authors(authors);
+        }
+        if !subjects.is_empty() {
+            metadata.tags = Some(subjects);
+        }
+    }
+
+    /// Index of the first image record, from the MOBI header
+    fn first_image_index(&self) -> Option<usize> {
+        read_u32(self.mobi_record0()?, FIRST_IMAGE_INDEX).map(|index| index as usize)
+    }
+
+    /// Decode the image records from `first_image_index` on. The EXTH cover (or, failing that,
+    /// the thumbnail) comes first and is named `cover.<ext>`; the rest are `image0001.<ext>`, ...
+    /// in record order. Non-image records (FLIS, FCIS, EOF markers) are skipped.
+    pub(crate) fn images(&self) -> Vec<ImageData> {
+        let Some(first) = self.first_image_index() else { return Vec::new() };
+
+        let exth = self.exth_records();
+        let exth_offset = |kind: u32| {
+            exth.iter()
+                .find(|(k, _)| *k == kind)
+                .and_then(|(_, value)| read_u32(value, 0))
+                .map(|offset| first + offset as usize)
+        };
+        let cover_record = exth_offset(EXTH_COVER_OFFSET).or_else(|| exth_offset(EXTH_THUMB_OFFSET));
+
+        let mut images = Vec::new();
+        let mut cover = None;
+        for index in first..self.len() {
+            let Some(data) = self.record(index) else { continue };
+            let Some(extension) = image_extension(data) else { continue };
+            let name = if Some(index) == cover_record {
+                cover = Some(images.len());
+                format!("cover.{extension}")
+            } else {
+                format!("image{:04}.{extension}", index - first + 1)
+            };
+            let mime_type = crate::utils::guess_mime_type(&name);
+            images.push(ImageData::new(name, mime_type, data.to_vec()));
+        }
+
+        // Put the cover first so callers can take it without searching
+        if let Some(position) = cover {
+            let image = images.remove(position);
+            images.insert(0, image);
+        }
+        images
+    }
+
+    /// Title and text of the book, the text decoded and cleaned up with [`clean_text`], and
+    /// whether all of it was there; `None` when [`Self::text`] is
+    pub(crate) fn book_text(&self) -> Option<(Option<String>, String, bool)> {
+        let (text, complete) = self.text()?;
+        Some((self.full_name().or_else(|| self.name()), clean_text(&self.decode_text(&text)), complete))
+    }
+
+    /// The [`Self::images`], after copying the EXTH text records into `metadata`; the EXTH
+    /// cover (listed first) also becomes the metadata cover
+    pub(crate) fn read_images(&self, metadata: &mut Metadata) -> Vec<ImageData> {
+        self.apply_exth_metadata(metadata);
+        let images = self.images();
+        if let Some(cover) = images.first().filter(|image| image.name.starts_with("cover.")) {
+            metadata.cover_image = Some(cover.data.clone());
+            metadata.cover_image_path = Some(cover.name.clone());
+        }
+        images
+    }
+}
+
+/// Text extracted from a Kindle book with its page breaks as [`PAGE_BREAK`] and the basic
+/// entities decoded
+pub(crate) fn clean_text(text: &str) -> String {
+    text.replace("<mbp:pagebreak>", PAGE_BREAK)
+        .replace("</mbp:pagebreak>", "")
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+}
+
+/// Non-empty sections of extracted text, separated by page breaks
+pub(crate) fn sections(text: &str) -> impl Iterator<Item = &str> {
+    text.split(PAGE_BREAK).map(str::trim).filter(|section| !section.is_empty())
+}
+
+/// The page-break sections, titled by their opening "Chapter N" line when they have one
+pub(crate) fn chapters(text: &str) -> Vec<ChapterView> {
+    sections(text)
+        .enumerate()
+        .map(|(idx, section)| {
+            let heading = section.lines().next().filter(|line| crate::utils::is_heading_line(line));
+            let title = heading.map_or_else(|| format!("Chapter {}", idx + 1), |heading| heading.trim().to_string());
+            ChapterView::new(title, section)
+        })
+        .collect()
+}
+
+/// Basic TOC of extracted text: a flat entry per line that looks like a chapter heading
+pub(crate) fn heading_toc(text: &str) -> Vec<TocEntry> {
+    let mut toc = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        // Look for potential chapter headings
+        if trimmed.starts_with("Chapter ")
+            || trimmed.starts_with("CHAPTER ")
+            || trimmed.starts_with("# ")
+            || (trimmed.len() < 100
+                && trimmed.chars().any(char::is_alphabetic)
+                && trimmed.chars().all(|c| c.is_uppercase() || c == ' '))
+        {
+            toc.push(TocEntry {
+                id: idx as u32,
+                level: 0,
+                title: trimmed.to_string(),
+                href: None,
+                children: Vec::new(),
+            });
+        }
+    }
+    toc
+}
+
+/// Language code of a MOBI/PalmDOC language ID; English for IDs we don't know
+pub(crate) fn language_code(id: u16) -> String {
+    match id {
+        1 => "fr",
+        2 => "de",
+        3 => "it",
+        4 => "es",
+        5 => "nl",
+        6 => "sv",
+        7 => "nb",
+        8 => "da",
+        9 => "fi",
+        10 => "ja",
+        11 => "zh",
+        12 => "ko",
+        13 => "ar",
+        _ => "en",
+    }
+    .to_string()
+}
+
+/// A MOBI book as a Palm database: record 0 with the PalmDOC and MOBI headers, the EXTH
+/// metadata and the full title, then the UTF-8 text uncompressed in 4 KB records, the images
+/// (the metadata cover, or else a `cover.*` image, flagged in EXTH) and an end-of-file record.
+/// Fails when the book needs more records than the 16-bit record counts can hold.
+pub(crate) fn build_mobi(metadata: &Metadata, text: &str, images: &[ImageData]) -> Result<Vec<u8>> {
+    let title = metadata.title.as_deref().map(str::trim).filter(|title| !title.is_empty()).unwrap_or("Untitled");
+    let text_records = split_text(text);
+    let record_count = |count: usize| {
+        u16::try_from(count).map_err(|_| {
+            EbookError::NotSupported(format!("a MOBI book holds at most {} records; this one needs {count}", u16::MAX))
+        })
+    };
+    let text_record_count = record_count(text_records.len())?;
+    // Record 0, the text, the images and the end-of-file record
+    let total_record_count = record_count(text_records.len() + images.len() + 2)?;
+    let first_image = text_records.len() + 1;
+    let cover = images
+        .iter()
+        .position(|image| metadata.cover_image_path.as_deref() == Some(image.name.as_str()))
+        .or_else(|| images.iter().position(|image| image.name.rsplit('/').next().is_some_and(|name| name.starts_with("cover."))));
+
+    let mut exth_entries: Vec<(u32, Vec<u8>)> = Vec::new();
+    let mut add = |kind: u32, value: &str| exth_entries.push((kind, value.as_bytes().to_vec()));
+    for author in metadata.all_authors() {
+        add(EXTH_AUTHOR, &author);
+    }
+    let fields = [
+        (EXTH_PUBLISHER, &metadata.publisher),
+        (EXTH_DESCRIPTION, &metadata.description),
+        (EXTH_ISBN, &metadata.isbn),
+        (EXTH_PUBLISHING_DATE, &metadata.publication_date),
+        (EXTH_RIGHTS, &metadata.rights),
+        (EXTH_LANGUAGE, &metadata.language),
+    ];
+    for (kind, value) in fields {
+        if let Some(value) = value {
+            add(kind, value);
+        }
+    }
+    for subject in metadata.tags.iter().flatten() {
+        add(EXTH_SUBJECT, subject);
+    }
+    add(EXTH_UPDATED_TITLE, title);
+    if let Some(cover) = cover {
+        exth_entries.push((EXTH_COVER_OFFSET, (cover as u32).to_be_bytes().to_vec()));
+    }
+
+    let mut exth = b"EXTH".to_vec();
+    let entries_len: usize = exth_entries.iter().map(|(_, value)| value.len() + 8).sum();
+    exth.extend(((12 + entries_len) as u32).to_be_bytes());
+    exth.extend((exth_entries.len() as u32).to_be_bytes());
+    for (kind, value) in &exth_entries {
+        exth.extend(kind.to_be_bytes());
+        exth.extend(((value.len() + 8) as u32).to_be_bytes());
+        exth.extend(value);
+    }
+    exth.resize(exth.len().next_multiple_of(4), 0);
+
+    let mut record0 = vec![0u8; MOBI_HEADER_OFFSET + MOBI_HEADER_LEN];
+    let full_name_offset = record0.len() + exth.len();
+    let mut put = |pos: usize, value: u32| record0[pos..pos + 4].copy_from_slice(&value.to_be_bytes());
+    put(TEXT_LENGTH, text.len() as u32);
+    put(MOBI_HEADER_OFFSET + 4, MOBI_HEADER_LEN as u32);
+    put(MOBI_HEADER_OFFSET + 8, 2); // MOBI book
+    put(TEXT_ENCODING, UTF8);
+    put(MOBI_HEADER_OFFSET + 20, 6); // file version
+    for pos in (0x28..0x50).step_by(4) {
+        put(pos, u32::MAX); // no dictionary indexes
+    }
+    put(0x50, first_image as u32); // first non-text record
+    put(FULL_NAME_OFFSET, full_name_offset as u32);
+    put(FULL_NAME_LENGTH, title.len() as u32);
+    put(0x68, 6); // minimum reader version
+    put(FIRST_IMAGE_INDEX, if images.is_empty() { u32::MAX } else { first_image as u32 });
+    put(EXTH_FLAGS, EXTH_FLAG);
+    put(0xA8, u32::MAX); // no DRM
+    put(0xC4, 1);
+    put(0xC8, u32::MAX); // no FCIS record
+    put(0xD0, u32::MAX); // no FLIS record
+    put(0xF4, u32::MAX); // no INDX record
+    record0[COMPRESSION..COMPRESSION + 2].copy_from_slice(&NO_COMPRESSION.to_be_bytes());
+    record0[TEXT_RECORD_COUNT..TEXT_RECORD_COUNT + 2].copy_from_slice(&text_record_count.to_be_bytes());
+    record0[TEXT_RECORD_SIZE_FIELD..TEXT_RECORD_SIZE_FIELD + 2].copy_from_slice(&(TEXT_RECORD_SIZE as u16).to_be_bytes());
+    record0[MOBI_HEADER_OFFSET..MOBI_HEADER_OFFSET + 4].copy_from_slice(b"MOBI");
+    record0[0xC0..0xC2].copy_from_slice(&1u16.to_be_bytes()); // first content record
+    record0[0xC2..0xC4].copy_from_slice(&text_record_count.to_be_bytes()); // last content record
+    record0.extend(exth);
+    record0.extend(title.as_bytes());
+    // The full name is followed by at least two zero bytes, padded to four
+    record0.resize((record0.len() + 2).next_multiple_of(4), 0);
+
+    let mut records: Vec<&[u8]> = vec![&record0];
+    records.extend(&text_records);
+    records.extend(images.iter().map(|image| image.data.as_slice()));
+    records.push(EOF_RECORD);
+
+    let mut data = vec![0u8; PDB_HEADER_LEN];
+    let name: Vec<u8> = title.bytes().map(|b| if b.is_ascii_alphanumeric() { b } else { b'_' }).take(31).collect();
+    data[..name.len()].copy_from_slice(&name);
+    data[60..68].copy_from_slice(b"BOOKMOBI");
+    data[68..72].copy_from_slice(&((records.len() * 2) as u32).to_be_bytes()); // unique id seed
+    data[76..78].copy_from_slice(&total_record_count.to_be_bytes());
+
+    let mut offset = PDB_HEADER_LEN + records.len() * RECORD_INFO_LEN + 2;
+    for (index, record) in records.iter().enumerate() {
+        data.extend((offset as u32).to_be_bytes());
+        // Attributes byte, then a three-byte unique id
+        data.extend(((index * 2) as u32 & 0x00FF_FFFF).to_be_bytes());
+        offset += record.len();
+    }
+    data.extend([0u8; 2]);
+    for record in records {
+        data.extend(record);
+    }
+    Ok(data)
+}
+
+/// `text` in records of at most `TEXT_RECORD_SIZE` bytes, split between characters
+fn split_text(text: &str) -> Vec<&[u8]> {
+    let mut records = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(TEXT_RECORD_SIZE);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        records.push(&rest.as_bytes()[..end]);
+        rest = &rest[end..];
+    }
+    records
+}
+
+/// A text record without the trailing entries that the extra-data `flags` of the MOBI header
+/// announce: one sized entry per flag bit above the lowest, then the multibyte overlap
+fn strip_trailing_entries(mut record: &[u8], flags: u16) -> &[u8] {
+    for _ in 0..(flags >> 1).count_ones() {
+        // The entry's size, itself included, ends the record as a backward varint
+        let mut size = 0usize;
+        for (shift, &byte) in record.iter().rev().take(4).enumerate() {
+            size |= ((byte & 0x7F) as usize) << (7 * shift);
+            if byte & 0x80 != 0 {
+                break;
+            }
+        }
+        record = &record[..record.len().saturating_sub(size)];
+    }
+    if flags & 1 != 0
+        && let Some(&last) = record.last()
+    {
+        record = &record[..record.len().saturating_sub((last & 0x3) as usize + 1)];
+    }
+    record
+}
+
+/// Expand PalmDOC (LZ77) compression
+fn palmdoc_decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    let mut i = 0;
+    while i < data.len() {
+        let byte = data[i];
+        i += 1;
+        match byte {
+            // The next 1-8 bytes are literal
+            0x01..=0x08 => {
+                let end = (i + byte as usize).min(data.len());
+                out.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+            0x00 | 0x09..=0x7F => out.push(byte),
+            // Two bytes: 11 bits of distance back, 3 of length - 3
+            0x80..=0xBF => {
+                let Some(&next) = data.get(i) else { break };
+                i += 1;
+                let pair = u16::from_be_bytes([byte, next]) & 0x3FFF;
+                let (distance, length) = ((pair >> 3) as usize, (pair & 0x7) as usize + 3);
+                if distance == 0 || distance > out.len() {
+                    break;
+                }
+                for _ in 0..length {
+                    out.push(out[out.len() - distance]);
+                }
+            }
+            // A space followed by an ASCII character
+            0xC0..=0xFF => out.extend([b' ', byte ^ 0x80]),
+        }
+    }
+    out
+}
+
+/// File extension for image record bytes, sniffed from their magic number
+fn image_extension(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0xFF, 0xD8, 0xFF, ..] => Some("jpg"),
+        [0x89, b'P', b'N', b'G', ..] => Some("png"),
+        [b'G', b'I', b'F', b'8', ..] => Some("gif"),
+        [b'B', b'M', ..] => Some("bmp"),
+        _ => None,
+    }
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
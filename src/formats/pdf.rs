@@ -1,13 +1,31 @@
 use crate::{EbookError, Metadata, Result};
-use crate::traits::{EbookReader, EbookWriter, EbookOperator, TocEntry, ImageData};
+use crate::metadata::DEFAULT_AUTHOR_SEPARATOR;
+use crate::traits::{Annotation, AnnotationLocation, EbookReader, EbookWriter, EbookOperator, TocEntry, ImageData};
+use std::collections::HashMap;
 use std::path::Path;
-use lopdf::{Document, dictionary};
+use crate::ocr::OcrBackend;
+use crate::render::PageRenderer;
+use lopdf::{Document, ObjectId, dictionary};
+
+mod layout;
+mod xmp;
 
 #[derive(Default)]
 pub struct PdfHandler {
     metadata: Metadata,
     content: String,
     document: Option<Document>,
+    author_separator: Option<String>,
+    chapter_titles: Vec<String>,
+    dehyphenate: bool,
+    /// Decode text through the fonts' encodings; set when the plain parser finds next to nothing
+    decode_with_fonts: bool,
+    /// Put a `[Figure: page N, WxH]` line where each image is drawn in the extracted text
+    figure_markers: bool,
+    /// Lines of a title page written before the content (title first); empty for none
+    title_page: Vec<String>,
+    /// Recognized text of each page once `ocr_if_image_only` has run
+    ocr_pages: Vec<String>,
 }
 
 impl PdfHandler {
@@ -15,6 +33,240 @@ impl PdfHandler {
         Self::default()
     }
 
+    /// Extracted text of a single page (1-based page number)
+    pub fn get_page_text(&self, page_number: u32) -> Option<String> {
+        if !self.ocr_pages.is_empty() {
+            return self.ocr_pages.get(page_number.checked_sub(1)? as usize).cloned();
+        }
+        let doc = self.document.as_ref()?;
+        let page_id = *doc.get_pages().get(&page_number)?;
+        Some(self.clean_pdf_text(&self.raw_page_text(doc, page_number, page_id)?))
+    }
+
+    /// Images drawn on a page (1-based), encoded for OCR: JPEGs as stored, 8-bit gray and
+    /// RGB samples as PNG. Other encodings (JPEG 2000, CCITT, indexed color) are skipped.
+    pub fn page_images(&self, page_number: u32) -> Vec<ImageData> {
+        let Some(doc) = &self.document else { return Vec::new() };
+        let Some(&page_id) = doc.get_pages().get(&page_number) else { return Vec::new() };
+        let Ok(images) = doc.get_page_images(page_id) else { return Vec::new() };
+
+        let mut encoded = Vec::new();
+        for (idx, image) in images.iter().enumerate() {
+            let filters = image.filters.as_deref().unwrap_or_default();
+            let name = format!("page{page_number:04}-{}", idx + 1);
+            if filters.iter().any(|filter| filter == "DCTDecode") {
+                encoded.push(ImageData::new(format!("{name}.jpg"), "image/jpeg".to_string(), image.content.to_vec()));
+                continue;
+            }
+            if image.bits_per_component != Some(8) {
+                continue;
+            }
+            let samples = match doc.get_object(image.id).and_then(lopdf::Object::as_stream) {
+                Ok(stream) => stream.decompressed_content().unwrap_or_else(|_| stream.content.clone()),
+                Err(_) => continue,
+            };
+            let (width, height) = (image.width as u32, image.height as u32);
+            let decoded = match image.color_space.as_deref() {
+                Some("DeviceGray") => image::GrayImage::from_raw(width, height, samples).map(image::DynamicImage::from),
+                Some("DeviceRGB") => image::RgbImage::from_raw(width, height, samples).map(image::DynamicImage::from),
+                _ => None,
+            };
+            let mut png = std::io::Cursor::new(Vec::new());
+            if let Some(decoded) = decoded
+                && decoded.write_to(&mut png, image::ImageFormat::Png).is_ok()
+            {
+                encoded.push(ImageData::new(format!("{name}.png"), "image/png".to_string(), png.into_inner()));
+            }
+        }
+        encoded
+    }
+
+    /// A cover made from the first page: the page image itself when page 1 is a scan with no
+    /// text, else page 1 rendered by `renderer` (the build's default renderer when `None`)
+    pub fn first_page_cover(&self, renderer: Option<&dyn PageRenderer>) -> Result<ImageData> {
+        let doc = self.document.as_ref().ok_or_else(|| EbookError::NotFound("PDF document".to_string()))?;
+        if doc.get_pages().is_empty() {
+            return Err(EbookError::NotFound("page 1".to_string()));
+        }
+        let text = self.get_page_text(1).unwrap_or_default();
+        if text.trim().is_empty()
+            && let Some(scan) = self.page_images(1).into_iter().max_by_key(|image| image.data.len())
+        {
+            let extension = Path::new(&scan.name).extension().and_then(|ext| ext.to_str()).unwrap_or("png");
+            return Ok(ImageData::new(format!("cover.{extension}"), scan.mime_type, scan.data));
+        }
+
+        let default_renderer;
+        let renderer = match renderer {
+            Some(renderer) => renderer,
+            None => {
+                default_renderer = crate::render::default_renderer()?;
+                default_renderer.as_ref()
+            }
+        };
+        let mut pdf = Vec::new();
+        doc.clone().save_to(&mut pdf)?;
+        let page = renderer.render_page(&pdf, 1, crate::render::COVER_WIDTH)?;
+        Ok(ImageData::new("cover.png".to_string(), page.mime_type, page.data))
+    }
+
+    /// Replace the extracted text with OCR output when the PDF has next to no text layer
+    /// (a scan). Returns whether OCR ran; pages without images come out empty.
+    pub fn ocr_if_image_only(&mut self, backend: &dyn OcrBackend, language: &str) -> Result<bool> {
+        let Some(page_count) = self.document.as_ref().map(|doc| doc.get_pages().len()) else {
+            return Ok(false);
+        };
+        if !crate::ocr::needs_ocr(&self.content, page_count) {
+            return Ok(false);
+        }
+
+        let mut pages = Vec::with_capacity(page_count);
+        let mut text = String::new();
+        for page_number in 1..=page_count as u32 {
+            let recognized = crate::ocr::recognize_images(backend, &self.page_images(page_number), language)?;
+            let page_text = recognized.join("\n\n");
+            text.push_str(&page_text);
+            text.push_str(&format!("\n\n--- Page {page_number} ---\n"));
+            pages.push(page_text);
+        }
+        self.content = self.clean_pdf_text(&text);
+        self.ocr_pages = pages;
+        Ok(true)
+    }
+
+    /// Join words hyphenated across line breaks when extracting text (see [`crate::utils::dehyphenate`])
+    pub fn set_dehyphenate(&mut self, dehyphenate: bool) {
+        self.dehyphenate = dehyphenate;
+    }
+
+    /// Mark where images are drawn with `[Figure: page N, WxH]` lines (the image's size in
+    /// pixels) in the extracted text; set before reading
+    pub fn set_figure_markers(&mut self, enabled: bool) {
+        self.figure_markers = enabled;
+    }
+
+    /// Separator used to join/split multiple authors in the Info `Author` entry
+    pub fn set_author_separator(&mut self, separator: impl Into<String>) {
+        self.author_separator = Some(separator.into());
+    }
+
+    /// Write a page with these lines (title first, in a larger size) before the content
+    pub fn set_title_page(&mut self, lines: Vec<String>) {
+        self.title_page = lines;
+    }
+
+    fn author_separator(&self) -> &str {
+        self.author_separator.as_deref().unwrap_or(DEFAULT_AUTHOR_SEPARATOR)
+    }
+
+    /// Walk an outline item and its siblings (`/First` → `/Next` chain) into TOC entries
+    fn read_outline_items(
+        doc: &Document,
+        first: Option<lopdf::ObjectId>,
+        level: usize,
+        visited: &mut std::collections::HashSet<lopdf::ObjectId>,
+    ) -> Vec<TocEntry> {
+        let mut entries = Vec::new();
+        let mut next = first;
+
+        while let Some(id) = next {
+            // Guard against malformed outlines that link back to an earlier item
+            if !visited.insert(id) {
+                break;
+            }
+            let Ok(item) = doc.get_dictionary(id) else {
+                break;
+            };
+
+            let title = item
+                .get(b"Title")
+                .ok()
+                .and_then(|t| t.as_str().ok())
+                .map(Self::decode_pdf_string)
+                .unwrap_or_default();
+            let children_start = item.get(b"First").ok().and_then(|o| o.as_reference().ok());
+            let children = Self::read_outline_items(doc, children_start, level + 1, visited);
+
+            let mut entry = TocEntry::new(title, level).with_id(entries.len() as u32);
+            entry.children = children;
+            entries.push(entry);
+
+            next = item.get(b"Next").ok().and_then(|o| o.as_reference().ok());
+        }
+
+        entries
+    }
+
+    /// Decode a PDF text string (UTF-16BE with BOM, otherwise PDFDocEncoding/Latin-1)
+    fn decode_pdf_string(bytes: &[u8]) -> String {
+        if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        } else {
+            bytes.iter().map(|&b| b as char).collect()
+        }
+    }
+
+    /// Link, text and highlight annotations from each page's `/Annots` array
+    fn read_annotations(doc: &Document) -> Vec<Annotation> {
+        let mut annotations = Vec::new();
+
+        for (page_index, page_id) in doc.get_pages().values().enumerate() {
+            let Ok(page) = doc.get_dictionary(*page_id) else {
+                continue;
+            };
+            let annots = match page.get(b"Annots") {
+                Ok(lopdf::Object::Reference(id)) => doc.get_object(*id).and_then(|o| o.as_array()).ok(),
+                Ok(obj) => obj.as_array().ok(),
+                Err(_) => None,
+            };
+
+            for annot in annots.into_iter().flatten() {
+                let annot = match annot.as_reference() {
+                    Ok(id) => doc.get_dictionary(id).ok(),
+                    Err(_) => annot.as_dict().ok(),
+                };
+                let Some(annot) = annot else {
+                    continue;
+                };
+                let Ok(subtype) = annot.get(b"Subtype").and_then(|s| s.as_name_str()) else {
+                    continue;
+                };
+                if !matches!(subtype, "Link" | "Text" | "Highlight") {
+                    continue;
+                }
+
+                let contents = annot
+                    .get(b"Contents")
+                    .and_then(|c| c.as_str())
+                    .map(Self::decode_pdf_string)
+                    .ok();
+                // Links usually carry no contents; fall back to their URI action
+                let uri = annot
+                    .get(b"A")
+                    .and_then(|a| match a.as_reference() {
+                        Ok(id) => doc.get_dictionary(id),
+                        Err(_) => a.as_dict(),
+                    })
+                    .and_then(|action| action.get(b"URI"))
+                    .and_then(|u| u.as_str())
+                    .map(Self::decode_pdf_string)
+                    .ok();
+
+                annotations.push(Annotation::new(
+                    subtype,
+                    AnnotationLocation::Page(page_index),
+                    contents.or(uri).unwrap_or_default(),
+                ));
+            }
+        }
+
+        annotations
+    }
+
     fn extract_metadata(&mut self, doc: &Document) -> Result<()> {
         if let Ok(info_ref) = doc.trailer.get(b"Info") {
             // Dereference if it's an indirect object
@@ -24,41 +276,101 @@ impl PdfHandler {
                 Some(info_ref)
             };
             
-            if let Some(info) = info_obj {
-                if let Ok(info_dict) = info.as_dict() {
-                    if let Ok(title) = info_dict.get(b"Title") {
-                        if let Ok(title_str) = title.as_string() {
-                            self.metadata.title = Some(title_str.to_string());
-                        }
-                    }
-                    
-                    if let Ok(author) = info_dict.get(b"Author") {
-                        if let Ok(author_str) = author.as_string() {
-                            self.metadata.author = Some(author_str.to_string());
-                        }
-                    }
-                    
-                    if let Ok(subject) = info_dict.get(b"Subject") {
-                        if let Ok(subject_str) = subject.as_string() {
-                            self.metadata.publisher = Some(subject_str.to_string());
-                        }
+            if let Some(info) = info_obj
+                && let Ok(info_dict) = info.as_dict()
+            {
+                if let Ok(title) = info_dict.get(b"Title")
+                    && let Ok(title_str) = title.as_string()
+                {
+                    self.metadata.title = Some(title_str.to_string());
+                }
+
+                if let Ok(author) = info_dict.get(b"Author")
+                    && let Ok(author_str) = author.as_string()
+                {
+                    let separator = self.author_separator().to_string();
+                    self.metadata.set_authors_from_joined(&author_str, &separator);
+                }
+
+                if let Ok(subject) = info_dict.get(b"Subject")
+                    && let Ok(subject_str) = subject.as_string()
+                {
+                    self.metadata.description = Some(subject_str.to_string());
+                }
+
+                if let Ok(keywords) = info_dict.get(b"Keywords")
+                    && let Ok(keywords_str) = keywords.as_string()
+                {
+                    let tags: Vec<String> = keywords_str
+                        .split([',', ';'])
+                        .map(|tag| tag.trim().to_string())
+                        .filter(|tag| !tag.is_empty())
+                        .collect();
+                    if !tags.is_empty() {
+                        self.metadata.tags = Some(tags);
                     }
                 }
             }
         }
-        
+
+        // The XMP packet is authoritative: its values win over the Info dictionary's
+        if let Some(packet) = Self::xmp_packet(doc) {
+            let dc = xmp::DublinCore::parse(&packet);
+            if let Some(title) = dc.first("dc:title") {
+                self.metadata.title = Some(title);
+            }
+            let creators = dc.all("dc:creator");
+            if !creators.is_empty() {
+                self.metadata.set_authors(creators);
+            }
+            let subjects = dc.all("dc:subject");
+            if !subjects.is_empty() {
+                self.metadata.tags = Some(subjects);
+            }
+            for (property, field) in [
+                ("dc:description", &mut self.metadata.description),
+                ("dc:publisher", &mut self.metadata.publisher),
+                ("dc:language", &mut self.metadata.language),
+                ("dc:rights", &mut self.metadata.rights),
+                ("dc:date", &mut self.metadata.publication_date),
+            ] {
+                if let Some(value) = dc.first(property) {
+                    *field = Some(value);
+                }
+            }
+        }
+
         self.metadata.format = Some("PDF".to_string());
         Ok(())
     }
 
+    /// Bytes of the XMP packet the catalog's `/Metadata` stream holds, if any
+    fn xmp_packet(doc: &Document) -> Option<Vec<u8>> {
+        let metadata = doc.catalog().ok()?.get(b"Metadata").ok()?;
+        let stream = match metadata.as_reference() {
+            Ok(id) => doc.get_object(id).ok()?.as_stream().ok()?,
+            Err(_) => metadata.as_stream().ok()?,
+        };
+        Some(stream.decompressed_content().unwrap_or_else(|_| stream.content.clone()))
+    }
+
     fn extract_text(&mut self, doc: &Document) -> Result<()> {
         let mut text = String::new();
         let pages = doc.get_pages();
 
+        // Under one printable character per page on average means the strings are most likely
+        // glyph codes of CID fonts, which only the fonts' ToUnicode CMaps can turn into text
+        self.decode_with_fonts = false;
+        let visible: usize = pages
+            .values()
+            .filter_map(|&page_id| self.page_text(doc, page_id, &HashMap::new()))
+            .map(|page_text| page_text.chars().filter(|c| !c.is_whitespace() && !c.is_control()).count())
+            .sum();
+        self.decode_with_fonts = visible < pages.len();
+
         for (page_num, page_id) in pages.iter() {
             // Try to extract text using the page's content
-            if let Ok(content) = doc.get_page_content(*page_id) {
-                let page_text = self.decode_pdf_text(&content);
+            if let Some(page_text) = self.raw_page_text(doc, *page_num, *page_id) {
                 text.push_str(&page_text);
                 text.push('\n');
             }
@@ -72,7 +384,86 @@ impl PdfHandler {
         Ok(())
     }
 
-    fn decode_pdf_text(&self, content: &[u8]) -> String {
+    /// Text of a page before cleanup, or `None` if its content can't be read
+    fn raw_page_text(&self, doc: &Document, page_number: u32, page_id: ObjectId) -> Option<String> {
+        let figures = if self.figure_markers { Self::figure_markers(doc, page_number, page_id) } else { HashMap::new() };
+        self.page_text(doc, page_id, &figures)
+    }
+
+    /// Text of a page before cleanup with `figures` (markers by XObject resource name) where
+    /// those XObjects are drawn
+    fn page_text(&self, doc: &Document, page_id: ObjectId, figures: &HashMap<Vec<u8>, String>) -> Option<String> {
+        if self.decode_with_fonts {
+            return Self::decode_text_with_fonts(doc, page_id, figures);
+        }
+        let content = doc.get_page_content(page_id).ok()?;
+        Some(self.decode_pdf_text(&content, figures))
+    }
+
+    /// `[Figure: page N, WxH]` markers for the image XObjects of a page's resources, by name
+    fn figure_markers(doc: &Document, page_number: u32, page_id: ObjectId) -> HashMap<Vec<u8>, String> {
+        let mut markers = HashMap::new();
+        let Ok((resources, resource_ids)) = doc.get_page_resources(page_id) else { return markers };
+        let dictionaries = resources.into_iter().chain(resource_ids.into_iter().filter_map(|id| doc.get_dictionary(id).ok()));
+        for resources in dictionaries {
+            let Some(xobjects) = resources.get(b"XObject").ok().and_then(|xobjects| match xobjects.as_reference() {
+                Ok(id) => doc.get_dictionary(id).ok(),
+                Err(_) => xobjects.as_dict().ok(),
+            }) else {
+                continue;
+            };
+            for (name, xobject) in xobjects.iter() {
+                let Some(image) = xobject.as_reference().ok().and_then(|id| doc.get_object(id).ok()).and_then(|o| o.as_stream().ok()) else {
+                    continue;
+                };
+                if image.dict.get(b"Subtype").and_then(|subtype| subtype.as_name()).ok() != Some(b"Image".as_slice()) {
+                    continue;
+                }
+                let dimension = |key: &[u8]| image.dict.get(key).and_then(|value| value.as_i64()).unwrap_or(0);
+                markers
+                    .entry(name.clone())
+                    .or_insert_with(|| format!("[Figure: page {page_number}, {}x{}]", dimension(b"Width"), dimension(b"Height")));
+            }
+        }
+        markers
+    }
+
+    /// Decode a page's text operators through the encoding of the font selected by `Tf`,
+    /// including `/ToUnicode` CMaps for Identity-encoded CID fonts, in reading order. Strings in
+    /// fonts whose encoding can't be determined are skipped.
+    fn decode_text_with_fonts(doc: &Document, page_id: ObjectId, figures: &HashMap<Vec<u8>, String>) -> Option<String> {
+        use lopdf::Encoding;
+        use lopdf::content::Content;
+
+        let encodings: std::collections::BTreeMap<Vec<u8>, Encoding> = doc
+            .get_page_fonts(page_id)
+            .ok()?
+            .into_iter()
+            .filter_map(|(name, font)| font.get_font_encoding(doc).ok().map(|encoding| (name, encoding)))
+            .collect();
+        let content = Content::decode(&doc.get_page_content(page_id).ok()?).ok()?;
+        let fragments = layout::fragments(
+            &content.operations,
+            |font, bytes| encodings.get(font)?.bytes_to_string(bytes).ok(),
+            |name| figures.get(name).cloned(),
+        );
+        Some(layout::reading_order(fragments))
+    }
+
+    /// Text of a content stream in reading order, placing each string where the text and
+    /// graphics matrices put it; a stream that doesn't parse is scanned in stream order
+    fn decode_pdf_text(&self, content: &[u8], figures: &HashMap<Vec<u8>, String>) -> String {
+        match lopdf::content::Content::decode(content) {
+            Ok(content) => layout::reading_order(layout::fragments(
+                &content.operations,
+                |_, bytes| Some(String::from_utf8_lossy(bytes).into_owned()),
+                |name| figures.get(name).cloned(),
+            )),
+            Err(_) => self.stream_order_text(content),
+        }
+    }
+
+    fn stream_order_text(&self, content: &[u8]) -> String {
         let mut text = String::new();
         let content_str = String::from_utf8_lossy(content);
 
@@ -99,6 +490,10 @@ impl PdfHandler {
                     text.push_str(&substring);
                     text.push(' ');
                 }
+                // Td, TD and T* move to a new line
+                else if c1 == 'T' && matches!(c2, 'd' | 'D' | '*') && !text.is_empty() {
+                    text.push('\n');
+                }
             }
             i += 1;
         }
@@ -153,27 +548,30 @@ impl PdfHandler {
     }
 
     fn clean_pdf_text(&self, text: &str) -> String {
-        text.replace("\\(", "(")
+        let text = text.replace("\\(", "(")
             .replace("\\)", ")")
             .replace("\\[", "[")
             .replace("\\]", "]")
             .replace("\\{", "{")
             .replace("\\}", "}")
             .replace("\\\\", "\\")
-            .split("--- Page")
-            .map(|s| s.trim())
-            .collect::<Vec<_>>()
-            .join("\n\n")
             .lines()
             .filter(|line| !line.trim().is_empty() && !line.starts_with("---"))
             .collect::<Vec<_>>()
-            .join("\n")
+            .join("\n");
+
+        if self.dehyphenate {
+            crate::utils::dehyphenate(&text)
+        } else {
+            text
+        }
     }
 }
 
 impl EbookReader for PdfHandler {
     fn read_from_file(&mut self, path: &Path) -> Result<()> {
         let doc = Document::load(path)?;
+        self.ocr_pages.clear();
         
         self.extract_metadata(&doc)?;
         self.extract_text(&doc)?;
@@ -191,12 +589,19 @@ impl EbookReader for PdfHandler {
     }
 
     fn get_toc(&self) -> Result<Vec<TocEntry>> {
-        if let Some(doc) = &self.document {
-            if let Ok(catalog) = doc.catalog() {
-                if let Ok(_outlines) = catalog.get(b"Outlines") {
-                    return Ok(Vec::new());
-                }
-            }
+        if let Some(doc) = &self.document
+            && let Ok(catalog) = doc.catalog()
+            && let Ok(outlines) = catalog.get(b"Outlines")
+        {
+            let outlines = match outlines.as_reference() {
+                Ok(id) => doc.get_dictionary(id).ok(),
+                Err(_) => outlines.as_dict().ok(),
+            };
+            let first = outlines
+                .and_then(|o| o.get(b"First").ok())
+                .and_then(|o| o.as_reference().ok());
+            let mut visited = std::collections::HashSet::new();
+            return Ok(Self::read_outline_items(doc, first, 1, &mut visited));
         }
         Ok(Vec::new())
     }
@@ -204,6 +609,15 @@ impl EbookReader for PdfHandler {
     fn extract_images(&self) -> Result<Vec<ImageData>> {
         Ok(Vec::new())
     }
+
+    /// Chapters of a PDF are its pages
+    fn get_chapter(&self, index: usize) -> Result<Option<String>> {
+        Ok(self.get_page_text(index as u32 + 1))
+    }
+
+    fn get_annotations(&self) -> Result<Vec<Annotation>> {
+        Ok(self.document.as_ref().map(Self::read_annotations).unwrap_or_default())
+    }
 }
 
 impl EbookWriter for PdfHandler {
@@ -217,7 +631,8 @@ impl EbookWriter for PdfHandler {
         Ok(())
     }
 
-    fn add_chapter(&mut self, _title: &str, content: &str) -> Result<()> {
+    fn add_chapter(&mut self, title: &str, content: &str) -> Result<()> {
+        self.chapter_titles.push(title.to_string());
         self.content.push_str("\n\n");
         self.content.push_str(content);
         Ok(())
@@ -262,34 +677,97 @@ impl EbookWriter for PdfHandler {
             "Resources" => resources_id,
             "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
         });
-        
+
+        let mut kids: Vec<lopdf::Object> = vec![page_id.into()];
+        if let Some((title, rest)) = self.title_page.split_first() {
+            let escape = |text: &str| text.replace(')', "\\)").replace('(', "\\(");
+            let mut content = format!("BT /F1 24 Tf 50 600 Td ({}) Tj /F1 14 Tf", escape(title));
+            for line in rest {
+                content.push_str(&format!(" 0 -32 Td ({}) Tj", escape(line)));
+            }
+            content.push_str(" ET");
+            let title_content_id = doc.add_object(lopdf::Stream::new(dictionary! {}, content.into_bytes()));
+            let title_page_id = doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => title_content_id,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            });
+            kids.insert(0, title_page_id.into());
+        }
+
         let pages = dictionary! {
             "Type" => "Pages",
-            "Kids" => vec![page_id.into()],
-            "Count" => 1,
+            "Count" => kids.len() as i64,
+            "Kids" => kids,
         };
         doc.objects.insert(pages_id, lopdf::Object::Dictionary(pages));
         
-        let catalog_id = doc.add_object(dictionary! {
+        let mut catalog = dictionary! {
             "Type" => "Catalog",
             "Pages" => pages_id,
-        });
+        };
+
+        // Chapters become a flat outline (bookmarks) pointing at the page
+        if !self.chapter_titles.is_empty() {
+            let outlines_id = doc.new_object_id();
+            let item_ids: Vec<lopdf::ObjectId> = self.chapter_titles.iter().map(|_| doc.new_object_id()).collect();
+            for (idx, title) in self.chapter_titles.iter().enumerate() {
+                let mut item = dictionary! {
+                    "Title" => lopdf::Object::String(title.as_bytes().to_vec(), lopdf::StringFormat::Literal),
+                    "Parent" => outlines_id,
+                    "Dest" => vec![page_id.into(), "Fit".into()],
+                };
+                if idx > 0 {
+                    item.set("Prev", item_ids[idx - 1]);
+                }
+                if let Some(next) = item_ids.get(idx + 1) {
+                    item.set("Next", *next);
+                }
+                doc.objects.insert(item_ids[idx], lopdf::Object::Dictionary(item));
+            }
+            let outlines = dictionary! {
+                "Type" => "Outlines",
+                "First" => item_ids[0],
+                "Last" => item_ids[item_ids.len() - 1],
+                "Count" => item_ids.len() as i64,
+            };
+            doc.objects.insert(outlines_id, lopdf::Object::Dictionary(outlines));
+            catalog.set("Outlines", outlines_id);
+        }
+
+        // Modern readers take metadata from the XMP packet; the Info dictionary is for older ones
+        let packet = xmp::packet(&self.metadata);
+        let metadata_id = doc.add_object(lopdf::Stream::new(
+            dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+            packet.into_bytes(),
+        ));
+        catalog.set("Metadata", metadata_id);
+
+        let catalog_id = doc.add_object(catalog);
         
         doc.trailer.set("Root", catalog_id);
         
         // Create Info dictionary with metadata
         let mut info_dict = lopdf::Dictionary::new();
         
-        if let Some(title) = &self.metadata.title {
+        if let Some(title) = &self.metadata.title
+            && self.metadata.has_title()
+        {
             info_dict.set("Title", lopdf::Object::String(title.as_bytes().to_vec(), lopdf::StringFormat::Literal));
         }
         
-        if let Some(author) = &self.metadata.author {
+        if let Some(author) = self.metadata.joined_authors(self.author_separator()) {
             info_dict.set("Author", lopdf::Object::String(author.as_bytes().to_vec(), lopdf::StringFormat::Literal));
         }
         
-        if let Some(publisher) = &self.metadata.publisher {
-            info_dict.set("Subject", lopdf::Object::String(publisher.as_bytes().to_vec(), lopdf::StringFormat::Literal));
+        if let Some(description) = &self.metadata.description {
+            info_dict.set("Subject", lopdf::Object::String(description.as_bytes().to_vec(), lopdf::StringFormat::Literal));
+        }
+
+        if let Some(tags) = self.metadata.tags.as_ref().filter(|tags| !tags.is_empty()) {
+            info_dict.set("Keywords", lopdf::Object::String(tags.join(", ").into_bytes(), lopdf::StringFormat::Literal));
         }
         
         if !info_dict.is_empty() {
@@ -315,6 +793,7 @@ impl EbookOperator for PdfHandler {
         if self.metadata.title.is_none() {
             self.metadata.title = Some("Untitled".to_string());
         }
+        self.metadata.infer_defaults(&self.content);
         Ok(())
     }
 }
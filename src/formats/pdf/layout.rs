@@ -0,0 +1,256 @@
+//! Placing the text a page shows, so extraction follows reading order rather than the order
+//! of the content stream: lines top to bottom, fragments left to right, columns one at a time
+
+use lopdf::Object;
+use lopdf::content::Operation;
+
+/// Average glyph width as a fraction of the font size, for estimating how far text reaches
+const GLYPH_WIDTH: f32 = 0.5;
+
+/// Text shown from one position, up to the next text positioning operator
+#[derive(Debug, Clone)]
+pub(super) struct Fragment {
+    x: f32,
+    y: f32,
+    /// Font size in page units
+    size: f32,
+    text: String,
+}
+
+impl Fragment {
+    /// Estimated right edge, from the longest line of the text
+    fn right(&self) -> f32 {
+        let chars = self.text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+        self.x + chars as f32 * self.size * GLYPH_WIDTH
+    }
+}
+
+/// An affine transformation `[a b c d e f]`, as in the PDF `cm` and `Tm` operators
+#[derive(Debug, Clone, Copy)]
+struct Matrix([f32; 6]);
+
+impl Matrix {
+    const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn translation(x: f32, y: f32) -> Self {
+        Matrix([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    fn from_operands(operands: &[Object]) -> Option<Self> {
+        let values: Vec<f32> = operands.iter().filter_map(|operand| operand.as_float().ok()).collect();
+        <[f32; 6]>::try_from(values).ok().map(Matrix)
+    }
+
+    /// `self` applied first, then `then`
+    fn then(self, then: Matrix) -> Matrix {
+        let [a, b, c, d, e, f] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = then.0;
+        Matrix([
+            a * a2 + b * c2,
+            a * b2 + b * d2,
+            c * a2 + d * c2,
+            c * b2 + d * d2,
+            e * a2 + f * c2 + e2,
+            e * b2 + f * d2 + f2,
+        ])
+    }
+}
+
+/// Text of the page's `operations` positioned on the page. `decode` turns the bytes of a shown
+/// string into text for the font selected by `Tf` (its resource name), or `None` to skip it.
+/// `figure` gives the marker for an XObject drawn with `Do` (by resource name), placed as its
+/// own line at the top of the image, or `None` to leave it out.
+pub(super) fn fragments(
+    operations: &[Operation],
+    decode: impl Fn(&[u8], &[u8]) -> Option<String>,
+    figure: impl Fn(&[u8]) -> Option<String>,
+) -> Vec<Fragment> {
+    let mut fragments: Vec<Fragment> = Vec::new();
+    let mut ctm = Matrix::IDENTITY;
+    let mut saved = Vec::new();
+    let (mut text_matrix, mut line_matrix) = (Matrix::IDENTITY, Matrix::IDENTITY);
+    let (mut font, mut font_size, mut leading): (Vec<u8>, f32, f32) = (Vec::new(), 0.0, 0.0);
+    // Whether shown text continues the last fragment, i.e. nothing moved the pen since
+    let mut continues = false;
+
+    let float = |operation: &Operation, index: usize| operation.operands.get(index).and_then(|o| o.as_float().ok());
+    // Td: start a new line offset from the start of the current one
+    let next_line = |line_matrix: &mut Matrix, text_matrix: &mut Matrix, x: f32, y: f32| {
+        *line_matrix = Matrix::translation(x, y).then(*line_matrix);
+        *text_matrix = *line_matrix;
+    };
+    for operation in operations {
+        match operation.operator.as_str() {
+            "q" => saved.push(ctm),
+            "Q" => ctm = saved.pop().unwrap_or(Matrix::IDENTITY),
+            "cm" => {
+                if let Some(matrix) = Matrix::from_operands(&operation.operands) {
+                    ctm = matrix.then(ctm);
+                }
+            }
+            "BT" => {
+                (text_matrix, line_matrix) = (Matrix::IDENTITY, Matrix::IDENTITY);
+                continues = false;
+            }
+            "Tf" => {
+                font = operation.operands.first().and_then(|name| name.as_name().ok()).unwrap_or_default().to_vec();
+                font_size = float(operation, 1).unwrap_or(font_size);
+            }
+            "TL" => leading = float(operation, 0).unwrap_or(leading),
+            "Td" | "TD" => {
+                let (x, y) = (float(operation, 0).unwrap_or(0.0), float(operation, 1).unwrap_or(0.0));
+                if operation.operator == "TD" {
+                    leading = -y;
+                }
+                next_line(&mut line_matrix, &mut text_matrix, x, y);
+                continues = false;
+            }
+            "Tm" => {
+                if let Some(matrix) = Matrix::from_operands(&operation.operands) {
+                    (text_matrix, line_matrix) = (matrix, matrix);
+                }
+                continues = false;
+            }
+            "T*" => {
+                next_line(&mut line_matrix, &mut text_matrix, 0.0, -leading);
+                continues = false;
+            }
+            "Tj" | "TJ" | "'" | "\"" => {
+                if operation.operator == "'" || operation.operator == "\"" {
+                    next_line(&mut line_matrix, &mut text_matrix, 0.0, -leading);
+                    continues = false;
+                }
+                let mut text = String::new();
+                // `"` takes word and character spacing before its string
+                let shown = if operation.operator == "\"" { operation.operands.get(2..) } else { Some(&operation.operands[..]) };
+                push_shown(&mut text, shown.unwrap_or_default(), &|bytes| decode(&font, bytes));
+                if text.is_empty() {
+                    continue;
+                }
+                match fragments.last_mut() {
+                    Some(last) if continues => last.text.push_str(&text),
+                    _ => {
+                        let [a, b, c, d, x, y] = text_matrix.then(ctm).0;
+                        let scale = (b * b + d * d).sqrt().max((a * a + c * c).sqrt());
+                        fragments.push(Fragment { x, y, size: (font_size * scale).abs(), text });
+                    }
+                }
+                continues = true;
+            }
+            "Do" => {
+                let Some(text) = operation.operands.first().and_then(|name| name.as_name().ok()).and_then(&figure) else {
+                    continue;
+                };
+                // The image fills the unit square the CTM maps onto the page
+                let [_, _, _, d, x, y] = ctm.0;
+                fragments.push(Fragment { x, y: y + d.max(0.0), size: 0.0, text });
+                continues = false;
+            }
+            _ => {}
+        }
+    }
+    fragments
+}
+
+/// Append the strings of `Tj`/`TJ` operands; large negative `TJ` adjustments are word gaps
+fn push_shown(text: &mut String, operands: &[Object], decode: &dyn Fn(&[u8]) -> Option<String>) {
+    for operand in operands {
+        match operand {
+            Object::String(bytes, _) => {
+                if let Some(decoded) = decode(bytes) {
+                    text.push_str(&decoded);
+                }
+            }
+            Object::Array(items) => push_shown(text, items, decode),
+            Object::Integer(adjustment) if *adjustment < -100 => text.push(' '),
+            Object::Real(adjustment) if *adjustment < -100.0 => text.push(' '),
+            _ => {}
+        }
+    }
+}
+
+/// The fragments' text in reading order: each column in turn, left to right, and within a
+/// column line by line from the top, fragments on a line joined left to right
+pub(super) fn reading_order(fragments: Vec<Fragment>) -> String {
+    let mut lines = Vec::new();
+    for column in columns(fragments) {
+        lines.extend(column_lines(column));
+    }
+    lines.join("\n")
+}
+
+/// Split fragments at vertical gutters: a column boundary is an x position that starts text on
+/// at least two lines on each side, while no text to its left reaches it
+fn columns(fragments: Vec<Fragment>) -> Vec<Vec<Fragment>> {
+    let mut starts: Vec<f32> = fragments.iter().map(|fragment| fragment.x).collect();
+    starts.sort_by(f32::total_cmp);
+    starts.dedup();
+
+    let gutter = starts.iter().skip(1).copied().find(|&gutter| {
+        let (left, right): (Vec<&Fragment>, Vec<&Fragment>) = fragments.iter().partition(|fragment| fragment.x < gutter);
+        let side_by_side = |left: &[&Fragment], right: &[&Fragment]| {
+            let span = |side: &[&Fragment]| {
+                side.iter().fold((f32::MAX, f32::MIN), |(low, high), fragment| (low.min(fragment.y), high.max(fragment.y)))
+            };
+            let ((left_low, left_high), (right_low, right_high)) = (span(left), span(right));
+            left_low.max(right_low) <= left_high.min(right_high)
+        };
+        left.iter().all(|fragment| fragment.right() <= gutter)
+            && line_count(&left) >= 2
+            && line_count(&right) >= 2
+            && side_by_side(&left, &right)
+    });
+
+    match gutter {
+        Some(gutter) => {
+            let (left, right): (Vec<Fragment>, Vec<Fragment>) = fragments.into_iter().partition(|fragment| fragment.x < gutter);
+            let mut split = vec![left];
+            split.extend(columns(right));
+            split
+        }
+        None => vec![fragments],
+    }
+}
+
+/// Number of distinct baselines among the fragments
+fn line_count(fragments: &[&Fragment]) -> usize {
+    let mut baselines: Vec<f32> = fragments.iter().map(|fragment| fragment.y).collect();
+    baselines.sort_by(f32::total_cmp);
+    baselines.dedup_by(|a, b| (*a - *b).abs() <= 1.0);
+    baselines.len()
+}
+
+/// Lines of one column from the top: fragments whose baselines lie within half a font size
+/// share a line, ordered left to right
+fn column_lines(mut fragments: Vec<Fragment>) -> Vec<String> {
+    fragments.sort_by(|a, b| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+
+    let mut lines: Vec<Vec<Fragment>> = Vec::new();
+    for fragment in fragments {
+        match lines.last_mut() {
+            Some(line) if (line[0].y - fragment.y).abs() <= (line[0].size / 2.0).max(1.0) => line.push(fragment),
+            _ => lines.push(vec![fragment]),
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|mut line| {
+            line.sort_by(|a, b| a.x.total_cmp(&b.x));
+            let mut text = String::new();
+            let mut previous_right = None;
+            for fragment in line {
+                // Fragments that pick up where the last one stopped are parts of one word
+                if let Some(right) = previous_right
+                    && fragment.x - right > fragment.size * 0.25
+                    && !text.ends_with(' ')
+                {
+                    text.push(' ');
+                }
+                previous_right = Some(fragment.right());
+                text.push_str(&fragment.text);
+            }
+            text
+        })
+        .collect()
+}
@@ -0,0 +1,154 @@
+//! XMP metadata packets, the `/Metadata` stream modern PDFs keep their authoritative
+//! metadata in: reading and writing the Dublin Core properties
+
+use crate::Metadata;
+use quick_xml::escape::escape;
+use std::collections::HashMap;
+
+/// Values of the `dc:*` properties of a packet, in document order; a language alternative's
+/// `x-default` value comes first
+#[derive(Debug, Default)]
+pub(super) struct DublinCore {
+    values: HashMap<String, Vec<String>>,
+}
+
+impl DublinCore {
+    /// Collect the Dublin Core properties of an XMP packet, as elements (with `rdf:Alt`,
+    /// `rdf:Bag` or `rdf:Seq` items, or plain text) or as `rdf:Description` attributes
+    pub(super) fn parse(packet: &[u8]) -> Self {
+        use quick_xml::Reader;
+        use quick_xml::events::Event;
+
+        let mut reader = Reader::from_reader(packet);
+        reader.config_mut().trim_text(true);
+        let mut buf = Vec::new();
+        let mut values: HashMap<String, Vec<String>> = HashMap::new();
+        // The open `dc:*` property, whether it has list items, and the text being read
+        let mut property: Option<(String, bool)> = None;
+        let mut default_language = false;
+        let mut text = String::new();
+
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.name().as_ref() == b"rdf:Description" => {
+                    for attr in e.attributes().flatten() {
+                        let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                        if key.starts_with("dc:")
+                            && let Ok(value) = attr.unescape_value()
+                        {
+                            values.entry(key).or_default().push(value.trim().to_string());
+                        }
+                    }
+                }
+                Ok(Event::Start(e)) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    if name.starts_with("dc:") {
+                        property = Some((name, false));
+                        text.clear();
+                    } else if name == "rdf:li"
+                        && let Some((_, has_items)) = &mut property
+                    {
+                        *has_items = true;
+                        default_language = e
+                            .try_get_attribute("xml:lang")
+                            .ok()
+                            .flatten()
+                            .is_some_and(|lang| lang.value.as_ref() == b"x-default");
+                        text.clear();
+                    }
+                }
+                Ok(Event::Text(e)) if property.is_some() => {
+                    text.push_str(&e.unescape().unwrap_or_default());
+                }
+                Ok(Event::CData(e)) if property.is_some() => {
+                    text.push_str(&String::from_utf8_lossy(&e));
+                }
+                Ok(Event::End(e)) => {
+                    let name = e.name();
+                    let Some((open, has_items)) = &property else { continue };
+                    if name.as_ref() == b"rdf:li" {
+                        let list = values.entry(open.clone()).or_default();
+                        let value = std::mem::take(&mut text).trim().to_string();
+                        if default_language {
+                            list.insert(0, value);
+                        } else {
+                            list.push(value);
+                        }
+                    } else if name.as_ref() == open.as_bytes() {
+                        if !has_items && !text.trim().is_empty() {
+                            values.entry(open.clone()).or_default().push(text.trim().to_string());
+                        }
+                        property = None;
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        for list in values.values_mut() {
+            list.retain(|value| !value.is_empty());
+        }
+        Self { values }
+    }
+
+    /// The first value of a property such as `dc:title`
+    pub(super) fn first(&self, property: &str) -> Option<String> {
+        self.values.get(property)?.first().cloned()
+    }
+
+    /// Every value of a property such as `dc:creator`
+    pub(super) fn all(&self, property: &str) -> Vec<String> {
+        self.values.get(property).cloned().unwrap_or_default()
+    }
+}
+
+/// An XMP packet with the Dublin Core properties of `metadata`
+pub(super) fn packet(metadata: &Metadata) -> String {
+    let alternative = |name: &str, value: &str| {
+        format!("      <{name}><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></{name}>\n", escape(value))
+    };
+    let list = |name: &str, kind: &str, values: &[String]| {
+        let items: String = values.iter().map(|value| format!("<rdf:li>{}</rdf:li>", escape(value.as_str()))).collect();
+        format!("      <{name}><rdf:{kind}>{items}</rdf:{kind}></{name}>\n")
+    };
+
+    let mut properties = String::new();
+    if let Some(title) = metadata.title.as_deref().filter(|_| metadata.has_title()) {
+        properties.push_str(&alternative("dc:title", title));
+    }
+    let authors = metadata.all_authors();
+    if !authors.is_empty() {
+        properties.push_str(&list("dc:creator", "Seq", &authors));
+    }
+    if let Some(description) = &metadata.description {
+        properties.push_str(&alternative("dc:description", description));
+    }
+    if let Some(tags) = metadata.tags.as_ref().filter(|tags| !tags.is_empty()) {
+        properties.push_str(&list("dc:subject", "Bag", tags));
+    }
+    if let Some(publisher) = &metadata.publisher {
+        properties.push_str(&list("dc:publisher", "Bag", std::slice::from_ref(publisher)));
+    }
+    if let Some(language) = &metadata.language {
+        properties.push_str(&list("dc:language", "Bag", std::slice::from_ref(language)));
+    }
+    if let Some(rights) = &metadata.rights {
+        properties.push_str(&alternative("dc:rights", rights));
+    }
+    if let Some(date) = &metadata.publication_date {
+        properties.push_str(&list("dc:date", "Seq", std::slice::from_ref(date)));
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">
+  <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
+    <rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">
+{properties}    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end=\"w\"?>"
+    )
+}
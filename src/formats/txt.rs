@@ -1,5 +1,7 @@
 use crate::{EbookError, Metadata, Result};
-use crate::traits::{EbookReader, EbookWriter, EbookOperator, TocEntry, ImageData};
+use crate::metadata::DEFAULT_AUTHOR_SEPARATOR;
+use crate::progress::ProgressHandler;
+use crate::traits::{ChapterView, EbookReader, EbookWriter, EbookOperator, TocEntry, ImageData};
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::Path;
@@ -8,9 +10,69 @@ use std::path::Path;
 pub struct TxtHandler {
     metadata: Metadata,
     content: String,
+    include_metadata_header: bool,
+    author_separator: Option<String>,
+    output_encoding: TextEncoding,
+}
+
+/// Character encoding of written text files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    /// UTF-8 starting with a byte order mark, for Windows tools that expect one
+    Utf8Bom,
+    /// UTF-16 little-endian starting with a byte order mark ("Unicode" in Windows Notepad)
+    Utf16Le,
+    /// A legacy single- or multi-byte code page such as `windows-1252` or `Shift_JIS`
+    CodePage(&'static encoding_rs::Encoding),
+}
+
+impl TextEncoding {
+    /// `text` in this encoding; characters a code page can't represent become `&#NNNN;`
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Self::Utf8 => text.as_bytes().to_vec(),
+            Self::Utf8Bom => [b"\xEF\xBB\xBF".as_slice(), text.as_bytes()].concat(),
+            Self::Utf16Le => [0xFF, 0xFE]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect(),
+            Self::CodePage(encoding) => {
+                let (bytes, _, had_unmappable) = encoding.encode(text);
+                if had_unmappable {
+                    log::warn!("Some characters have no {} form and were written as character references", encoding.name());
+                }
+                bytes.into_owned()
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for TextEncoding {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "utf-8-bom" | "utf8-bom" => return Ok(Self::Utf8Bom),
+            "utf-16" | "utf16" | "utf16le" => return Ok(Self::Utf16Le),
+            _ => {}
+        }
+        match encoding_rs::Encoding::for_label(value.as_bytes()) {
+            Some(encoding) if encoding == encoding_rs::UTF_8 => Ok(Self::Utf8),
+            Some(encoding) if encoding == encoding_rs::UTF_16LE => Ok(Self::Utf16Le),
+            // encoding_rs only decodes UTF-16BE and ISO-2022-JP; their encoders write UTF-8
+            Some(encoding) if encoding.output_encoding() == encoding => Ok(Self::CodePage(encoding)),
+            _ => Err(format!(
+                "unknown or unsupported output encoding '{value}' (expected utf-8, utf-8-bom, utf-16le or a code page such as windows-1252)"
+            )),
+        }
+    }
 }
 
 const STREAMING_THRESHOLD: usize = 10 * 1024 * 1024; // 10 MB
+/// Characters read beyond a peek's limit so a `Title:`/`Author:` header fits
+const PEEK_HEADER_CHARS: usize = 1024;
 
 impl TxtHandler {
     pub fn new() -> Self {
@@ -18,6 +80,15 @@ impl TxtHandler {
     }
 
     fn detect_encoding(data: &[u8]) -> Result<String> {
+        if let Some((encoding, bom_length)) = encoding_rs::Encoding::for_bom(data) {
+            let (decoded, _) = encoding.decode_without_bom_handling(&data[bom_length..]);
+            return Ok(decoded.into_owned());
+        }
+        if let Some(encoding) = Self::bomless_utf16(data) {
+            let (decoded, _) = encoding.decode_without_bom_handling(data);
+            return Ok(decoded.into_owned());
+        }
+
         if let Ok(text) = std::str::from_utf8(data) {
             return Ok(text.to_string());
         }
@@ -31,8 +102,112 @@ impl TxtHandler {
         Ok(decoded.to_string())
     }
 
-    /// Optimized streaming read for large text files
-    pub fn read_from_file_streaming(&mut self, path: &Path) -> Result<()> {
+    /// UTF-16 without a byte order mark, recognized by its ASCII characters putting a zero byte
+    /// in every other position
+    fn bomless_utf16(data: &[u8]) -> Option<&'static encoding_rs::Encoding> {
+        let sample = &data[..data.len().min(4096) & !1];
+        if sample.len() < 4 {
+            return None;
+        }
+        let pairs = sample.len() / 2;
+        let zeros_at = |offset: usize| sample.iter().skip(offset).step_by(2).filter(|&&byte| byte == 0).count();
+        let (even, odd) = (zeros_at(0), zeros_at(1));
+        if odd * 2 > pairs && even * 4 < odd {
+            Some(encoding_rs::UTF_16LE)
+        } else if even * 2 > pairs && odd * 4 < even {
+            Some(encoding_rs::UTF_16BE)
+        } else {
+            None
+        }
+    }
+
+    /// Encoding the text is written in; UTF-8 without a byte order mark by default
+    pub fn set_output_encoding(&mut self, encoding: TextEncoding) {
+        self.output_encoding = encoding;
+    }
+
+    /// Prepend a `Title:`/`Author:` header block when writing
+    pub fn set_include_metadata_header(&mut self, include: bool) {
+        self.include_metadata_header = include;
+    }
+
+    /// Separator used to join multiple authors in the header block
+    pub fn set_author_separator(&mut self, separator: impl Into<String>) {
+        self.author_separator = Some(separator.into());
+    }
+
+    fn author_separator(&self) -> &str {
+        self.author_separator.as_deref().unwrap_or(DEFAULT_AUTHOR_SEPARATOR)
+    }
+
+    fn metadata_header(&self) -> String {
+        let mut header = String::new();
+        if let Some(title) = &self.metadata.title
+            && self.metadata.has_title()
+        {
+            header.push_str(&format!("Title: {title}\n"));
+        }
+        if let Some(author) = self.metadata.joined_authors(self.author_separator()) {
+            header.push_str(&format!("Author: {author}\n"));
+        }
+        if !header.is_empty() {
+            header.push('\n');
+        }
+        header
+    }
+
+    /// The content, after the metadata header when one is included
+    fn text_to_write(&self) -> String {
+        if self.include_metadata_header {
+            self.metadata_header() + &self.content
+        } else {
+            self.content.clone()
+        }
+    }
+
+    /// Parse a leading `Title:`/`Author:` block terminated by a blank line
+    /// into metadata and strip it from the content
+    fn parse_metadata_header(&mut self) {
+        let mut title = None;
+        let mut author = None;
+        let mut header_len = 0;
+        let mut terminated = false;
+
+        for line in self.content.split_inclusive('\n') {
+            let trimmed = line.trim();
+            header_len += line.len();
+            if trimmed.is_empty() {
+                terminated = true;
+                break;
+            }
+
+            if let Some(value) = trimmed.strip_prefix("Title:") {
+                title = Some(value.trim().to_string());
+            } else if let Some(value) = trimmed.strip_prefix("Author:") {
+                author = Some(value.trim().to_string());
+            } else {
+                return;
+            }
+        }
+
+        // A header must be terminated by a blank line, otherwise it is ordinary content
+        if !terminated || (title.is_none() && author.is_none()) {
+            return;
+        }
+
+        if title.is_some() {
+            self.metadata.title = title;
+        }
+        if let Some(author) = author {
+            let separator = self.author_separator().to_string();
+            self.metadata.set_authors_from_joined(&author, &separator);
+        }
+        self.content.drain(..header_len);
+    }
+
+    /// Optimized streaming read for large text files. `progress` is told how many bytes of
+    /// the file have been read, about every 1% and once more when done.
+    pub fn read_from_file_streaming(&mut self, path: &Path, progress: Option<&ProgressHandler>) -> Result<()> {
         let file = File::open(path)?;
         let metadata = file.metadata()?;
         let file_size = metadata.len() as usize;
@@ -45,24 +220,149 @@ impl TxtHandler {
 
         // For small files, use the regular method
         if file_size < STREAMING_THRESHOLD {
-            return self.read_from_file(path);
+            self.read_from_file(path)?;
+            if let Some(progress) = progress {
+                progress.report(file_size, file_size);
+            }
+            return Ok(());
         }
 
         // For large files, use streaming with buffered reading
         log::info!("Streaming large TXT file ({} bytes)", file_size);
         let reader = BufReader::with_capacity(128 * 1024, file); // 128KB buffer
         let mut content = String::with_capacity(file_size);
+        let report_every = (file_size / 100).max(1);
+        let mut next_report = report_every;
+        let mut lines = 0;
 
         for line in reader.lines() {
             let line = line?;
             content.push_str(&line);
             content.push('\n');
+            lines += 1;
+
+            // Line endings are normalized, so this can trail the real offset by a byte per CRLF
+            if let Some(progress) = progress
+                && content.len() >= next_report
+            {
+                progress.report(content.len().min(file_size), file_size);
+                next_report = content.len() + report_every;
+            }
+        }
+        log::debug!("Read {lines} lines");
+        if let Some(progress) = progress {
+            progress.report(file_size, file_size);
         }
 
         self.content = content;
+        self.parse_metadata_header();
         Ok(())
     }
 
+    /// Lines `start..start + count` (0-based) without loading the rest of the file; memory use
+    /// is bounded by the buffer plus the requested window. UTF-16 files, detected as on a full
+    /// read, are split at their two-byte newlines.
+    pub fn read_lines(path: &Path, start: usize, count: usize) -> Result<String> {
+        let mut reader = BufReader::with_capacity(128 * 1024, File::open(path)?);
+        let head = reader.fill_buf()?;
+        let utf16 = match encoding_rs::Encoding::for_bom(head) {
+            Some((encoding, bom_length)) if encoding != encoding_rs::UTF_8 => Some((encoding, bom_length)),
+            Some(_) => None,
+            None => Self::bomless_utf16(head).map(|encoding| (encoding, 0)),
+        };
+        if let Some((encoding, bom_length)) = utf16 {
+            reader.consume(bom_length);
+            let newline = if encoding == encoding_rs::UTF_16LE { [b'\n', 0] } else { [0, b'\n'] };
+            let mut skipped = Vec::new();
+            for _ in 0..start {
+                skipped.clear();
+                if !Self::read_utf16_line(&mut reader, newline, &mut skipped)? {
+                    return Ok(String::new());
+                }
+            }
+            let mut window = Vec::new();
+            for _ in 0..count {
+                if !Self::read_utf16_line(&mut reader, newline, &mut window)? {
+                    break;
+                }
+            }
+            return Ok(encoding.decode_without_bom_handling(&window).0.into_owned());
+        }
+
+        for _ in 0..start {
+            if reader.skip_until(b'\n')? == 0 {
+                return Ok(String::new());
+            }
+        }
+
+        let mut window = Vec::new();
+        for _ in 0..count {
+            if reader.read_until(b'\n', &mut window)? == 0 {
+                break;
+            }
+        }
+        Self::detect_encoding(&window)
+    }
+
+    /// Append the code units of the next UTF-16 line, up to and including its `newline`, to
+    /// `line`; `false` at the end of the file
+    fn read_utf16_line(reader: &mut impl BufRead, newline: [u8; 2], line: &mut Vec<u8>) -> io::Result<bool> {
+        let mut unit = [0u8; 2];
+        let mut read_any = false;
+        loop {
+            match reader.read_exact(&mut unit) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(read_any),
+                Err(e) => return Err(e),
+            }
+            read_any = true;
+            line.extend_from_slice(&unit);
+            if unit == newline {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// The first `max_chars` characters of the file, reading only as many bytes as that can take
+    pub fn read_prefix(path: &Path, max_chars: usize) -> Result<String> {
+        let mut data = Vec::new();
+        File::open(path)?
+            .take(max_chars.saturating_mul(4) as u64)
+            .read_to_end(&mut data)?;
+
+        // Drop a UTF-8 sequence cut off by the byte limit so it isn't taken for another encoding
+        if let Err(e) = std::str::from_utf8(&data)
+            && e.error_len().is_none()
+        {
+            data.truncate(e.valid_up_to());
+        }
+        Ok(Self::detect_encoding(&data)?.chars().take(max_chars).collect())
+    }
+
+    /// Metadata and the first `max_chars` characters of the text of the file, reading only
+    /// as much of it as that takes; a `Title:`/`Author:` header becomes metadata, as on a
+    /// full read, rather than part of the text
+    pub fn read_prefix_with_metadata(path: &Path, max_chars: usize) -> Result<(Metadata, String)> {
+        let parse = |prefix: String| {
+            let mut handler = Self::new();
+            handler.metadata.title = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+            handler.metadata.format = Some("TXT".to_string());
+            handler.content = prefix;
+            handler.parse_metadata_header();
+            handler
+        };
+
+        // Read enough past `max_chars` for a typical header to end within the prefix
+        let prefix = Self::read_prefix(path, max_chars + PEEK_HEADER_CHARS)?;
+        let prefix_chars = prefix.chars().count();
+        let mut handler = parse(prefix);
+        let header_chars = prefix_chars - handler.content.chars().count();
+        if header_chars > PEEK_HEADER_CHARS {
+            handler = parse(Self::read_prefix(path, max_chars + header_chars)?);
+        }
+        Ok((handler.metadata, handler.content.chars().take(max_chars).collect()))
+    }
+
     /// Optimized streaming write for large text files
     pub fn write_to_file_streaming(&self, path: &Path) -> Result<()> {
         // Ensure parent directory exists
@@ -73,6 +373,15 @@ impl TxtHandler {
         let file = File::create(path)?;
         let mut writer = io::BufWriter::with_capacity(128 * 1024, file); // 128KB buffer
 
+        if self.output_encoding != TextEncoding::Utf8 {
+            writer.write_all(&self.output_encoding.encode(&self.text_to_write()))?;
+            writer.flush()?;
+            return Ok(());
+        }
+        if self.include_metadata_header {
+            writer.write_all(self.metadata_header().as_bytes())?;
+        }
+
         // Write in chunks to avoid memory pressure
         let content_bytes = self.content.as_bytes();
         let chunk_size = 64 * 1024; // 64KB chunks
@@ -99,6 +408,7 @@ impl EbookReader for TxtHandler {
             .and_then(|s| s.to_str())
             .map(|s| s.to_string());
         self.metadata.format = Some("TXT".to_string());
+        self.parse_metadata_header();
 
         Ok(())
     }
@@ -116,9 +426,8 @@ impl EbookReader for TxtHandler {
         let lines: Vec<&str> = self.content.lines().collect();
         
         for line in lines.iter() {
-            let trimmed = line.trim();
-            if trimmed.starts_with("Chapter ") || trimmed.starts_with("CHAPTER ") {
-                toc.push(TocEntry::new(trimmed.to_string(), 1));
+            if crate::utils::is_heading_line(line) {
+                toc.push(TocEntry::new(line.trim().to_string(), 1));
             }
         }
         
@@ -128,6 +437,16 @@ impl EbookReader for TxtHandler {
     fn extract_images(&self) -> Result<Vec<ImageData>> {
         Ok(Vec::new())
     }
+
+    /// One chapter per "Chapter N" heading; the whole text when there are none
+    fn get_chapters(&self) -> Result<Vec<ChapterView>> {
+        let sections = crate::utils::split_at_headings(&self.content);
+        if sections.is_empty() {
+            let title = self.metadata.title.clone().unwrap_or_else(|| "Untitled".to_string());
+            return Ok(vec![ChapterView::new(title, self.content.clone())]);
+        }
+        Ok(sections.into_iter().map(|(title, section)| ChapterView::new(title, section)).collect())
+    }
 }
 
 impl EbookWriter for TxtHandler {
@@ -160,7 +479,7 @@ impl EbookWriter for TxtHandler {
         }
 
         let mut file = File::create(path)?;
-        file.write_all(self.content.as_bytes())?;
+        file.write_all(&self.output_encoding.encode(&self.text_to_write()))?;
         Ok(())
     }
 }
@@ -187,6 +506,7 @@ impl EbookOperator for TxtHandler {
         if self.metadata.title.is_none() {
             self.metadata.title = Some("Untitled".to_string());
         }
+        self.metadata.infer_defaults(&self.content);
         Ok(())
     }
 }
@@ -1,13 +1,22 @@
 use crate::{Result, EbookError};
 use image::{DynamicImage, ImageFormat, ImageReader, GenericImageView};
+use serde::Serialize;
 use std::io::Cursor;
 
+const SVG_MIME_TYPE: &str = "image/svg+xml";
+
 #[derive(Debug, Clone, Copy)]
 pub struct OptimizationOptions {
     pub max_width: Option<u32>,
     pub max_height: Option<u32>,
     pub quality: u8,
     pub preserve_aspect_ratio: bool,
+    /// Enlarge images smaller than the maximum dimensions up to them
+    pub upscale: bool,
+    /// Transcode every image to this format (JPEG output is composited onto white)
+    pub convert_to: Option<ImageFormat>,
+    /// Worker threads for batch optimization; 0 uses all available cores
+    pub threads: usize,
 }
 
 impl Default for OptimizationOptions {
@@ -17,6 +26,9 @@ impl Default for OptimizationOptions {
             max_height: Some(1920),
             quality: 85,
             preserve_aspect_ratio: true,
+            upscale: false,
+            convert_to: None,
+            threads: 0,
         }
     }
 }
@@ -42,6 +54,159 @@ impl OptimizationOptions {
         self.max_height = None;
         self
     }
+
+    /// Keep each image's proportions (the default), or stretch each dimension to its maximum
+    /// on its own
+    pub fn with_preserve_aspect_ratio(mut self, preserve: bool) -> Self {
+        self.preserve_aspect_ratio = preserve;
+        self
+    }
+
+    pub fn with_upscale(mut self, upscale: bool) -> Self {
+        self.upscale = upscale;
+        self
+    }
+
+    pub fn with_convert_to(mut self, format: ImageFormat) -> Self {
+        self.convert_to = Some(format);
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+}
+
+/// Outcome of optimizing towards a total output size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetSizeResult {
+    /// JPEG quality the optimizer settled on
+    pub quality: u8,
+    /// Resulting output size in bytes
+    pub size: u64,
+    /// Whether the size is within the requested budget
+    pub reached: bool,
+}
+
+/// Images stored more than once with identical bytes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DuplicateImages {
+    /// `(duplicate, original)` image names; the original is the first image with those bytes
+    pub duplicates: Vec<(String, String)>,
+    /// Bytes taken up by the duplicates
+    pub bytes: u64,
+}
+
+impl DuplicateImages {
+    /// Find the images whose bytes repeat an earlier image's
+    pub fn find(images: &[crate::traits::ImageData]) -> Self {
+        use std::collections::HashMap;
+        use std::hash::{DefaultHasher, Hash, Hasher};
+
+        // Hash to find candidates, then compare bytes so a collision is never a duplicate
+        let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut found = Self::default();
+        for (idx, image) in images.iter().enumerate() {
+            let mut hasher = DefaultHasher::new();
+            image.data.hash(&mut hasher);
+            let candidates = seen.entry(hasher.finish()).or_default();
+            match candidates.iter().find(|&&original| images[original].data == image.data) {
+                Some(&original) => {
+                    found.duplicates.push((image.name.clone(), images[original].name.clone()));
+                    found.bytes += image.data.len() as u64;
+                }
+                None => candidates.push(idx),
+            }
+        }
+        found
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.duplicates.is_empty()
+    }
+}
+
+/// The options an optimization ran with, as recorded in its report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OptimizationSettings {
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub quality: u8,
+    pub preserve_aspect_ratio: bool,
+    pub upscale: bool,
+    /// MIME type every image was transcoded to
+    pub convert_to: Option<String>,
+    pub threads: usize,
+}
+
+impl From<&OptimizationOptions> for OptimizationSettings {
+    fn from(options: &OptimizationOptions) -> Self {
+        Self {
+            max_width: options.max_width,
+            max_height: options.max_height,
+            quality: options.quality,
+            preserve_aspect_ratio: options.preserve_aspect_ratio,
+            upscale: options.upscale,
+            convert_to: options.convert_to.map(|format| format.to_mime_type().to_string()),
+            threads: options.threads,
+        }
+    }
+}
+
+/// What optimization did to one image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageReport {
+    pub name: String,
+    /// Name after optimization, which differs when the image was transcoded
+    pub new_name: String,
+    pub original_size: u64,
+    pub new_size: u64,
+    /// Width and height in pixels, `None` when the image can't be decoded (e.g. SVG)
+    pub original_dimensions: Option<(u32, u32)>,
+    pub new_dimensions: Option<(u32, u32)>,
+    /// Left as it was: optimizing failed or wouldn't have made it smaller
+    pub skipped: bool,
+}
+
+/// Per-image outcome of an optimization run, with the totals and settings used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OptimizationReport {
+    pub settings: OptimizationSettings,
+    pub images: Vec<ImageReport>,
+    pub original_bytes: u64,
+    pub new_bytes: u64,
+    pub total_savings: u64,
+}
+
+impl OptimizationReport {
+    /// Report on the images `before` optimization against the same images `after` it, paired
+    /// by position
+    pub fn compare(before: &[crate::traits::ImageData], after: &[crate::traits::ImageData], options: &OptimizationOptions) -> Self {
+        let dimensions = |data: &[u8]| ImageReader::new(Cursor::new(data)).with_guessed_format().ok()?.into_dimensions().ok();
+        let images: Vec<ImageReport> = before
+            .iter()
+            .zip(after)
+            .map(|(before, after)| ImageReport {
+                name: before.name.clone(),
+                new_name: after.name.clone(),
+                original_size: before.data.len() as u64,
+                new_size: after.data.len() as u64,
+                original_dimensions: dimensions(&before.data),
+                new_dimensions: dimensions(&after.data),
+                skipped: before.name == after.name && before.data == after.data,
+            })
+            .collect();
+        let original_bytes = images.iter().map(|image| image.original_size).sum();
+        let new_bytes = images.iter().map(|image| image.new_size).sum();
+        Self {
+            settings: options.into(),
+            images,
+            original_bytes,
+            new_bytes,
+            total_savings: u64::saturating_sub(original_bytes, new_bytes),
+        }
+    }
 }
 
 pub struct ImageOptimizer {
@@ -59,17 +224,142 @@ impl ImageOptimizer {
 
     pub fn optimize(&self, image_data: &[u8], mime_type: &str) -> Result<Vec<u8>> {
         // Load the image
-        let img = ImageReader::new(Cursor::new(image_data))
-            .with_guessed_format()
-            .map_err(|e| EbookError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))?
-            .decode()
-            .map_err(|e| EbookError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))?;
+        let img = self.decode(image_data, mime_type)?;
 
         // Resize if needed
         let resized_img = self.resize_if_needed(img)?;
 
         // Encode with compression
-        self.encode_image(resized_img, mime_type)
+        self.encode_image(resized_img, &self.output_mime_type(mime_type))
+    }
+
+    /// Optimized copy of `image`, named and typed for its output format; `image` itself is
+    /// left untouched, so the two can be compared or the copy discarded
+    pub fn optimize_into(&self, image: &crate::traits::ImageData) -> Result<crate::traits::ImageData> {
+        Ok(crate::traits::ImageData::new(
+            self.output_name(&image.name, &image.mime_type),
+            self.output_mime_type(&image.mime_type),
+            self.optimize(&image.data, &image.mime_type)?,
+        ))
+    }
+
+    /// Optimize a batch of images across `options.threads` workers. Each image is encoded
+    /// on its own and results come back in input order, so the output is identical for
+    /// any thread count.
+    pub fn optimize_all(&self, images: &[crate::traits::ImageData]) -> Vec<Result<crate::traits::ImageData>> {
+        let threads = match self.options.threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        if threads <= 1 || images.len() <= 1 {
+            return images.iter().map(|image| self.optimize_into(image)).collect();
+        }
+
+        let chunk_size = images.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = images
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || chunk.iter().map(|image| self.optimize_into(image)).collect::<Vec<_>>()))
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("image optimization worker panicked"))
+                .collect()
+        })
+    }
+
+    fn decode(&self, image_data: &[u8], mime_type: &str) -> Result<DynamicImage> {
+        #[cfg(feature = "svg")]
+        if mime_type == SVG_MIME_TYPE {
+            return self.rasterize_svg(image_data);
+        }
+        #[cfg(not(feature = "svg"))]
+        let _ = mime_type;
+
+        ImageReader::new(Cursor::new(image_data))
+            .with_guessed_format()
+            .map_err(|e| EbookError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))?
+            .decode()
+            .map_err(|e| EbookError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+    }
+
+    /// Render an SVG at its intrinsic size, scaled down to fit the maximum dimensions
+    #[cfg(feature = "svg")]
+    fn rasterize_svg(&self, svg_data: &[u8]) -> Result<DynamicImage> {
+        use resvg::{tiny_skia, usvg};
+
+        let tree = usvg::Tree::from_data(svg_data, &usvg::Options::default())
+            .map_err(|e| EbookError::ImageError(format!("Invalid SVG: {e}")))?;
+        let size = tree.size();
+        let (width, height) = self.calculate_aspect_ratio_dimensions(
+            size.width().ceil() as u32,
+            size.height().ceil() as u32,
+            self.options.max_width.unwrap_or(u32::MAX),
+            self.options.max_height.unwrap_or(u32::MAX),
+        );
+
+        let mut pixmap = tiny_skia::Pixmap::new(width.max(1), height.max(1))
+            .ok_or_else(|| EbookError::ImageError("SVG has no drawable size".to_string()))?;
+        let transform = tiny_skia::Transform::from_scale(
+            pixmap.width() as f32 / size.width(),
+            pixmap.height() as f32 / size.height(),
+        );
+        resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+        // tiny-skia stores premultiplied alpha; `image` expects straight alpha
+        let pixels = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|p| {
+                let c = p.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            })
+            .collect();
+        image::RgbaImage::from_raw(pixmap.width(), pixmap.height(), pixels)
+            .map(DynamicImage::ImageRgba8)
+            .ok_or_else(|| EbookError::ImageError("Failed to rasterize SVG".to_string()))
+    }
+
+    /// MIME type of the optimized image: the conversion target if set, PNG for
+    /// rasterized SVG, otherwise the original
+    pub fn output_mime_type(&self, mime_type: &str) -> String {
+        match self.options.convert_to {
+            Some(format) => format.to_mime_type().to_string(),
+            None if cfg!(feature = "svg") && mime_type == SVG_MIME_TYPE => "image/png".to_string(),
+            None => mime_type.to_string(),
+        }
+    }
+
+    /// File name of the optimized image, with the extension matching its output format
+    pub fn output_name(&self, name: &str, mime_type: &str) -> String {
+        let output_mime_type = self.output_mime_type(mime_type);
+        if output_mime_type == mime_type {
+            return name.to_string();
+        }
+        let Some(extension) = ImageFormat::from_mime_type(&output_mime_type)
+            .and_then(|f| f.extensions_str().first().copied())
+        else {
+            return name.to_string();
+        };
+        let stem_end = name
+            .rfind('.')
+            .filter(|&dot| !name[dot..].contains('/'))
+            .unwrap_or(name.len());
+        format!("{}.{}", &name[..stem_end], extension)
+    }
+
+    /// Composite transparent pixels onto white for formats without alpha
+    fn flatten_alpha(img: DynamicImage) -> DynamicImage {
+        if !img.color().has_alpha() {
+            return img;
+        }
+        let rgba = img.to_rgba8();
+        let rgb = image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let pixel = rgba.get_pixel(x, y);
+            let alpha = pixel[3] as u32;
+            image::Rgb([0, 1, 2].map(|c| ((pixel[c] as u32 * alpha + 255 * (255 - alpha)) / 255) as u8))
+        });
+        DynamicImage::ImageRgb8(rgb)
     }
 
     fn resize_if_needed(&self, img: DynamicImage) -> Result<DynamicImage> {
@@ -78,17 +368,23 @@ impl ImageOptimizer {
         let max_width = self.options.max_width.unwrap_or(u32::MAX);
         let max_height = self.options.max_height.unwrap_or(u32::MAX);
 
-        if width <= max_width && height <= max_height {
-            return Ok(img);
-        }
-
         let (new_width, new_height) = if self.options.preserve_aspect_ratio {
             self.calculate_aspect_ratio_dimensions(width, height, max_width, max_height)
         } else {
-            (max_width.min(width), max_height.min(height))
+            // Each dimension is clamped on its own, stretching the image; an unbounded one
+            // keeps its size even when upscaling
+            let clamp = |size: u32, max: Option<u32>| match max {
+                Some(max) if self.options.upscale => max,
+                Some(max) => size.min(max),
+                None => size,
+            };
+            (clamp(width, self.options.max_width), clamp(height, self.options.max_height))
         };
 
-        Ok(img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3))
+        if (new_width, new_height) == (width, height) {
+            return Ok(img);
+        }
+        Ok(img.resize_exact(new_width.max(1), new_height.max(1), image::imageops::FilterType::Lanczos3))
     }
 
     fn calculate_aspect_ratio_dimensions(
@@ -101,8 +397,9 @@ impl ImageOptimizer {
         let width_ratio = max_width as f64 / width as f64;
         let height_ratio = max_height as f64 / height as f64;
         let ratio = width_ratio.min(height_ratio);
+        let upscale = self.options.upscale && max_width.min(max_height) < u32::MAX;
 
-        if ratio >= 1.0 {
+        if ratio >= 1.0 && !upscale {
             (width, height)
         } else {
             ((width as f64 * ratio) as u32, (height as f64 * ratio) as u32)
@@ -118,7 +415,7 @@ impl ImageOptimizer {
                     &mut buffer,
                     self.options.quality,
                 );
-                img.write_with_encoder(encoder)
+                Self::flatten_alpha(img).write_with_encoder(encoder)
                     .map_err(|e| EbookError::Io(std::io::Error::other(e)))?;
             }
             "image/png" => {
@@ -189,6 +486,32 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_stretch_clamps_each_dimension() {
+        let optimizer = ImageOptimizer::new(
+            OptimizationOptions::default().with_max_dimensions(200, 1000).with_preserve_aspect_ratio(false),
+        );
+
+        let resized = optimizer.resize_if_needed(DynamicImage::new_rgb8(400, 100)).unwrap();
+        assert_eq!(resized.dimensions(), (200, 100));
+
+        let small = optimizer.resize_if_needed(DynamicImage::new_rgb8(40, 10)).unwrap();
+        assert_eq!(small.dimensions(), (40, 10));
+    }
+
+    #[test]
+    fn test_upscale() {
+        let options = OptimizationOptions::default().with_max_dimensions(200, 300).with_upscale(true);
+
+        let fitted = ImageOptimizer::new(options).resize_if_needed(DynamicImage::new_rgb8(40, 10)).unwrap();
+        assert_eq!(fitted.dimensions(), (200, 50));
+
+        let stretched = ImageOptimizer::new(options.with_preserve_aspect_ratio(false))
+            .resize_if_needed(DynamicImage::new_rgb8(40, 10))
+            .unwrap();
+        assert_eq!(stretched.dimensions(), (200, 300));
+    }
+
     #[test]
     fn test_quality_setting() {
         let options = OptimizationOptions::default().with_quality(50);
@@ -7,10 +7,24 @@ pub mod mcp;
 pub mod conversion;
 pub mod progress;
 pub mod image_optimizer;
+pub mod registry;
+pub mod diff;
+pub mod catalog;
+pub mod salvage;
+pub mod watch;
+pub mod ocr;
+pub mod render;
+pub mod cache;
+pub mod doctor;
+pub mod batch;
+pub mod merge;
 
-pub use error::{EbookError, Result};
+pub use error::{EbookError, ErrorCategory, Result};
 pub use traits::{EbookReader, EbookWriter, EbookOperator};
-pub use metadata::Metadata;
-pub use conversion::Converter;
+pub use metadata::{Contributor, Metadata};
+pub use conversion::{Converter, ConversionOptions, SUPPORTED_CONVERSIONS};
+pub use registry::FormatRegistry;
+pub use cache::ConversionCache;
+pub use diff::BookDiff;
 pub use progress::{Progress, ProgressHandler, console_progress_callback, silent_progress_callback};
 pub use formats::EpubVersion;
@@ -1,8 +1,9 @@
 use clap::{Parser, Subcommand};
-use ebook_cli::{EbookError, Result, Converter};
-use ebook_cli::formats::{EpubHandler, MobiHandler, Fb2Handler, CbzHandler, TxtHandler, PdfHandler, AzwHandler};
-use ebook_cli::traits::{EbookReader, EbookWriter, EbookOperator};
-use std::path::PathBuf;
+use ebook_cli::{EbookError, Result, Converter, ConversionCache, ConversionOptions, FormatRegistry, ProgressHandler, console_progress_callback};
+use ebook_cli::formats::{ChapterTitleSource, EpubHandler, EpubVersion, CbzHandler, ComicInfoVersion, NcxMode, PageOrder, PdfHandler, TextEncoding, TxtHandler};
+use ebook_cli::ocr;
+use ebook_cli::traits::{ArchiveEntry, EbookOperator, EbookReader, EbookWriter};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "ebook-cli")]
@@ -17,6 +18,9 @@ enum Commands {
     Read {
         #[arg(help = "Path to the ebook file")]
         input: PathBuf,
+
+        #[arg(long = "as", value_name = "FORMAT", value_parser = parse_format_name, help = "Read the file as this format instead of going by its extension")]
+        as_format: Option<String>,
         
         #[arg(short, long, help = "Show metadata only")]
         metadata: bool,
@@ -26,6 +30,42 @@ enum Commands {
         
         #[arg(short, long, help = "Show table of contents")]
         toc: bool,
+
+        #[arg(long, value_name = "N", help = "Only print the first N characters of the content")]
+        head: Option<usize>,
+
+        #[arg(long, value_name = "K", help = "Only print chapter K (1-based; a page for PDF)")]
+        chapter: Option<usize>,
+
+        #[arg(
+            long,
+            value_name = "START:COUNT",
+            value_parser = parse_line_range,
+            conflicts_with_all = ["metadata", "toc", "chapter", "extract_images"],
+            help = "Only print COUNT lines starting at line START (1-based); streamed for TXT"
+        )]
+        range: Option<(usize, usize)>,
+
+        #[arg(long, help = "With --toc, build a TOC from \"Chapter N\" lines when the book has none")]
+        toc_from_headings: bool,
+
+        #[arg(long, requires = "metadata", help = "With --metadata, detect the language from the text when the book declares none")]
+        detect_language: bool,
+
+        #[arg(long, help = "EPUB: split chapters at the anchors the TOC points to")]
+        chapters_from_toc: bool,
+
+        #[arg(long, value_name = "SOURCE", default_value = "title", help = "EPUB: take chapter titles from \"title\", \"heading\" or a selector such as p.chapter-title")]
+        chapter_titles: ChapterTitleSource,
+
+        #[arg(long, help = "OCR scanned PDFs (when they have no text layer) and CBZ pages; needs a build with the ocr feature")]
+        ocr: bool,
+
+        #[arg(long, value_name = "LANG", default_value = ocr::DEFAULT_LANGUAGE, requires = "ocr", help = "Tesseract language for --ocr, e.g. deu or eng+fra")]
+        ocr_lang: String,
+
+        #[arg(short, long, help = "Show how much of a TXT file has been loaded, with an ETA")]
+        progress: bool,
     },
     
     Write {
@@ -35,8 +75,8 @@ enum Commands {
         #[arg(short, long, help = "Title of the ebook")]
         title: Option<String>,
 
-        #[arg(short, long, help = "Author of the ebook")]
-        author: Option<String>,
+        #[arg(short, long, help = "Author of the ebook (repeat for multiple authors)")]
+        author: Vec<String>,
 
         #[arg(short, long, help = "Content file (text)")]
         content: Option<PathBuf>,
@@ -46,30 +86,137 @@ enum Commands {
 
         #[arg(short, long, help = "Show progress during write")]
         progress: bool,
+
+        #[arg(long, help = "Leave ComicInfo.xml out of a CBZ")]
+        no_comicinfo: bool,
+
+        #[arg(long, value_name = "VERSION", default_value = "legacy", help = "ComicInfo.xml schema for a CBZ: legacy or 2.0")]
+        comicinfo_version: ComicInfoVersion,
+
+        #[arg(long, value_name = "ENCODING", default_value = "utf-8", help = "TXT output encoding: utf-8, utf-8-bom, utf-16le or a code page such as windows-1252")]
+        output_encoding: TextEncoding,
     },
     
     Convert {
-        #[arg(help = "Input file path")]
-        input: PathBuf,
+        #[arg(help = "Input file path", required_unless_present = "list_conversions")]
+        input: Option<PathBuf>,
 
-        #[arg(help = "Output file path")]
-        output: PathBuf,
+        #[arg(help = "Output file path", required_unless_present = "list_conversions")]
+        output: Option<PathBuf>,
+
+        #[arg(long, help = "List the supported source -> target conversions and exit")]
+        list_conversions: bool,
 
-        #[arg(short, long, help = "Target format")]
+        #[arg(short, long, visible_alias = "to", value_parser = parse_format_name, help = "Target format (default: from the output extension)")]
         format: Option<String>,
 
+        #[arg(long, value_name = "FORMAT", value_parser = parse_format_name, help = "Source format, for inputs without a telling extension (default: from the input extension)")]
+        from: Option<String>,
+
         #[arg(short, long, help = "Show progress during conversion")]
         progress: bool,
+
+        #[arg(long, help = "Title of the output, in place of the source's")]
+        title: Option<String>,
+
+        #[arg(long = "author", value_name = "AUTHOR", help = "Author of the output, in place of the source's; repeat for several authors")]
+        authors: Vec<String>,
+
+        #[arg(long, value_name = "LANG", help = "Language of the output, e.g. en or fr-CA, in place of the source's")]
+        language: Option<String>,
+
+        #[arg(long, conflicts_with = "language", help = "Detect the language from the text when the source declares none; left unset when the guess is unsure")]
+        detect_language: bool,
+
+        #[arg(long, help = "Prepend a Title/Author header block to TXT output")]
+        include_metadata_header: bool,
+
+        #[arg(long, help = "Downscale images wider than this (image-based output such as CBZ to EPUB)")]
+        max_width: Option<u32>,
+
+        #[arg(long, help = "Downscale images taller than this (image-based output such as CBZ to EPUB)")]
+        max_height: Option<u32>,
+
+        #[arg(long, help = "Separator for joining multiple authors in TXT/PDF output (default \"; \")")]
+        author_separator: Option<String>,
+
+        #[arg(long, help = "Join words hyphenated across line breaks in PDF text (inter-/national -> international)")]
+        dehyphenate: bool,
+
+        #[arg(long, help = "Put a \"[Figure: page N, WxH]\" line where each image is drawn in PDF text")]
+        figure_markers: bool,
+
+        #[arg(long, help = "Split sources without chapter markers at \"Chapter N\" lines so the output gets a TOC")]
+        toc_from_headings: bool,
+
+        #[arg(long, value_name = "ORDER", default_value = "filename", help = "CBZ page order: filename (natural sort) or comicinfo (ComicInfo.xml <Pages>, cover first)")]
+        page_order: PageOrder,
+
+        #[arg(long, help = "CBZ to EPUB: pages turn right to left, as in manga")]
+        rtl: bool,
+
+        #[arg(long, value_name = "TEMPLATE", value_parser = parse_chapter_title_template, help = "Title for chapters without a heading: {n}, {n:03} (zero-padded) or {roman}, e.g. \"Kapitel {n}\" (default \"Chapter {n}\")")]
+        chapter_title_template: Option<String>,
+
+        #[arg(long, help = "Open EPUB/PDF output with a generated title page (title, authors, date, cover) unless the source already has one")]
+        title_page: bool,
+
+        #[arg(long, value_name = "N", value_parser = parse_sample_size, help = "Write a sample edition: only the first N chapters (pages for PDF/CBZ sources), titled \"... (Sample)\"")]
+        sample: Option<usize>,
+
+        #[arg(long, help = "OCR scanned PDFs (when they have no text layer) and CBZ pages; needs a build with the ocr feature")]
+        ocr: bool,
+
+        #[arg(long, value_name = "LANG", default_value = ocr::DEFAULT_LANGUAGE, requires = "ocr", help = "Tesseract language for --ocr, e.g. deu or eng+fra")]
+        ocr_lang: String,
+
+        #[arg(long, value_name = "SIZE", value_parser = ebook_cli::utils::parse_byte_size, help = "Split TXT chapters larger than SIZE (e.g. 200KB) at paragraph breaks into \"Title (part k)\" chapters")]
+        split_on_size: Option<u64>,
+
+        #[arg(long, help = "Reuse the output of an earlier conversion of the same input with the same options (also on when EBOOK_CACHE_DIR is set)")]
+        cache: bool,
+
+        #[arg(long, value_name = "DIR", help = "Cache directory; implies --cache (default $EBOOK_CACHE_DIR, else ~/.cache/ebook)")]
+        cache_dir: Option<PathBuf>,
+
+        #[arg(long, conflicts_with_all = ["cache", "cache_dir"], help = "Don't use the conversion cache, even when EBOOK_CACHE_DIR is set")]
+        no_cache: bool,
+
+        #[arg(long, help = "Give the output file the input's modification time")]
+        preserve_mtime: bool,
+
+        #[arg(long, value_name = "WHEN", default_value = "both", help = "EPUB output: write the NCX with the EPUB 3 nav (both), leave it out (never, pure EPUB 3) or write only the NCX (only, EPUB 2)")]
+        emit_ncx: NcxMode,
+
+        #[arg(long, value_name = "ENCODING", default_value = "utf-8", help = "TXT output encoding: utf-8, utf-8-bom, utf-16le or a code page such as windows-1252")]
+        output_encoding: TextEncoding,
     },
     
     Info {
         #[arg(help = "Path to the ebook file")]
         input: PathBuf,
+
+        #[arg(long = "as", value_name = "FORMAT", value_parser = parse_format_name, help = "Read the file as this format instead of going by its extension")]
+        as_format: Option<String>,
+
+        #[arg(short, long, help = "Also list the files inside an EPUB/CBZ with their sizes")]
+        verbose: bool,
+
+        #[arg(long, help = "Print the information (and EPUB/CBZ archive entries) as JSON")]
+        json: bool,
     },
     
     Validate {
         #[arg(help = "Path to the ebook file")]
         input: PathBuf,
+
+        #[arg(long, help = "Run epubcheck-style structural checks on an EPUB and list every error and warning")]
+        strict: bool,
+    },
+    
+    Doctor {
+        #[arg(help = "Path to the ebook file")]
+        input: PathBuf,
     },
     
     Repair {
@@ -81,6 +228,9 @@ enum Commands {
 
         #[arg(short, long, help = "Show progress during repair")]
         progress: bool,
+
+        #[arg(long, help = "Give the output file the input's modification time")]
+        preserve_mtime: bool,
     },
     
     Optimize {
@@ -102,256 +252,884 @@ enum Commands {
         #[arg(long, help = "Skip resizing, only compress")]
         no_resize: bool,
 
+        #[arg(long, conflicts_with = "no_resize", help = "Clamp width and height to their maximums independently instead of keeping proportions")]
+        stretch: bool,
+
+        #[arg(long, conflicts_with = "no_resize", help = "Also enlarge images smaller than the maximum dimensions")]
+        upscale: bool,
+
+        #[arg(long, value_name = "FORMAT", help = "Transcode every image to this format (jpeg, png, webp); EPUB references are renamed to match")]
+        image_format: Option<String>,
+
+        #[arg(long, value_name = "SIZE", value_parser = ebook_cli::utils::parse_byte_size, help = "Lower JPEG quality until the CBZ fits in SIZE (e.g. 50MB, 800KB, 1048576; CBZ only)")]
+        max_filesize: Option<u64>,
+
+        #[arg(long, help = "Lowest JPEG quality to try with --max-filesize", default_value = "10")]
+        min_quality: u8,
+
+        #[arg(long, default_value_t = 0, help = "Worker threads for image optimization (0 = all cores); output is identical for any value")]
+        threads: usize,
+
+        #[arg(long, help = "Edit the EPUB in place: replace image entries only, copying every other entry unchanged (EPUB only)")]
+        preserve_structure: bool,
+
+        #[arg(long, help = "Rewrite the book even when it has no images to optimize")]
+        force: bool,
+
+        #[arg(long, help = "Also recompress the archive: text entries at maximum deflate, images stored (EPUB only)")]
+        recompress: bool,
+
+        #[arg(long, help = "Find images with identical bytes: EPUBs keep one copy and point every reference at it, CBZs report repeated pages")]
+        dedupe: bool,
+
+        #[arg(long, requires = "dedupe", help = "With --dedupe, also remove repeated CBZ pages")]
+        drop_duplicate_pages: bool,
+
+        #[arg(long, value_name = "PATH", help = "Also write a JSON report of every image's sizes, dimensions and the settings used to PATH")]
+        report: Option<PathBuf>,
+
         #[arg(short, long, help = "Show progress during optimization")]
         progress: bool,
+
+        #[arg(long, help = "Give the output file the input's modification time")]
+        preserve_mtime: bool,
     },
     
+    #[command(about = "Extract all images from an ebook into a directory")]
+    ExtractImages {
+        #[arg(help = "Path to the ebook file")]
+        input: PathBuf,
+
+        #[arg(help = "Directory to write images to")]
+        output_dir: PathBuf,
+
+        #[arg(long, help = "Rename images to page0001.ext, page0002.ext, ... in natural order")]
+        rename: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "rename",
+            help = "Name images from a template with {title}, {author}, {index}, {ext} and {chapter} (the image's own name), e.g. \"{title}-{index:03}.{ext}\""
+        )]
+        name_template: Option<String>,
+
+        #[arg(long, value_name = "ORDER", default_value = "filename", help = "CBZ page order: filename (natural sort) or comicinfo (ComicInfo.xml <Pages>, cover first)")]
+        page_order: PageOrder,
+    },
+
+    #[command(about = "Rewrite an EPUB, optionally restructuring its spine")]
+    Repack {
+        #[arg(help = "Path to the EPUB file")]
+        input: PathBuf,
+
+        #[arg(help = "Output EPUB path")]
+        output: PathBuf,
+
+        #[arg(long, help = "Merge the chapters into fewer, larger files, keeping links between them working")]
+        flatten_spine: bool,
+
+        #[arg(long, value_name = "N", requires = "flatten_spine", value_parser = clap::value_parser!(u64).range(1..), help = "With --flatten-spine, merge N chapters per file instead of all of them")]
+        chapters_per_file: Option<u64>,
+
+        #[arg(long, help = "Drop chapters with no text or images (blank pages) and their TOC entries; fixed-layout books are kept as they are")]
+        drop_empty_chapters: bool,
+
+        #[arg(long, help = "Move all images into one images/ folder, renaming clashing names and updating the references to them")]
+        collapse_images: bool,
+
+        #[arg(long, value_name = "WHEN", default_value = "both", help = "EPUB output: write the NCX with the EPUB 3 nav (both), leave it out (never, pure EPUB 3) or write only the NCX (only, EPUB 2)")]
+        emit_ncx: NcxMode,
+    },
+
+    #[command(about = "Write a downscaled copy of an ebook's cover image")]
+    Thumbnail {
+        #[arg(help = "Path to the ebook file")]
+        input: PathBuf,
+
+        #[arg(help = "Image to write; the extension picks the format (png, jpg, webp)")]
+        output: PathBuf,
+
+        #[arg(long, default_value_t = 300, help = "Width of the thumbnail in pixels (smaller covers are kept as they are)")]
+        width: u32,
+
+        #[arg(long, help = "Without a declared cover, use the first page: rendered for PDFs (needs a build with the pdf-render feature unless it is a scan), the first image otherwise")]
+        cover_from_first_page: bool,
+    },
+
+    #[command(about = "List PDF annotations or EPUB 3 landmarks as JSON")]
+    Annotations {
+        #[arg(help = "Path to the ebook file")]
+        input: PathBuf,
+    },
+
+    #[command(about = "Export an ebook's metadata to a JSON (or .opf fragment) sidecar file")]
+    ExportMetadata {
+        #[arg(help = "Path to the ebook file")]
+        input: PathBuf,
+
+        #[arg(help = "Sidecar file to write (.json, or .opf for an OPF metadata fragment)")]
+        output: PathBuf,
+    },
+
+    #[command(about = "Overlay metadata from a JSON sidecar onto an ebook and rewrite it")]
+    ImportMetadata {
+        #[arg(help = "Path to the ebook file")]
+        input: PathBuf,
+
+        #[arg(help = "JSON sidecar file written by export-metadata")]
+        metadata_json: PathBuf,
+
+        #[arg(short, long, help = "Output file path (if different from input)")]
+        output: Option<PathBuf>,
+    },
+
+    #[command(about = "Re-run convert or optimize whenever the input file changes (Ctrl+C to stop)")]
+    Watch {
+        #[arg(help = "File to watch")]
+        input: PathBuf,
+
+        #[arg(help = "Output file path")]
+        output: PathBuf,
+
+        #[arg(value_parser = ["convert", "optimize"], help = "Operation to re-run (convert or optimize)")]
+        operation: String,
+
+        #[arg(long, default_value_t = 500, help = "Milliseconds to wait after the last change before running")]
+        debounce_ms: u64,
+    },
+
+    #[command(about = "Compare the metadata, chapters and text of two ebooks")]
+    Diff {
+        #[arg(help = "First ebook")]
+        a: PathBuf,
+
+        #[arg(help = "Second ebook")]
+        b: PathBuf,
+
+        #[arg(long, help = "Print the differences as JSON")]
+        json: bool,
+    },
+
+    #[command(about = "Report the metadata of every ebook in a directory as CSV or JSON")]
+    Catalog {
+        #[arg(help = "Directory to catalog")]
+        dir: PathBuf,
+
+        #[arg(short, long, help = "Include subdirectories")]
+        recursive: bool,
+
+        #[arg(short, long, help = "Write the report to this file instead of stdout")]
+        output: Option<PathBuf>,
+
+        #[arg(long, help = "Emit JSON instead of CSV")]
+        json: bool,
+
+        #[arg(long, help = "After the report, print a summary of unreadable books and exit nonzero if there were any")]
+        keep_going: bool,
+    },
+
+    #[command(about = "Convert every ebook in a directory, resuming from a JSON manifest of earlier runs")]
+    Batch {
+        #[arg(help = "Directory of ebooks to convert")]
+        input_dir: PathBuf,
+
+        #[arg(help = "Directory to write the converted files to")]
+        output_dir: PathBuf,
+
+        #[arg(short, long, visible_alias = "to", value_parser = parse_format_name, help = "Target format")]
+        format: String,
+
+        #[arg(short, long, help = "Include subdirectories")]
+        recursive: bool,
+
+        #[arg(long, help = "Manifest file recording the run (default: batch-manifest.json in the output directory)")]
+        manifest: Option<PathBuf>,
+
+        #[arg(long, help = "Convert files again even if the manifest records them as done")]
+        force: bool,
+
+        #[arg(long, help = "Record files that fail to convert and carry on instead of stopping at the first")]
+        keep_going: bool,
+
+        #[arg(
+            long,
+            help = "Name outputs from a template with {title}, {author}, {index}, {ext} and {chapter} (the source file name), e.g. \"{index:03}-{chapter}.{ext}\""
+        )]
+        name_template: Option<String>,
+    },
+
+    #[command(about = "Join several ebooks into one EPUB")]
+    Merge {
+        #[arg(required = true, num_args = 2.., help = "Ebooks to join, in reading order")]
+        inputs: Vec<PathBuf>,
+
+        #[arg(short, long, help = "Output EPUB file")]
+        output: PathBuf,
+
+        #[arg(long, help = "Title of the merged book (default: the first book's title)")]
+        title: Option<String>,
+
+        #[arg(long, help = "Leave out books that cannot be read and carry on instead of stopping at the first")]
+        keep_going: bool,
+    },
+
+    #[command(about = "Show which operations each format supports")]
+    Formats {
+        #[arg(long, help = "Print the capability matrix as JSON")]
+        json: bool,
+    },
+
     #[command(about = "Start MCP server for Model Context Protocol integration")]
-    Mcp,
+    Mcp {
+        #[arg(long, value_name = "SECONDS", default_value_t = 120, help = "Answer tool calls still running after this long with a timeout error")]
+        tool_timeout: u64,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
+    // Warnings (e.g. a sample covering the whole book) are shown unless RUST_LOG says otherwise
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Read { input, metadata, extract_images, toc } => {
-            handle_read(input, metadata, extract_images, toc)?;
+        Commands::Read { input, as_format, range: Some((start, count)), head, .. } => {
+            handle_read_range(input, as_format, start, count, head)?;
+        }
+        Commands::Read { input, as_format, metadata, extract_images, toc, head, chapter, toc_from_headings, detect_language, chapters_from_toc, chapter_titles, ocr, ocr_lang, progress, .. } => {
+            let ocr_language = ocr.then_some(ocr_lang);
+            let mut handler = open_for_reading(&input, as_format, chapters_from_toc, chapter_titles, ocr_language.as_deref(), progress)?;
+            if detect_language {
+                let mut book_metadata = handler.get_metadata()?;
+                if book_metadata.detect_language(&handler.get_content()?) {
+                    handler.set_metadata(book_metadata)?;
+                }
+            }
+            handle_read(handler, metadata, extract_images, toc, toc_from_headings, head, chapter)?;
+        }
+        Commands::Write { output, title, author, content, format, progress, no_comicinfo, comicinfo_version, output_encoding } => {
+            let settings = WriteSettings { comic_info: (!no_comicinfo).then_some(comicinfo_version), output_encoding };
+            handle_write(output, title, author, content, format, settings, progress)?;
+        }
+        Commands::Convert { list_conversions: true, .. } => {
+            print_conversions();
+        }
+        Commands::Convert { input: Some(input), output: Some(output), format, from, progress, title, authors, language, detect_language, include_metadata_header, max_width, max_height, author_separator, dehyphenate, figure_markers, toc_from_headings, page_order, rtl, chapter_title_template, title_page, sample, ocr, ocr_lang, split_on_size, cache, cache_dir, no_cache, preserve_mtime, emit_ncx, output_encoding, .. } => {
+            let mut options = conversion_options(include_metadata_header, max_width, max_height, author_separator)
+                .with_dehyphenation(dehyphenate)
+                .with_figure_markers(figure_markers)
+                .with_toc_from_headings(toc_from_headings)
+                .with_page_order(page_order)
+                .with_right_to_left(rtl)
+                .with_title_page(title_page)
+                .with_ncx_mode(emit_ncx)
+                .with_output_encoding(output_encoding)
+                .with_authors(authors)
+                .with_language_detection(detect_language);
+            if let Some(title) = title {
+                options = options.with_title(title);
+            }
+            if let Some(language) = language {
+                options = options.with_language(language);
+            }
+            if let Some(source_format) = from {
+                options = options.with_source_format(source_format);
+            }
+            if let Some(template) = chapter_title_template {
+                options = options.with_chapter_title_template(template);
+            }
+            if let Some(chapters) = sample {
+                options = options.with_sample(chapters);
+            }
+            if ocr {
+                options = options.with_ocr(ocr_lang);
+            }
+            if let Some(max_bytes) = split_on_size {
+                options = options.with_max_chapter_bytes(usize::try_from(max_bytes).unwrap_or(usize::MAX));
+            }
+            let cache_enabled = !no_cache && (cache || cache_dir.is_some() || std::env::var_os(ebook_cli::cache::CACHE_DIR_ENV).is_some());
+            let cache = cache_enabled.then(|| ConversionCache::new(cache_dir.unwrap_or_else(ConversionCache::default_dir)));
+            let (source, target) = (input.clone(), output.clone());
+            with_preserved_mtime(&source, &target, preserve_mtime, || {
+                handle_convert(input, output, format, progress, options, cache)
+            })?;
+        }
+        Commands::Convert { .. } => {
+            return Err(EbookError::InvalidStructure("convert needs an input and an output path".to_string()));
+        }
+        Commands::Info { input, as_format, verbose, json } => {
+            handle_info(input, as_format, verbose, json)?;
+        }
+        Commands::Validate { input, strict } => {
+            handle_validate(input, strict)?;
+        }
+        Commands::Doctor { input } => {
+            println!("{}", ebook_cli::doctor::DoctorReport::diagnose(&input));
+        }
+        Commands::Repair { input, output, progress, preserve_mtime } => {
+            let (source, target) = (input.clone(), output.clone().unwrap_or_else(|| input.clone()));
+            with_preserved_mtime(&source, &target, preserve_mtime, || handle_repair(input, output, progress))?;
+        }
+        Commands::Optimize { input, output, max_width, max_height, quality, no_resize, stretch, upscale, image_format, max_filesize, min_quality, threads, preserve_structure, force, recompress, dedupe, drop_duplicate_pages, report, progress, preserve_mtime } => {
+            let options = optimization_options(max_width, max_height, quality, no_resize, image_format)?
+                .with_preserve_aspect_ratio(!stretch)
+                .with_upscale(upscale)
+                .with_threads(threads);
+            let size_target = max_filesize.map(|max_bytes| (max_bytes, min_quality));
+            let mode = OptimizeMode { preserve_structure, force, recompress, dedupe, drop_duplicate_pages, report };
+            let (source, target) = (input.clone(), output.clone().unwrap_or_else(|| input.clone()));
+            with_preserved_mtime(&source, &target, preserve_mtime, || {
+                handle_optimize(input, output, options, size_target, mode, progress)
+            })?;
+        }
+        Commands::ExtractImages { input, output_dir, rename, name_template, page_order } => {
+            handle_extract_images(input, output_dir, rename, name_template, page_order)?;
+        }
+        Commands::Repack { input, output, flatten_spine, chapters_per_file, drop_empty_chapters, collapse_images, emit_ncx } => {
+            let changes = RepackChanges { flatten_spine, chapters_per_file: chapters_per_file.map(|n| n as usize), drop_empty_chapters, collapse_images, emit_ncx };
+            handle_repack(input, output, changes)?;
+        }
+        Commands::Thumbnail { input, output, width, cover_from_first_page } => {
+            handle_thumbnail(input, output, width, cover_from_first_page)?;
+        }
+        Commands::Annotations { input } => {
+            handle_annotations(input)?;
+        }
+        Commands::Watch { input, output, operation, debounce_ms } => {
+            handle_watch(input, output, operation, debounce_ms)?;
         }
-        Commands::Write { output, title, author, content, format, progress } => {
-            handle_write(output, title, author, content, format, progress)?;
+        Commands::ExportMetadata { input, output } => {
+            handle_export_metadata(input, output)?;
         }
-        Commands::Convert { input, output, format, progress } => {
-            handle_convert(input, output, format, progress)?;
+        Commands::ImportMetadata { input, metadata_json, output } => {
+            handle_import_metadata(input, metadata_json, output)?;
         }
-        Commands::Info { input } => {
-            handle_info(input)?;
+        Commands::Diff { a, b, json } => {
+            handle_diff(a, b, json)?;
         }
-        Commands::Validate { input } => {
-            handle_validate(input)?;
+        Commands::Catalog { dir, recursive, output, json, keep_going } => {
+            handle_catalog(dir, recursive, output, json, keep_going)?;
         }
-        Commands::Repair { input, output, progress } => {
-            handle_repair(input, output, progress)?;
+        Commands::Batch { input_dir, output_dir, format, recursive, manifest, force, keep_going, name_template } => {
+            let options = ebook_cli::batch::BatchOptions {
+                recursive,
+                force,
+                keep_going,
+                manifest_path: manifest,
+                name_template,
+                ..Default::default()
+            };
+            handle_batch(input_dir, output_dir, format, options)?;
         }
-        Commands::Optimize { input, output, max_width, max_height, quality, no_resize, progress } => {
-            handle_optimize(input, output, max_width, max_height, quality, no_resize, progress)?;
+        Commands::Merge { inputs, output, title, keep_going } => {
+            handle_merge(inputs, output, title, keep_going)?;
         }
-        Commands::Mcp => {
-            handle_mcp().await?;
+        Commands::Formats { json } => print_formats(json),
+        Commands::Mcp { tool_timeout } => {
+            handle_mcp(std::time::Duration::from_secs(tool_timeout)).await?;
         }
     }
 
     Ok(())
 }
 
-fn handle_read(
-    input: PathBuf,
-    show_metadata: bool,
-    extract_images: Option<PathBuf>,
-    show_toc: bool,
-) -> Result<()> {
-    let format = ebook_cli::utils::detect_format(&input)?;
-    
-    match format.as_str() {
-        "epub" => {
+/// Open a book, applying reader options that only some formats understand
+fn open_for_reading(
+    input: &Path,
+    format: Option<String>,
+    chapters_from_toc: bool,
+    chapter_titles: ChapterTitleSource,
+    ocr_language: Option<&str>,
+    show_progress: bool,
+) -> Result<Box<dyn EbookOperator>> {
+    let registry = FormatRegistry::default();
+    let format = match format {
+        Some(format) => format,
+        None => registry.format_for_path(input)?,
+    };
+    match (format.as_str(), ocr_language) {
+        ("epub", _) => {
             let mut handler = EpubHandler::new();
-            handler.read_from_file(&input)?;
-            
-            if show_metadata {
-                let metadata = handler.get_metadata()?;
-                println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-            } else if show_toc {
-                let toc = handler.get_toc()?;
-                for entry in toc {
-                    println!("{}{}", "  ".repeat(entry.level - 1), entry.title);
-                }
-            } else {
-                let content = handler.get_content()?;
-                println!("{}", content);
-            }
-            
-            if let Some(dir) = extract_images {
-                std::fs::create_dir_all(&dir)?;
-                let images = handler.extract_images()?;
-                for image in &images {
-                    let path = dir.join(&image.name);
-                    std::fs::write(path, &image.data)?;
-                }
-                println!("Extracted {} images to {:?}", images.len(), dir);
-            }
-        }
-        "mobi" => {
-            let mut handler = MobiHandler::new();
-            handler.read_from_file(&input)?;
-
-            if show_metadata {
-                let metadata = handler.get_metadata()?;
-                println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-            } else {
-                let content = handler.get_content()?;
-                println!("{}", content);
-            }
-        }
-        "azw" | "azw3" => {
-            let mut handler = AzwHandler::new();
-            handler.read_from_file(&input)?;
-
-            if show_metadata {
-                let metadata = handler.get_metadata()?;
-                println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-            } else {
-                let content = handler.get_content()?;
-                println!("{}", content);
-            }
+            handler.set_chapters_from_toc(chapters_from_toc);
+            handler.set_chapter_title_source(chapter_titles);
+            handler.read_from_file(input)?;
+            Ok(Box::new(handler))
         }
-        "fb2" => {
-            let mut handler = Fb2Handler::new();
-            handler.read_from_file(&input)?;
-            
-            if show_metadata {
-                let metadata = handler.get_metadata()?;
-                println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-            } else {
-                let content = handler.get_content()?;
-                println!("{}", content);
+        ("pdf", Some(language)) => {
+            let mut handler = PdfHandler::new();
+            handler.read_from_file(input)?;
+            if handler.ocr_if_image_only(ocr::default_backend()?.as_ref(), language)? {
+                eprintln!("No text layer found; text recognized with OCR ({language})");
             }
+            Ok(Box::new(handler))
         }
-        "cbz" => {
+        ("cbz", Some(language)) => {
             let mut handler = CbzHandler::new();
-            handler.read_from_file(&input)?;
-            
-            if show_metadata {
-                let metadata = handler.get_metadata()?;
-                println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-            } else {
-                let content = handler.get_content()?;
-                println!("{}", content);
-            }
-            
-            if let Some(dir) = extract_images {
-                std::fs::create_dir_all(&dir)?;
-                let images = handler.extract_images()?;
-                for image in &images {
-                    let path = dir.join(&image.name);
-                    std::fs::write(path, &image.data)?;
-                }
-                println!("Extracted {} images to {:?}", images.len(), dir);
-            }
+            handler.read_from_file(input)?;
+            handler.recognize_pages(ocr::default_backend()?.as_ref(), language)?;
+            Ok(Box::new(handler))
         }
-        "txt" => {
+        ("txt", None) if show_progress => {
             let mut handler = TxtHandler::new();
-            handler.read_from_file(&input)?;
-            
-            if show_metadata {
-                let metadata = handler.get_metadata()?;
-                println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-            } else if show_toc {
-                let toc = handler.get_toc()?;
-                for entry in toc {
-                    println!("{}", entry.title);
-                }
-            } else {
-                let content = handler.get_content()?;
-                println!("{}", content);
-            }
-        }
-        "pdf" => {
-            let mut handler = PdfHandler::new();
-            handler.read_from_file(&input)?;
-            
-            if show_metadata {
-                let metadata = handler.get_metadata()?;
-                println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-            } else {
-                let content = handler.get_content()?;
-                println!("{}", content);
-            }
+            let progress = ProgressHandler::with_callback(console_progress_callback("Reading".to_string()));
+            handler.read_from_file_streaming(input, Some(&progress))?;
+            eprintln!();
+            Ok(Box::new(handler))
         }
-        _ => return Err(EbookError::UnsupportedFormat(format)),
+        (_, Some(_)) => Err(EbookError::NotSupported("--ocr only applies to PDF and CBZ files".to_string())),
+        _ => registry.open_as(input, &format),
     }
-    
-    Ok(())
 }
 
-fn handle_write(
-    output: PathBuf,
-    title: Option<String>,
-    author: Option<String>,
-    content_file: Option<PathBuf>,
-    format: String,
-    show_progress: bool,
+fn handle_read(
+    handler: Box<dyn EbookOperator>,
+    show_metadata: bool,
+    extract_images: Option<PathBuf>,
+    show_toc: bool,
+    toc_from_headings: bool,
+    head: Option<usize>,
+    chapter: Option<usize>,
 ) -> Result<()> {
-    let content = if let Some(path) = content_file {
-        if show_progress {
-            eprint!("Reading content from file...");
-        }
-        let c = std::fs::read_to_string(path)?;
-        if show_progress {
-            eprintln!(" Done.");
+
+    if show_metadata {
+        let metadata = handler.get_metadata()?;
+        println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
+    } else if show_toc {
+        let mut toc = handler.get_toc()?;
+        if toc.is_empty() && toc_from_headings {
+            toc = ebook_cli::utils::toc_from_headings(handler.as_ref())?;
         }
-        c
+        print_toc(&toc);
     } else {
-        String::new()
-    };
-
-    let mut metadata = ebook_cli::Metadata::new();
-    if let Some(t) = title {
-        metadata.title = Some(t);
-    }
-    if let Some(a) = author {
-        metadata.author = Some(a);
+        let content = match chapter {
+            Some(k) => k
+                .checked_sub(1)
+                .map(|i| handler.get_chapter(i))
+                .transpose()?
+                .flatten()
+                .ok_or_else(|| chapter_not_found(k))?,
+            None => handler.get_content()?,
+        };
+        print_content(&content, head);
     }
 
-    if show_progress {
-        eprint!("Writing {} ebook...", format);
+    if let Some(dir) = extract_images {
+        let written = ebook_cli::utils::write_images_to_dir(&handler.extract_images()?, &dir, false)?;
+        println!("Extracted {} images to {:?}", written.len(), dir);
     }
 
-    match format.as_str() {
-        "epub" => {
-            let mut handler = EpubHandler::new();
-            handler.set_metadata(metadata)?;
-            handler.set_content(&content)?;
-            handler.write_to_file(&output)?;
-        }
-        "mobi" => {
-            let mut handler = MobiHandler::new();
-            handler.set_metadata(metadata)?;
-            handler.set_content(&content)?;
-            handler.write_to_file(&output)?;
-        }
-        "azw" | "azw3" => {
-            let mut handler = AzwHandler::new();
-            handler.set_metadata(metadata)?;
-            handler.set_content(&content)?;
-            handler.write_to_file(&output)?;
-        }
-        "fb2" => {
-            let mut handler = Fb2Handler::new();
-            handler.set_metadata(metadata)?;
-            handler.set_content(&content)?;
-            handler.write_to_file(&output)?;
-        }
-        "cbz" => {
-            let mut handler = CbzHandler::new();
-            handler.set_metadata(metadata)?;
-            handler.write_to_file(&output)?;
+    Ok(())
+}
+
+/// Print a window of lines; TXT files are streamed so only the window is held in memory
+fn handle_read_range(input: PathBuf, format: Option<String>, start: usize, count: usize, head: Option<usize>) -> Result<()> {
+    let registry = FormatRegistry::default();
+    let format = match format {
+        Some(format) => format,
+        None => registry.format_for_path(&input)?,
+    };
+    let window = if format == "txt" {
+        ebook_cli::formats::TxtHandler::read_lines(&input, start - 1, count)?
+    } else {
+        let content = registry.open_as(&input, &format)?.get_content()?;
+        content.split_inclusive('\n').skip(start - 1).take(count).collect()
+    };
+    print_content(window.strip_suffix('\n').unwrap_or(&window), head);
+    Ok(())
+}
+
+fn handle_annotations(input: PathBuf) -> Result<()> {
+    let handler = FormatRegistry::default().open(&input)?;
+    let annotations = handler.get_annotations()?;
+    println!("{}", serde_json::to_string_pretty(&annotations).unwrap());
+    Ok(())
+}
+
+fn handle_watch(input: PathBuf, output: PathBuf, operation: String, debounce_ms: u64) -> Result<()> {
+    let mut runs = 0;
+    let mut run = || {
+        runs += 1;
+        let result = match operation.as_str() {
+            "optimize" => handle_optimize(input.clone(), Some(output.clone()), Default::default(), None, OptimizeMode::default(), false),
+            _ => handle_convert(input.clone(), output.clone(), None, false, ConversionOptions::default(), None),
+        };
+        match result {
+            Ok(()) => println!("[run {runs}] {operation} succeeded"),
+            Err(e) => eprintln!("[run {runs}] {operation} failed: {e}"),
         }
-        "txt" => {
-            let mut handler = TxtHandler::new();
-            handler.set_metadata(metadata)?;
-            handler.set_content(&content)?;
-            handler.write_to_file(&output)?;
+    };
+
+    run();
+    println!("Watching {:?} for changes (Ctrl+C to stop)", input);
+    ebook_cli::watch::watch_file(&input, std::time::Duration::from_millis(debounce_ms), || {
+        run();
+        std::ops::ControlFlow::Continue(())
+    })
+}
+
+fn handle_export_metadata(input: PathBuf, output: PathBuf) -> Result<()> {
+    let metadata = FormatRegistry::default().open(&input)?.get_metadata()?;
+    let is_opf = output
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("opf"));
+
+    let sidecar = if is_opf {
+        metadata.to_opf_fragment()
+    } else {
+        serde_json::to_string_pretty(&metadata).unwrap()
+    };
+    std::fs::write(&output, sidecar)?;
+
+    println!("Exported metadata to {:?}", output);
+    Ok(())
+}
+
+fn handle_import_metadata(input: PathBuf, metadata_json: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let imported = ebook_cli::Metadata::from_json_file(&metadata_json)?;
+    let mut handler = FormatRegistry::default().open(&input)?;
+
+    let mut metadata = handler.get_metadata()?;
+    metadata.overlay(&imported);
+    handler.set_metadata(metadata)?;
+
+    let output_path = output.unwrap_or(input);
+    handler.write_to_file(&output_path)?;
+
+    println!("Imported metadata into {:?}", output_path);
+    Ok(())
+}
+
+fn handle_catalog(dir: PathBuf, recursive: bool, output: Option<PathBuf>, json: bool, keep_going: bool) -> Result<()> {
+    let entries = ebook_cli::catalog::build_catalog(&dir, recursive, &FormatRegistry::default())?;
+    let report = if json {
+        serde_json::to_string_pretty(&entries).unwrap() + "\n"
+    } else {
+        ebook_cli::catalog::to_csv(&entries)
+    };
+
+    let failures: Vec<&ebook_cli::catalog::CatalogEntry> = entries.iter().filter(|entry| entry.error.is_some()).collect();
+    match output {
+        Some(path) => {
+            std::fs::write(&path, report)?;
+            println!("Catalogued {} files ({} unreadable) to {}", entries.len(), failures.len(), path.display());
         }
-        "pdf" => {
-            let mut handler = PdfHandler::new();
-            handler.set_metadata(metadata)?;
-            handler.set_content(&content)?;
-            handler.write_to_file(&output)?;
+        None => print!("{report}"),
+    }
+
+    // Unreadable books are always in the report; --keep-going also makes them fail the run
+    if !keep_going || failures.is_empty() {
+        return Ok(());
+    }
+    eprintln!("{} of {} files failed:", failures.len(), entries.len());
+    for entry in &failures {
+        eprintln!("  {}: {}", entry.filename, entry.error.as_deref().unwrap_or_default());
+    }
+    Err(EbookError::BatchFailed { failed: failures.len(), total: entries.len() })
+}
+
+fn handle_batch(input_dir: PathBuf, output_dir: PathBuf, format: String, options: ebook_cli::batch::BatchOptions) -> Result<()> {
+    use ebook_cli::batch::BatchEvent;
+
+    let manifest = ebook_cli::batch::run_batch(&input_dir, &output_dir, &format, &options, |file, event| match event {
+        BatchEvent::Converted => println!("Converted {} -> {}", file.input, file.output),
+        BatchEvent::Skipped => println!("Skipped {} (already converted)", file.input),
+        BatchEvent::Failed => eprintln!("Failed {}: {}", file.input, file.error.as_deref().unwrap_or_default()),
+    })?;
+    println!("Batch complete: {} files in {}", manifest.files.len(), output_dir.display());
+
+    let failures = manifest.failures();
+    if failures.is_empty() {
+        return Ok(());
+    }
+    eprintln!("{} of {} files failed:", failures.len(), manifest.files.len());
+    for file in &failures {
+        eprintln!("  {}: {}", file.input, file.error.as_deref().unwrap_or_default());
+    }
+    Err(EbookError::BatchFailed { failed: failures.len(), total: manifest.files.len() })
+}
+
+fn handle_merge(inputs: Vec<PathBuf>, output: PathBuf, title: Option<String>, keep_going: bool) -> Result<()> {
+    let failures = ebook_cli::merge::merge_books(&inputs, &output, title.as_deref(), keep_going)?;
+    println!("Merged {} of {} books into {}", inputs.len() - failures.len(), inputs.len(), output.display());
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+    eprintln!("{} of {} files failed:", failures.len(), inputs.len());
+    for failure in &failures {
+        eprintln!("  {}: {}", failure.path.display(), failure.error);
+    }
+    Err(EbookError::BatchFailed { failed: failures.len(), total: inputs.len() })
+}
+
+fn handle_diff(a: PathBuf, b: PathBuf, json: bool) -> Result<()> {
+    let registry = FormatRegistry::default();
+    let diff = ebook_cli::BookDiff::compare(registry.open(&a)?.as_ref(), registry.open(&b)?.as_ref())?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff).unwrap());
+        return Ok(());
+    }
+
+    let show = |value: &Option<String>| value.as_deref().map_or("(none)".to_string(), |v| format!("{v:?}"));
+    if diff.metadata.is_empty() {
+        println!("Metadata: identical");
+    } else {
+        println!("Metadata differences:");
+        for field in &diff.metadata {
+            println!("  {}: {} vs {}", field.field, show(&field.a), show(&field.b));
+        }
+    }
+
+    match (diff.chapters_a, diff.chapters_b) {
+        (Some(a), Some(b)) => println!("Chapters: {a} vs {b} ({:+})", b as i64 - a as i64),
+        (a, b) => println!(
+            "Chapters: {} vs {}",
+            a.map_or("n/a".to_string(), |n| n.to_string()),
+            b.map_or("n/a".to_string(), |n| n.to_string())
+        ),
+    }
+
+    println!("Text similarity: {:.1}%", diff.similarity);
+    match &diff.text_diff {
+        Some(lines) if !lines.is_empty() => {
+            println!("--- {}", a.display());
+            println!("+++ {}", b.display());
+            for line in lines {
+                println!("{line}");
+            }
+        }
+        Some(_) => {}
+        None => println!("(texts too large for a line diff)"),
+    }
+
+    Ok(())
+}
+
+fn handle_extract_images(
+    input: PathBuf,
+    output_dir: PathBuf,
+    rename: bool,
+    name_template: Option<String>,
+    page_order: PageOrder,
+) -> Result<()> {
+    use ebook_cli::utils::{image_extension, natural_cmp, page_file_name, render_name_template, write_images_named, NameTemplateContext};
+
+    let registry = FormatRegistry::default();
+    let handler: Box<dyn EbookOperator> = if registry.format_for_path(&input)? == "cbz" {
+        let mut cbz = CbzHandler::new();
+        cbz.set_page_order(page_order);
+        cbz.read_from_file(&input)?;
+        Box::new(cbz)
+    } else {
+        registry.open(&input)?
+    };
+    let mut images = handler.extract_images()?;
+    // With ComicInfo order the pages are already in reading order; otherwise number them by name
+    if page_order == PageOrder::Filename {
+        images.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+    }
+    let written = match name_template {
+        Some(template) => {
+            let metadata = handler.get_metadata()?;
+            write_images_named(&images, &output_dir, |idx, image| {
+                let context = NameTemplateContext {
+                    title: metadata.title.clone(),
+                    author: metadata.all_authors().first().cloned(),
+                    index: idx + 1,
+                    ext: image_extension(image),
+                    chapter: Path::new(&image.name).file_stem().map(|stem| stem.to_string_lossy().into_owned()),
+                };
+                render_name_template(&template, &context)
+            })?
+        }
+        None if rename => write_images_named(&images, &output_dir, |idx, image| Ok(page_file_name(idx, image)))?,
+        None => ebook_cli::utils::write_images_to_dir(&images, &output_dir, false)?,
+    };
+    println!("Extracted {} images to {:?}", written.len(), output_dir);
+    Ok(())
+}
+
+/// Run `command` and, when `preserve` is set, give `output` the modification time `input` had
+/// before it ran (the input may be rewritten in place)
+fn with_preserved_mtime(input: &Path, output: &Path, preserve: bool, command: impl FnOnce() -> Result<()>) -> Result<()> {
+    let modified = if preserve { Some(std::fs::metadata(input)?.modified()?) } else { None };
+    command()?;
+    if let Some(modified) = modified {
+        ebook_cli::utils::set_modified_time(output, modified)?;
+    }
+    Ok(())
+}
+
+/// What `repack` changes about the EPUB it rewrites
+#[derive(Debug, Clone, Copy, Default)]
+struct RepackChanges {
+    /// Merge chapters into fewer files, `chapters_per_file` at a time (all when `None`)
+    flatten_spine: bool,
+    chapters_per_file: Option<usize>,
+    /// Remove blank pages from the spine and TOC
+    drop_empty_chapters: bool,
+    /// Move all images into one `images/` folder
+    collapse_images: bool,
+    /// Navigation documents to write
+    emit_ncx: NcxMode,
+}
+
+fn handle_repack(input: PathBuf, output: PathBuf, changes: RepackChanges) -> Result<()> {
+    let registry = FormatRegistry::default();
+    for path in [&input, &output] {
+        if registry.format_for_path(path)? != "epub" {
+            return Err(EbookError::NotSupported(format!("repack works on EPUB files, not {path:?}")));
+        }
+    }
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&input)?;
+    // Drop blank pages first so they are not merged into the flattened files
+    if changes.drop_empty_chapters {
+        println!("Dropped {} empty chapters", handler.drop_empty_chapters());
+    }
+    if changes.flatten_spine {
+        let before = handler.get_spine_properties().len();
+        let removed = handler.flatten_spine(changes.chapters_per_file);
+        println!("Merged {} spine files into {}", before, before - removed);
+    }
+    if changes.collapse_images {
+        println!("Moved {} images into images/", handler.collapse_images());
+    }
+    handler.set_ncx_mode(changes.emit_ncx);
+    if changes.emit_ncx == NcxMode::Only {
+        handler.set_epub_version(EpubVersion::V2);
+    }
+    handler.write_to_file(&output)?;
+    println!("Repacked {:?} to {:?}", input, output);
+    Ok(())
+}
+
+/// The book's declared cover or, with `from_first_page`, its first page
+fn book_cover(input: &Path, from_first_page: bool) -> Result<ebook_cli::traits::ImageData> {
+    let no_cover = || EbookError::NotFound(format!("a cover in {input:?}; pass --cover-from-first-page to use the first page"));
+    let registry = FormatRegistry::default();
+    if registry.format_for_path(input)? == "pdf" {
+        if !from_first_page {
+            return Err(no_cover());
+        }
+        let mut pdf = PdfHandler::new();
+        pdf.read_from_file(input)?;
+        return pdf.first_page_cover(None);
+    }
+
+    let handler = registry.open(input)?;
+    let mut images = handler.extract_images()?;
+    let metadata = handler.get_metadata()?;
+    if let Some(path) = metadata.cover_image_path.as_deref()
+        && let Some(position) = images.iter().position(|image| image.name == path)
+    {
+        return Ok(images.swap_remove(position));
+    }
+    if !from_first_page {
+        return Err(no_cover());
+    }
+    images.sort_by(|a, b| ebook_cli::utils::natural_cmp(&a.name, &b.name));
+    images.into_iter().next().ok_or_else(no_cover)
+}
+
+fn handle_thumbnail(input: PathBuf, output: PathBuf, width: u32, cover_from_first_page: bool) -> Result<()> {
+    let cover = book_cover(&input, cover_from_first_page)?;
+    let image = image::load_from_memory(&cover.data).map_err(|e| EbookError::ImageError(e.to_string()))?;
+    let image = if image.width() > width {
+        image.resize(width, u32::MAX, image::imageops::FilterType::Lanczos3)
+    } else {
+        image
+    };
+    // JPEG has no alpha channel, and rendered pages come out as RGBA
+    let is_jpeg = output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"));
+    let image = if is_jpeg { image::DynamicImage::from(image.to_rgb8()) } else { image };
+    image.save(&output).map_err(|e| EbookError::ImageError(e.to_string()))?;
+    println!("Wrote {}x{} thumbnail to {:?}", image.width(), image.height(), output);
+    Ok(())
+}
+
+/// Print TOC entries, indenting children by nesting depth
+fn print_toc(entries: &[ebook_cli::traits::TocEntry]) {
+    for (depth, entry) in ebook_cli::traits::flatten_toc(entries) {
+        println!("{}{}", "  ".repeat(depth), entry.title);
+    }
+}
+
+/// Print extracted content, truncated to the first `head` characters if given
+fn print_content(content: &str, head: Option<usize>) {
+    match head {
+        Some(n) => println!("{}", content.chars().take(n).collect::<String>()),
+        None => println!("{}", content),
+    }
+}
+
+fn chapter_not_found(chapter: usize) -> EbookError {
+    EbookError::NotFound(format!("Chapter {chapter} not found"))
+}
+
+/// Format-specific settings of `write`
+#[derive(Debug, Clone, Copy)]
+struct WriteSettings {
+    /// Schema of a CBZ's ComicInfo.xml, `None` to leave it out
+    comic_info: Option<ComicInfoVersion>,
+    output_encoding: TextEncoding,
+}
+
+fn handle_write(
+    output: PathBuf,
+    title: Option<String>,
+    author: Vec<String>,
+    content_file: Option<PathBuf>,
+    format: String,
+    settings: WriteSettings,
+    show_progress: bool,
+) -> Result<()> {
+    let content = if let Some(path) = content_file {
+        if show_progress {
+            eprint!("Reading content from file...");
         }
-        _ => return Err(EbookError::UnsupportedFormat(format)),
+        let c = std::fs::read_to_string(path)?;
+        if show_progress {
+            eprintln!(" Done.");
+        }
+        c
+    } else {
+        String::new()
+    };
+
+    let mut metadata = ebook_cli::Metadata::new();
+    if let Some(t) = title {
+        metadata.title = Some(t);
+    }
+    if !author.is_empty() {
+        metadata = metadata.with_authors(author);
     }
 
+    if show_progress {
+        eprint!("Writing {} ebook...", format);
+    }
+
+    let mut handler: Box<dyn EbookOperator> = if format == "cbz" {
+        let mut cbz = CbzHandler::new();
+        match settings.comic_info {
+            Some(version) => cbz.set_comic_info_version(version),
+            None => cbz.set_write_comic_info(false),
+        }
+        Box::new(cbz)
+    } else if format == "txt" {
+        let mut txt = TxtHandler::new();
+        txt.set_output_encoding(settings.output_encoding);
+        Box::new(txt)
+    } else {
+        FormatRegistry::default().create(&format)?
+    };
+    handler.set_metadata(metadata)?;
+    handler.set_content(&content)?;
+    handler.write_to_file(&output)?;
+
     if show_progress {
         eprintln!(" Done.");
     }
@@ -360,132 +1138,186 @@ fn handle_write(
     Ok(())
 }
 
-fn handle_convert(input: PathBuf, output: PathBuf, target_format: Option<String>, show_progress: bool) -> Result<()> {
-    let source_format = ebook_cli::utils::detect_format(&input)?;
+fn conversion_options(
+    include_metadata_header: bool,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    author_separator: Option<String>,
+) -> ConversionOptions {
+    use ebook_cli::image_optimizer::OptimizationOptions;
+
+    let mut options = ConversionOptions::new().with_metadata_header(include_metadata_header);
+    if max_width.is_some() || max_height.is_some() {
+        let defaults = OptimizationOptions::default();
+        options = options.with_image_optimization(defaults.with_max_dimensions(
+            max_width.or(defaults.max_width).unwrap_or(u32::MAX),
+            max_height.or(defaults.max_height).unwrap_or(u32::MAX),
+        ));
+    }
+    if let Some(separator) = author_separator {
+        options = options.with_author_separator(separator);
+    }
+    options
+}
+
+/// Print the capability matrix as an aligned grid, or as JSON
+fn print_formats(json: bool) {
+    let matrix = ebook_cli::registry::capability_matrix();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&matrix).unwrap());
+        return;
+    }
+
+    let mark = |supported: bool| if supported { "yes" } else { "no" }.to_string();
+    let list = |formats: &[&str]| if formats.is_empty() { "-".to_string() } else { formats.join(", ") };
+    let header = ["Format", "Read", "Write", "Extract images", "Optimize", "Convert from", "Convert to"].map(String::from);
+    let rows: Vec<[String; 7]> = matrix
+        .iter()
+        .map(|row| {
+            [
+                row.format.to_string(),
+                mark(row.read),
+                mark(row.write),
+                mark(row.extract_images),
+                mark(row.optimize),
+                list(&row.convert_from),
+                list(&row.convert_to),
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| rows.iter().chain([&header]).map(|row| row[column].len()).max().unwrap_or(0))
+        .collect();
+    for row in [&header].into_iter().chain(&rows) {
+        let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, width)| format!("{cell:<width$}")).collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+    println!();
+    println!("Write means building a book from text content with `ebook write`.");
+}
+
+fn print_conversions() {
+    let mut sources: Vec<&str> = ebook_cli::SUPPORTED_CONVERSIONS.iter().map(|(source, _)| *source).collect();
+    sources.sort_unstable();
+    sources.dedup();
+    for source in sources {
+        println!("{} -> {}", source, Converter::supported_targets(source).join(", "));
+    }
+}
+
+fn handle_convert(
+    input: PathBuf,
+    output: PathBuf,
+    target_format: Option<String>,
+    show_progress: bool,
+    options: ConversionOptions,
+    cache: Option<ConversionCache>,
+) -> Result<()> {
+    let source_format = match &options.source_format {
+        Some(format) => format.clone(),
+        None => ebook_cli::utils::detect_format(&input)?,
+    };
     let target = target_format.unwrap_or_else(|| {
         ebook_cli::utils::detect_format(&output).unwrap_or_else(|_| "txt".to_string())
     });
 
     println!("Converting from {} to {}", source_format, target);
 
-    if show_progress {
-        let progress_name = format!("Converting {} to {}", source_format, target);
-        Converter::convert_with_progress(&input, &output, &target, Some(progress_name))?;
+    let progress_name = show_progress.then(|| format!("Converting {} to {}", source_format, target));
+    let cached = match &cache {
+        Some(cache) => cache.convert(&input, &output, &target, &options, progress_name)?,
+        None => {
+            Converter::convert_with_options(&input, &output, &target, &options, progress_name)?;
+            false
+        }
+    };
+
+    if cached {
+        println!("Copied cached conversion to {:?}", output);
     } else {
-        Converter::convert(&input, &output, &target)?;
+        println!("Successfully converted to {:?}", output);
     }
-
-    println!("Successfully converted to {:?}", output);
     Ok(())
 }
 
-fn handle_info(input: PathBuf) -> Result<()> {
-    let format = ebook_cli::utils::detect_format(&input)?;
-    
-    println!("File: {:?}", input);
-    println!("Format: {}", format);
-    
-    match format.as_str() {
+/// Files inside an EPUB or CBZ; `None` for formats that aren't zip containers
+fn archive_entries(input: &Path, format: &str) -> Result<Option<Vec<ArchiveEntry>>> {
+    match format {
         "epub" => {
             let mut handler = EpubHandler::new();
-            handler.read_from_file(&input)?;
-            let metadata = handler.get_metadata()?;
-            println!("\nMetadata:");
-            println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-        }
-        "mobi" => {
-            let mut handler = MobiHandler::new();
-            handler.read_from_file(&input)?;
-            let metadata = handler.get_metadata()?;
-            println!("\nMetadata:");
-            println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-        }
-        "azw" | "azw3" => {
-            let mut handler = AzwHandler::new();
-            handler.read_from_file(&input)?;
-            let metadata = handler.get_metadata()?;
-            println!("\nMetadata:");
-            println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-        }
-        "fb2" => {
-            let mut handler = Fb2Handler::new();
-            handler.read_from_file(&input)?;
-            let metadata = handler.get_metadata()?;
-            println!("\nMetadata:");
-            println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
+            handler.read_from_file(input)?;
+            handler.list_entries().map(Some)
         }
         "cbz" => {
             let mut handler = CbzHandler::new();
-            handler.read_from_file(&input)?;
-            let metadata = handler.get_metadata()?;
-            let images = handler.extract_images()?;
-            println!("\nMetadata:");
-            println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-            println!("\nImages: {}", images.len());
+            handler.read_from_file(input)?;
+            handler.list_entries().map(Some)
         }
-        "txt" => {
-            let mut handler = TxtHandler::new();
-            handler.read_from_file(&input)?;
-            let metadata = handler.get_metadata()?;
-            let content = handler.get_content()?;
-            println!("\nMetadata:");
-            println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
-            println!("\nSize: {} characters", content.len());
+        _ => Ok(None),
+    }
+}
+
+fn handle_info(input: PathBuf, format: Option<String>, verbose: bool, json: bool) -> Result<()> {
+    let registry = FormatRegistry::default();
+    let format = match format {
+        Some(format) => format,
+        None => registry.format_for_path(&input)?,
+    };
+    
+    let handler = registry.open_as(&input, &format)?;
+    let metadata = handler.get_metadata()?;
+    let content = handler.get_content()?;
+    let images = handler.extract_images()?;
+    let entries = if verbose || json { archive_entries(&input, &format)? } else { None };
+
+    if json {
+        let mut info = serde_json::json!({
+            "file": input,
+            "format": format,
+            "metadata": metadata,
+            "images": images.len(),
+            "characters": content.len(),
+        });
+        if let Some(entries) = entries {
+            info["entries"] = serde_json::json!(entries);
         }
-        "pdf" => {
-            let mut handler = PdfHandler::new();
-            handler.read_from_file(&input)?;
-            let metadata = handler.get_metadata()?;
-            println!("\nMetadata:");
-            println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
+        println!("{}", serde_json::to_string_pretty(&info).unwrap());
+        return Ok(());
+    }
+
+    println!("File: {:?}", input);
+    println!("Format: {}", format);
+    println!("\nMetadata:");
+    println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
+    if !images.is_empty() {
+        println!("\nImages: {}", images.len());
+    }
+    println!("\nSize: {} characters", content.len());
+
+    if let Some(entries) = entries {
+        println!("\nEntries: {}", entries.len());
+        println!("  {:>10}  {:>10}  Name", "Size", "Packed");
+        for entry in &entries {
+            println!(
+                "  {:>10}  {:>10}  {}{}",
+                ebook_cli::utils::format_size(entry.uncompressed_size),
+                ebook_cli::utils::format_size(entry.compressed_size),
+                entry.name,
+                if entry.is_image { " (image)" } else { "" }
+            );
         }
-        _ => return Err(EbookError::UnsupportedFormat(format)),
     }
     
     Ok(())
 }
 
-fn handle_validate(input: PathBuf) -> Result<()> {
-    let format = ebook_cli::utils::detect_format(&input)?;
-    
-    let is_valid = match format.as_str() {
-        "epub" => {
-            let mut handler = EpubHandler::new();
-            handler.read_from_file(&input)?;
-            handler.validate()?
-        }
-        "mobi" => {
-            let mut handler = MobiHandler::new();
-            handler.read_from_file(&input)?;
-            handler.validate()?
-        }
-        "azw" | "azw3" => {
-            let mut handler = AzwHandler::new();
-            handler.read_from_file(&input)?;
-            handler.validate()?
-        }
-        "fb2" => {
-            let mut handler = Fb2Handler::new();
-            handler.read_from_file(&input)?;
-            handler.validate()?
-        }
-        "cbz" => {
-            let mut handler = CbzHandler::new();
-            handler.read_from_file(&input)?;
-            handler.validate()?
-        }
-        "txt" => {
-            let mut handler = TxtHandler::new();
-            handler.read_from_file(&input)?;
-            handler.validate()?
-        }
-        "pdf" => {
-            let mut handler = PdfHandler::new();
-            handler.read_from_file(&input)?;
-            handler.validate()?
-        }
-        _ => return Err(EbookError::UnsupportedFormat(format)),
-    };
+fn handle_validate(input: PathBuf, strict: bool) -> Result<()> {
+    if strict {
+        return handle_validate_strict(&input);
+    }
+
+    let handler = FormatRegistry::default().open(&input)?;
+    let is_valid = handler.validate()?;
     
     if is_valid {
         println!("✓ File is valid");
@@ -496,115 +1328,68 @@ fn handle_validate(input: PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Print every finding of the strict EPUB checks; any error fails the command
+fn handle_validate_strict(input: &Path) -> Result<()> {
+    if FormatRegistry::default().format_for_path(input)? != "epub" {
+        return Err(EbookError::NotSupported("--strict validation is only available for EPUB files".to_string()));
+    }
+
+    let report = EpubHandler::validate_detailed(input)?;
+    for issue in &report.issues {
+        println!("{issue}");
+    }
+
+    let errors = report.errors().count();
+    let warnings = report.warnings().count();
+    if errors > 0 {
+        return Err(EbookError::ValidationError(format!("{errors} error(s) and {warnings} warning(s) found")));
+    }
+    if warnings > 0 {
+        println!("✓ File is valid ({warnings} warning(s))");
+    } else {
+        println!("✓ File is valid");
+    }
+    Ok(())
+}
+
 fn handle_repair(input: PathBuf, output: Option<PathBuf>, show_progress: bool) -> Result<()> {
-    let format = ebook_cli::utils::detect_format(&input)?;
+    let registry = FormatRegistry::default();
+    let format = registry.format_for_path(&input)?;
     let output_path = output.unwrap_or_else(|| input.clone());
 
     if show_progress {
         eprint!("Reading {} file...", format);
     }
 
-    match format.as_str() {
-        "epub" => {
-            let mut handler = EpubHandler::new();
-            handler.read_from_file(&input)?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Repairing...");
-            }
-            handler.repair()?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Writing...");
-            }
-            handler.write_to_file(&output_path)?;
-        }
-        "mobi" => {
-            let mut handler = MobiHandler::new();
-            handler.read_from_file(&input)?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Repairing...");
-            }
-            handler.repair()?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Writing...");
-            }
-            handler.write_to_file(&output_path)?;
-        }
-        "azw" | "azw3" => {
-            let mut handler = AzwHandler::new();
-            handler.read_from_file(&input)?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Repairing...");
-            }
-            handler.repair()?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Writing...");
-            }
-            handler.write_to_file(&output_path)?;
-        }
-        "fb2" => {
-            let mut handler = Fb2Handler::new();
-            handler.read_from_file(&input)?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Repairing...");
-            }
-            handler.repair()?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Writing...");
-            }
-            handler.write_to_file(&output_path)?;
-        }
-        "cbz" => {
-            let mut handler = CbzHandler::new();
-            handler.read_from_file(&input)?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Repairing...");
-            }
-            handler.repair()?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Writing...");
-            }
-            handler.write_to_file(&output_path)?;
-        }
-        "txt" => {
-            let mut handler = TxtHandler::new();
-            handler.read_from_file(&input)?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Repairing...");
-            }
-            handler.repair()?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Writing...");
+    let mut handler = match registry.open(&input) {
+        Ok(handler) => handler,
+        Err(EbookError::Zip(e)) => {
+            // Damaged central directory: rebuild the archive from its local entries and retry
+            let report = ebook_cli::salvage::salvage_zip(&std::fs::read(&input)?);
+            if report.entries.is_empty() {
+                return Err(EbookError::Zip(e));
             }
-            handler.write_to_file(&output_path)?;
+            let expected = report.expected.map_or("an unknown number of".to_string(), |n| n.to_string());
+            eprintln!("Archive index unreadable ({e}); recovered {} of {} entries", report.entries.len(), expected);
+            // Only replace the target (by default the input) once the rebuilt archive reads back
+            let salvaged = ebook_cli::traits::TempFile::beside(&output_path);
+            report.write_archive(salvaged.path())?;
+            let handler = registry.open_as(salvaged.path(), &format)?;
+            salvaged.persist(&output_path)?;
+            handler
         }
-        "pdf" => {
-            let mut handler = PdfHandler::new();
-            handler.read_from_file(&input)?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Repairing...");
-            }
-            handler.repair()?;
-            if show_progress {
-                eprintln!(" Done.");
-                eprint!("Writing...");
-            }
-            handler.write_to_file(&output_path)?;
-        }
-        _ => return Err(EbookError::UnsupportedFormat(format)),
+        Err(e) => return Err(e),
+    };
+    if show_progress {
+        eprintln!(" Done.");
+        eprint!("Repairing...");
+    }
+    handler.repair()?;
+    if show_progress {
+        eprintln!(" Done.");
+        eprint!("Writing...");
     }
+    handler.write_to_file(&output_path)?;
 
     if show_progress {
         eprintln!(" Done.");
@@ -614,23 +1399,15 @@ fn handle_repair(input: PathBuf, output: Option<PathBuf>, show_progress: bool) -
     Ok(())
 }
 
-fn handle_optimize(
-    input: PathBuf,
-    output: Option<PathBuf>,
+fn optimization_options(
     max_width: u32,
     max_height: u32,
     quality: u8,
     no_resize: bool,
-    show_progress: bool,
-) -> Result<()> {
+    image_format: Option<String>,
+) -> Result<ebook_cli::image_optimizer::OptimizationOptions> {
     use ebook_cli::image_optimizer::OptimizationOptions;
-    
-    let format = ebook_cli::utils::detect_format(&input)?;
-    let output_path = output.unwrap_or_else(|| input.clone());
-
-    if show_progress {
-        eprint!("Reading {}...", input.display());
-    }
+    use image::ImageFormat;
 
     let mut options = OptimizationOptions::default()
         .with_quality(quality);
@@ -641,17 +1418,235 @@ fn handle_optimize(
         options = options.with_max_dimensions(max_width, max_height);
     }
 
+    if let Some(name) = image_format {
+        let format = ImageFormat::from_extension(name.to_lowercase())
+            .filter(|f| matches!(f, ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP))
+            .ok_or_else(|| EbookError::UnsupportedFormat(format!("Image format '{name}' (use jpeg, png or webp)")))?;
+        options = options.with_convert_to(format);
+    }
+
+    Ok(options)
+}
+
+/// Parse a format name given to `--from`, `--to` or `--as`
+fn parse_format_name(value: &str) -> std::result::Result<String, String> {
+    ebook_cli::utils::format_name(value)
+        .map(String::from)
+        .ok_or_else(|| format!("unknown format '{value}' (use epub, mobi, azw, fb2, cbz, txt or pdf)"))
+}
+
+/// Parse a `START:COUNT` line window with a 1-based start
+fn parse_line_range(value: &str) -> std::result::Result<(usize, usize), String> {
+    let (start, count) = value
+        .split_once(':')
+        .ok_or_else(|| format!("invalid range '{value}' (expected START:COUNT)"))?;
+    let start: usize = start.trim().parse().map_err(|_| format!("invalid start line '{start}'"))?;
+    let count: usize = count.trim().parse().map_err(|_| format!("invalid line count '{count}'"))?;
+    if start == 0 {
+        return Err("line numbers start at 1".to_string());
+    }
+    Ok((start, count))
+}
+
+/// Check a chapter title template by rendering it once
+fn parse_chapter_title_template(value: &str) -> std::result::Result<String, String> {
+    ebook_cli::utils::render_chapter_title(value, 1)
+        .map(|_| value.to_string())
+        .map_err(|e| e.to_string().lines().next().unwrap_or_default().to_string())
+}
+
+/// Parse a `--sample` size, which must be at least one chapter or page
+fn parse_sample_size(value: &str) -> std::result::Result<usize, String> {
+    match value.trim().parse() {
+        Ok(0) => Err("a sample needs at least one chapter".to_string()),
+        Ok(n) => Ok(n),
+        Err(_) => Err(format!("invalid sample size '{value}'")),
+    }
+}
+
+/// How `optimize` rewrites the book
+#[derive(Debug, Clone, Default)]
+struct OptimizeMode {
+    /// Replace EPUB image entries in place instead of rebuilding the archive
+    preserve_structure: bool,
+    /// Rewrite even a book with no images
+    force: bool,
+    /// Recompress the EPUB's zip packaging after the images
+    recompress: bool,
+    /// Collapse (EPUB) or report (CBZ) images with identical bytes
+    dedupe: bool,
+    /// Remove repeated CBZ pages found by `dedupe`
+    drop_duplicate_pages: bool,
+    /// Where to write the JSON optimization report
+    report: Option<PathBuf>,
+}
+
+/// Print the duplicates `--dedupe` found and the bytes they take up
+fn report_duplicates(found: &ebook_cli::image_optimizer::DuplicateImages, removed: bool) {
+    if found.is_empty() {
+        println!("No duplicate images found");
+        return;
+    }
+    for (duplicate, original) in &found.duplicates {
+        println!("  {duplicate} duplicates {original}");
+    }
+    let size = ebook_cli::utils::format_size(found.bytes);
+    if removed {
+        println!("Dedupe removed {} duplicate image(s), saving {size}", found.duplicates.len());
+    } else {
+        println!(
+            "Found {} duplicate page(s) taking {size} (use --drop-duplicate-pages to remove them)",
+            found.duplicates.len()
+        );
+    }
+}
+
+/// Print how much `original` bytes shrank by, e.g. "Saved 1.2 MB of 3.4 MB (35.3% reduction)"
+fn report_savings(savings: u64, original: u64) {
+    println!(
+        "Saved {} of {} ({:.1}% reduction)",
+        ebook_cli::utils::format_size(savings),
+        ebook_cli::utils::format_size(original),
+        ebook_cli::utils::percent_reduction(original, original.saturating_sub(savings))
+    );
+}
+
+/// Recompress the EPUB written to `output` and print what the packaging saved, apart from the
+/// `image_savings` already reported, against the `input_size` the source file had
+fn recompress_epub(output: &Path, input_size: u64, image_savings: u64, show_progress: bool) -> Result<()> {
+    if show_progress {
+        eprint!("Recompressing archive...");
+    }
+    EpubHandler::recompress_archive(output, output)?;
+    if show_progress {
+        eprintln!(" Done.");
+    }
+    let output_size = std::fs::metadata(output)?.len();
+    let packaging = input_size.saturating_sub(image_savings).saturating_sub(output_size);
+    println!(
+        "Packaging: saved {} by recompressing the archive ({} now)",
+        ebook_cli::utils::format_size(packaging),
+        ebook_cli::utils::format_size(output_size)
+    );
+    Ok(())
+}
+
+/// Images of the EPUB at `path`, in manifest order
+fn read_images(path: &Path) -> Result<Vec<ebook_cli::traits::ImageData>> {
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(path)?;
+    handler.extract_images()
+}
+
+/// Write the `--report` JSON comparing each image `before` optimization with itself `after`
+fn write_optimization_report(
+    path: &Path,
+    before: &[ebook_cli::traits::ImageData],
+    after: &[ebook_cli::traits::ImageData],
+    options: &ebook_cli::image_optimizer::OptimizationOptions,
+) -> Result<()> {
+    let report = ebook_cli::image_optimizer::OptimizationReport::compare(before, after, options);
+    std::fs::write(path, serde_json::to_string_pretty(&report).unwrap() + "\n")?;
+    Ok(())
+}
+
+fn report_nothing_to_optimize(input: &Path) {
+    println!("Nothing to optimize: {} has no images; no file written (use --force to rewrite it anyway)", input.display());
+}
+
+fn handle_optimize(
+    input: PathBuf,
+    output: Option<PathBuf>,
+    options: ebook_cli::image_optimizer::OptimizationOptions,
+    size_target: Option<(u64, u8)>,
+    mode: OptimizeMode,
+    show_progress: bool,
+) -> Result<()> {
+    let format = ebook_cli::utils::detect_format(&input)?;
+    let output_path = output.unwrap_or_else(|| input.clone());
+    let OptimizeMode { preserve_structure, force, recompress, dedupe, drop_duplicate_pages, report } = mode;
+
+    if preserve_structure && format != "epub" {
+        return Err(EbookError::NotSupported("--preserve-structure is only supported for EPUB".to_string()));
+    }
+
+    if recompress && format != "epub" {
+        return Err(EbookError::NotSupported("--recompress is only supported for EPUB".to_string()));
+    }
+    let input_size = std::fs::metadata(&input)?.len();
+
+    if format == "epub" && size_target.is_some() {
+        return Err(EbookError::NotSupported("--max-filesize is only supported for CBZ".to_string()));
+    }
+
+    if preserve_structure && options.convert_to.is_some() {
+        return Err(EbookError::NotSupported(
+            "--image-format cannot be combined with --preserve-structure, which keeps every file name".to_string()
+        ));
+    }
+
+    if preserve_structure && dedupe {
+        return Err(EbookError::NotSupported(
+            "--dedupe cannot be combined with --preserve-structure, which keeps every file".to_string()
+        ));
+    }
+
+    if show_progress {
+        eprint!("Reading {}...", input.display());
+    }
+
     match format.as_str() {
+        "epub" if preserve_structure => {
+            if show_progress {
+                eprintln!(" Done.");
+            }
+            if !force && !recompress && EpubHandler::count_archive_images(&input)? == 0 {
+                report_nothing_to_optimize(&input);
+                return Ok(());
+            }
+            if show_progress {
+                eprint!("Optimizing images in place...");
+            }
+
+            let original = EpubHandler::archive_image_bytes(&input)?;
+            let before = report.is_some().then(|| read_images(&input)).transpose()?;
+            let savings = EpubHandler::optimize_preserving_structure(&input, &output_path, options)?;
+            if let (Some(path), Some(before)) = (&report, before) {
+                write_optimization_report(path, &before, &read_images(&output_path)?, &options)?;
+            }
+
+            if show_progress {
+                eprintln!(" Done.");
+            }
+
+            println!("Successfully optimized EPUB");
+            report_savings(savings as u64, original);
+            if recompress {
+                recompress_epub(&output_path, input_size, savings as u64, show_progress)?;
+            }
+        }
         "epub" => {
             let mut handler = EpubHandler::new();
             handler.read_from_file(&input)?;
             
             if show_progress {
                 eprintln!(" Done.");
+            }
+            if !force && !recompress && handler.image_count() == 0 {
+                report_nothing_to_optimize(&input);
+                return Ok(());
+            }
+            if show_progress {
                 eprint!("Optimizing images...");
             }
             
+            let original = handler.image_bytes();
+            let before = report.is_some().then(|| handler.extract_images()).transpose()?;
             let savings = handler.optimize_images(options)?;
+            if let (Some(path), Some(before)) = (&report, before) {
+                write_optimization_report(path, &before, &handler.extract_images()?, &options)?;
+            }
+            let duplicates = dedupe.then(|| handler.dedupe_images());
             
             if show_progress {
                 eprintln!(" Done.");
@@ -665,10 +1660,13 @@ fn handle_optimize(
             }
             
             println!("Successfully optimized EPUB");
-            println!("Saved {} bytes ({:.1}% reduction)", 
-                savings, 
-                if savings > 0 { (savings as f64 / 1024.0 / 1024.0) } else { 0.0 }
-            );
+            report_savings(savings as u64, original);
+            if let Some(duplicates) = duplicates {
+                report_duplicates(&duplicates, true);
+            }
+            if recompress {
+                recompress_epub(&output_path, input_size, savings as u64, show_progress)?;
+            }
         }
         "cbz" => {
             let mut handler = CbzHandler::new();
@@ -676,13 +1674,53 @@ fn handle_optimize(
             
             if show_progress {
                 eprintln!(" Done.");
+            }
+            if !force && handler.image_count() == 0 {
+                report_nothing_to_optimize(&input);
+                return Ok(());
+            }
+            if show_progress {
                 eprint!("Optimizing images...");
             }
             
-            let savings = handler.optimize_images(options)?;
+            let before = report.is_some().then(|| handler.extract_images()).transpose()?;
+            // The size target is for the whole archive, so report against the file size then
+            let (savings, original) = match size_target {
+                Some((max_bytes, min_quality)) => {
+                    let original_size = std::fs::metadata(&input)?.len();
+                    let result = handler.optimize_to_size(options, max_bytes, min_quality)?;
+                    if show_progress {
+                        eprintln!(" Done.");
+                    }
+                    if result.reached {
+                        println!("Settled on JPEG quality {} ({} bytes, target {} bytes)", result.quality, result.size, max_bytes);
+                    } else {
+                        println!(
+                            "Could not reach target of {} bytes; best effort at minimum quality {} is {} bytes",
+                            max_bytes, result.quality, result.size
+                        );
+                    }
+                    (original_size.saturating_sub(result.size), original_size)
+                }
+                None => {
+                    let original = handler.image_bytes();
+                    (handler.optimize_images(options)? as u64, original)
+                }
+            };
+            if let (Some(path), Some(before)) = (&report, before) {
+                write_optimization_report(path, &before, &handler.extract_images()?, &options)?;
+            }
             
+            let duplicates = match (dedupe, drop_duplicate_pages) {
+                (true, true) => Some(handler.remove_duplicate_pages()),
+                (true, false) => Some(handler.find_duplicate_pages()),
+                _ => None,
+            };
+
             if show_progress {
-                eprintln!(" Done.");
+                if size_target.is_none() {
+                    eprintln!(" Done.");
+                }
                 eprint!("Writing optimized CBZ...");
             }
             
@@ -693,10 +1731,10 @@ fn handle_optimize(
             }
             
             println!("Successfully optimized CBZ");
-            println!("Saved {} bytes ({:.1} MB reduction)", 
-                savings,
-                savings as f64 / 1024.0 / 1024.0
-            );
+            report_savings(savings, original);
+            if let Some(duplicates) = duplicates {
+                report_duplicates(&duplicates, drop_duplicate_pages);
+            }
         }
         _ => {
             return Err(EbookError::UnsupportedFormat(
@@ -709,11 +1747,11 @@ fn handle_optimize(
     Ok(())
 }
 
-async fn handle_mcp() -> Result<()> {
+async fn handle_mcp(tool_timeout: std::time::Duration) -> Result<()> {
     use ebook_cli::mcp::McpServer;
     
     eprintln!("Starting MCP server...");
-    let server = McpServer::new();
+    let server = McpServer::new().with_tool_timeout(tool_timeout);
     server.run().await.map_err(|e| EbookError::Parse(e.to_string()))?;
     
     Ok(())
@@ -1,5 +1,5 @@
 pub mod server;
 pub mod types;
 
-pub use server::McpServer;
+pub use server::{McpServer, DEFAULT_BATCH_CONCURRENCY, DEFAULT_TOOL_TIMEOUT, TOOL_TIMEOUT_ERROR};
 pub use types::*;
@@ -1,36 +1,99 @@
 use crate::mcp::types::*;
 use crate::formats::{AzwHandler, CbzHandler, EpubHandler, Fb2Handler, MobiHandler, PdfHandler, TxtHandler};
 use crate::traits::{EbookReader, EbookWriter, EbookOperator};
-use crate::{Metadata, Converter};
+use crate::{ErrorCategory, Metadata, Converter, FormatRegistry, SUPPORTED_CONVERSIONS};
 use serde_json::json;
-use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
-pub struct McpServer;
+/// How long a `tools/call` may run before the server answers with a timeout error
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// JSON-RPC error code for a tool call that ran past the timeout (server-defined range)
+pub const TOOL_TIMEOUT_ERROR: i32 = -32001;
+
+/// Most `convert_batch` jobs that run at once when the call doesn't say
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+
+/// Supported conversions as "source -> target" pairs for tool descriptions
+fn conversion_list() -> String {
+    SUPPORTED_CONVERSIONS
+        .iter()
+        .map(|(source, target)| format!("{source} -> {target}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Convert one file, as `convert_ebook` and each `convert_batch` job do
+fn convert_file(job: &ConvertJob) -> Result<(), ToolError> {
+    Converter::convert(&PathBuf::from(&job.input_path), &PathBuf::from(&job.output_path), &job.target_format)
+        .map_err(|e| ToolError::ebook("Conversion failed", e))
+}
+
+/// Number of chapters (pages for PDF) `get_chapter` can return, or `None` if the format
+/// can't select single chapters
+fn count_chapters(handler: &dyn EbookOperator) -> Option<usize> {
+    let mut count = 0;
+    loop {
+        match handler.get_chapter(count) {
+            Ok(Some(_)) => count += 1,
+            Ok(None) => return Some(count),
+            Err(_) => return None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct McpServer {
+    registry: Arc<FormatRegistry>,
+    tool_timeout: Duration,
+}
 
 impl McpServer {
     pub fn new() -> Self {
-        Self
+        Self::with_registry(FormatRegistry::default())
+    }
+
+    /// Create a server that dispatches formats through a custom registry
+    pub fn with_registry(registry: FormatRegistry) -> Self {
+        Self { registry: Arc::new(registry), tool_timeout: DEFAULT_TOOL_TIMEOUT }
+    }
+
+    /// Answer a `tools/call` that runs longer than `timeout` with a `TOOL_TIMEOUT_ERROR`
+    pub fn with_tool_timeout(mut self, timeout: Duration) -> Self {
+        self.tool_timeout = timeout;
+        self
+    }
+
+    /// Look up the handler for a file's format and read the file into it
+    fn open_ebook(&self, path: &std::path::Path) -> Result<Box<dyn EbookOperator>, ToolError> {
+        let format = self.registry.format_for_path(path)
+            .map_err(|e| ToolError::ebook("Failed to detect format", e))?;
+        let mut handler = self.registry.create(&format)
+            .map_err(|e| ToolError::ebook("Unsupported format", e))?;
+        handler.read_from_file(path)
+            .map_err(|e| ToolError::ebook(&format!("Failed to read {}", format.to_uppercase()), e))?;
+        Ok(handler)
     }
 
     pub async fn run(&self) -> anyhow::Result<()> {
-        let stdin = std::io::stdin();
-        let mut reader = BufReader::new(stdin);
-        let mut stdout = std::io::stdout();
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
 
         loop {
-            let mut line = String::new();
-            match reader.read_line(&mut line) {
-                Ok(0) => break,
-                Ok(_) => {
+            match lines.next_line().await {
+                Ok(None) => break,
+                Ok(Some(line)) => {
                     if line.trim().is_empty() {
                         continue;
                     }
 
                     let response = self.handle_request(&line).await;
                     let response_str = serde_json::to_string(&response)?;
-                    writeln!(stdout, "{response_str}")?;
-                    stdout.flush()?;
+                    stdout.write_all(format!("{response_str}\n").as_bytes()).await?;
+                    stdout.flush().await?;
                 }
                 Err(e) => {
                     eprintln!("Error reading input: {e}");
@@ -42,7 +105,8 @@ impl McpServer {
         Ok(())
     }
 
-    async fn handle_request(&self, request_str: &str) -> JsonRpcResponse {
+    /// Answer one JSON-RPC request line, as `run` does for each line of stdin
+    pub async fn handle_request(&self, request_str: &str) -> JsonRpcResponse {
         let request: JsonRpcRequest = match serde_json::from_str(request_str) {
             Ok(req) => req,
             Err(e) => {
@@ -147,6 +211,11 @@ impl McpServer {
                             "type": "string",
                             "description": "Author of the ebook"
                         },
+                        "authors": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "All authors of the ebook, in order (overrides 'author')"
+                        },
                         "content": {
                             "type": "string",
                             "description": "Text content of the ebook"
@@ -183,15 +252,41 @@ impl McpServer {
                     "required": ["path"]
                 }),
             },
+            Tool {
+                name: "peek_ebook".to_string(),
+                description: "Cheap preview of an ebook: format, basic metadata and the first characters of its text (TXT is read only as far as needed)".to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "Path to the ebook file"
+                        },
+                        "chars": {
+                            "type": "integer",
+                            "description": "Number of characters of text to return",
+                            "default": 500,
+                            "minimum": 0
+                        }
+                    },
+                    "required": ["path"]
+                }),
+            },
             Tool {
                 name: "get_ebook_info".to_string(),
-                description: "Get detailed information about an ebook file (supports: epub, pdf, txt, mobi, fb2, azw, cbz)".to_string(),
+                description: "Get information about an ebook file as {path, format, metadata, stats: {size_chars, page_count, image_count, chapter_count}, summary}; stats that don't apply to the format are null (supports: epub, pdf, txt, mobi, fb2, azw, cbz)".to_string(),
                 input_schema: json!({
                     "type": "object",
                     "properties": {
                         "path": {
                             "type": "string",
                             "description": "Path to the ebook file"
+                        },
+                        "output_format": {
+                            "type": "string",
+                            "enum": ["json", "text"],
+                            "description": "\"json\" for the structured object, \"text\" for just the human-readable summary",
+                            "default": "json"
                         }
                     },
                     "required": ["path"]
@@ -213,13 +308,42 @@ impl McpServer {
                         },
                         "target_format": {
                             "type": "string",
-                            "description": "Target format (Converter supports: epub, mobi, fb2, pdf, txt)",
-                            "enum": ["epub", "mobi", "fb2", "pdf", "txt"]
+                            "description": format!("Target format (supported conversions: {})", conversion_list()),
+                            "enum": Converter::all_targets()
                         }
                     },
                     "required": ["input_path", "output_path", "target_format"]
                 }),
             },
+            Tool {
+                name: "convert_batch".to_string(),
+                description: "Convert several ebooks in one call, a few at a time, with a result per job".to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "jobs": {
+                            "type": "array",
+                            "description": "Conversions to run, each with the arguments convert_ebook takes",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "input_path": { "type": "string" },
+                                    "output_path": { "type": "string" },
+                                    "target_format": { "type": "string", "enum": Converter::all_targets() }
+                                },
+                                "required": ["input_path", "output_path", "target_format"]
+                            }
+                        },
+                        "concurrency": {
+                            "type": "integer",
+                            "description": "Most conversions to run at once",
+                            "default": DEFAULT_BATCH_CONCURRENCY,
+                            "minimum": 1
+                        }
+                    },
+                    "required": ["jobs"]
+                }),
+            },
             Tool {
                 name: "optimize_images".to_string(),
                 description: "Optimize images in EPUB or CBZ files by resizing and compressing them".to_string(),
@@ -255,6 +379,11 @@ impl McpServer {
                             "type": "boolean",
                             "description": "Skip resizing, only compress",
                             "default": false
+                        },
+                        "force": {
+                            "type": "boolean",
+                            "description": "Rewrite the file even when it has no images to optimize",
+                            "default": false
                         }
                     },
                     "required": ["input_path"]
@@ -305,15 +434,26 @@ impl McpServer {
             }
         };
 
-        let result = match params.name.as_str() {
-            "read_ebook" => self.tool_read_ebook(params.arguments).await,
-            "write_ebook" => self.tool_write_ebook(params.arguments).await,
-            "extract_images" => self.tool_extract_images(params.arguments).await,
-            "validate_ebook" => self.tool_validate_ebook(params.arguments).await,
-            "get_ebook_info" => self.tool_get_ebook_info(params.arguments).await,
-            "convert_ebook" => self.tool_convert_ebook(params.arguments).await,
-            "optimize_images" => self.tool_optimize_images(params.arguments).await,
-            _ => Err(format!("Unknown tool: {}", params.name)),
+        // Tools do blocking file and CPU work: run them off the async threads, and give up
+        // waiting after the timeout (the work itself can't be interrupted and finishes unseen)
+        let name = params.name.clone();
+        let server = self.clone();
+        let task = tokio::task::spawn_blocking(move || futures::executor::block_on(server.call_tool(params)));
+        let result = match tokio::time::timeout(self.tool_timeout, task).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(ToolError::new(ErrorCategory::Internal, "tool_failed", format!("Tool {name} failed: {e}"))),
+            Err(_) => {
+                return JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id,
+                    result: None,
+                    error: Some(JsonRpcError {
+                        code: TOOL_TIMEOUT_ERROR,
+                        message: format!("Tool {name} timed out after {:.1}s", self.tool_timeout.as_secs_f64()),
+                        data: Some(json!({ "tool": name, "timeout_ms": self.tool_timeout.as_millis() as u64 })),
+                    }),
+                };
+            }
         };
 
         match result {
@@ -328,23 +468,39 @@ impl McpServer {
                 id,
                 result: Some(serde_json::to_value(ToolResult {
                     content: vec![ToolContent::Text {
-                        text: format!("Error: {e}"),
+                        text: format!("Error: {}", e.message),
                     }],
                     is_error: Some(true),
+                    structured_content: Some(serde_json::to_value(&e).unwrap()),
                 }).unwrap()),
                 error: None,
             },
         }
     }
 
+    async fn call_tool(&self, params: CallToolParams) -> Result<ToolResult, ToolError> {
+        match params.name.as_str() {
+            "read_ebook" => self.tool_read_ebook(params.arguments).await,
+            "write_ebook" => self.tool_write_ebook(params.arguments).await,
+            "extract_images" => self.tool_extract_images(params.arguments).await,
+            "validate_ebook" => self.tool_validate_ebook(params.arguments).await,
+            "peek_ebook" => self.tool_peek_ebook(params.arguments).await,
+            "get_ebook_info" => self.tool_get_ebook_info(params.arguments).await,
+            "convert_ebook" => self.tool_convert_ebook(params.arguments).await,
+            "convert_batch" => self.tool_convert_batch(params.arguments).await,
+            "optimize_images" => self.tool_optimize_images(params.arguments).await,
+            _ => Err(ToolError::invalid_argument(format!("Unknown tool: {}", params.name))),
+        }
+    }
+
     async fn tool_read_ebook(
         &self,
         args: std::collections::HashMap<String, serde_json::Value>,
-    ) -> Result<ToolResult, String> {
+    ) -> Result<ToolResult, ToolError> {
         let path = args
             .get("path")
             .and_then(|v| v.as_str())
-            .ok_or("Missing 'path' argument")?;
+            .ok_or_else(|| ToolError::missing_argument("path"))?;
         let extract_metadata = args
             .get("extract_metadata")
             .and_then(|v| v.as_bool())
@@ -355,180 +511,40 @@ impl McpServer {
             .unwrap_or(false);
 
         let path_buf = PathBuf::from(path);
-        let format = crate::utils::detect_format(&path_buf)
-            .map_err(|e| format!("Failed to detect format: {e}"))?;
+        let handler = self.open_ebook(&path_buf)?;
 
-        let text = match format.as_str() {
-            "epub" => {
-                let mut handler = EpubHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read EPUB: {e}"))?;
-
-                if extract_metadata {
-                    let metadata = handler.get_metadata()
-                        .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                    serde_json::to_string_pretty(&metadata).unwrap()
-                } else if extract_toc {
-                    let toc = handler.get_toc()
-                        .map_err(|e| format!("Failed to get TOC: {e}"))?;
-                    format!("Table of Contents:\n{}", 
-                        toc.iter().map(|e| format!("{}{}", "  ".repeat(e.level - 1), e.title))
-                            .collect::<Vec<_>>().join("\n"))
-                } else {
-                    handler.get_content()
-                        .map_err(|e| format!("Failed to get content: {e}"))?
-                }
-            }
-            "cbz" => {
-                let mut handler = CbzHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read CBZ: {e}"))?;
-
-                if extract_metadata {
-                    let metadata = handler.get_metadata()
-                        .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                    serde_json::to_string_pretty(&metadata).unwrap()
-                } else if extract_toc {
-                    let toc = handler.get_toc()
-                        .map_err(|e| format!("Failed to get TOC: {e}"))?;
-                    format!(
-                        "Table of Contents:\n{}",
-                        toc.iter()
-                            .map(|e| format!("{}{}", "  ".repeat(e.level.saturating_sub(1)), e.title))
-                            .collect::<Vec<_>>()
-                            .join("\n")
-                    )
-                } else {
-                    handler.get_content()
-                        .map_err(|e| format!("Failed to get content: {e}"))?
-                }
-            }
-            "txt" => {
-                let mut handler = TxtHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read TXT: {e}"))?;
-
-                if extract_metadata {
-                    let metadata = handler.get_metadata()
-                        .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                    serde_json::to_string_pretty(&metadata).unwrap()
-                } else if extract_toc {
-                    let toc = handler.get_toc()
-                        .map_err(|e| format!("Failed to get TOC: {e}"))?;
-                    format!("Table of Contents:\n{}", 
-                        toc.iter().map(|e| e.title.clone()).collect::<Vec<_>>().join("\n"))
-                } else {
-                    handler.get_content()
-                        .map_err(|e| format!("Failed to get content: {e}"))?
-                }
-            }
-            "pdf" => {
-                let mut handler = PdfHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read PDF: {e}"))?;
-
-                if extract_metadata {
-                    let metadata = handler.get_metadata()
-                        .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                    serde_json::to_string_pretty(&metadata).unwrap()
-                } else {
-                    handler.get_content()
-                        .map_err(|e| format!("Failed to get content: {e}"))?
-                }
-            }
-            "mobi" => {
-                let mut handler = MobiHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read MOBI: {e}"))?;
-
-                if extract_metadata {
-                    let metadata = handler.get_metadata()
-                        .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                    serde_json::to_string_pretty(&metadata).unwrap()
-                } else if extract_toc {
-                    let toc = handler.get_toc()
-                        .map_err(|e| format!("Failed to get TOC: {e}"))?;
-                    format!(
-                        "Table of Contents:\n{}",
-                        toc.iter()
-                            .map(|e| format!("{}{}", "  ".repeat(e.level.saturating_sub(1)), e.title))
-                            .collect::<Vec<_>>()
-                            .join("\n")
-                    )
-                } else {
-                    handler.get_content()
-                        .map_err(|e| format!("Failed to get content: {e}"))?
-                }
-            }
-            "azw" => {
-                let mut handler = AzwHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read AZW: {e}"))?;
-
-                if extract_metadata {
-                    let metadata = handler.get_metadata()
-                        .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                    serde_json::to_string_pretty(&metadata).unwrap()
-                } else if extract_toc {
-                    let toc = handler.get_toc()
-                        .map_err(|e| format!("Failed to get TOC: {e}"))?;
-                    format!(
-                        "Table of Contents:\n{}",
-                        toc.iter()
-                            .map(|e| format!("{}{}", "  ".repeat(e.level.saturating_sub(1)), e.title))
-                            .collect::<Vec<_>>()
-                            .join("\n")
-                    )
-                } else {
-                    handler.get_content()
-                        .map_err(|e| format!("Failed to get content: {e}"))?
-                }
-            }
-            "fb2" => {
-                let mut handler = Fb2Handler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read FB2: {e}"))?;
-
-                if extract_metadata {
-                    let metadata = handler.get_metadata()
-                        .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                    serde_json::to_string_pretty(&metadata).unwrap()
-                } else if extract_toc {
-                    let toc = handler.get_toc()
-                        .map_err(|e| format!("Failed to get TOC: {e}"))?;
-                    format!(
-                        "Table of Contents:\n{}",
-                        toc.iter()
-                            .map(|e| format!("{}{}", "  ".repeat(e.level.saturating_sub(1)), e.title))
-                            .collect::<Vec<_>>()
-                            .join("\n")
-                    )
-                } else {
-                    handler.get_content()
-                        .map_err(|e| format!("Failed to get content: {e}"))?
-                }
-            }
-            _ => return Err(format!("Unsupported format: {format}")),
+        let text = if extract_metadata {
+            let metadata = handler.get_metadata()
+                .map_err(|e| ToolError::ebook("Failed to get metadata", e))?;
+            serde_json::to_string_pretty(&metadata).unwrap()
+        } else if extract_toc {
+            let toc = handler.get_toc()
+                .map_err(|e| ToolError::ebook("Failed to get TOC", e))?;
+            format!("Table of Contents:\n{}", crate::traits::toc_to_markdown(&toc))
+        } else {
+            handler.get_content()
+                .map_err(|e| ToolError::ebook("Failed to get content", e))?
         };
 
         Ok(ToolResult {
             content: vec![ToolContent::Text { text }],
             is_error: None,
+            structured_content: None,
         })
     }
 
     async fn tool_write_ebook(
         &self,
         args: std::collections::HashMap<String, serde_json::Value>,
-    ) -> Result<ToolResult, String> {
+    ) -> Result<ToolResult, ToolError> {
         let path = args
             .get("path")
             .and_then(|v| v.as_str())
-            .ok_or("Missing 'path' argument")?;
+            .ok_or_else(|| ToolError::missing_argument("path"))?;
         let format = args
             .get("format")
             .and_then(|v| v.as_str())
-            .ok_or("Missing 'format' argument")?;
+            .ok_or_else(|| ToolError::missing_argument("format"))?;
         let title = args.get("title").and_then(|v| v.as_str());
         let author = args.get("author").and_then(|v| v.as_str());
         let content = args.get("content").and_then(|v| v.as_str()).unwrap_or("");
@@ -539,65 +555,68 @@ impl McpServer {
             metadata.title = Some(t.to_string());
         }
         if let Some(a) = author {
-            metadata.author = Some(a.to_string());
+            metadata.set_authors([a]);
+        }
+        if let Some(authors) = args.get("authors").and_then(|v| v.as_array()) {
+            metadata = metadata.with_authors(authors.iter().filter_map(|v| v.as_str()));
         }
 
         match format {
             "txt" => {
                 let mut handler = TxtHandler::new();
                 handler.set_metadata(metadata)
-                    .map_err(|e| format!("Failed to set metadata: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set metadata", e))?;
                 handler.set_content(content)
-                    .map_err(|e| format!("Failed to set content: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set content", e))?;
                 handler.write_to_file(&path_buf)
-                    .map_err(|e| format!("Failed to write file: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to write file", e))?;
             }
             "epub" => {
                 let mut handler = EpubHandler::new();
                 handler.set_metadata(metadata)
-                    .map_err(|e| format!("Failed to set metadata: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set metadata", e))?;
                 handler.set_content(content)
-                    .map_err(|e| format!("Failed to set content: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set content", e))?;
                 handler.write_to_file(&path_buf)
-                    .map_err(|e| format!("Failed to write file: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to write file", e))?;
             }
             "pdf" => {
                 let mut handler = PdfHandler::new();
                 handler.set_metadata(metadata)
-                    .map_err(|e| format!("Failed to set metadata: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set metadata", e))?;
                 handler.set_content(content)
-                    .map_err(|e| format!("Failed to set content: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set content", e))?;
                 handler.write_to_file(&path_buf)
-                    .map_err(|e| format!("Failed to write file: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to write file", e))?;
             }
             "mobi" => {
                 let mut handler = MobiHandler::new();
                 handler.set_metadata(metadata)
-                    .map_err(|e| format!("Failed to set metadata: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set metadata", e))?;
                 handler.set_content(content)
-                    .map_err(|e| format!("Failed to set content: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set content", e))?;
                 handler.write_to_file(&path_buf)
-                    .map_err(|e| format!("Failed to write file: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to write file", e))?;
             }
             "azw" => {
                 let mut handler = AzwHandler::new();
                 handler.set_metadata(metadata)
-                    .map_err(|e| format!("Failed to set metadata: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set metadata", e))?;
                 handler.set_content(content)
-                    .map_err(|e| format!("Failed to set content: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set content", e))?;
                 handler.write_to_file(&path_buf)
-                    .map_err(|e| format!("Failed to write file: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to write file", e))?;
             }
             "fb2" => {
                 let mut handler = Fb2Handler::new();
                 handler.set_metadata(metadata)
-                    .map_err(|e| format!("Failed to set metadata: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set metadata", e))?;
                 handler.set_content(content)
-                    .map_err(|e| format!("Failed to set content: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to set content", e))?;
                 handler.write_to_file(&path_buf)
-                    .map_err(|e| format!("Failed to write file: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to write file", e))?;
             }
-            _ => return Err(format!("Unsupported format: {format}")),
+            _ => return Err(ToolError::unsupported(format!("Unsupported format: {format}"))),
         }
 
         Ok(ToolResult {
@@ -605,38 +624,39 @@ impl McpServer {
                 text: format!("Successfully wrote ebook to {path}"),
             }],
             is_error: None,
+            structured_content: None,
         })
     }
 
     async fn tool_extract_images(
         &self,
         args: std::collections::HashMap<String, serde_json::Value>,
-    ) -> Result<ToolResult, String> {
+    ) -> Result<ToolResult, ToolError> {
         let path = args
             .get("path")
             .and_then(|v| v.as_str())
-            .ok_or("Missing 'path' argument")?;
+            .ok_or_else(|| ToolError::missing_argument("path"))?;
 
         let path_buf = PathBuf::from(path);
         let format = crate::utils::detect_format(&path_buf)
-            .map_err(|e| format!("Failed to detect format: {e}"))?;
+            .map_err(|e| ToolError::ebook("Failed to detect format", e))?;
 
         let images = match format.as_str() {
             "epub" => {
                 let mut handler = EpubHandler::new();
                 handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read EPUB: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to read EPUB", e))?;
                 handler.extract_images()
-                    .map_err(|e| format!("Failed to extract images: {e}"))?
+                    .map_err(|e| ToolError::ebook("Failed to extract images", e))?
             }
             "cbz" => {
                 let mut handler = CbzHandler::new();
                 handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read CBZ: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to read CBZ", e))?;
                 handler.extract_images()
-                    .map_err(|e| format!("Failed to extract images: {e}"))?
+                    .map_err(|e| ToolError::ebook("Failed to extract images", e))?
             }
-            _ => return Err(format!("Format {format} does not support image extraction")),
+            _ => return Err(ToolError::unsupported(format!("Format {format} does not support image extraction"))),
         };
 
         let mut content = vec![];
@@ -651,74 +671,23 @@ impl McpServer {
         Ok(ToolResult {
             content,
             is_error: None,
+            structured_content: None,
         })
     }
 
     async fn tool_validate_ebook(
         &self,
         args: std::collections::HashMap<String, serde_json::Value>,
-    ) -> Result<ToolResult, String> {
+    ) -> Result<ToolResult, ToolError> {
         let path = args
             .get("path")
             .and_then(|v| v.as_str())
-            .ok_or("Missing 'path' argument")?;
+            .ok_or_else(|| ToolError::missing_argument("path"))?;
 
         let path_buf = PathBuf::from(path);
-        let format = crate::utils::detect_format(&path_buf)
-            .map_err(|e| format!("Failed to detect format: {e}"))?;
-
-        let is_valid = match format.as_str() {
-            "txt" => {
-                let mut handler = TxtHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read TXT: {e}"))?;
-                handler.validate()
-                    .map_err(|e| format!("Failed to validate: {e}"))?
-            }
-            "epub" => {
-                let mut handler = EpubHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read EPUB: {e}"))?;
-                handler.validate()
-                    .map_err(|e| format!("Failed to validate: {e}"))?
-            }
-            "pdf" => {
-                let mut handler = PdfHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read PDF: {e}"))?;
-                handler.validate()
-                    .map_err(|e| format!("Failed to validate: {e}"))?
-            }
-            "mobi" => {
-                let mut handler = MobiHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read MOBI: {e}"))?;
-                handler.validate()
-                    .map_err(|e| format!("Failed to validate: {e}"))?
-            }
-            "azw" => {
-                let mut handler = AzwHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read AZW: {e}"))?;
-                handler.validate()
-                    .map_err(|e| format!("Failed to validate: {e}"))?
-            }
-            "fb2" => {
-                let mut handler = Fb2Handler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read FB2: {e}"))?;
-                handler.validate()
-                    .map_err(|e| format!("Failed to validate: {e}"))?
-            }
-            "cbz" => {
-                let mut handler = CbzHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read CBZ: {e}"))?;
-                handler.validate()
-                    .map_err(|e| format!("Failed to validate: {e}"))?
-            }
-            _ => return Err(format!("Validation not supported for format: {format}")),
-        };
+        let handler = self.open_ebook(&path_buf)?;
+        let is_valid = handler.validate()
+            .map_err(|e| ToolError::ebook("Failed to validate", e))?;
 
         let text = if is_valid {
             format!("✓ File {path} is valid")
@@ -729,145 +698,242 @@ impl McpServer {
         Ok(ToolResult {
             content: vec![ToolContent::Text { text }],
             is_error: None,
+            structured_content: None,
+        })
+    }
+
+    async fn tool_peek_ebook(
+        &self,
+        args: std::collections::HashMap<String, serde_json::Value>,
+    ) -> Result<ToolResult, ToolError> {
+        let path = args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::missing_argument("path"))?;
+        let chars = args
+            .get("chars")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(500) as usize;
+
+        let path_buf = PathBuf::from(path);
+        let format = self.registry.format_for_path(&path_buf)
+            .map_err(|e| ToolError::ebook("Failed to detect format", e))?;
+
+        // TXT metadata is its name or a leading header, so only the requested prefix is read
+        let (metadata, text) = if format == "txt" {
+            crate::formats::TxtHandler::read_prefix_with_metadata(&path_buf, chars + 1)
+                .map_err(|e| ToolError::ebook("Failed to read text", e))?
+        } else {
+            let handler = self.open_ebook(&path_buf)?;
+            let metadata = handler.get_metadata()
+                .map_err(|e| ToolError::ebook("Failed to get metadata", e))?;
+            let content = handler.get_content()
+                .map_err(|e| ToolError::ebook("Failed to get content", e))?;
+            (metadata, content.chars().take(chars + 1).collect())
+        };
+
+        // One character past the limit tells us whether there is more
+        let truncated = text.chars().count() > chars;
+        let preview = json!({
+            "format": format,
+            "title": metadata.title,
+            "authors": metadata.all_authors(),
+            "language": metadata.language,
+            "text": text.chars().take(chars).collect::<String>(),
+            "truncated": truncated,
+        });
+
+        Ok(ToolResult {
+            content: vec![ToolContent::Text { text: serde_json::to_string_pretty(&preview).unwrap() }],
+            is_error: None,
+            structured_content: None,
         })
     }
 
     async fn tool_get_ebook_info(
         &self,
         args: std::collections::HashMap<String, serde_json::Value>,
-    ) -> Result<ToolResult, String> {
+    ) -> Result<ToolResult, ToolError> {
         let path = args
             .get("path")
             .and_then(|v| v.as_str())
-            .ok_or("Missing 'path' argument")?;
+            .ok_or_else(|| ToolError::missing_argument("path"))?;
+
+        let output_format = args
+            .get("output_format")
+            .and_then(|v| v.as_str())
+            .unwrap_or("json");
+        if !matches!(output_format, "json" | "text") {
+            return Err(ToolError::invalid_argument(format!("Unknown output_format '{output_format}' (expected \"json\" or \"text\")")));
+        }
 
         let path_buf = PathBuf::from(path);
-        let format = crate::utils::detect_format(&path_buf)
-            .map_err(|e| format!("Failed to detect format: {e}"))?;
+        let format = self.registry.format_for_path(&path_buf)
+            .map_err(|e| ToolError::ebook("Failed to detect format", e))?;
+        let handler = self.open_ebook(&path_buf)?;
+        let metadata = handler.get_metadata()
+            .map_err(|e| ToolError::ebook("Failed to get metadata", e))?;
+        let content = handler.get_content()
+            .map_err(|e| ToolError::ebook("Failed to get content", e))?;
+        let images = handler.extract_images()
+            .map_err(|e| ToolError::ebook("Failed to get images", e))?;
 
-        let info = match format.as_str() {
-            "txt" => {
-                let mut handler = TxtHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read TXT: {e}"))?;
-                let metadata = handler.get_metadata()
-                    .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                let content = handler.get_content()
-                    .map_err(|e| format!("Failed to get content: {e}"))?;
-                
-                format!("File: {}\nFormat: {}\nMetadata:\n{}\n\nSize: {} characters",
-                    path, format, serde_json::to_string_pretty(&metadata).unwrap(), content.len())
-            }
-            "epub" => {
-                let mut handler = EpubHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read EPUB: {e}"))?;
-                let metadata = handler.get_metadata()
-                    .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                
-                format!("File: {}\nFormat: {}\nMetadata:\n{}",
-                    path, format, serde_json::to_string_pretty(&metadata).unwrap())
-            }
-            "pdf" => {
-                let mut handler = PdfHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read PDF: {e}"))?;
-                let metadata = handler.get_metadata()
-                    .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                
-                format!("File: {}\nFormat: {}\nMetadata:\n{}",
-                    path, format, serde_json::to_string_pretty(&metadata).unwrap())
-            }
-            "mobi" => {
-                let mut handler = MobiHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read MOBI: {e}"))?;
-                let metadata = handler.get_metadata()
-                    .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                
-                format!("File: {}\nFormat: {}\nMetadata:\n{}",
-                    path, format, serde_json::to_string_pretty(&metadata).unwrap())
-            }
-            "azw" => {
-                let mut handler = AzwHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read AZW: {e}"))?;
-                let metadata = handler.get_metadata()
-                    .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                
-                format!("File: {}\nFormat: {}\nMetadata:\n{}",
-                    path, format, serde_json::to_string_pretty(&metadata).unwrap())
-            }
-            "fb2" => {
-                let mut handler = Fb2Handler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read FB2: {e}"))?;
-                let metadata = handler.get_metadata()
-                    .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                
-                format!("File: {}\nFormat: {}\nMetadata:\n{}",
-                    path, format, serde_json::to_string_pretty(&metadata).unwrap())
-            }
-            "cbz" => {
-                let mut handler = CbzHandler::new();
-                handler.read_from_file(&path_buf)
-                    .map_err(|e| format!("Failed to read CBZ: {e}"))?;
-                let metadata = handler.get_metadata()
-                    .map_err(|e| format!("Failed to get metadata: {e}"))?;
-                
-                format!("File: {}\nFormat: {}\nMetadata:\n{}",
-                    path, format, serde_json::to_string_pretty(&metadata).unwrap())
+        // Comics have no text and PDF images aren't extracted, so those counts would mislead
+        let chapters = count_chapters(handler.as_ref());
+        let size_chars = (format != "cbz").then(|| content.chars().count());
+        let (page_count, chapter_count) = match format.as_str() {
+            "pdf" => (chapters, None),
+            "cbz" => (Some(images.len()), None),
+            _ => (None, chapters),
+        };
+        let image_count = (format != "txt" && format != "pdf").then_some(images.len());
+
+        let mut summary = format!("File: {path}\nFormat: {format}\nTitle: {}", metadata.title.as_deref().unwrap_or("(none)"));
+        let authors = metadata.all_authors();
+        if !authors.is_empty() {
+            summary.push_str(&format!("\nAuthors: {}", authors.join(", ")));
+        }
+        for (label, value) in [
+            ("Size", size_chars.map(|n| format!("{n} characters"))),
+            ("Pages", page_count.map(|n| n.to_string())),
+            ("Chapters", chapter_count.map(|n| n.to_string())),
+            ("Images", image_count.map(|n| n.to_string())),
+        ] {
+            if let Some(value) = value {
+                summary.push_str(&format!("\n{label}: {value}"));
             }
-            _ => return Err(format!("Info not supported for format: {format}")),
+        }
+
+        let text = if output_format == "text" {
+            summary
+        } else {
+            let info = json!({
+                "path": path,
+                "format": format,
+                "metadata": metadata,
+                "stats": {
+                    "size_chars": size_chars,
+                    "page_count": page_count,
+                    "image_count": image_count,
+                    "chapter_count": chapter_count,
+                },
+                "summary": summary,
+            });
+            serde_json::to_string_pretty(&info).unwrap()
         };
 
         Ok(ToolResult {
-            content: vec![ToolContent::Text { text: info }],
+            content: vec![ToolContent::Text { text }],
             is_error: None,
+            structured_content: None,
         })
     }
 
     async fn tool_convert_ebook(
         &self,
         args: std::collections::HashMap<String, serde_json::Value>,
-    ) -> Result<ToolResult, String> {
+    ) -> Result<ToolResult, ToolError> {
         let input_path = args
             .get("input_path")
             .and_then(|v| v.as_str())
-            .ok_or("Missing 'input_path' argument")?;
+            .ok_or_else(|| ToolError::missing_argument("input_path"))?;
         let output_path = args
             .get("output_path")
             .and_then(|v| v.as_str())
-            .ok_or("Missing 'output_path' argument")?;
+            .ok_or_else(|| ToolError::missing_argument("output_path"))?;
         let target_format = args
             .get("target_format")
             .and_then(|v| v.as_str())
-            .ok_or("Missing 'target_format' argument")?;
+            .ok_or_else(|| ToolError::missing_argument("target_format"))?;
 
-        let input_buf = PathBuf::from(input_path);
-        let output_buf = PathBuf::from(output_path);
-
-        Converter::convert(&input_buf, &output_buf, target_format)
-            .map_err(|e| format!("Conversion failed: {e}"))?;
+        convert_file(&ConvertJob {
+            input_path: input_path.to_string(),
+            output_path: output_path.to_string(),
+            target_format: target_format.to_string(),
+        })?;
 
         Ok(ToolResult {
             content: vec![ToolContent::Text {
                 text: format!("Successfully converted {input_path} to {target_format} format"),
             }],
             is_error: None,
+            structured_content: None,
+        })
+    }
+
+    /// Run the conversions of `jobs` on the blocking pool, at most `concurrency` at a time.
+    /// A failed job doesn't stop the others; each gets its own result, in job order.
+    async fn tool_convert_batch(
+        &self,
+        args: std::collections::HashMap<String, serde_json::Value>,
+    ) -> Result<ToolResult, ToolError> {
+        use futures::StreamExt;
+
+        let jobs = args
+            .get("jobs")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| ToolError::missing_argument("jobs"))?
+            .iter()
+            .enumerate()
+            .map(|(index, job)| {
+                serde_json::from_value::<ConvertJob>(job.clone())
+                    .map_err(|e| ToolError::invalid_argument(format!("Invalid job {index}: {e}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let concurrency = match args.get("concurrency") {
+            None => DEFAULT_BATCH_CONCURRENCY,
+            Some(value) => value
+                .as_u64()
+                .filter(|&n| n >= 1)
+                .map(|n| usize::try_from(n).unwrap_or(usize::MAX))
+                .ok_or_else(|| ToolError::invalid_argument("'concurrency' must be a positive integer"))?,
+        };
+
+        let results: Vec<ConvertJobResult> = futures::stream::iter(jobs)
+            .map(|job| async move {
+                let task = tokio::task::spawn_blocking({
+                    let job = job.clone();
+                    move || convert_file(&job)
+                });
+                let error = match task.await {
+                    Ok(result) => result.err(),
+                    Err(e) => Some(ToolError::new(
+                        ErrorCategory::Internal,
+                        "tool_failed",
+                        format!("Conversion of {} failed: {e}", job.input_path),
+                    )),
+                };
+                ConvertJobResult { success: error.is_none(), job, error }
+            })
+            .buffered(concurrency)
+            .collect()
+            .await;
+
+        let converted = results.iter().filter(|result| result.success).count();
+        let mut text = format!("Converted {converted} of {} files", results.len());
+        for result in &results {
+            if let Some(error) = &result.error {
+                text.push_str(&format!("\n{}: {}", result.job.input_path, error.message));
+            }
+        }
+
+        Ok(ToolResult {
+            content: vec![ToolContent::Text { text }],
+            is_error: None,
+            structured_content: Some(json!({ "results": results })),
         })
     }
 
     async fn tool_optimize_images(
         &self,
         args: std::collections::HashMap<String, serde_json::Value>,
-    ) -> Result<ToolResult, String> {
+    ) -> Result<ToolResult, ToolError> {
         use crate::image_optimizer::OptimizationOptions;
 
         let input_path = args
             .get("input_path")
             .and_then(|v| v.as_str())
-            .ok_or("Missing 'input_path' argument")?;
+            .ok_or_else(|| ToolError::missing_argument("input_path"))?;
         
         let output_path = args
             .get("output_path")
@@ -894,11 +960,24 @@ impl McpServer {
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
 
+        let force = args
+            .get("force")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let nothing_to_optimize = || ToolResult {
+            content: vec![ToolContent::Text {
+                text: format!("Nothing to optimize: {input_path} has no images; no file written (pass force to rewrite it anyway)"),
+            }],
+            is_error: None,
+            structured_content: None,
+        };
+
         let input_buf = PathBuf::from(input_path);
         let output_buf = PathBuf::from(output_path);
 
         let format = crate::utils::detect_format(&input_buf)
-            .map_err(|e| format!("Failed to detect format: {e}"))?;
+            .map_err(|e| ToolError::ebook("Failed to detect format", e))?;
 
         let mut options = OptimizationOptions::default().with_quality(quality);
         
@@ -912,31 +991,37 @@ impl McpServer {
             "epub" => {
                 let mut handler = EpubHandler::new();
                 handler.read_from_file(&input_buf)
-                    .map_err(|e| format!("Failed to read EPUB: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to read EPUB", e))?;
+                if !force && handler.image_count() == 0 {
+                    return Ok(nothing_to_optimize());
+                }
                 
                 let savings = handler.optimize_images(options)
-                    .map_err(|e| format!("Failed to optimize images: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to optimize images", e))?;
                 
                 handler.write_to_file(&output_buf)
-                    .map_err(|e| format!("Failed to write EPUB: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to write EPUB", e))?;
                 
                 savings
             }
             "cbz" => {
                 let mut handler = CbzHandler::new();
                 handler.read_from_file(&input_buf)
-                    .map_err(|e| format!("Failed to read CBZ: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to read CBZ", e))?;
+                if !force && handler.image_count() == 0 {
+                    return Ok(nothing_to_optimize());
+                }
                 
                 let savings = handler.optimize_images(options)
-                    .map_err(|e| format!("Failed to optimize images: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to optimize images", e))?;
                 
                 handler.write_to_file(&output_buf)
-                    .map_err(|e| format!("Failed to write CBZ: {e}"))?;
+                    .map_err(|e| ToolError::ebook("Failed to write CBZ", e))?;
                 
                 savings
             }
             _ => {
-                return Err(format!("Image optimization only supports EPUB and CBZ formats, got: {format}"));
+                return Err(ToolError::unsupported(format!("Image optimization only supports EPUB and CBZ formats, got: {format}")));
             }
         };
 
@@ -948,6 +1033,7 @@ impl McpServer {
         Ok(ToolResult {
             content: vec![ToolContent::Text { text: message }],
             is_error: None,
+            structured_content: None,
         })
     }
 }
@@ -1,3 +1,4 @@
+use crate::{EbookError, ErrorCategory};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -73,6 +74,60 @@ pub struct ToolResult {
     pub content: Vec<ToolContent>,
     #[serde(rename = "isError", skip_serializing_if = "Option::is_none")]
     pub is_error: Option<bool>,
+    /// Machine-readable form of the result; a `ToolError` for failed calls
+    #[serde(rename = "structuredContent", skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<serde_json::Value>,
+}
+
+/// Why a tool call failed: a `code` and `category` to branch on, and the message for people
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolError {
+    /// The `EbookError` variant (see `EbookError::code`), or a tool-level code such as
+    /// `missing_argument`
+    pub code: String,
+    pub category: ErrorCategory,
+    pub message: String,
+}
+
+impl ToolError {
+    pub fn new(category: ErrorCategory, code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { code: code.into(), category, message: message.into() }
+    }
+
+    /// An `EbookError`, its message prefixed with what the tool was doing
+    pub fn ebook(context: &str, error: EbookError) -> Self {
+        Self::new(error.category(), error.code(), format!("{context}: {error}"))
+    }
+
+    pub fn missing_argument(name: &str) -> Self {
+        Self::new(ErrorCategory::InvalidArguments, "missing_argument", format!("Missing '{name}' argument"))
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(ErrorCategory::InvalidArguments, "invalid_argument", message)
+    }
+
+    pub fn unsupported(message: impl Into<String>) -> Self {
+        Self::new(ErrorCategory::UnsupportedFormat, "unsupported_format", message)
+    }
+}
+
+/// One conversion of a `convert_batch` call, with the arguments `convert_ebook` takes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConvertJob {
+    pub input_path: String,
+    pub output_path: String,
+    pub target_format: String,
+}
+
+/// How one `convert_batch` job went; `error` says why when it failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConvertJobResult {
+    #[serde(flatten)]
+    pub job: ConvertJob,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ToolError>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,59 @@
+//! Join several ebooks into one EPUB, one run of chapters per source book
+
+use crate::conversion::chapter_xhtml;
+use crate::formats::EpubHandler;
+use crate::registry::FormatRegistry;
+use crate::traits::EbookWriter;
+use crate::{EbookError, Metadata, Result};
+use std::path::{Path, PathBuf};
+
+/// An input that could not be read; `error` is kept to one line for the summary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeFailure {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Write the chapters of every input, in the order given, to `output` as one EPUB. The book
+/// takes `title` if given, else the first readable input's title, and that input's authors.
+/// Images of the sources are not carried over. The first input that cannot be read stops the
+/// merge, unless `keep_going` is set: then it is left out and returned among the failures.
+pub fn merge_books(inputs: &[PathBuf], output: &Path, title: Option<&str>, keep_going: bool) -> Result<Vec<MergeFailure>> {
+    let registry = FormatRegistry::default();
+    let mut epub = EpubHandler::new();
+    let mut metadata: Option<Metadata> = None;
+    let mut failures = Vec::new();
+
+    for input in inputs {
+        let chapters = registry.open(input).and_then(|book| {
+            let chapters = book.get_chapters()?;
+            if metadata.is_none() {
+                metadata = Some(book.get_metadata()?);
+            }
+            Ok(chapters)
+        });
+        match chapters {
+            Ok(chapters) => {
+                for chapter in chapters {
+                    epub.add_chapter(&chapter.title, &chapter_xhtml(&chapter.title, &chapter.content))?;
+                }
+            }
+            Err(e) if !keep_going => return Err(EbookError::InFile { path: input.clone(), source: Box::new(e) }),
+            Err(e) => failures.push(MergeFailure {
+                path: input.clone(),
+                error: e.to_string().lines().map(str::trim).collect::<Vec<_>>().join(" "),
+            }),
+        }
+    }
+
+    let Some(source) = metadata else {
+        return Err(EbookError::NotFound("no readable books to merge".to_string()));
+    };
+    let mut merged = Metadata::new();
+    merged.set_authors(source.all_authors());
+    merged.title = title.map(str::to_string).or(source.title);
+    merged.language = source.language;
+    epub.set_metadata(merged)?;
+    epub.write_to_file(output)?;
+    Ok(failures)
+}
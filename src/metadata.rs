@@ -1,14 +1,68 @@
+use crate::{EbookError, Result};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Separator used when flattening multiple authors into a single field
+pub const DEFAULT_AUTHOR_SEPARATOR: &str = "; ";
+
+/// A person credited on the book (`dc:creator` or `dc:contributor`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contributor {
+    pub name: String,
+    /// MARC relator code, e.g. `aut` (author), `edt` (editor), `ill` (illustrator) or
+    /// `trl` (translator); `None` when the source gives none
+    pub role: Option<String>,
+    /// Name in sort order, e.g. "Austen, Jane" (`opf:file-as`)
+    pub file_as: Option<String>,
+}
+
+impl Contributor {
+    pub fn new(name: impl Into<String>, role: Option<&str>) -> Self {
+        Self { name: name.into(), role: role.map(str::to_string), file_as: None }
+    }
+
+    pub fn with_file_as(mut self, file_as: impl Into<String>) -> Self {
+        self.file_as = Some(file_as.into());
+        self
+    }
+
+    /// Whether the role makes this an author; creators without a role are authors
+    pub fn is_author(&self) -> bool {
+        self.role.as_deref().is_none_or(|role| role == "aut")
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Metadata {
     pub title: Option<String>,
+    /// Subtitle, kept apart from `title` (an EPUB 3 `dc:title` refined as `subtitle`)
+    pub subtitle: Option<String>,
+    /// Title in sort order, e.g. "Hobbit, The" (`file-as` of the main title)
+    pub title_sort: Option<String>,
+    /// Primary author; mirrors the first entry of `authors` when set through
+    /// [`Metadata::set_authors`] or the helpers built on it
     pub author: Option<String>,
+    /// All authors in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    /// Everyone credited, authors included, with their roles and sort names in source order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contributors: Vec<Contributor>,
     pub publisher: Option<String>,
     pub description: Option<String>,
     pub language: Option<String>,
     pub isbn: Option<String>,
     pub publication_date: Option<String>,
+    /// Copyright or license statement (`dc:rights`, EXTH 109); ComicInfo has no such element
+    pub rights: Option<String>,
+    /// Series the book belongs to (`calibre:series`)
+    pub series: Option<String>,
+    /// Position within `series`; Calibre allows fractional indexes such as 1.5
+    pub series_index: Option<f64>,
+    /// Rating on Calibre's 0-10 scale, where 10 is five stars (`calibre:rating`)
+    pub rating: Option<f64>,
+    /// Author names in sort order, e.g. "Tolkien, J. R. R." (`calibre:author_sort`)
+    pub author_sort: Option<String>,
     pub cover_image: Option<Vec<u8>>,
     pub cover_image_path: Option<String>,
     pub tags: Option<Vec<String>>,
@@ -27,10 +81,128 @@ impl Metadata {
     }
 
     pub fn with_author(mut self, author: impl Into<String>) -> Self {
-        self.author = Some(author.into());
+        self.set_authors([author]);
+        self
+    }
+
+    pub fn with_authors<I, S>(mut self, authors: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.set_authors(authors);
         self
     }
 
+    /// Replace the authors, keeping `author` on the first of them; every change to the
+    /// authors goes through here so the two fields cannot drift apart
+    pub fn set_authors<I, S>(&mut self, authors: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.authors = authors.into_iter().map(Into::into).collect();
+        self.author = self.authors.first().cloned();
+    }
+
+    /// Append an author, making it the primary author if none is set yet
+    pub fn add_author(&mut self, author: impl Into<String>) {
+        let mut authors = self.all_authors();
+        authors.push(author.into());
+        self.set_authors(authors);
+    }
+
+    /// Record a credit; authors are also appended to `authors`
+    pub fn add_contributor(&mut self, contributor: Contributor) {
+        if contributor.is_author() {
+            self.add_author(contributor.name.clone());
+        }
+        self.contributors.push(contributor);
+    }
+
+    /// Contributors credited with the MARC relator `role`, e.g. `edt` for the editors
+    pub fn contributors_with_role<'a>(&'a self, role: &'a str) -> impl Iterator<Item = &'a Contributor> {
+        self.contributors.iter().filter(move |contributor| contributor.role.as_deref() == Some(role))
+    }
+
+    /// Credits to write: each author (with the role and sort name recorded for it, if any),
+    /// then every contributor who is not an author
+    pub fn credits(&self) -> Vec<Contributor> {
+        let mut credits: Vec<Contributor> = self
+            .all_authors()
+            .into_iter()
+            .map(|name| {
+                self.contributors
+                    .iter()
+                    .find(|contributor| contributor.is_author() && contributor.name == name)
+                    .cloned()
+                    .unwrap_or_else(|| Contributor::new(name, None))
+            })
+            .collect();
+        credits.extend(self.contributors.iter().filter(|contributor| !contributor.is_author()).cloned());
+        credits
+    }
+
+    /// Whether the record says nothing about the book; `format` (where it was read from) is
+    /// not counted
+    pub fn is_empty(&self) -> bool {
+        !self.has_title()
+            && self.subtitle.is_none()
+            && self.title_sort.is_none()
+            && !self.has_author()
+            && self.contributors.is_empty()
+            && self.publisher.is_none()
+            && self.description.is_none()
+            && self.language.is_none()
+            && self.isbn.is_none()
+            && self.publication_date.is_none()
+            && self.rights.is_none()
+            && self.series.is_none()
+            && self.series_index.is_none()
+            && self.rating.is_none()
+            && self.author_sort.is_none()
+            && self.cover_image.is_none()
+            && self.cover_image_path.is_none()
+            && self.tags.as_ref().is_none_or(Vec::is_empty)
+            && self.custom_fields.is_empty()
+    }
+
+    /// Whether a non-blank title is set
+    pub fn has_title(&self) -> bool {
+        self.title.as_deref().is_some_and(|title| !title.trim().is_empty())
+    }
+
+    /// Whether at least one non-blank author is set
+    pub fn has_author(&self) -> bool {
+        self.all_authors().iter().any(|author| !author.trim().is_empty())
+    }
+
+    /// All authors, falling back to the single `author` field for older callers
+    pub fn all_authors(&self) -> Vec<String> {
+        if self.authors.is_empty() {
+            self.author.iter().cloned().collect()
+        } else {
+            self.authors.clone()
+        }
+    }
+
+    /// All authors joined with `separator`, for formats with a single author field
+    pub fn joined_authors(&self, separator: &str) -> Option<String> {
+        let authors = self.all_authors();
+        (!authors.is_empty()).then(|| authors.join(separator))
+    }
+
+    /// Replace the authors with the non-empty parts of `value` split on `separator`
+    pub fn set_authors_from_joined(&mut self, value: &str, separator: &str) {
+        let separator = separator.trim();
+        let parts: Vec<String> = if separator.is_empty() {
+            vec![value.trim().to_string()]
+        } else {
+            value.split(separator).map(|s| s.trim().to_string()).collect()
+        };
+        self.set_authors(parts.into_iter().filter(|s| !s.is_empty()));
+    }
+
     pub fn with_format(mut self, format: impl Into<String>) -> Self {
         self.format = Some(format.into());
         self
@@ -39,4 +211,166 @@ impl Metadata {
     pub fn add_custom_field(&mut self, key: String, value: String) {
         self.custom_fields.insert(key, value);
     }
+
+    /// Store a Calibre `<meta name="calibre:…" content="…"/>` entry. Series, rating and author
+    /// sort fill their fields; other `calibre:` names (custom columns, timestamps) are kept in
+    /// `custom_fields` under their full name so they can be written back.
+    pub fn set_calibre_field(&mut self, name: &str, content: &str) {
+        match name {
+            "calibre:series" => self.series = Some(content.to_string()),
+            "calibre:series_index" => self.series_index = content.trim().parse().ok(),
+            "calibre:rating" => self.rating = content.trim().parse().ok(),
+            "calibre:author_sort" => self.author_sort = Some(content.to_string()),
+            _ if name.starts_with("calibre:") => {
+                self.custom_fields.insert(name.to_string(), content.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    /// The Calibre `<meta>` name/content pairs for this record, the inverse of `set_calibre_field`
+    pub fn calibre_fields(&self) -> Vec<(String, String)> {
+        let mut fields = Vec::new();
+        if let Some(series) = &self.series {
+            fields.push(("calibre:series".to_string(), series.clone()));
+        }
+        if let Some(index) = self.series_index {
+            fields.push(("calibre:series_index".to_string(), index.to_string()));
+        }
+        if let Some(rating) = self.rating {
+            fields.push(("calibre:rating".to_string(), rating.to_string()));
+        }
+        if let Some(author_sort) = &self.author_sort {
+            fields.push(("calibre:author_sort".to_string(), author_sort.clone()));
+        }
+        let mut custom: Vec<_> = self
+            .custom_fields
+            .iter()
+            .filter(|(key, _)| key.starts_with("calibre:"))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        custom.sort();
+        fields.extend(custom);
+        fields
+    }
+
+    /// Fill in what `repair` can infer: the language from `sample_text` and today's date as
+    /// the publication date. Fields that are already set are kept, so this is idempotent.
+    pub fn infer_defaults(&mut self, sample_text: &str) {
+        if self.language.is_none() {
+            self.language = crate::utils::guess_language(sample_text);
+        }
+        if self.publication_date.is_none() {
+            self.publication_date = Some(crate::utils::today_iso_date());
+        }
+    }
+
+    /// Set the language detected in `sample_text` when none is declared and the guess is at
+    /// least `utils::MIN_LANGUAGE_CONFIDENCE` sure; returns whether it was set
+    pub fn detect_language(&mut self, sample_text: &str) -> bool {
+        if self.language.is_some() {
+            return false;
+        }
+        match crate::utils::detect_language(sample_text) {
+            Some((code, confidence)) if confidence >= crate::utils::MIN_LANGUAGE_CONFIDENCE => {
+                self.language = Some(code);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Load a metadata record previously exported as JSON
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| EbookError::InvalidMetadata(format!("{}: {e}", path.display())))
+    }
+
+    /// Copy every field set in `other` over this record; unset fields are left alone.
+    /// `format` describes the container and is never overlaid.
+    pub fn overlay(&mut self, other: &Metadata) {
+        fn set<T: Clone>(target: &mut Option<T>, value: &Option<T>) {
+            if value.is_some() {
+                target.clone_from(value);
+            }
+        }
+
+        set(&mut self.title, &other.title);
+        set(&mut self.subtitle, &other.subtitle);
+        set(&mut self.title_sort, &other.title_sort);
+        let authors = other.all_authors();
+        if !authors.is_empty() {
+            self.set_authors(authors);
+        }
+        if !other.contributors.is_empty() {
+            self.contributors.clone_from(&other.contributors);
+        }
+        set(&mut self.publisher, &other.publisher);
+        set(&mut self.description, &other.description);
+        set(&mut self.language, &other.language);
+        set(&mut self.isbn, &other.isbn);
+        set(&mut self.publication_date, &other.publication_date);
+        set(&mut self.rights, &other.rights);
+        set(&mut self.series, &other.series);
+        set(&mut self.series_index, &other.series_index);
+        set(&mut self.rating, &other.rating);
+        set(&mut self.author_sort, &other.author_sort);
+        set(&mut self.cover_image, &other.cover_image);
+        set(&mut self.cover_image_path, &other.cover_image_path);
+        set(&mut self.tags, &other.tags);
+        self.custom_fields.extend(other.custom_fields.clone());
+    }
+
+    /// Render the record as an OPF `<metadata>` element (Dublin Core plus `<meta>` custom fields)
+    pub fn to_opf_fragment(&self) -> String {
+        use quick_xml::escape::escape;
+
+        let mut fragment = String::from(
+            "<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:opf=\"http://www.idpf.org/2007/opf\">\n",
+        );
+        if let Some(title) = &self.title {
+            fragment.push_str(&format!("    <dc:title>{}</dc:title>\n", escape(title)));
+        }
+        for credit in self.credits() {
+            let name = if credit.is_author() { "creator" } else { "contributor" };
+            let mut attributes = String::new();
+            if let Some(role) = &credit.role {
+                attributes.push_str(&format!(" opf:role=\"{}\"", escape(role)));
+            }
+            if let Some(file_as) = &credit.file_as {
+                attributes.push_str(&format!(" opf:file-as=\"{}\"", escape(file_as)));
+            }
+            fragment.push_str(&format!("    <dc:{name}{attributes}>{}</dc:{name}>\n", escape(&credit.name)));
+        }
+        let mut element = |name: &str, value: &str| {
+            fragment.push_str(&format!("    <dc:{name}>{}</dc:{name}>\n", escape(value)));
+        };
+        for (name, value) in [
+            ("publisher", &self.publisher),
+            ("description", &self.description),
+            ("language", &self.language),
+            ("identifier", &self.isbn),
+            ("date", &self.publication_date),
+            ("rights", &self.rights),
+        ] {
+            if let Some(value) = value {
+                element(name, value);
+            }
+        }
+        for tag in self.tags.iter().flatten() {
+            element("subject", tag);
+        }
+
+        for (key, value) in self.calibre_fields() {
+            fragment.push_str(&format!("    <meta name=\"{}\" content=\"{}\"/>\n", escape(&key), escape(&value)));
+        }
+        let mut custom_fields: Vec<_> = self.custom_fields.iter().filter(|(key, _)| !key.starts_with("calibre:")).collect();
+        custom_fields.sort();
+        for (key, value) in custom_fields {
+            fragment.push_str(&format!("    <meta name=\"{}\" content=\"{}\"/>\n", escape(key), escape(value)));
+        }
+
+        fragment.push_str("</metadata>\n");
+        fragment
+    }
 }
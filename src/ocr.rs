@@ -0,0 +1,63 @@
+//! Optical character recognition for books without a text layer (scanned PDFs, comics)
+
+use crate::traits::ImageData;
+use crate::{EbookError, Result};
+
+/// Tesseract language used when none is given
+pub const DEFAULT_LANGUAGE: &str = "eng";
+
+/// Books averaging fewer visible characters per page than this are treated as image-only
+pub const MIN_CHARS_PER_PAGE: usize = 16;
+
+/// Turns a page image into text
+pub trait OcrBackend {
+    /// Recognize the text in an encoded image (PNG, JPEG, ...). `language` is a Tesseract
+    /// language code such as `eng`, or several joined with `+` (`deu+fra`).
+    fn recognize(&self, image: &[u8], language: &str) -> Result<String>;
+}
+
+/// OCR through the Tesseract library
+#[cfg(feature = "ocr")]
+#[derive(Debug, Default)]
+pub struct TesseractOcr {
+    /// Directory holding the `*.traineddata` files; Tesseract's default when `None`
+    pub data_path: Option<String>,
+}
+
+#[cfg(feature = "ocr")]
+impl OcrBackend for TesseractOcr {
+    fn recognize(&self, image: &[u8], language: &str) -> Result<String> {
+        let mut tesseract = leptess::LepTess::new(self.data_path.as_deref(), language)
+            .map_err(|e| EbookError::Ocr(format!("could not load language {language:?}: {e}")))?;
+        tesseract.set_image_from_mem(image).map_err(|e| EbookError::Ocr(e.to_string()))?;
+        tesseract.get_utf8_text().map_err(|e| EbookError::Ocr(e.to_string()))
+    }
+}
+
+/// The backend this build ships with; an error when built without the `ocr` feature
+pub fn default_backend() -> Result<Box<dyn OcrBackend>> {
+    #[cfg(feature = "ocr")]
+    {
+        Ok(Box::new(TesseractOcr::default()))
+    }
+    #[cfg(not(feature = "ocr"))]
+    {
+        Err(EbookError::NotSupported(
+            "OCR is not available in this build; rebuild with `--features ocr`".to_string(),
+        ))
+    }
+}
+
+/// Whether `text` extracted from `pages` pages is too sparse to be a real text layer
+pub fn needs_ocr(text: &str, pages: usize) -> bool {
+    let visible = text.chars().filter(|c| c.is_alphanumeric()).count();
+    visible < pages.max(1) * MIN_CHARS_PER_PAGE
+}
+
+/// Recognize each image in turn, one text per image
+pub fn recognize_images(backend: &dyn OcrBackend, images: &[ImageData], language: &str) -> Result<Vec<String>> {
+    images
+        .iter()
+        .map(|image| backend.recognize(&image.data, language).map(|text| text.trim().to_string()))
+        .collect()
+}
@@ -1,6 +1,7 @@
 //! Progress reporting utilities for long-running operations
 
 use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use std::time::{Duration, Instant};
 
 /// A simple progress reporter for tracking operation progress
 #[derive(Clone)]
@@ -73,6 +74,23 @@ impl Progress {
         );
     }
 
+    /// A handler showing how far the current step has got, with an ETA, after `message`
+    pub fn step_handler(&self, message: &str) -> ProgressHandler {
+        let progress = self.clone();
+        let message = message.to_string();
+        let started = Instant::now();
+        ProgressHandler::with_callback(Box::new(move |current: usize, total: usize| {
+            let percentage = if total > 0 { current as f64 / total as f64 * 100.0 } else { 100.0 };
+            match estimate_remaining(started.elapsed(), current, total) {
+                Some(eta) if current < total => progress.print_with_message(&format!(
+                    "{message} {percentage:.0}%, ETA {}   ",
+                    format_duration(eta)
+                )),
+                _ => progress.print_with_message(&format!("{message} {percentage:.0}%")),
+            }
+        }))
+    }
+
     /// Finish the progress display
     pub fn finish(&self) {
         eprintln!("\r{}: Complete! (100%)", self.name);
@@ -122,18 +140,44 @@ impl Default for ProgressHandler {
     }
 }
 
-/// Create a simple console progress callback
+/// Create a simple console progress callback, with an ETA once progress has started
 pub fn console_progress_callback(name: String) -> ProgressCallback {
+    let started = Instant::now();
     Box::new(move |current: usize, total: usize| {
         let percentage = if total > 0 {
             (current as f64 / total as f64 * 100.0).min(100.0)
         } else {
             100.0
         };
-        eprint!("\r{name}: {percentage:.0}% ({current}/{total})");
+        match estimate_remaining(started.elapsed(), current, total) {
+            Some(eta) if current < total => {
+                eprint!("\r{name}: {percentage:.0}% ({current}/{total}), ETA {}   ", format_duration(eta));
+            }
+            _ => eprint!("\r{name}: {percentage:.0}% ({current}/{total})"),
+        }
     })
 }
 
+/// Rough time left when `current` of `total` units took `elapsed`, assuming the rate stays
+/// the same; `None` before anything has been done
+pub fn estimate_remaining(elapsed: Duration, current: usize, total: usize) -> Option<Duration> {
+    if current == 0 {
+        return None;
+    }
+    let remaining = total.saturating_sub(current) as f64;
+    Some(elapsed.mul_f64(remaining / current as f64))
+}
+
+/// A duration rounded to whole seconds, as `1h02m`, `3m05s` or `42s`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64().round() as u64;
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m{s:02}s"),
+        (h, m, _) => format!("{h}h{m:02}m"),
+    }
+}
+
 /// Create a silent progress callback (does nothing)
 pub fn silent_progress_callback() -> ProgressCallback {
     Box::new(|_current: usize, _total: usize| {})
@@ -185,6 +229,16 @@ mod tests {
         assert_eq!(progress.percentage(), 100.0);
     }
 
+    #[test]
+    fn test_estimate_remaining() {
+        assert_eq!(estimate_remaining(Duration::from_secs(10), 0, 100), None);
+        assert_eq!(estimate_remaining(Duration::from_secs(10), 25, 100), Some(Duration::from_secs(30)));
+        assert_eq!(estimate_remaining(Duration::from_secs(10), 100, 100), Some(Duration::ZERO));
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3m05s");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h02m");
+    }
+
     #[test]
     fn test_progress_handler() {
         let handler = ProgressHandler::new();
@@ -0,0 +1,163 @@
+//! Registry mapping format names to handler factories
+
+use crate::formats::{AzwHandler, CbzHandler, EpubHandler, Fb2Handler, MobiHandler, PdfHandler, TxtHandler};
+use crate::traits::EbookOperator;
+use crate::{EbookError, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Operations a built-in format supports, one row of [`FORMAT_CAPABILITIES`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The format can be opened and read
+    pub read: bool,
+    /// A book can be written from text content (`ebook write`)
+    pub write: bool,
+    /// Embedded images can be extracted
+    pub extract_images: bool,
+    /// `optimize` can shrink the book's images
+    pub optimize: bool,
+}
+
+const fn capabilities(read: bool, write: bool, extract_images: bool, optimize: bool) -> Capabilities {
+    Capabilities { read, write, extract_images, optimize }
+}
+
+/// What each built-in format supports, the single source of truth for the `formats` listing;
+/// conversions come from [`crate::SUPPORTED_CONVERSIONS`]
+pub const FORMAT_CAPABILITIES: &[(&str, Capabilities)] = &[
+    ("azw", capabilities(true, true, true, false)),
+    ("cbz", capabilities(true, false, true, true)),
+    ("epub", capabilities(true, true, true, true)),
+    ("fb2", capabilities(true, true, false, false)),
+    ("mobi", capabilities(true, true, true, false)),
+    ("pdf", capabilities(true, true, false, false)),
+    ("txt", capabilities(true, true, false, false)),
+];
+
+/// A format's row of the capability matrix, conversions included
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FormatCapabilities {
+    pub format: &'static str,
+    pub read: bool,
+    pub write: bool,
+    pub extract_images: bool,
+    pub optimize: bool,
+    /// Formats that convert into this one
+    pub convert_from: Vec<&'static str>,
+    /// Formats this one converts to
+    pub convert_to: Vec<&'static str>,
+}
+
+/// Capabilities of every built-in format, sorted by format name
+pub fn capability_matrix() -> Vec<FormatCapabilities> {
+    FORMAT_CAPABILITIES
+        .iter()
+        .map(|&(format, capabilities)| {
+            let mut convert_from: Vec<&str> = crate::SUPPORTED_CONVERSIONS
+                .iter()
+                .filter(|(_, target)| *target == format)
+                .map(|(source, _)| *source)
+                .collect();
+            convert_from.sort_unstable();
+            let mut convert_to = crate::Converter::supported_targets(format);
+            convert_to.sort_unstable();
+            FormatCapabilities {
+                format,
+                read: capabilities.read,
+                write: capabilities.write,
+                extract_images: capabilities.extract_images,
+                optimize: capabilities.optimize,
+                convert_from,
+                convert_to,
+            }
+        })
+        .collect()
+}
+
+/// Factory producing a fresh handler for a format
+pub type HandlerFactory = Box<dyn Fn() -> Box<dyn EbookOperator> + Send + Sync>;
+
+/// Maps format names (e.g. "epub") to handler factories so dispatch is a single lookup
+pub struct FormatRegistry {
+    factories: BTreeMap<String, HandlerFactory>,
+}
+
+impl FormatRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+        }
+    }
+
+    /// Create a registry with all built-in formats registered
+    pub fn with_builtin_formats() -> Self {
+        let mut registry = Self::new();
+        registry.register("epub", || Box::new(EpubHandler::new()));
+        registry.register("mobi", || Box::new(MobiHandler::new()));
+        registry.register("azw", || Box::new(AzwHandler::new()));
+        registry.register("azw3", || Box::new(AzwHandler::new()));
+        registry.register("fb2", || Box::new(Fb2Handler::new()));
+        registry.register("cbz", || Box::new(CbzHandler::new()));
+        registry.register("txt", || Box::new(TxtHandler::new()));
+        registry.register("pdf", || Box::new(PdfHandler::new()));
+        registry
+    }
+
+    /// Register (or replace) the factory for a format
+    pub fn register<F>(&mut self, format: impl Into<String>, factory: F)
+    where
+        F: Fn() -> Box<dyn EbookOperator> + Send + Sync + 'static,
+    {
+        self.factories.insert(format.into().to_lowercase(), Box::new(factory));
+    }
+
+    /// Check whether a format is registered
+    pub fn contains(&self, format: &str) -> bool {
+        self.factories.contains_key(&format.to_lowercase())
+    }
+
+    /// Registered format names in sorted order
+    pub fn formats(&self) -> Vec<&str> {
+        self.factories.keys().map(String::as_str).collect()
+    }
+
+    /// Create a new, empty handler for a format
+    pub fn create(&self, format: &str) -> Result<Box<dyn EbookOperator>> {
+        self.factories
+            .get(&format.to_lowercase())
+            .map(|factory| factory())
+            .ok_or_else(|| EbookError::UnsupportedFormat(format.to_string()))
+    }
+
+    /// Resolve the format of a path, preferring a registered extension over built-in detection
+    pub fn format_for_path(&self, path: &Path) -> Result<String> {
+        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            let extension = extension.to_lowercase();
+            if self.contains(&extension) {
+                return Ok(extension);
+            }
+        }
+        crate::utils::detect_format(path)
+    }
+
+    /// Create a handler for the file's format and read the file into it
+    pub fn open(&self, path: &Path) -> Result<Box<dyn EbookOperator>> {
+        self.open_as(path, &self.format_for_path(path)?)
+    }
+
+    /// Read the file as `format`, whatever its name says
+    pub fn open_as(&self, path: &Path, format: &str) -> Result<Box<dyn EbookOperator>> {
+        let mut handler = self.create(format)?;
+        handler.read_from_file(path)?;
+        Ok(handler)
+    }
+}
+
+impl Default for FormatRegistry {
+    fn default() -> Self {
+        Self::with_builtin_formats()
+    }
+}
@@ -0,0 +1,70 @@
+//! Rasterizing PDF pages, for the cover and thumbnail of books that don't declare a cover
+
+use crate::traits::ImageData;
+use crate::{EbookError, Result};
+
+/// Width in pixels a page is rendered at when it becomes a cover
+pub const COVER_WIDTH: u32 = 1200;
+
+/// Environment variable naming the directory that holds the pdfium library
+pub const PDFIUM_DIR_ENV: &str = "PDFIUM_DYNAMIC_LIB_PATH";
+
+/// Turns a PDF page into an image
+pub trait PageRenderer {
+    /// Render page `page_number` (1-based) of the PDF in `pdf` to a PNG `width` pixels wide
+    fn render_page(&self, pdf: &[u8], page_number: u32, width: u32) -> Result<ImageData>;
+}
+
+/// Rendering through the pdfium library, loaded at run time
+#[cfg(feature = "pdf-render")]
+#[derive(Debug, Default)]
+pub struct PdfiumRenderer {
+    /// Directory holding the pdfium library; `$PDFIUM_DYNAMIC_LIB_PATH`, then the system's when `None`
+    pub library_dir: Option<String>,
+}
+
+#[cfg(feature = "pdf-render")]
+impl PageRenderer for PdfiumRenderer {
+    fn render_page(&self, pdf: &[u8], page_number: u32, width: u32) -> Result<ImageData> {
+        use pdfium_render::prelude::{PdfRenderConfig, Pdfium};
+
+        let render_error = |e: pdfium_render::prelude::PdfiumError| EbookError::Render(e.to_string());
+        let library_dir = self.library_dir.clone().or_else(|| std::env::var(PDFIUM_DIR_ENV).ok());
+        let bindings = match library_dir {
+            Some(dir) => Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(&dir)),
+            None => Pdfium::bind_to_system_library(),
+        }
+        .map_err(|e| EbookError::Render(format!("could not load pdfium: {e}")))?;
+        let pdfium = Pdfium::new(bindings);
+
+        let document = pdfium.load_pdf_from_byte_slice(pdf, None).map_err(render_error)?;
+        let index = page_number
+            .checked_sub(1)
+            .and_then(|index| u16::try_from(index).ok())
+            .ok_or_else(|| EbookError::NotFound(format!("page {page_number}")))?;
+        let page = document.pages().get(index).map_err(render_error)?;
+        let config = PdfRenderConfig::new().set_target_width(width.min(i32::MAX as u32) as i32);
+        let bitmap = page.render_with_config(&config).map_err(render_error)?;
+
+        let mut png = std::io::Cursor::new(Vec::new());
+        bitmap
+            .as_image()
+            .write_to(&mut png, image::ImageFormat::Png)
+            .map_err(|e| EbookError::Render(e.to_string()))?;
+        Ok(ImageData::new(format!("page{page_number:04}.png"), "image/png".to_string(), png.into_inner()))
+    }
+}
+
+/// The renderer this build ships with; an error when built without the `pdf-render` feature
+pub fn default_renderer() -> Result<Box<dyn PageRenderer>> {
+    #[cfg(feature = "pdf-render")]
+    {
+        Ok(Box::new(PdfiumRenderer::default()))
+    }
+    #[cfg(not(feature = "pdf-render"))]
+    {
+        Err(EbookError::NotSupported(
+            "rendering PDF pages is not available in this build; rebuild with `--features pdf-render`".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,111 @@
+//! Recover entries from zip-based ebooks (EPUB, CBZ) whose central directory is damaged.
+//!
+//! The central directory at the end of a zip is only an index; every entry is also preceded
+//! by a local file header (`PK\x03\x04`) carrying its name and sizes. When the index is
+//! unreadable, scanning for those headers still finds every intact entry.
+
+use crate::Result;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::write::{FileOptions, ZipWriter};
+
+const LOCAL_FILE_HEADER: &[u8] = b"PK\x03\x04";
+const END_OF_CENTRAL_DIRECTORY: &[u8] = b"PK\x05\x06";
+const LOCAL_HEADER_LEN: usize = 30;
+
+/// One entry recovered from a damaged archive
+#[derive(Debug, Clone)]
+pub struct SalvagedEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// What a salvage pass recovered
+#[derive(Debug, Clone, Default)]
+pub struct SalvageReport {
+    pub entries: Vec<SalvagedEntry>,
+    /// Entry count from the end-of-central-directory record, if that survived
+    pub expected: Option<usize>,
+    /// Local headers found whose data could not be recovered (bad CRC, truncated, data descriptors)
+    pub damaged: usize,
+}
+
+impl SalvageReport {
+    /// Write the recovered entries, in their original order, as a fresh archive.
+    /// An EPUB `mimetype` entry is stored uncompressed as the spec requires.
+    pub fn write_archive(&self, path: &Path) -> Result<()> {
+        let mut zip = ZipWriter::new(std::fs::File::create(path)?);
+        for entry in &self.entries {
+            let method = if entry.name == "mimetype" {
+                zip::CompressionMethod::Stored
+            } else {
+                zip::CompressionMethod::Deflated
+            };
+            zip.start_file(entry.name.as_str(), FileOptions::<()>::default().compression_method(method))?;
+            zip.write_all(&entry.data)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+}
+
+/// Scan `data` for local file headers and extract every entry that decodes with a valid CRC
+pub fn salvage_zip(data: &[u8]) -> SalvageReport {
+    let mut report = SalvageReport {
+        expected: expected_entries(data),
+        ..Default::default()
+    };
+
+    let mut offset = 0;
+    while let Some(found) = find(data, LOCAL_FILE_HEADER, offset) {
+        match read_entry(&data[found..]) {
+            Some((entry, len)) => {
+                if !entry.name.ends_with('/') {
+                    report.entries.push(entry);
+                }
+                offset = found + len;
+            }
+            None => {
+                report.damaged += 1;
+                offset = found + LOCAL_FILE_HEADER.len();
+            }
+        }
+    }
+
+    report
+}
+
+/// Decode the entry whose local header starts `data`, returning it with its total length
+fn read_entry(data: &[u8]) -> Option<(SalvagedEntry, usize)> {
+    let header = data.get(..LOCAL_HEADER_LEN)?;
+    let compressed_size = u32::from_le_bytes(header[18..22].try_into().ok()?) as usize;
+    let name_len = u16::from_le_bytes(header[26..28].try_into().ok()?) as usize;
+    let extra_len = u16::from_le_bytes(header[28..30].try_into().ok()?) as usize;
+    let len = LOCAL_HEADER_LEN + name_len + extra_len + compressed_size;
+
+    let mut cursor = Cursor::new(data.get(..len)?);
+    let mut file = zip::read::read_zipfile_from_stream(&mut cursor).ok()??;
+    let name = file.name().to_string();
+    let mut contents = Vec::new();
+    // Reading to the end verifies the CRC
+    file.read_to_end(&mut contents).ok()?;
+
+    Some((SalvagedEntry { name, data: contents }, len))
+}
+
+/// Total entry count recorded in the last end-of-central-directory record
+fn expected_entries(data: &[u8]) -> Option<usize> {
+    let start = data
+        .windows(END_OF_CENTRAL_DIRECTORY.len())
+        .rposition(|window| window == END_OF_CENTRAL_DIRECTORY)?;
+    let count = data.get(start + 10..start + 12)?;
+    Some(u16::from_le_bytes([count[0], count[1]]) as usize)
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| from + position)
+}
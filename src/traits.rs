@@ -1,4 +1,5 @@
-use crate::{Metadata, Result};
+use crate::{EbookError, Metadata, Result};
+use serde::Serialize;
 use std::path::Path;
 use std::io::{Read, Write};
 use std::path::PathBuf;
@@ -7,20 +8,67 @@ use std::time::SystemTime;
 
 static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
 
-fn unique_temp_file(name: &str) -> PathBuf {
-    let nanos = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_nanos();
-    let c = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
-    std::env::temp_dir().join(format!("{name}_{}_{}_{}.tmp", std::process::id(), nanos, c))
+/// Environment variable that overrides where the default byte/stream I/O keeps its temp files
+pub const TEMP_DIR_ENV: &str = "EBOOK_TMPDIR";
+
+/// Directory for temp files: `$EBOOK_TMPDIR` when set, else the system temp directory
+pub fn temp_dir() -> PathBuf {
+    std::env::var_os(TEMP_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Uniquely named temp file that is removed when dropped, also while unwinding from a panic
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    /// A temp file in [`temp_dir`]
+    pub fn new(name: &str) -> Self {
+        Self(temp_dir().join(Self::unique_name(name)))
+    }
+
+    /// A hidden temp file in the directory of `path`, so [`TempFile::persist`] onto `path` is
+    /// a rename within one filesystem
+    pub fn beside(path: &Path) -> Self {
+        let name = path.file_name().map_or_else(|| "ebook".into(), |name| name.to_string_lossy());
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Self(dir.join(format!(".{}", Self::unique_name(&name))))
+    }
+
+    fn unique_name(name: &str) -> String {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let c = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        format!("{name}_{}_{}_{}.tmp", std::process::id(), nanos, c)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Move the file to `target`, replacing what is there, instead of removing it
+    pub fn persist(self, target: &Path) -> std::io::Result<()> {
+        std::fs::rename(&self.0, target)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
 }
 
 pub trait EbookReader {
     fn read_from_file(&mut self, path: &Path) -> Result<()>;
 
     /// Read ebook from a generic reader (for streaming large files)
-    fn read_from_reader<R: Read>(&mut self, reader: R) -> Result<()> {
+    fn read_from_reader<R: Read>(&mut self, reader: R) -> Result<()>
+    where
+        Self: Sized,
+    {
         // Default implementation buffers the entire reader
         let mut buffer = Vec::new();
         let mut reader = reader;
@@ -31,20 +79,35 @@ pub trait EbookReader {
     /// Read ebook from bytes (helper for streaming)
     fn read_from_bytes(&mut self, data: &[u8]) -> Result<()> {
         // Default implementation creates a temporary file
-        let temp_file = unique_temp_file("ebook_temp_read");
+        let temp_file = TempFile::new("ebook_temp_read");
         {
-            let mut file = std::fs::File::create(&temp_file)?;
+            let mut file = std::fs::File::create(temp_file.path())?;
             file.write_all(data)?;
         }
-        let result = self.read_from_file(&temp_file);
-        let _ = std::fs::remove_file(&temp_file);
-        result
+        self.read_from_file(temp_file.path())
     }
 
     fn get_metadata(&self) -> Result<Metadata>;
     fn get_content(&self) -> Result<String>;
     fn get_toc(&self) -> Result<Vec<TocEntry>>;
     fn extract_images(&self) -> Result<Vec<ImageData>>;
+
+    /// The book's chapters in reading order. By default the whole content is one chapter
+    /// titled after the book.
+    fn get_chapters(&self) -> Result<Vec<ChapterView>> {
+        let title = self.get_metadata()?.title.unwrap_or_else(|| "Untitled".to_string());
+        Ok(vec![ChapterView::new(title, self.get_content()?)])
+    }
+
+    /// Content of a single chapter (0-based; a page for page-based formats), `None` if out of range
+    fn get_chapter(&self, _index: usize) -> Result<Option<String>> {
+        Err(EbookError::NotSupported("Selecting a single chapter is not supported for this format".to_string()))
+    }
+
+    /// Annotations and landmarks stored in the book (read-only, distinct from the TOC)
+    fn get_annotations(&self) -> Result<Vec<Annotation>> {
+        Err(EbookError::NotSupported("Annotations are not supported for this format".to_string()))
+    }
 }
 
 pub trait EbookWriter {
@@ -55,7 +118,10 @@ pub trait EbookWriter {
     fn write_to_file(&self, path: &Path) -> Result<()>;
 
     /// Write ebook to a generic writer (for streaming large files)
-    fn write_to_writer<W: Write>(&self, writer: W) -> Result<()> {
+    fn write_to_writer<W: Write>(&self, writer: W) -> Result<()>
+    where
+        Self: Sized,
+    {
         // Default implementation writes to a buffer first
         let mut buffer = Vec::new();
         {
@@ -68,16 +134,18 @@ pub trait EbookWriter {
     }
 
     /// Internal write method for streaming (override in handlers)
-    fn write_to_writer_internal<W: Write>(&self, writer: &mut W) -> Result<()> {
+    fn write_to_writer_internal<W: Write>(&self, writer: &mut W) -> Result<()>
+    where
+        Self: Sized,
+    {
         // Default implementation: create temp file and copy it
         use std::io::Read;
-        let temp_file = unique_temp_file("ebook_temp_write");
-        self.write_to_file(&temp_file)?;
-        let mut file = std::fs::File::open(&temp_file)?;
+        let temp_file = TempFile::new("ebook_temp_write");
+        self.write_to_file(temp_file.path())?;
+        let mut file = std::fs::File::open(temp_file.path())?;
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)?;
         writer.write_all(&buffer)?;
-        let _ = std::fs::remove_file(&temp_file);
         Ok(())
     }
 }
@@ -88,6 +156,27 @@ pub trait EbookOperator: EbookReader + EbookWriter {
     fn repair(&mut self) -> Result<()>;
 }
 
+/// A chapter as returned by `EbookReader::get_chapters`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChapterView {
+    pub title: String,
+    /// Chapter content in the book's own markup (XHTML for EPUB, plain text for TXT)
+    pub content: String,
+    /// Where the chapter lives, e.g. the EPUB spine file; `None` for formats without addresses
+    pub href: Option<String>,
+}
+
+impl ChapterView {
+    pub fn new(title: impl Into<String>, content: impl Into<String>) -> Self {
+        Self { title: title.into(), content: content.into(), href: None }
+    }
+
+    pub fn with_href(mut self, href: impl Into<String>) -> Self {
+        self.href = Some(href.into());
+        self
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TocEntry {
     pub id: u32,
@@ -97,6 +186,54 @@ pub struct TocEntry {
     pub children: Vec<TocEntry>,
 }
 
+/// A PDF annotation (`/Annots`) or EPUB 3 landmark
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Annotation {
+    /// Annotation subtype (e.g. "Text", "Link", "Highlight") or landmark type (e.g. "bodymatter")
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub location: AnnotationLocation,
+    /// Annotation contents, link target or landmark label
+    pub text: String,
+}
+
+/// Where an annotation points: a 0-based page index or a document href
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationLocation {
+    Page(usize),
+    Href(String),
+}
+
+/// A file inside an EPUB or CBZ container, as listed in the zip index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub is_image: bool,
+}
+
+impl ArchiveEntry {
+    /// Every entry of `archive` in archive order, read from the index without decompressing
+    pub(crate) fn list<R: Read + std::io::Seek>(
+        archive: &mut zip::ZipArchive<R>,
+        is_image: impl Fn(&str) -> bool,
+    ) -> Result<Vec<Self>> {
+        (0..archive.len())
+            .map(|i| {
+                let file = archive.by_index_raw(i)?;
+                Ok(Self {
+                    name: crate::utils::normalize_entry_name(file.name()),
+                    compressed_size: file.compressed_size(),
+                    uncompressed_size: file.size(),
+                    is_image: is_image(file.name()),
+                })
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ImageData {
     pub name: String,
@@ -124,6 +261,68 @@ impl TocEntry {
         self.href = Some(href);
         self
     }
+
+    /// This entry followed by all its descendants, in reading order
+    pub fn flatten(&self) -> Vec<&TocEntry> {
+        flatten_toc(std::slice::from_ref(self)).into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// The first of this entry and its descendants pointing at `href`. An `href` without a
+    /// fragment also matches entries pointing into that file, e.g. `ch1.xhtml#s2`.
+    pub fn find_by_href(&self, href: &str) -> Option<&TocEntry> {
+        let entries = self.flatten();
+        let points_into = |entry: &TocEntry| {
+            !href.contains('#') && entry.href.as_deref().and_then(|target| target.split('#').next()) == Some(href)
+        };
+        entries
+            .iter()
+            .find(|entry| entry.href.as_deref() == Some(href))
+            .or_else(|| entries.iter().find(|entry| points_into(entry)))
+            .copied()
+    }
+
+    /// Levels of nesting from this entry down: 1 for an entry without children
+    pub fn depth(&self) -> usize {
+        1 + self.children.iter().map(TocEntry::depth).max().unwrap_or(0)
+    }
+}
+
+/// Every entry of a TOC with how deeply it is nested (0 for top-level entries), in reading
+/// order
+pub fn flatten_toc(entries: &[TocEntry]) -> Vec<(usize, &TocEntry)> {
+    fn walk<'a>(entries: &'a [TocEntry], depth: usize, flat: &mut Vec<(usize, &'a TocEntry)>) {
+        for entry in entries {
+            flat.push((depth, entry));
+            walk(&entry.children, depth + 1, flat);
+        }
+    }
+    let mut flat = Vec::new();
+    walk(entries, 0, &mut flat);
+    flat
+}
+
+/// A TOC as a nested Markdown list, entries with an href as links
+pub fn toc_to_markdown(entries: &[TocEntry]) -> String {
+    flatten_toc(entries)
+        .into_iter()
+        .map(|(depth, entry)| {
+            let indent = "  ".repeat(depth);
+            match &entry.href {
+                Some(href) => format!("{indent}- [{}]({href})\n", entry.title),
+                None => format!("{indent}- {}\n", entry.title),
+            }
+        })
+        .collect()
+}
+
+impl Annotation {
+    pub fn new(kind: impl Into<String>, location: AnnotationLocation, text: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            location,
+            text: text.into(),
+        }
+    }
 }
 
 impl ImageData {
@@ -134,4 +333,55 @@ impl ImageData {
             data,
         }
     }
+
+    /// Optimized copy of this image, leaving it unchanged; see [`ImageOptimizer::optimize_into`]
+    ///
+    /// [`ImageOptimizer::optimize_into`]: crate::image_optimizer::ImageOptimizer::optimize_into
+    pub fn with_optimized(&self, optimizer: &crate::image_optimizer::ImageOptimizer) -> Result<Self> {
+        optimizer.optimize_into(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nested_toc() -> Vec<TocEntry> {
+        let mut part = TocEntry::new("Part I".to_string(), 0).with_href("part1.xhtml".to_string());
+        let mut chapter = TocEntry::new("Chapter 1".to_string(), 1).with_href("ch1.xhtml".to_string());
+        chapter.children.push(TocEntry::new("Section 1.1".to_string(), 2).with_href("ch1.xhtml#s1".to_string()));
+        part.children.push(chapter);
+        part.children.push(TocEntry::new("Chapter 2".to_string(), 1).with_href("ch2.xhtml#start".to_string()));
+        vec![part, TocEntry::new("Afterword".to_string(), 0)]
+    }
+
+    #[test]
+    fn test_toc_flatten_order_and_depth() {
+        let toc = nested_toc();
+        let titles: Vec<&str> = toc[0].flatten().iter().map(|entry| entry.title.as_str()).collect();
+        assert_eq!(titles, ["Part I", "Chapter 1", "Section 1.1", "Chapter 2"]);
+        let depths: Vec<(usize, &str)> = flatten_toc(&toc).into_iter().map(|(depth, entry)| (depth, entry.title.as_str())).collect();
+        assert_eq!(depths, [(0, "Part I"), (1, "Chapter 1"), (2, "Section 1.1"), (1, "Chapter 2"), (0, "Afterword")]);
+        assert_eq!(toc[0].depth(), 3);
+        assert_eq!(toc[1].depth(), 1);
+    }
+
+    #[test]
+    fn test_toc_find_by_href() {
+        let toc = nested_toc();
+        assert_eq!(toc[0].find_by_href("ch1.xhtml#s1").map(|entry| entry.title.as_str()), Some("Section 1.1"));
+        // The entry for the file itself wins over ones pointing into it
+        assert_eq!(toc[0].find_by_href("ch1.xhtml").map(|entry| entry.title.as_str()), Some("Chapter 1"));
+        assert_eq!(toc[0].find_by_href("ch2.xhtml").map(|entry| entry.title.as_str()), Some("Chapter 2"));
+        assert!(toc[0].find_by_href("ch2.xhtml#end").is_none());
+        assert!(toc[1].find_by_href("part1.xhtml").is_none());
+    }
+
+    #[test]
+    fn test_toc_to_markdown() {
+        assert_eq!(
+            toc_to_markdown(&nested_toc()),
+            "- [Part I](part1.xhtml)\n  - [Chapter 1](ch1.xhtml)\n    - [Section 1.1](ch1.xhtml#s1)\n  - [Chapter 2](ch2.xhtml#start)\n- Afterword\n"
+        );
+    }
 }
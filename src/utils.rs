@@ -1,5 +1,8 @@
 use crate::{EbookError, Result};
-use std::path::Path;
+use crate::traits::{EbookOperator, ImageData, TocEntry};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 pub fn detect_format(path: &Path) -> Result<String> {
     let extension = path
@@ -7,20 +10,49 @@ pub fn detect_format(path: &Path) -> Result<String> {
         .and_then(|e| e.to_str())
         .ok_or_else(|| EbookError::UnsupportedFormat("No file extension".to_string()))?;
 
-    match extension.to_lowercase().as_str() {
-        "epub" => Ok("epub".to_string()),
-        "mobi" => Ok("mobi".to_string()),
-        "azw" | "azw3" => Ok("azw".to_string()),
-        "fb2" => Ok("fb2".to_string()),
-        "cbz" => Ok("cbz".to_string()),
-        "txt" => Ok("txt".to_string()),
-        "pdf" => Ok("pdf".to_string()),
-        ext => Err(EbookError::UnsupportedFormat(format!(
-            "Unsupported extension: {ext}"
-        ))),
+    format_name(extension)
+        .map(str::to_string)
+        .ok_or_else(|| EbookError::UnsupportedFormat(format!("Unsupported extension: {}", extension.to_lowercase())))
+}
+
+/// Canonical name of a format given by name or extension (`AZW3` is `azw`), `None` if unknown
+pub fn format_name(name: &str) -> Option<&'static str> {
+    match name.to_lowercase().as_str() {
+        "epub" => Some("epub"),
+        "mobi" => Some("mobi"),
+        "azw" | "azw3" => Some("azw"),
+        "fb2" => Some("fb2"),
+        "cbz" => Some("cbz"),
+        "txt" => Some("txt"),
+        "pdf" => Some("pdf"),
+        _ => None,
     }
 }
 
+/// Format of a book from its leading bytes rather than its name, `None` when unrecognized
+pub fn detect_format_from_content(data: &[u8]) -> Option<String> {
+    let format = if data.starts_with(b"%PDF") {
+        "pdf"
+    } else if data.starts_with(b"PK\x03\x04") {
+        // Entry names are stored in the clear, so this works even when the zip index is damaged
+        let is_epub = data.get(30..58) == Some(b"mimetypeapplication/epub+zip".as_slice())
+            || data.windows(22).any(|window| window == b"META-INF/container.xml");
+        if is_epub { "epub" } else { "cbz" }
+    } else if data.get(60..68) == Some(b"BOOKMOBI".as_slice()) {
+        "mobi"
+    } else if String::from_utf8_lossy(&data[..data.len().min(1024)]).contains("<FictionBook") {
+        "fb2"
+    } else if data.starts_with(&[0xFF, 0xFE]) || data.starts_with(&[0xFE, 0xFF]) {
+        // UTF-16 text, whose zero bytes would otherwise look binary
+        "txt"
+    } else if !data.is_empty() && !data[..data.len().min(4096)].contains(&0) {
+        "txt"
+    } else {
+        return None;
+    };
+    Some(format.to_string())
+}
+
 pub fn sanitize_filename(name: &str) -> String {
     name.chars()
         .map(|c| match c {
@@ -30,6 +62,158 @@ pub fn sanitize_filename(name: &str) -> String {
         .collect()
 }
 
+/// A zip entry name with Windows `\` separators turned into `/` and any leading `/` removed
+pub fn normalize_entry_name(name: &str) -> String {
+    name.replace('\\', "/").trim_start_matches('/').to_string()
+}
+
+/// Open the entry `name` of `archive`, matching entry names after normalizing their separators
+pub fn zip_entry<'a, R: std::io::Read + std::io::Seek>(
+    archive: &'a mut zip::ZipArchive<R>,
+    name: &str,
+) -> zip::result::ZipResult<zip::read::ZipFile<'a>> {
+    let wanted = normalize_entry_name(name);
+    let index = archive
+        .index_for_name(name)
+        .or_else(|| (0..archive.len()).find(|&i| archive.name_for_index(i).is_some_and(|n| normalize_entry_name(n) == wanted)))
+        .ok_or(zip::result::ZipError::FileNotFound)?;
+    archive.by_index(index)
+}
+
+/// Environment variable overriding [`ExtractionLimits::max_total_bytes`], e.g. `16GB`
+pub const MAX_UNCOMPRESSED_SIZE_ENV: &str = "EBOOK_MAX_UNCOMPRESSED_SIZE";
+
+/// Bounds on what reading an archive may decompress, so a zip bomb fails with an error
+/// instead of exhausting memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractionLimits {
+    /// Most bytes all entries together may decompress to
+    pub max_total_bytes: u64,
+    /// Highest uncompressed-to-compressed ratio of an entry larger than `ratio_threshold`
+    pub max_ratio: u64,
+    /// Entries decompressing to no more than this are exempt from the ratio check
+    pub ratio_threshold: u64,
+}
+
+impl Default for ExtractionLimits {
+    /// 4 GB in total (or `$EBOOK_MAX_UNCOMPRESSED_SIZE`), and a ratio of 200 past 1 MB
+    fn default() -> Self {
+        let mut max_total_bytes = 4 * 1024 * 1024 * 1024;
+        if let Ok(value) = std::env::var(MAX_UNCOMPRESSED_SIZE_ENV) {
+            match parse_byte_size(&value) {
+                Ok(bytes) => max_total_bytes = bytes,
+                Err(e) => log::warn!("Ignoring {MAX_UNCOMPRESSED_SIZE_ENV}: {e}"),
+            }
+        }
+        Self { max_total_bytes, max_ratio: 200, ratio_threshold: 1024 * 1024 }
+    }
+}
+
+impl ExtractionLimits {
+    pub fn with_max_total_bytes(mut self, bytes: u64) -> Self {
+        self.max_total_bytes = bytes;
+        self
+    }
+
+    pub fn with_max_ratio(mut self, ratio: u64) -> Self {
+        self.max_ratio = ratio;
+        self
+    }
+}
+
+/// Running total of the bytes decompressed from one archive, checked against its limits
+#[derive(Debug)]
+pub struct ExtractionBudget {
+    limits: ExtractionLimits,
+    used: u64,
+}
+
+impl ExtractionBudget {
+    pub fn new(limits: ExtractionLimits) -> Self {
+        Self { limits, used: 0 }
+    }
+
+    /// Reject an archive whose entries declare more than the remaining budget or ratio allows,
+    /// before reading any of them
+    pub fn check_declared<R: std::io::Read + std::io::Seek>(&self, archive: &mut zip::ZipArchive<R>) -> Result<()> {
+        let mut total = self.used;
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if file.size() > self.ratio_cap(file.compressed_size()) {
+                return Err(self.ratio_error(file.name(), file.compressed_size()));
+            }
+            total = total.saturating_add(file.size());
+            if total > self.limits.max_total_bytes {
+                return Err(self.total_error(file.name()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Decompress `file`, failing as soon as it outgrows the remaining budget or its ratio cap;
+    /// the sizes an entry declares can lie, so the read itself is bounded
+    pub fn read(&mut self, file: &mut zip::read::ZipFile<'_>) -> Result<Vec<u8>> {
+        use std::io::Read;
+
+        let remaining = self.limits.max_total_bytes.saturating_sub(self.used);
+        let ratio_cap = self.ratio_cap(file.compressed_size());
+        let cap = remaining.min(ratio_cap);
+        let mut data = Vec::new();
+        file.take(cap.saturating_add(1)).read_to_end(&mut data)?;
+        if data.len() as u64 > cap {
+            return Err(if ratio_cap < remaining {
+                self.ratio_error(file.name(), file.compressed_size())
+            } else {
+                self.total_error(file.name())
+            });
+        }
+        self.used += data.len() as u64;
+        Ok(data)
+    }
+
+    /// [`Self::read`] for an entry holding UTF-8 text
+    pub fn read_string(&mut self, file: &mut zip::read::ZipFile<'_>) -> Result<String> {
+        let data = self.read(file)?;
+        String::from_utf8(data).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
+    }
+
+    /// Most bytes an entry of `compressed` bytes may decompress to
+    fn ratio_cap(&self, compressed: u64) -> u64 {
+        compressed.saturating_mul(self.limits.max_ratio).max(self.limits.ratio_threshold)
+    }
+
+    fn ratio_error(&self, name: &str, compressed: u64) -> EbookError {
+        EbookError::ArchiveTooLarge(format!(
+            "{name} expands more than {}x its {} compressed size",
+            self.limits.max_ratio,
+            format_size(compressed)
+        ))
+    }
+
+    fn total_error(&self, name: &str) -> EbookError {
+        EbookError::ArchiveTooLarge(format!(
+            "decompressing {name} would exceed the {} limit",
+            format_size(self.limits.max_total_bytes)
+        ))
+    }
+}
+
+/// Parse a byte count with an optional KB/MB/GB suffix (powers of 1024)
+pub fn parse_byte_size(value: &str) -> std::result::Result<u64, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().map_err(|_| format!("invalid size '{value}'"))?;
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("unknown size unit '{unit}' (use B, KB, MB or GB)")),
+    };
+    number.checked_mul(multiplier).ok_or_else(|| format!("size '{value}' is too large"))
+}
+
 pub fn guess_mime_type(filename: &str) -> String {
     let extension = Path::new(filename)
         .extension()
@@ -49,3 +233,540 @@ pub fn guess_mime_type(filename: &str) -> String {
     }
     .to_string()
 }
+
+/// Human-readable byte count in powers of 1024, e.g. `512 B`, `1.5 KB`, `3.2 MB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// How much smaller `new` is than `original`, in percent (0 when nothing shrank)
+pub fn percent_reduction(original: u64, new: u64) -> f64 {
+    if original == 0 || new >= original {
+        return 0.0;
+    }
+    (original - new) as f64 / original as f64 * 100.0
+}
+
+/// Compare two names so that embedded numbers sort numerically ("page2" < "page10")
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ca), Some(cb)) if ca.is_ascii_digit() && cb.is_ascii_digit() => {
+                let mut num_a = String::new();
+                while let Some(c) = a_chars.peek().copied().filter(|c| c.is_ascii_digit()) {
+                    num_a.push(c);
+                    a_chars.next();
+                }
+                let mut num_b = String::new();
+                while let Some(c) = b_chars.peek().copied().filter(|c| c.is_ascii_digit()) {
+                    num_b.push(c);
+                    b_chars.next();
+                }
+
+                let trimmed_a = num_a.trim_start_matches('0');
+                let trimmed_b = num_b.trim_start_matches('0');
+                let ordering = trimmed_a
+                    .len()
+                    .cmp(&trimmed_b.len())
+                    .then_with(|| trimmed_a.cmp(trimmed_b))
+                    .then_with(|| num_a.len().cmp(&num_b.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(ca), Some(cb)) => {
+                if ca != cb {
+                    return ca.cmp(&cb);
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+/// Undo soft hyphenation at line ends: `inter-\nnational` becomes `international`.
+///
+/// A break is joined only when a line ends in a letter followed by `-` and the
+/// next line starts with a lowercase letter, so hyphenated compounds within a
+/// line and list items or headings on the next line are kept as they are.
+pub fn dehyphenate(text: &str) -> String {
+    let mut lines: Vec<String> = text.split('\n').map(String::from).collect();
+    let mut result = Vec::with_capacity(lines.len());
+
+    let mut i = 0;
+    while i < lines.len() {
+        let mut line = std::mem::take(&mut lines[i]);
+        while i + 1 < lines.len() && is_soft_hyphen_break(&line, &lines[i + 1]) {
+            // Move the rest of the broken word up; whatever follows it stays on its own line
+            let next = std::mem::take(&mut lines[i + 1]);
+            let next = next.trim_start();
+            let (tail, rest) = next.split_once(char::is_whitespace).unwrap_or((next, ""));
+            line.truncate(line.trim_end().len() - 1);
+            line.push_str(tail);
+
+            let rest = rest.trim_start();
+            if rest.is_empty() {
+                i += 1;
+            } else {
+                lines[i + 1] = rest.to_string();
+                break;
+            }
+        }
+        result.push(line);
+        i += 1;
+    }
+
+    result.join("\n")
+}
+
+fn is_soft_hyphen_break(line: &str, next: &str) -> bool {
+    line.trim_end()
+        .strip_suffix('-')
+        .and_then(|head| head.chars().last())
+        .is_some_and(char::is_alphabetic)
+        && next.trim_start().chars().next().is_some_and(char::is_lowercase)
+}
+
+/// Whether a line looks like a chapter heading ("Chapter 3", "CHAPTER IV")
+pub fn is_heading_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with("Chapter ") || trimmed.starts_with("CHAPTER ")
+}
+
+/// Split text into `(heading, section)` pairs at heading lines; each section starts with
+/// its heading, and any text before the first heading is kept with the first section.
+/// Returns an empty list when the text has no headings.
+pub fn split_at_headings(content: &str) -> Vec<(String, String)> {
+    let mut sections: Vec<(String, String)> = Vec::new();
+    let mut preamble = String::new();
+
+    for line in content.lines() {
+        if is_heading_line(line) {
+            let mut body = std::mem::take(&mut preamble);
+            body.push_str(line);
+            body.push('\n');
+            sections.push((line.trim().to_string(), body));
+        } else {
+            let body = match sections.last_mut() {
+                Some((_, body)) => body,
+                None => &mut preamble,
+            };
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+
+    sections
+}
+
+/// Split `text` at paragraph breaks (blank lines) into pieces of at most `max_bytes`. A
+/// paragraph longer than that becomes a piece of its own rather than being cut mid-sentence.
+pub fn split_at_paragraphs(text: &str, max_bytes: usize) -> Vec<String> {
+    let mut pieces: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let paragraphs = text.split("\n\n").map(str::trim).filter(|paragraph| !paragraph.is_empty());
+    for paragraph in paragraphs {
+        if !current.is_empty() && current.len() + 2 + paragraph.len() > max_bytes {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(paragraph);
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// All chapters (pages for PDF) in order, or `None` if the format has no chapter access
+pub fn collect_chapters(book: &dyn EbookOperator) -> Result<Option<Vec<String>>> {
+    let mut chapters = Vec::new();
+    loop {
+        match book.get_chapter(chapters.len()) {
+            Ok(Some(chapter)) => chapters.push(chapter),
+            Ok(None) => return Ok(Some(chapters)),
+            Err(EbookError::NotSupported(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Synthesize a TOC from heading lines for books without a native one.
+///
+/// Chapters (pages for PDF) are scanned in order so each entry gets an approximate
+/// `#page=N` or `#chapter-N` href; formats without chapter access scan the whole text.
+pub fn toc_from_headings(book: &dyn EbookOperator) -> Result<Vec<TocEntry>> {
+    let is_pdf = book.get_metadata()?.format.as_deref() == Some("PDF");
+    let (chapters, chapter_access) = match collect_chapters(book)? {
+        Some(chapters) => (chapters, true),
+        None => (vec![book.get_content()?], false),
+    };
+
+    let mut toc = Vec::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        for line in chapter.lines().filter(|line| is_heading_line(line)) {
+            let mut entry = TocEntry::new(line.trim().to_string(), 1).with_id(toc.len() as u32);
+            if chapter_access {
+                entry = entry.with_href(if is_pdf {
+                    format!("#page={}", index + 1)
+                } else {
+                    format!("#chapter-{}", index + 1)
+                });
+            }
+            toc.push(entry);
+        }
+    }
+
+    Ok(toc)
+}
+
+/// Write images into `dir` and return the paths written.
+///
+/// Without `rename` the in-archive folder structure is mirrored (so equal basenames in
+/// different folders don't collide); with `rename` images are written flat as
+/// `page0001.ext`, `page0002.ext`, ... in natural name order.
+pub fn write_images_to_dir(images: &[ImageData], dir: &Path, rename: bool) -> Result<Vec<PathBuf>> {
+    if rename {
+        let mut ordered = images.to_vec();
+        ordered.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+        return write_images_named(&ordered, dir, |idx, image| Ok(page_file_name(idx, image)));
+    }
+
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::with_capacity(images.len());
+    for image in images {
+        let path = dir.join(safe_relative_path(&image.name));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, &image.data)?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// `page0001.ext`-style name for the image at 0-based position `idx`
+pub fn page_file_name(idx: usize, image: &ImageData) -> String {
+    format!("page{:04}.{}", idx + 1, image_extension(image))
+}
+
+/// Write images flat into `dir` in the given order, naming each with
+/// `name_for(index, image)` (0-based index). A name already used by an earlier image gets
+/// a `-2`, `-3`, ... suffix before its extension instead of overwriting it.
+pub fn write_images_named(
+    images: &[ImageData],
+    dir: &Path,
+    name_for: impl Fn(usize, &ImageData) -> Result<String>,
+) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+
+    let mut written = Vec::with_capacity(images.len());
+    let mut taken = std::collections::HashSet::new();
+    for (idx, image) in images.iter().enumerate() {
+        let relative = safe_relative_path(&name_for(idx, image)?);
+        let mut path = dir.join(&relative);
+        let mut n = 1;
+        while taken.contains(&path) {
+            n += 1;
+            path = dir.join(suffixed_path(&relative, n));
+        }
+        std::fs::write(&path, &image.data)?;
+        taken.insert(path.clone());
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// `path` with `-n` added to its file stem (`page.png` -> `page-2.png`)
+pub(crate) fn suffixed_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let file = match path.extension() {
+        Some(extension) => format!("{stem}-{n}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{n}"),
+    };
+    path.with_file_name(file)
+}
+
+/// Values for the tokens of an output filename template
+#[derive(Debug, Clone, Default)]
+pub struct NameTemplateContext {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// 1-based position of the file in the output
+    pub index: usize,
+    pub ext: String,
+    pub chapter: Option<String>,
+}
+
+/// Expand an output filename template such as `{index:03}-{chapter}.{ext}`.
+///
+/// Tokens are `{title}`, `{author}`, `{index}`, `{ext}` and `{chapter}`; a `:0N` (or `:N`)
+/// suffix pads the value to N characters with zeros (or spaces). Substituted values are
+/// sanitized so they can't introduce path separators or other illegal characters.
+pub fn render_name_template(template: &str, context: &NameTemplateContext) -> Result<String> {
+    let mut name = String::new();
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        name.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .map(|close| open + close)
+            .ok_or_else(|| EbookError::Parse(format!("Unclosed '{{' in name template '{template}'")))?;
+        let (token, width) = match rest[open + 1..close].split_once(':') {
+            Some((token, width)) => (token, Some(width)),
+            None => (&rest[open + 1..close], None),
+        };
+
+        let value = match token {
+            "title" => context.title.clone().unwrap_or_else(|| "Untitled".to_string()),
+            "author" => context.author.clone().unwrap_or_else(|| "Unknown".to_string()),
+            "index" => context.index.to_string(),
+            "ext" => context.ext.clone(),
+            "chapter" => context.chapter.clone().unwrap_or_default(),
+            _ => return Err(EbookError::Parse(format!("Unknown token '{{{token}}}' in name template '{template}'"))),
+        };
+        let value = sanitize_filename(&value);
+
+        match width {
+            Some(width) => {
+                let pad_width: usize = width
+                    .parse()
+                    .map_err(|_| EbookError::Parse(format!("Invalid width '{width}' in name template '{template}'")))?;
+                if width.starts_with('0') {
+                    name.push_str(&format!("{value:0>pad_width$}"));
+                } else {
+                    name.push_str(&format!("{value:>pad_width$}"));
+                }
+            }
+            None => name.push_str(&value),
+        }
+        rest = &rest[close + 1..];
+    }
+    name.push_str(rest);
+
+    Ok(name)
+}
+
+/// Title for the 1-based chapter `n` from a template such as `Kapitel {n}`.
+///
+/// `{n}` is the chapter number (`{n:03}` zero-pads it to 3 digits) and `{roman}` the
+/// upper-case Roman numeral; any other text is kept as is.
+pub fn render_chapter_title(template: &str, n: usize) -> Result<String> {
+    let mut title = String::new();
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        title.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .map(|close| open + close)
+            .ok_or_else(|| EbookError::Parse(format!("Unclosed '{{' in chapter title template '{template}'")))?;
+        match &rest[open + 1..close] {
+            "n" => title.push_str(&n.to_string()),
+            "roman" => title.push_str(&to_roman(n)),
+            token => {
+                let width = token
+                    .strip_prefix("n:")
+                    .and_then(|width| width.parse::<usize>().ok())
+                    .ok_or_else(|| {
+                        EbookError::Parse(format!("Unknown token '{{{token}}}' in chapter title template '{template}'"))
+                    })?;
+                title.push_str(&format!("{n:0width$}"));
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+    title.push_str(rest);
+
+    Ok(title)
+}
+
+/// Upper-case Roman numeral for `n` (empty for 0)
+fn to_roman(mut n: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            roman.push_str(numeral);
+            n -= value;
+        }
+    }
+    roman
+}
+
+/// File extension for an image, from its name or else its MIME type
+pub fn image_extension(image: &ImageData) -> String {
+    Path::new(&image.name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_else(|| match image.mime_type.as_str() {
+            "image/jpeg" => "jpg".to_string(),
+            "image/svg+xml" => "svg".to_string(),
+            mime => mime.rsplit('/').next().unwrap_or("bin").to_string(),
+        })
+}
+
+/// Turn an archive entry name into a relative path that cannot escape the target directory
+pub(crate) fn safe_relative_path(name: &str) -> PathBuf {
+    name.split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+        .map(sanitize_filename)
+        .collect()
+}
+
+/// Common words that are frequent in one language and rare in the others
+const STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "of", "to", "is", "that", "it", "was", "with", "he", "she", "you"]),
+    ("fr", &["le", "les", "et", "est", "une", "des", "du", "dans", "pas", "il", "elle", "vous"]),
+    ("de", &["der", "die", "das", "und", "ist", "nicht", "ein", "zu", "mit", "sie", "ich", "auf"]),
+    ("es", &["el", "los", "las", "y", "es", "una", "del", "por", "con", "pero", "muy", "su"]),
+    ("it", &["il", "di", "che", "è", "non", "gli", "della", "sono", "per", "anche", "ma", "lei"]),
+    ("pt", &["não", "uma", "os", "do", "da", "em", "com", "para", "é", "mas", "ele", "ela"]),
+    ("nl", &["het", "een", "wij", "van", "niet", "dat", "op", "zijn", "ik", "je", "maar", "ook"]),
+];
+
+/// Confidence `detect_language` must reach for `Metadata::detect_language` to set a language
+pub const MIN_LANGUAGE_CONFIDENCE: f64 = 0.6;
+
+/// Guess an ISO 639-1 language code for `text`: by script for non-Latin text, otherwise by
+/// counting common stopwords. Markup tags are ignored. Returns `None` when the sample is too
+/// small or no language clearly wins.
+pub fn guess_language(text: &str) -> Option<String> {
+    detect_language(text).map(|(code, _)| code)
+}
+
+/// `guess_language` with how sure the guess is, from 0 to 1: the share of letters written in
+/// the language's script, or the share of stopword hits that went to the winning language
+pub fn detect_language(text: &str) -> Option<(String, f64)> {
+    let mut plain = String::with_capacity(text.len().min(100_000));
+    let mut in_tag = false;
+    for c in text.chars().take(100_000) {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                plain.push(' ');
+            }
+            c if !in_tag => plain.push(c),
+            _ => {}
+        }
+    }
+
+    let mut letters = 0usize;
+    let mut script_counts: HashMap<&str, usize> = HashMap::new();
+    for c in plain.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        if let Some(code) = script_language(c) {
+            *script_counts.entry(code).or_default() += 1;
+        }
+    }
+    if letters >= 10 {
+        // Japanese mixes kana with kanji, so a real share of kana is enough
+        if let Some(&kana) = script_counts.get("ja").filter(|&&kana| kana * 5 > letters) {
+            let kanji = script_counts.get("zh").copied().unwrap_or(0);
+            return Some(("ja".to_string(), (kana + kanji) as f64 / letters as f64));
+        }
+        if let Some((code, &count)) = script_counts.iter().find(|&(_, &count)| count * 2 > letters) {
+            return Some((code.to_string(), count as f64 / letters as f64));
+        }
+    }
+
+    let words: Vec<String> = plain
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .take(5_000)
+        .map(str::to_lowercase)
+        .collect();
+    let mut scores: Vec<(&str, usize)> = STOPWORDS
+        .iter()
+        .map(|(code, stopwords)| (*code, words.iter().filter(|w| stopwords.contains(&w.as_str())).count()))
+        .collect();
+    scores.sort_by_key(|&(_, hits)| std::cmp::Reverse(hits));
+
+    let (best, hits) = scores[0];
+    let total: usize = scores.iter().map(|&(_, hits)| hits).sum();
+    (hits >= 3 && hits > scores[1].1).then(|| (best.to_string(), hits as f64 / total as f64))
+}
+
+/// Language written in a non-Latin script, if `c` belongs to one
+fn script_language(c: char) -> Option<&'static str> {
+    match c {
+        '\u{3040}'..='\u{30ff}' => Some("ja"),
+        '\u{4e00}'..='\u{9fff}' => Some("zh"),
+        '\u{ac00}'..='\u{d7af}' => Some("ko"),
+        '\u{0400}'..='\u{04ff}' => Some("ru"),
+        '\u{0370}'..='\u{03ff}' => Some("el"),
+        '\u{0600}'..='\u{06ff}' => Some("ar"),
+        '\u{0590}'..='\u{05ff}' => Some("he"),
+        _ => None,
+    }
+}
+
+/// Set the modification time of the file at `path`
+pub fn set_modified_time(path: &Path, time: std::time::SystemTime) -> Result<()> {
+    std::fs::File::options().write(true).open(path)?.set_modified(time)?;
+    Ok(())
+}
+
+/// Today's date (UTC) as `YYYY-MM-DD`
+pub fn today_iso_date() -> String {
+    iso_date(unix_now())
+}
+
+/// The current UTC time as `YYYY-MM-DDThh:mm:ssZ`, the form `dcterms:modified` uses
+pub fn now_iso_timestamp() -> String {
+    let secs = unix_now();
+    format!("{}T{:02}:{:02}:{:02}Z", iso_date(secs), secs % 86_400 / 3_600, secs % 3_600 / 60, secs % 60)
+}
+
+/// Seconds since the Unix epoch, or `SOURCE_DATE_EPOCH` when set so that generated dates
+/// (EPUB `dcterms:modified`, title pages) are reproducible
+fn unix_now() -> u64 {
+    if let Some(epoch) = std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|value| value.trim().parse().ok()) {
+        return epoch;
+    }
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// UTC date of a Unix timestamp as `YYYY-MM-DD`
+fn iso_date(secs: u64) -> String {
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
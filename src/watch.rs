@@ -0,0 +1,52 @@
+//! Re-run an operation whenever a file changes
+
+use crate::{EbookError, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Call `on_change` after each burst of modifications to `path`, once it has been quiet for
+/// `debounce`. The parent directory is watched, so editors that save by replacing the file
+/// are still noticed. Runs until `on_change` returns `ControlFlow::Break`.
+pub fn watch_file(path: &Path, debounce: Duration, mut on_change: impl FnMut() -> ControlFlow<()>) -> Result<()> {
+    let path = path.canonicalize()?;
+    let dir = path
+        .parent()
+        .ok_or_else(|| EbookError::NotFound(format!("parent directory of {path:?}")))?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    let touches_file = |event: &notify::Result<Event>| {
+        event.as_ref().is_ok_and(|event| {
+            matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                && event.paths.iter().any(|changed| changed == &path)
+        })
+    };
+
+    loop {
+        match rx.recv() {
+            Ok(event) if touches_file(&event) => {}
+            Ok(_) => continue,
+            Err(_) => return Ok(()),
+        }
+
+        // Rapid saves arrive as several events; wait until they stop
+        let mut deadline = Instant::now() + debounce;
+        loop {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(event) if touches_file(&event) => deadline = Instant::now() + debounce,
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+
+        if on_change().is_break() {
+            return Ok(());
+        }
+    }
+}
@@ -2,7 +2,6 @@ use ebook_cli::formats::AzwHandler;
 use ebook_cli::traits::{EbookReader, EbookWriter, EbookOperator};
 use ebook_cli::Metadata;
 use tempfile::TempDir;
-use std::fs;
 
 #[test]
 fn test_azw_creation() {
@@ -133,6 +132,26 @@ fn test_azw_toc_extraction() {
     reader.read_from_file(&azw_path).unwrap();
     
     let toc = reader.get_toc().unwrap();
-    // TOC extraction is basic, so we just check it doesn't error
-    assert!(toc.len() >= 0);
+    let titles: Vec<&str> = toc.iter().map(|entry| entry.title.as_str()).collect();
+    assert_eq!(titles, ["Chapter 1", "Chapter 2"]);
+}
+
+#[test]
+fn test_azw_get_chapters_splits_at_page_breaks() {
+    let temp_dir = TempDir::new().unwrap();
+    let azw_path = temp_dir.path().join("breaks.azw");
+    let mut handler = AzwHandler::new();
+    handler.set_metadata(Metadata::new().with_title("Breaks")).unwrap();
+    handler
+        .set_content("Chapter 1\nFirst part.<mbp:pagebreak></mbp:pagebreak>Second part, no heading.")
+        .unwrap();
+    handler.write_to_file(&azw_path).unwrap();
+
+    let mut reader = AzwHandler::new();
+    reader.read_from_file(&azw_path).unwrap();
+    let chapters = reader.get_chapters().unwrap();
+    let titles: Vec<&str> = chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+    assert_eq!(titles, ["Chapter 1", "Chapter 2"]);
+    assert_eq!(chapters[1].content, "Second part, no heading.");
+    assert_eq!(reader.get_chapter(1).unwrap().as_deref(), Some("Second part, no heading."));
 }
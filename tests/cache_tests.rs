@@ -0,0 +1,81 @@
+//! Tests for the on-disk conversion cache
+
+use ebook_cli::{ConversionCache, ConversionOptions};
+use tempfile::TempDir;
+
+#[test]
+fn test_cache_hit_on_second_conversion() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = temp_dir.path().join("book.txt");
+    std::fs::write(&input, "Chapter 1\nThe first chapter.\n").unwrap();
+    let cache = ConversionCache::new(temp_dir.path().join("cache"));
+    let options = ConversionOptions::default();
+
+    let first = temp_dir.path().join("first.epub");
+    assert!(!cache.convert(&input, &first, "epub", &options, None).unwrap());
+    let second = temp_dir.path().join("second.epub");
+    assert!(cache.convert(&input, &second, "epub", &options, None).unwrap());
+    assert_eq!(std::fs::read(&first).unwrap(), std::fs::read(&second).unwrap());
+}
+
+#[test]
+fn test_cache_hit_creates_output_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = temp_dir.path().join("book.txt");
+    std::fs::write(&input, "Chapter 1\nThe first chapter.\n").unwrap();
+    let cache = ConversionCache::new(temp_dir.path().join("cache"));
+    let options = ConversionOptions::default();
+
+    assert!(!cache.convert(&input, &temp_dir.path().join("first.epub"), "epub", &options, None).unwrap());
+    let nested = temp_dir.path().join("out").join("nested").join("second.epub");
+    assert!(cache.convert(&input, &nested, "epub", &options, None).unwrap());
+    assert!(nested.is_file());
+}
+
+#[test]
+fn test_cache_key_changes_with_input_options_and_target() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = temp_dir.path().join("book.txt");
+    std::fs::write(&input, "Some text").unwrap();
+    let options = ConversionOptions::default();
+    let key = ConversionCache::key(&input, "epub", &options).unwrap();
+
+    assert_eq!(ConversionCache::key(&input, "epub", &options).unwrap(), key);
+    assert_ne!(ConversionCache::key(&input, "pdf", &options).unwrap(), key);
+    let header = ConversionOptions::default().with_metadata_header(true);
+    assert_ne!(ConversionCache::key(&input, "epub", &header).unwrap(), key);
+
+    std::fs::write(&input, "Some other text").unwrap();
+    assert_ne!(ConversionCache::key(&input, "epub", &options).unwrap(), key);
+}
+
+#[test]
+fn test_cli_convert_cache() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = temp_dir.path().join("book.txt");
+    std::fs::write(&input, "Cached text").unwrap();
+    let cache_dir = temp_dir.path().join("cache");
+
+    let convert = |output: &str, extra: &[&str]| {
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+        cmd.env_remove(ebook_cli::cache::CACHE_DIR_ENV)
+            .arg("convert")
+            .arg(&input)
+            .arg(temp_dir.path().join(output))
+            .args(extra)
+            .assert()
+            .success()
+    };
+
+    let cache_arg = ["--cache-dir", cache_dir.to_str().unwrap()];
+    convert("a.epub", &cache_arg).stdout(predicates::str::contains("Successfully converted"));
+    convert("b.epub", &cache_arg).stdout(predicates::str::contains("Copied cached conversion"));
+    convert("c.epub", &[]).stdout(predicates::str::contains("Successfully converted"));
+    convert("d.epub", &["--no-cache"]).stdout(predicates::str::contains("Successfully converted"));
+    assert!(temp_dir.path().join("b.epub").is_file());
+
+    // Changed options miss the cache
+    let mut with_header = cache_arg.to_vec();
+    with_header.push("--include-metadata-header");
+    convert("e.epub", &with_header).stdout(predicates::str::contains("Successfully converted"));
+}
@@ -142,3 +142,283 @@ fn test_cbz_metadata_preservation() {
     assert_eq!(read_metadata.publisher, Some("Original Publisher".to_string()));
     assert_eq!(read_metadata.format, Some("CBZ".to_string()));
 }
+
+#[test]
+fn test_cbz_pages_in_natural_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let cbz_path = temp_dir.path().join("numbered.cbz");
+
+    let mut handler = CbzHandler::new();
+    for name in ["page10.png", "page2.png", "page1.png", "page09.png"] {
+        handler.add_image(name, create_test_image()).unwrap();
+    }
+    handler.write_to_file(&cbz_path).unwrap();
+
+    let mut reader = CbzHandler::new();
+    reader.read_from_file(&cbz_path).unwrap();
+    let names: Vec<String> = reader.extract_images().unwrap().into_iter().map(|image| image.name).collect();
+    assert_eq!(names, ["page1.png", "page2.png", "page09.png", "page10.png"]);
+}
+
+#[test]
+fn test_cbz_repair_sets_page_count() {
+    use ebook_cli::traits::EbookOperator;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let cbz_path = temp_dir.path().join("bare.cbz");
+    let repaired_path = temp_dir.path().join("repaired.cbz");
+
+    // Pages only, no ComicInfo.xml
+    let mut zip = ZipWriter::new(std::fs::File::create(&cbz_path).unwrap());
+    for name in ["page01.png", "page02.png", "page03.png"] {
+        zip.start_file(name, FileOptions::<()>::default()).unwrap();
+        zip.write_all(&create_test_image()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let mut handler = CbzHandler::new();
+    handler.read_from_file(&cbz_path).unwrap();
+    assert_eq!(handler.page_count(), None);
+
+    handler.repair().unwrap();
+    assert_eq!(handler.page_count(), Some(3));
+    handler.write_to_file(&repaired_path).unwrap();
+
+    let mut reader = CbzHandler::new();
+    reader.read_from_file(&repaired_path).unwrap();
+    assert_eq!(reader.page_count(), Some(3));
+    assert_eq!(reader.get_metadata().unwrap().title, Some("bare".to_string()));
+}
+
+#[test]
+fn test_cbz_comic_info_page_order() {
+    use ebook_cli::formats::PageOrder;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let cbz_path = temp_dir.path().join("ordered.cbz");
+
+    // Filename order is page1, page2, page3, page10; <Pages> reads 10, 2, 1 with page3 as the
+    // cover, and leaves nothing out
+    let mut zip = ZipWriter::new(std::fs::File::create(&cbz_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("ComicInfo.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<ComicInfo>
+  <Title>Reordered</Title>
+  <Pages>
+    <Page Image="3"/>
+    <Page Image="1" Type="Story"/>
+    <Page Image="2" Type="FrontCover"/>
+    <Page Image="0"/>
+  </Pages>
+</ComicInfo>"#).unwrap();
+    for name in ["page10.png", "page1.png", "page3.png", "page2.png"] {
+        zip.start_file(name, options).unwrap();
+        zip.write_all(&create_test_image()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let page_names = |order: PageOrder| {
+        let mut handler = CbzHandler::new();
+        handler.set_page_order(order);
+        handler.read_from_file(&cbz_path).unwrap();
+        handler.extract_images().unwrap().into_iter().map(|image| image.name).collect::<Vec<_>>()
+    };
+
+    assert_eq!(page_names(PageOrder::Filename), vec!["page1.png", "page2.png", "page3.png", "page10.png"]);
+    assert_eq!(page_names(PageOrder::ComicInfo), vec!["page3.png", "page10.png", "page2.png", "page1.png"]);
+
+    // The <Pages> list survives a rewrite, so the order can be recovered again
+    let mut handler = CbzHandler::new();
+    handler.set_page_order(PageOrder::ComicInfo);
+    handler.read_from_file(&cbz_path).unwrap();
+    let rewritten_path = temp_dir.path().join("rewritten.cbz");
+    handler.write_to_file(&rewritten_path).unwrap();
+    let mut rewritten = CbzHandler::new();
+    rewritten.set_page_order(PageOrder::ComicInfo);
+    rewritten.read_from_file(&rewritten_path).unwrap();
+    let names: Vec<String> = rewritten.extract_images().unwrap().into_iter().map(|image| image.name).collect();
+    assert_eq!(names, vec!["page3.png", "page10.png", "page2.png", "page1.png"]);
+}
+
+#[test]
+fn test_cbz_write_omits_comic_info() {
+    let temp_dir = TempDir::new().unwrap();
+    let cbz_path = temp_dir.path().join("minimal.cbz");
+
+    let mut handler = CbzHandler::new();
+    handler.set_metadata(Metadata::new().with_title("Minimal")).unwrap();
+    handler.add_image("page01.png", create_test_image()).unwrap();
+    handler.set_write_comic_info(false);
+    handler.write_to_file(&cbz_path).unwrap();
+
+    let archive = zip::ZipArchive::new(std::fs::File::open(&cbz_path).unwrap()).unwrap();
+    let names: Vec<&str> = archive.file_names().collect();
+    assert_eq!(names, vec!["page01.png"]);
+}
+
+#[test]
+fn test_cbz_comic_info_v2_schema() {
+    use ebook_cli::formats::{ComicInfo, ComicInfoVersion};
+    use std::io::Read;
+
+    let temp_dir = TempDir::new().unwrap();
+    let legacy_path = temp_dir.path().join("legacy.cbz");
+    let v2_path = temp_dir.path().join("v2.cbz");
+
+    let mut comic_info = ComicInfo::new();
+    comic_info.title = Some("Arc Issue".to_string());
+    comic_info.format = Some("TPB".to_string());
+    comic_info.age_rating = Some("Teen".to_string());
+    comic_info.story_arc = Some("The Long Arc".to_string());
+
+    let mut handler = CbzHandler::new();
+    handler.add_image("page01.png", create_test_image()).unwrap();
+    handler.set_comic_info(comic_info);
+    handler.write_to_file(&legacy_path).unwrap();
+    handler.set_comic_info_version(ComicInfoVersion::V2);
+    handler.write_to_file(&v2_path).unwrap();
+
+    let comic_info_xml = |path: &std::path::Path| {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        let mut xml = String::new();
+        archive.by_name("ComicInfo.xml").unwrap().read_to_string(&mut xml).unwrap();
+        xml
+    };
+
+    // The default stays the original element set
+    let legacy = comic_info_xml(&legacy_path);
+    assert!(!legacy.contains("<Format>") && !legacy.contains("noNamespaceSchemaLocation"));
+
+    let v2 = comic_info_xml(&v2_path);
+    assert!(v2.contains("schema/v2.0/ComicInfo.xsd"));
+    assert!(v2.contains("<Format>TPB</Format>"));
+    assert!(v2.contains("<AgeRating>Teen</AgeRating>"));
+    assert!(v2.contains("<StoryArc>The Long Arc</StoryArc>"));
+    // The v2.0 schema is a sequence, so order matters
+    assert!(v2.find("<StoryArc>").unwrap() < v2.find("<AgeRating>").unwrap());
+
+    let mut reader = CbzHandler::new();
+    reader.read_from_file(&v2_path).unwrap();
+    let read_back = reader.get_comic_info().unwrap();
+    assert_eq!(read_back.age_rating.as_deref(), Some("Teen"));
+    assert_eq!(read_back.story_arc.as_deref(), Some("The Long Arc"));
+    assert_eq!(read_back.format.as_deref(), Some("TPB"));
+}
+
+#[test]
+fn test_cbz_subfolders_and_nested_archives_become_toc_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let cbz_path = temp_dir.path().join("volume.cbz");
+    let issue_path = temp_dir.path().join("issue3.cbz");
+    let collection_path = temp_dir.path().join("collection.cbz");
+
+    let mut handler = CbzHandler::new();
+    handler.set_metadata(Metadata::new().with_title("Volume")).unwrap();
+    for name in ["Chapter 2/p1.png", "Chapter 1/p2.png", "Chapter 1/p1.png", "Chapter 2/p2.png", "Chapter 2/p3.png"] {
+        handler.add_image(name, create_test_image()).unwrap();
+    }
+    handler.write_to_file(&cbz_path).unwrap();
+
+    let mut reader = CbzHandler::new();
+    reader.read_from_file(&cbz_path).unwrap();
+    let toc = reader.get_toc().unwrap();
+    let pages: Vec<String> = reader.extract_images().unwrap().into_iter().map(|image| image.name).collect();
+
+    let entries: Vec<(&str, Option<&str>)> = toc.iter().map(|entry| (entry.title.as_str(), entry.href.as_deref())).collect();
+    assert_eq!(entries, vec![("Chapter 1", Some("Chapter 1/p1.png")), ("Chapter 2", Some("Chapter 2/p1.png"))]);
+    assert_eq!(pages, vec!["Chapter 1/p1.png", "Chapter 1/p2.png", "Chapter 2/p1.png", "Chapter 2/p2.png", "Chapter 2/p3.png"]);
+
+    // A CBZ of CBZs: each nested issue contributes its pages under its own entry
+    let mut issue = CbzHandler::new();
+    issue.add_image("page1.png", create_test_image()).unwrap();
+    issue.write_to_file(&issue_path).unwrap();
+
+    let file = std::fs::File::create(&collection_path).unwrap();
+    let mut zip = zip::ZipWriter::new(file);
+    for name in ["issue3.cbz", "issue10.cbz"] {
+        zip.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+        std::io::Write::write_all(&mut zip, &std::fs::read(&issue_path).unwrap()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let mut reader = CbzHandler::new();
+    reader.read_from_file(&collection_path).unwrap();
+    let titles: Vec<String> = reader.get_toc().unwrap().into_iter().map(|entry| entry.title).collect();
+    assert_eq!(titles, vec!["issue3", "issue10"]);
+    assert_eq!(reader.image_count(), 2);
+}
+
+#[test]
+fn test_cbz_reads_backslash_entry_names() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let cbz_path = temp_dir.path().join("windows.cbz");
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&cbz_path).unwrap());
+    zip.start_file("/ComicInfo.xml", FileOptions::<()>::default()).unwrap();
+    zip.write_all(b"<?xml version=\"1.0\"?><ComicInfo><Title>Windows Comic</Title></ComicInfo>").unwrap();
+    for name in ["Issue 1\\page02.png", "Issue 1\\page01.png"] {
+        zip.start_file(name, FileOptions::<()>::default()).unwrap();
+        zip.write_all(&create_test_image()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let mut handler = CbzHandler::new();
+    handler.read_from_file(&cbz_path).unwrap();
+    assert_eq!(handler.get_metadata().unwrap().title, Some("Windows Comic".to_string()));
+    let pages: Vec<String> = handler.extract_images().unwrap().into_iter().map(|image| image.name).collect();
+    assert_eq!(pages, vec!["Issue 1/page01.png", "Issue 1/page02.png"]);
+}
+
+/// A zip whose single page is `size` zero bytes, deflated to a tiny fraction of that
+fn zip_with_zero_page(name: &str, size: usize) -> Vec<u8> {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file(name, FileOptions::<()>::default().compression_method(zip::CompressionMethod::Deflated))
+        .unwrap();
+    zip.write_all(&vec![0u8; size]).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn test_cbz_high_ratio_entry_is_rejected() {
+    use ebook_cli::utils::ExtractionLimits;
+    use ebook_cli::EbookError;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let bomb_path = temp_dir.path().join("bomb.cbz");
+    std::fs::write(&bomb_path, zip_with_zero_page("page01.png", 16 * 1024 * 1024)).unwrap();
+
+    let error = CbzHandler::new().read_from_file(&bomb_path).unwrap_err();
+    assert!(matches!(error, EbookError::ArchiveTooLarge(_)), "{error}");
+    assert!(error.to_string().contains("page01.png expands more than 200x"), "{error}");
+
+    // The total limit applies even when the ratio is allowed, and a generous one lets it through
+    let mut handler = CbzHandler::new();
+    handler.set_extraction_limits(ExtractionLimits::default().with_max_ratio(u64::MAX).with_max_total_bytes(1024 * 1024));
+    let error = handler.read_from_file(&bomb_path).unwrap_err();
+    assert!(error.to_string().contains("would exceed the 1.0 MB limit"), "{error}");
+    handler.set_extraction_limits(ExtractionLimits::default().with_max_ratio(u64::MAX));
+    handler.read_from_file(&bomb_path).unwrap();
+
+    // The same page inside a nested archive is caught too
+    let nested_path = temp_dir.path().join("nested.cbz");
+    let mut zip = ZipWriter::new(std::fs::File::create(&nested_path).unwrap());
+    zip.start_file("issue1.cbz", FileOptions::<()>::default().compression_method(zip::CompressionMethod::Stored))
+        .unwrap();
+    zip.write_all(&zip_with_zero_page("page01.png", 16 * 1024 * 1024)).unwrap();
+    zip.finish().unwrap();
+    let error = CbzHandler::new().read_from_file(&nested_path).unwrap_err();
+    assert!(matches!(error, EbookError::ArchiveTooLarge(_)), "{error}");
+}
@@ -1,4 +1,3 @@
-use assert_cmd::Command;
 use predicates::prelude::*;
 use tempfile::TempDir;
 use std::fs;
@@ -62,7 +61,7 @@ fn test_cli_optimize_cbz() {
     
     create_test_cbz(&input_path);
     
-    let mut cmd = Command::cargo_bin("ebook").unwrap();
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
     cmd.arg("optimize")
         .arg(&input_path)
         .arg("--output")
@@ -85,7 +84,7 @@ fn test_cli_optimize_epub() {
     
     create_test_epub(&input_path);
     
-    let mut cmd = Command::cargo_bin("ebook").unwrap();
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
     cmd.arg("optimize")
         .arg(&input_path)
         .arg("--output")
@@ -105,7 +104,7 @@ fn test_cli_optimize_with_progress() {
     
     create_test_cbz(&input_path);
     
-    let mut cmd = Command::cargo_bin("ebook").unwrap();
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
     cmd.arg("optimize")
         .arg(&input_path)
         .arg("--progress");
@@ -123,7 +122,7 @@ fn test_cli_optimize_custom_dimensions() {
     
     create_test_cbz(&input_path);
     
-    let mut cmd = Command::cargo_bin("ebook").unwrap();
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
     cmd.arg("optimize")
         .arg(&input_path)
         .arg("--output")
@@ -146,7 +145,7 @@ fn test_cli_optimize_no_resize() {
     
     create_test_cbz(&input_path);
     
-    let mut cmd = Command::cargo_bin("ebook").unwrap();
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
     cmd.arg("optimize")
         .arg(&input_path)
         .arg("--no-resize")
@@ -164,7 +163,7 @@ fn test_cli_optimize_unsupported_format() {
     
     fs::write(&txt_path, "Test content").unwrap();
     
-    let mut cmd = Command::cargo_bin("ebook").unwrap();
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
     cmd.arg("optimize")
         .arg(&txt_path);
     
@@ -175,7 +174,7 @@ fn test_cli_optimize_unsupported_format() {
 
 #[test]
 fn test_cli_optimize_help() {
-    let mut cmd = Command::cargo_bin("ebook").unwrap();
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
     cmd.arg("optimize")
         .arg("--help");
     
@@ -185,3 +184,517 @@ fn test_cli_optimize_help() {
         .stdout(predicate::str::contains("max-width"))
         .stdout(predicate::str::contains("quality"));
 }
+
+fn create_noisy_jpeg_cbz(path: &std::path::Path) {
+    use ebook_cli::formats::CbzHandler;
+    use ebook_cli::traits::EbookWriter;
+    use image::{codecs::jpeg::JpegEncoder, RgbImage};
+
+    // High-entropy pages at quality 100 compress poorly, so lowering quality matters
+    let mut seed = 0x2545F491u32;
+    let mut handler = CbzHandler::new();
+    for page in 1..=3 {
+        let img = RgbImage::from_fn(256, 256, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let [r, g, b, _] = seed.to_le_bytes();
+            image::Rgb([r, g, b])
+        });
+        let mut data = Vec::new();
+        JpegEncoder::new_with_quality(&mut data, 100).encode_image(&img).unwrap();
+        handler.add_image(&format!("page{page:02}.jpg"), data).unwrap();
+    }
+    handler.write_to_file(path).unwrap();
+}
+
+#[test]
+fn test_cli_optimize_max_filesize() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("oversized.cbz");
+    let output_path = temp_dir.path().join("fitted.cbz");
+    let unreachable_path = temp_dir.path().join("unreachable.cbz");
+
+    create_noisy_jpeg_cbz(&input_path);
+    let budget = fs::metadata(&input_path).unwrap().len() / 2;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.arg("optimize")
+        .arg(&input_path)
+        .arg("--output")
+        .arg(&output_path)
+        .arg("--no-resize")
+        .arg("--max-filesize")
+        .arg(budget.to_string());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Settled on JPEG quality"));
+    assert!(fs::metadata(&output_path).unwrap().len() <= budget);
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.arg("optimize")
+        .arg(&input_path)
+        .arg("--output")
+        .arg(&unreachable_path)
+        .arg("--no-resize")
+        .arg("--max-filesize")
+        .arg("1KB");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Could not reach target of 1024 bytes"));
+}
+
+#[test]
+fn test_cli_optimize_output_independent_of_threads() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("pages.cbz");
+    create_noisy_jpeg_cbz(&input_path);
+
+    let optimize_with = |threads: &str| {
+        let output_path = temp_dir.path().join(format!("threads{threads}.cbz"));
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+        cmd.arg("optimize")
+            .arg(&input_path)
+            .arg("--output")
+            .arg(&output_path)
+            .arg("--max-width")
+            .arg("128")
+            .arg("--threads")
+            .arg(threads);
+        cmd.assert().success();
+        fs::read(&output_path).unwrap()
+    };
+
+    assert_eq!(optimize_with("1"), optimize_with("4"));
+}
+
+#[test]
+fn test_cli_optimize_epub_output_reproducible() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::EbookWriter;
+    use image::{codecs::jpeg::JpegEncoder, RgbImage};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("photos.epub");
+
+    let mut seed = 0x2545F491u32;
+    let mut handler = EpubHandler::new();
+    for idx in 1..=3 {
+        let img = RgbImage::from_fn(256, 256, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let [r, g, b, _] = seed.to_le_bytes();
+            image::Rgb([r, g, b])
+        });
+        let mut data = Vec::new();
+        JpegEncoder::new_with_quality(&mut data, 100).encode_image(&img).unwrap();
+        handler.add_image(&format!("images/photo{idx}.jpg"), data).unwrap();
+    }
+    handler.add_chapter("Chapter 1", r#"<p><img src="images/photo1.jpg"/></p>"#).unwrap();
+    handler.write_to_file(&input_path).unwrap();
+
+    let optimize_with = |threads: &str, preserve_structure: bool| {
+        let output_path = temp_dir.path().join(format!("threads{threads}-{preserve_structure}.epub"));
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+        cmd.env("SOURCE_DATE_EPOCH", "1700000000")
+            .arg("optimize")
+            .arg(&input_path)
+            .arg("--output")
+            .arg(&output_path)
+            .arg("--max-width")
+            .arg("128")
+            .arg("--threads")
+            .arg(threads);
+        if preserve_structure {
+            cmd.arg("--preserve-structure");
+        }
+        cmd.assert().success();
+        fs::read(&output_path).unwrap()
+    };
+
+    for preserve_structure in [false, true] {
+        let output = optimize_with("1", preserve_structure);
+        assert_eq!(output, optimize_with("4", preserve_structure));
+
+        // Entries carry the fixed timestamp rather than the time of writing
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(output)).unwrap();
+        for idx in 0..archive.len() {
+            let entry = archive.by_index(idx).unwrap();
+            if preserve_structure && !entry.name().ends_with(".jpg") {
+                continue;
+            }
+            assert_eq!(entry.last_modified(), Some(zip::DateTime::default()), "{}", entry.name());
+        }
+    }
+}
+
+#[test]
+fn test_cli_optimize_epub_preserve_structure() {
+    use std::io::{Read, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("structured.epub");
+    let output_path = temp_dir.path().join("optimized.epub");
+
+    // A PNG larger than --max-width, so resizing makes it smaller
+    let mut photo = image::RgbImage::new(400, 300);
+    for (x, y, pixel) in photo.enumerate_pixels_mut() {
+        *pixel = image::Rgb([(x * 7 % 256) as u8, (y * 13 % 256) as u8, ((x ^ y) % 256) as u8]);
+    }
+    let mut photo_png = Vec::new();
+    image::DynamicImage::ImageRgb8(photo)
+        .write_to(&mut std::io::Cursor::new(&mut photo_png), image::ImageFormat::Png)
+        .unwrap();
+
+    let stored = zip::CompressionMethod::Stored;
+    let deflated = zip::CompressionMethod::Deflated;
+    let entries: Vec<(&str, Vec<u8>, zip::CompressionMethod)> = vec![
+        ("mimetype", b"application/epub+zip".to_vec(), stored),
+        ("META-INF/container.xml", br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="book/package.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#.to_vec(), deflated),
+        ("book/package.opf", br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Structured</dc:title></metadata>
+  <manifest>
+    <item id="text" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="css" href="styles/book.css" media-type="text/css"/>
+    <item id="font" href="fonts/serif.otf" media-type="font/otf"/>
+    <item id="photo" href="art/photo.png" media-type="image/png"/>
+  </manifest>
+  <spine><itemref idref="text"/></spine>
+</package>"#.to_vec(), deflated),
+        ("book/text/ch1.xhtml", b"<html><body><h1>One</h1><img src=\"../art/photo.png\"/></body></html>".to_vec(), deflated),
+        ("book/styles/book.css", b"body { font-family: serif; }".to_vec(), stored),
+        ("book/fonts/serif.otf", vec![0x4F, 0x54, 0x54, 0x4F, 0, 1, 2, 3], stored),
+        ("book/art/photo.png", photo_png.clone(), stored),
+    ];
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&input_path).unwrap());
+    for (name, data, method) in &entries {
+        zip.start_file(*name, FileOptions::<()>::default().compression_method(*method)).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.arg("optimize")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--preserve-structure")
+        .args(["--max-width", "100", "--max-height", "100"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Successfully optimized EPUB"));
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&output_path).unwrap()).unwrap();
+    assert_eq!(archive.len(), entries.len());
+    for (index, (name, data, method)) in entries.iter().enumerate() {
+        let mut file = archive.by_index(index).unwrap();
+        assert_eq!(file.name(), *name);
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        if name.ends_with(".png") {
+            assert!(contents.len() < photo_png.len(), "image was not optimized");
+            assert!(contents.starts_with(b"\x89PNG"));
+        } else {
+            assert_eq!(&contents, data, "{name} changed");
+        }
+        assert_eq!(file.compression(), *method, "{name} compression changed");
+    }
+}
+
+#[test]
+fn test_cli_optimize_without_images_writes_nothing() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::EbookWriter;
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("text-only.epub");
+    let mut epub = EpubHandler::new();
+    epub.add_chapter("Chapter 1", "<p>No pictures here</p>").unwrap();
+    epub.write_to_file(&epub_path).unwrap();
+
+    // A CBZ with nothing but an empty zip directory
+    let cbz_path = temp_dir.path().join("empty.cbz");
+    zip::ZipWriter::new(fs::File::create(&cbz_path).unwrap()).finish().unwrap();
+
+    let cases: [(&std::path::Path, &[&str]); 3] = [
+        (&epub_path, &[]),
+        (&epub_path, &["--preserve-structure"]),
+        (&cbz_path, &[]),
+    ];
+    for (input_path, extra) in cases {
+        let original = fs::read(input_path).unwrap();
+        let output_path = temp_dir.path().join("out").with_extension(input_path.extension().unwrap());
+
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+        cmd.arg("optimize").arg(input_path).args(extra);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Nothing to optimize").and(predicate::str::contains("has no images")));
+
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+        cmd.arg("optimize").arg(input_path).arg("-o").arg(&output_path).args(extra);
+        cmd.assert().success().stdout(predicate::str::contains("Nothing to optimize"));
+
+        assert_eq!(fs::read(input_path).unwrap(), original, "{input_path:?} was rewritten");
+        assert!(!output_path.exists(), "no output should be written for {input_path:?}");
+
+        // --force still rewrites
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+        cmd.arg("optimize").arg(input_path).arg("-o").arg(&output_path).args(extra).arg("--force");
+        cmd.assert().success().stdout(predicate::str::contains("Successfully optimized"));
+        assert!(output_path.exists());
+        fs::remove_file(&output_path).unwrap();
+    }
+}
+
+#[test]
+fn test_cli_optimize_reports_true_percentage() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::EbookWriter;
+    use image::{ImageFormat, RgbImage};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("gradient.epub");
+    let output_path = temp_dir.path().join("gradient-small.epub");
+
+    let gradient = RgbImage::from_fn(400, 400, |x, y| image::Rgb([x as u8, y as u8, (x ^ y) as u8]));
+    let mut png = std::io::Cursor::new(Vec::new());
+    gradient.write_to(&mut png, ImageFormat::Png).unwrap();
+    let mut handler = EpubHandler::new();
+    handler.add_chapter("Chapter 1", r#"<p><img src="gradient.png"/></p>"#).unwrap();
+    handler.add_image("gradient.png", png.into_inner()).unwrap();
+    handler.write_to_file(&input_path).unwrap();
+
+    let output = assert_cmd::cargo::cargo_bin_cmd!("ebook")
+        .args(["optimize", "--max-width", "100", "--max-height", "100", "--output"])
+        .arg(&output_path)
+        .arg(&input_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    let image_bytes = |path: &std::path::Path| {
+        let mut archive = zip::ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+        (0..archive.len())
+            .filter_map(|i| {
+                let file = archive.by_index(i).unwrap();
+                file.name().ends_with(".png").then(|| file.size())
+            })
+            .sum::<u64>()
+    };
+    let (original, new) = (image_bytes(&input_path), image_bytes(&output_path));
+    assert!(new < original);
+
+    let line = stdout.lines().find(|line| line.starts_with("Saved ")).expect(&stdout);
+    let percent: f64 = line
+        .rsplit_once('(')
+        .and_then(|(_, rest)| rest.strip_suffix("% reduction)"))
+        .and_then(|value| value.parse().ok())
+        .expect(line);
+    assert!((0.0..=100.0).contains(&percent), "{line}");
+    let expected = (original - new) as f64 / original as f64 * 100.0;
+    assert_eq!(format!("{percent:.1}"), format!("{expected:.1}"), "{line}");
+    assert!(line.contains(" KB"), "sizes should be human-readable: {line}");
+}
+
+#[test]
+fn test_cli_optimize_dedupe_stores_repeated_image_once() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::{EbookReader, EbookWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("repeated.epub");
+    let output_path = temp_dir.path().join("deduped.epub");
+
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::RgbImage::from_pixel(8, 8, image::Rgb([200, 10, 10]))
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    let png = png.into_inner();
+    let mut handler = EpubHandler::new();
+    handler.add_chapter("Chapter 1", r#"<p><img src="images/ornament.png"/></p>"#).unwrap();
+    handler.add_chapter("Chapter 2", r#"<p><img src="images/ornament-copy.png"/></p>"#).unwrap();
+    handler.add_image("images/ornament.png", png.clone()).unwrap();
+    handler.add_image("images/ornament-copy.png", png).unwrap();
+    handler.write_to_file(&input_path).unwrap();
+
+    let output = assert_cmd::cargo::cargo_bin_cmd!("ebook")
+        .args(["optimize", "--no-resize", "--dedupe", "--output"])
+        .arg(&output_path)
+        .arg(&input_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Dedupe removed 1 duplicate image(s)"), "{stdout}");
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&output_path).unwrap();
+    let images = reader.extract_images().unwrap();
+    let second_chapter = reader.get_chapter(1).unwrap().unwrap();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].name, "OEBPS/images/ornament.png");
+    assert!(second_chapter.contains(r#"src="images/ornament.png""#), "{second_chapter}");
+}
+
+#[test]
+fn test_cli_optimize_dedupe_rewrites_css_urls() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::EbookReader;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("styled.epub");
+    let output_path = temp_dir.path().join("deduped.epub");
+
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::RgbImage::from_pixel(8, 8, image::Rgb([10, 200, 10]))
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    let png = png.into_inner();
+
+    // Chapters in a subdirectory point at the images with `../`, from markup and from CSS
+    let mut zip = ZipWriter::new(fs::File::create(&input_path).unwrap());
+    let options = FileOptions::<()>::default();
+    let chapter = br#"<html><head><title>Styled</title><style>body { background: url('../Images/leaf-copy.png') }</style></head>
+<body><p><img src="../Images/leaf.png"/></p><div style="background: url(../Images/leaf-copy.png)"></div></body></html>"#;
+    let entries: [(&str, &[u8]); 6] = [
+        ("mimetype", b"application/epub+zip"),
+        ("META-INF/container.xml", br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#),
+        ("OEBPS/content.opf", br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Styled</dc:title></metadata>
+  <manifest>
+    <item id="text" href="Text/ch1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="leaf" href="Images/leaf.png" media-type="image/png"/>
+    <item id="copy" href="Images/leaf-copy.png" media-type="image/png"/>
+  </manifest>
+  <spine><itemref idref="text"/></spine>
+</package>"#),
+        ("OEBPS/Text/ch1.xhtml", chapter),
+        ("OEBPS/Images/leaf.png", &png),
+        ("OEBPS/Images/leaf-copy.png", &png),
+    ];
+    for (name, data) in entries {
+        zip.start_file(name, options).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let output = assert_cmd::cargo::cargo_bin_cmd!("ebook")
+        .args(["optimize", "--no-resize", "--dedupe", "--output"])
+        .arg(&output_path)
+        .arg(&input_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&output_path).unwrap();
+    assert_eq!(reader.extract_images().unwrap().len(), 1);
+    let chapter = reader.get_chapter(0).unwrap().unwrap();
+    assert!(chapter.contains("url('../Images/leaf.png')"), "{chapter}");
+    assert!(chapter.contains("url(../Images/leaf.png)"), "{chapter}");
+    assert!(!chapter.contains("leaf-copy"), "{chapter}");
+}
+
+#[test]
+fn test_cli_optimize_recompress_shrinks_text_only_epub() {
+    use std::io::{Read, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("novel.epub");
+
+    let chapter = format!("<html><body>{}</body></html>", "<p>It was a dark and stormy night.</p>".repeat(500));
+    let entries: Vec<(&str, Vec<u8>)> = vec![
+        ("mimetype", b"application/epub+zip".to_vec()),
+        ("META-INF/container.xml", br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#.to_vec()),
+        ("OEBPS/content.opf", br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Novel</dc:title></metadata>
+  <manifest><item id="text" href="ch1.xhtml" media-type="application/xhtml+xml"/></manifest>
+  <spine><itemref idref="text"/></spine>
+</package>"#.to_vec()),
+        ("OEBPS/ch1.xhtml", chapter.into_bytes()),
+    ];
+
+    // Every entry stored, as some packaging tools leave them
+    let mut zip = ZipWriter::new(fs::File::create(&input_path).unwrap());
+    for (name, data) in &entries {
+        zip.start_file(*name, FileOptions::<()>::default().compression_method(zip::CompressionMethod::Stored)).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+    let original_size = fs::metadata(&input_path).unwrap().len();
+
+    for extra in [&[][..], &["--preserve-structure"][..]] {
+        let output_path = temp_dir.path().join("recompressed.epub");
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+        cmd.arg("optimize").arg(&input_path).arg("-o").arg(&output_path).arg("--recompress").args(extra);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Saved 0 B").and(predicate::str::contains("Packaging: saved")));
+
+        assert!(fs::metadata(&output_path).unwrap().len() < original_size / 4, "{extra:?} did not shrink the archive");
+        let mut archive = zip::ZipArchive::new(fs::File::open(&output_path).unwrap()).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        assert_eq!(archive.by_index(0).unwrap().compression(), zip::CompressionMethod::Stored);
+        let mut text = archive.by_name("OEBPS/ch1.xhtml").unwrap();
+        assert_eq!(text.compression(), zip::CompressionMethod::Deflated);
+        let mut contents = String::new();
+        text.read_to_string(&mut contents).unwrap();
+        assert!(contents.contains("dark and stormy night"));
+        drop(text);
+        fs::remove_file(&output_path).unwrap();
+    }
+
+    let cbz_path = temp_dir.path().join("comic.cbz");
+    create_test_cbz(&cbz_path);
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.arg("optimize").arg(&cbz_path).arg("--recompress");
+    cmd.assert().failure().stderr(predicate::str::contains("--recompress is only supported for EPUB"));
+}
+
+#[test]
+fn test_cli_optimize_writes_json_report() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("test.cbz");
+    let output_path = temp_dir.path().join("optimized.cbz");
+    let report_path = temp_dir.path().join("report.json");
+
+    create_test_cbz(&input_path);
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.arg("optimize")
+        .arg(&input_path)
+        .arg("--output")
+        .arg(&output_path)
+        .arg("--quality")
+        .arg("70")
+        .arg("--report")
+        .arg(&report_path);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Successfully optimized CBZ"));
+
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+    let images = report["images"].as_array().unwrap();
+    assert_eq!(images.len(), 2);
+    assert_eq!(images[0]["name"], "page01.png");
+    assert_eq!(images[0]["original_dimensions"], serde_json::json!([1, 1]));
+    assert_eq!(report["settings"]["quality"], 70);
+    assert!(report["total_savings"].is_u64());
+}
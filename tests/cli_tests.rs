@@ -97,6 +97,44 @@ fn test_cli_info_command() {
     cleanup_test_dir(&test_dir);
 }
 
+#[test]
+fn test_cli_info_json_lists_archive_entries() {
+    use ebook_cli::traits::EbookWriter;
+
+    let test_dir = setup_test_dir("info_json");
+    let cbz_path = test_dir.join("comic.cbz");
+    let mut comic = ebook_cli::formats::CbzHandler::new();
+    comic.add_image("page01.png", vec![7; 64]).unwrap();
+    comic.write_to_file(&cbz_path).unwrap();
+
+    let output = Command::new(get_cli_executable())
+        .args(["info", "--json"])
+        .arg(&cbz_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["format"], "cbz");
+    let page = info["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["name"] == "page01.png")
+        .expect("page listed");
+    assert_eq!(page["uncompressed_size"], 64);
+    assert_eq!(page["is_image"], true);
+
+    let output = Command::new(get_cli_executable())
+        .args(["info", "--verbose"])
+        .arg(&cbz_path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Entries:") && stdout.contains("page01.png (image)"), "{stdout}");
+
+    cleanup_test_dir(&test_dir);
+}
+
 #[test]
 fn test_cli_validate_command() {
     let test_dir = setup_test_dir("validate");
@@ -208,6 +246,37 @@ fn test_cli_repair_command() {
     cleanup_test_dir(&test_dir);
 }
 
+#[test]
+fn test_cli_preserve_mtime() {
+    use std::time::{Duration, SystemTime};
+
+    let test_dir = setup_test_dir("preserve_mtime");
+    let txt_path = test_dir.join("test.txt");
+    create_test_txt(&txt_path);
+    let original = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    File::options().write(true).open(&txt_path).unwrap().set_modified(original).unwrap();
+    let mtime = |path: &PathBuf| fs::metadata(path).unwrap().modified().unwrap();
+    let close = |time: SystemTime| time.duration_since(original).unwrap_or_else(|e| e.duration()) < Duration::from_secs(2);
+
+    let cli = get_cli_executable();
+    let epub_path = test_dir.join("kept.epub");
+    let status = Command::new(&cli).arg("convert").arg(&txt_path).arg(&epub_path).arg("--preserve-mtime").status().unwrap();
+    assert!(status.success());
+    assert!(close(mtime(&epub_path)), "{:?}", mtime(&epub_path));
+
+    // Rewriting in place keeps the time the file had before
+    let status = Command::new(&cli).arg("repair").arg(&txt_path).arg("--preserve-mtime").status().unwrap();
+    assert!(status.success());
+    assert!(close(mtime(&txt_path)));
+
+    let fresh_path = test_dir.join("fresh.epub");
+    let status = Command::new(&cli).arg("convert").arg(&txt_path).arg(&fresh_path).status().unwrap();
+    assert!(status.success());
+    assert!(!close(mtime(&fresh_path)));
+
+    cleanup_test_dir(&test_dir);
+}
+
 #[test]
 fn test_cli_convert_auto_format_detection() {
     let test_dir = setup_test_dir("auto_detect");
@@ -314,3 +383,874 @@ fn test_cli_write_with_content_file() {
 
     cleanup_test_dir(&test_dir);
 }
+
+#[test]
+fn test_cli_read_head_truncates() {
+    let test_dir = setup_test_dir("read_head");
+    let txt_path = test_dir.join("long.txt");
+    fs::write(&txt_path, "x".repeat(500)).unwrap();
+
+    let cli = get_cli_executable();
+    let output = Command::new(&cli)
+        .arg("read")
+        .arg(&txt_path)
+        .arg("--head")
+        .arg("100")
+        .output()
+        .unwrap();
+
+    cleanup_test_dir(&test_dir);
+    assert!(output.status.success(), "CLI read --head should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim_end().chars().count(), 100);
+}
+
+#[test]
+fn test_cli_read_single_chapter() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::EbookWriter;
+
+    let test_dir = setup_test_dir("read_chapter");
+    let epub_path = test_dir.join("chapters.epub");
+
+    let mut epub = EpubHandler::new();
+    epub.set_metadata(ebook_cli::Metadata::new().with_title("Chapters")).unwrap();
+    epub.add_chapter("One", "<p>First chapter body</p>").unwrap();
+    epub.add_chapter("Two", "<p>Second chapter body</p>").unwrap();
+    epub.write_to_file(&epub_path).unwrap();
+
+    let cli = get_cli_executable();
+    let output = Command::new(&cli)
+        .arg("read")
+        .arg(&epub_path)
+        .arg("--chapter")
+        .arg("1")
+        .output()
+        .unwrap();
+
+    cleanup_test_dir(&test_dir);
+    assert!(output.status.success(), "CLI read --chapter should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("First chapter body"));
+    assert!(!stdout.contains("Second chapter body"));
+}
+
+fn read_toc_output(path: &PathBuf) -> String {
+    let output = Command::new(get_cli_executable())
+        .arg("read")
+        .arg(path)
+        .arg("--toc")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "CLI read --toc should succeed");
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn test_cli_read_toc_mobi() {
+    use ebook_cli::formats::MobiHandler;
+    use ebook_cli::traits::EbookWriter;
+
+    let test_dir = setup_test_dir("toc_mobi");
+    let mobi_path = test_dir.join("toc.mobi");
+
+    let mut mobi = MobiHandler::new();
+    mobi.set_metadata(ebook_cli::Metadata::new().with_title("Toc Book")).unwrap();
+    mobi.set_content("Chapter 1: Departure\nWe left early.\n\nChapter 2: Arrival\nWe got there late.\n").unwrap();
+    mobi.write_to_file(&mobi_path).unwrap();
+
+    let stdout = read_toc_output(&mobi_path);
+    cleanup_test_dir(&test_dir);
+
+    assert!(stdout.contains("Chapter 1: Departure"));
+    assert!(stdout.contains("Chapter 2: Arrival"));
+    assert!(!stdout.contains("We left early."));
+}
+
+#[test]
+fn test_cli_read_toc_pdf() {
+    use ebook_cli::formats::PdfHandler;
+    use ebook_cli::traits::EbookWriter;
+
+    let test_dir = setup_test_dir("toc_pdf");
+    let pdf_path = test_dir.join("toc.pdf");
+
+    let mut pdf = PdfHandler::new();
+    pdf.set_metadata(ebook_cli::Metadata::new().with_title("Toc Book")).unwrap();
+    pdf.add_chapter("Opening", "First part").unwrap();
+    pdf.add_chapter("Closing", "Second part").unwrap();
+    pdf.write_to_file(&pdf_path).unwrap();
+
+    let stdout = read_toc_output(&pdf_path);
+    cleanup_test_dir(&test_dir);
+
+    let titles: Vec<&str> = stdout.lines().collect();
+    assert_eq!(titles, vec!["Opening", "Closing"]);
+}
+
+#[test]
+fn test_cli_extract_images_colliding_basenames() {
+    use ebook_cli::formats::CbzHandler;
+    use ebook_cli::traits::EbookWriter;
+
+    let test_dir = setup_test_dir("extract_images");
+    let cbz_path = test_dir.join("collide.cbz");
+    let flat_dir = test_dir.join("flat");
+    let renamed_dir = test_dir.join("renamed");
+
+    let mut cbz = CbzHandler::new();
+    cbz.add_image("ch1/001.png", vec![1, 1, 1]).unwrap();
+    cbz.add_image("ch2/001.png", vec![2, 2, 2]).unwrap();
+    cbz.add_image("ch10/001.png", vec![10, 10, 10]).unwrap();
+    cbz.write_to_file(&cbz_path).unwrap();
+
+    let run = |dir: &PathBuf, rename: bool| {
+        let mut cmd = Command::new(get_cli_executable());
+        cmd.arg("extract-images").arg(&cbz_path).arg(dir);
+        if rename {
+            cmd.arg("--rename");
+        }
+        let output = cmd.output().unwrap();
+        assert!(output.status.success(), "CLI extract-images should succeed");
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let stdout = run(&flat_dir, false);
+    assert!(stdout.contains("Extracted 3 images"));
+    assert_eq!(fs::read(flat_dir.join("ch1/001.png")).unwrap(), vec![1, 1, 1]);
+    assert_eq!(fs::read(flat_dir.join("ch2/001.png")).unwrap(), vec![2, 2, 2]);
+    assert_eq!(fs::read(flat_dir.join("ch10/001.png")).unwrap(), vec![10, 10, 10]);
+
+    run(&renamed_dir, true);
+    let renamed = [
+        fs::read(renamed_dir.join("page0001.png")).unwrap(),
+        fs::read(renamed_dir.join("page0002.png")).unwrap(),
+        fs::read(renamed_dir.join("page0003.png")).unwrap(),
+    ];
+    cleanup_test_dir(&test_dir);
+
+    assert_eq!(renamed, [vec![1, 1, 1], vec![2, 2, 2], vec![10, 10, 10]]);
+}
+
+#[test]
+fn test_cli_annotations_lists_pdf_text_annotation() {
+    use lopdf::{dictionary, Document, Object, StringFormat};
+
+    let test_dir = setup_test_dir("annotations");
+    let pdf_path = test_dir.join("annotated.pdf");
+
+    // Two pages, with a single text annotation on the second
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let note_id = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Text",
+        "Rect" => vec![100.into(), 100.into(), 120.into(), 120.into()],
+        "Contents" => Object::String(b"Check this figure".to_vec(), StringFormat::Literal),
+    });
+    let first_page = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+    });
+    let second_page = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        "Annots" => vec![note_id.into()],
+    });
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![first_page.into(), second_page.into()],
+        "Count" => 2,
+    }));
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    doc.save(&pdf_path).unwrap();
+
+    let output = Command::new(get_cli_executable())
+        .arg("annotations")
+        .arg(&pdf_path)
+        .output()
+        .unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(output.status.success(), "CLI annotations should succeed");
+    let annotations: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        annotations,
+        serde_json::json!([
+            { "type": "Text", "location": { "page": 1 }, "text": "Check this figure" }
+        ])
+    );
+}
+
+#[test]
+fn test_cli_read_toc_from_headings_pdf() {
+    use ebook_cli::formats::PdfHandler;
+    use ebook_cli::traits::{EbookReader, EbookWriter};
+
+    let test_dir = setup_test_dir("toc_from_headings");
+    let pdf_path = test_dir.join("headless.pdf");
+
+    let mut pdf = PdfHandler::new();
+    pdf.set_metadata(ebook_cli::Metadata::new().with_title("Headless")).unwrap();
+    pdf.set_content("Chapter 1\nIt begins.\nChapter 2\nIt ends.").unwrap();
+    pdf.write_to_file(&pdf_path).unwrap();
+
+    let mut reader = PdfHandler::new();
+    reader.read_from_file(&pdf_path).unwrap();
+    assert!(reader.get_toc().unwrap().is_empty(), "fixture should have no outline");
+
+    let native = read_toc_output(&pdf_path);
+    let output = Command::new(get_cli_executable())
+        .arg("read")
+        .arg(&pdf_path)
+        .arg("--toc")
+        .arg("--toc-from-headings")
+        .output()
+        .unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(native.trim().is_empty());
+    assert!(output.status.success(), "CLI read --toc --toc-from-headings should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), vec!["Chapter 1", "Chapter 2"]);
+}
+
+#[test]
+fn test_cli_diff_flags_dropped_author() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::EbookWriter;
+
+    let test_dir = setup_test_dir("diff");
+    let epub_path = test_dir.join("original.epub");
+    let txt_path = test_dir.join("round_trip.txt");
+    let round_trip_path = test_dir.join("round_trip.epub");
+
+    let mut epub = EpubHandler::new();
+    epub.set_metadata(ebook_cli::Metadata::new().with_title("Diff Book").with_author("Jane Doe")).unwrap();
+    epub.add_chapter("Chapter 1", "<p>The only paragraph.</p>").unwrap();
+    epub.write_to_file(&epub_path).unwrap();
+
+    // EPUB -> TXT (no metadata header) -> EPUB loses the author
+    ebook_cli::Converter::convert(&epub_path, &txt_path, "txt").unwrap();
+    ebook_cli::Converter::convert(&txt_path, &round_trip_path, "epub").unwrap();
+
+    let output = Command::new(get_cli_executable())
+        .arg("diff")
+        .arg(&epub_path)
+        .arg(&round_trip_path)
+        .arg("--json")
+        .output()
+        .unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(output.status.success(), "CLI diff should succeed");
+    let diff: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let authors = diff["metadata"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|field| field["field"] == "authors")
+        .expect("dropped author should be reported");
+    assert_eq!(authors["a"], "Jane Doe");
+    assert_ne!(authors["b"], "Jane Doe");
+    assert!(diff["similarity"].as_f64().unwrap() > 0.0);
+}
+
+#[test]
+fn test_cli_convert_list_conversions() {
+    let output = Command::new(get_cli_executable())
+        .arg("convert")
+        .arg("--list-conversions")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "CLI convert --list-conversions should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().any(|line| line == "txt -> epub, pdf, mobi, fb2"));
+    assert!(stdout.lines().any(|line| line == "cbz -> epub"));
+}
+
+#[test]
+fn test_cli_export_and_import_metadata() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::{EbookReader, EbookWriter};
+
+    let test_dir = setup_test_dir("metadata_sidecar");
+    let source_path = test_dir.join("source.epub");
+    let target_path = test_dir.join("target.epub");
+    let imported_path = test_dir.join("imported.epub");
+    let json_path = test_dir.join("metadata.json");
+    let opf_path = test_dir.join("metadata.opf");
+
+    let mut source = EpubHandler::new();
+    let metadata = ebook_cli::Metadata::new()
+        .with_title("Sidecar Book")
+        .with_authors(["Ann Author", "Bob Writer"]);
+    source.set_metadata(metadata).unwrap();
+    source.add_chapter("Chapter 1", "<p>Source text</p>").unwrap();
+    source.write_to_file(&source_path).unwrap();
+
+    let mut target = EpubHandler::new();
+    target.set_metadata(ebook_cli::Metadata::new().with_title("Other Book")).unwrap();
+    target.add_chapter("Chapter 1", "<p>Target text</p>").unwrap();
+    target.write_to_file(&target_path).unwrap();
+
+    let run = |args: &[&std::ffi::OsStr]| {
+        let output = Command::new(get_cli_executable()).args(args).output().unwrap();
+        assert!(output.status.success(), "CLI {:?} should succeed", args[0]);
+    };
+    run(&["export-metadata".as_ref(), source_path.as_os_str(), json_path.as_os_str()]);
+    run(&["export-metadata".as_ref(), source_path.as_os_str(), opf_path.as_os_str()]);
+    run(&[
+        "import-metadata".as_ref(),
+        target_path.as_os_str(),
+        json_path.as_os_str(),
+        "--output".as_ref(),
+        imported_path.as_os_str(),
+    ]);
+
+    let opf = fs::read_to_string(&opf_path).unwrap();
+    let mut imported = EpubHandler::new();
+    imported.read_from_file(&imported_path).unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(opf.contains("<dc:title>Sidecar Book</dc:title>"));
+    assert!(opf.contains("<dc:creator>Bob Writer</dc:creator>"));
+
+    let metadata = imported.get_metadata().unwrap();
+    assert_eq!(metadata.title.as_deref(), Some("Sidecar Book"));
+    assert_eq!(metadata.all_authors(), vec!["Ann Author", "Bob Writer"]);
+    assert!(imported.get_content().unwrap().contains("Target text"));
+}
+
+#[test]
+fn test_cli_repair_salvages_cbz_with_corrupt_central_directory() {
+    use ebook_cli::formats::CbzHandler;
+    use ebook_cli::traits::{EbookReader, EbookWriter};
+
+    let test_dir = setup_test_dir("salvage_cbz");
+    let damaged_path = test_dir.join("damaged.cbz");
+    let repaired_path = test_dir.join("repaired.cbz");
+
+    let mut page = Vec::new();
+    image::DynamicImage::new_rgb8(8, 8)
+        .write_to(&mut std::io::Cursor::new(&mut page), image::ImageFormat::Png)
+        .unwrap();
+    let mut comic = CbzHandler::new();
+    for name in ["page1.png", "page2.png", "page3.png"] {
+        comic.add_image(name, page.clone()).unwrap();
+    }
+    comic.write_to_file(&damaged_path).unwrap();
+
+    // Zero out the central directory, leaving the local entries and the end record intact
+    let mut bytes = fs::read(&damaged_path).unwrap();
+    let central = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    let end = bytes.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+    bytes[central..end].fill(0);
+    fs::write(&damaged_path, &bytes).unwrap();
+    assert!(CbzHandler::new().read_from_file(&damaged_path).is_err());
+
+    let output = Command::new(get_cli_executable())
+        .args(["repair", damaged_path.to_str().unwrap(), "--output", repaired_path.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    let mut repaired = CbzHandler::new();
+    let read = repaired.read_from_file(&repaired_path);
+    cleanup_test_dir(&test_dir);
+
+    assert!(output.status.success(), "repair should salvage the archive");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("recovered 4 of 4 entries"));
+    read.unwrap();
+    assert_eq!(repaired.extract_images().unwrap().len(), 3);
+}
+
+#[test]
+fn test_cli_repair_keeps_input_when_salvaged_archive_is_unreadable() {
+    use zip::write::{FileOptions, ZipWriter};
+
+    let test_dir = setup_test_dir("salvage_unreadable");
+    let damaged_path = test_dir.join("damaged.epub");
+
+    // An EPUB without META-INF/container.xml: salvaging its entries cannot make it readable
+    let mut zip = ZipWriter::new(File::create(&damaged_path).unwrap());
+    let stored = FileOptions::<()>::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("mimetype", stored).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("OEBPS/chapter1.xhtml", stored).unwrap();
+    zip.write_all(b"<html><body><p>Text</p></body></html>").unwrap();
+    zip.finish().unwrap();
+    let mut bytes = fs::read(&damaged_path).unwrap();
+    let central = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    let end = bytes.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+    bytes[central..end].fill(0);
+    fs::write(&damaged_path, &bytes).unwrap();
+
+    let output = Command::new(get_cli_executable()).arg("repair").arg(&damaged_path).output().unwrap();
+    let after = fs::read(&damaged_path).unwrap();
+    let leftovers: Vec<_> = fs::read_dir(&test_dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    cleanup_test_dir(&test_dir);
+
+    assert!(!output.status.success(), "an unreadable salvage should fail the repair");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("recovered 2 of 2 entries"));
+    assert!(after == bytes, "the damaged input must not be overwritten");
+    assert_eq!(leftovers, ["damaged.epub"], "the salvaged temp file should be removed");
+}
+
+#[test]
+fn test_cli_read_line_range() {
+    let test_dir = setup_test_dir("read_range");
+    let txt_path = test_dir.join("lines.txt");
+    let content: String = (1..=50).map(|i| format!("line {i}\n")).collect();
+    fs::write(&txt_path, content).unwrap();
+
+    let output = Command::new(get_cli_executable())
+        .args(["read", txt_path.to_str().unwrap(), "--range", "10:3"])
+        .output()
+        .unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "line 10\nline 11\nline 12\n");
+}
+
+#[test]
+fn test_name_template_rendering() {
+    use ebook_cli::utils::{render_name_template, NameTemplateContext};
+
+    let first_chapter = NameTemplateContext {
+        title: Some("Guide".to_string()),
+        index: 1,
+        ext: "epub".to_string(),
+        chapter: Some("Introduction".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(render_name_template("{index:03}-{chapter}.{ext}", &first_chapter).unwrap(), "001-Introduction.epub");
+
+    let awkward = NameTemplateContext { title: Some("AC/DC: Live?".to_string()), ..first_chapter };
+    assert_eq!(render_name_template("{title} by {author}", &awkward).unwrap(), "AC_DC_ Live_ by Unknown");
+    assert!(render_name_template("{volume}.{ext}", &awkward).is_err());
+}
+
+#[test]
+fn test_cli_extract_images_name_template() {
+    use ebook_cli::formats::CbzHandler;
+    use ebook_cli::traits::EbookWriter;
+
+    let test_dir = setup_test_dir("extract_images_template");
+    let cbz_path = test_dir.join("comic.cbz");
+    let out_dir = test_dir.join("out");
+
+    let mut cbz = CbzHandler::new();
+    cbz.set_metadata(ebook_cli::Metadata::new().with_title("Space Cats")).unwrap();
+    cbz.add_image("b.png", vec![2]).unwrap();
+    cbz.add_image("a.png", vec![1]).unwrap();
+    cbz.write_to_file(&cbz_path).unwrap();
+
+    let output = Command::new(get_cli_executable())
+        .args(["extract-images", cbz_path.to_str().unwrap(), out_dir.to_str().unwrap()])
+        .args(["--name-template", "{title}-{index:02}-{chapter}.{ext}"])
+        .output()
+        .unwrap();
+    let first = fs::read(out_dir.join("Space Cats-01-a.png"));
+    let second = fs::read(out_dir.join("Space Cats-02-b.png"));
+    cleanup_test_dir(&test_dir);
+
+    assert!(output.status.success());
+    assert_eq!(first.unwrap(), vec![1]);
+    assert_eq!(second.unwrap(), vec![2]);
+}
+
+#[test]
+fn test_cli_extract_images_name_template_without_index() {
+    use ebook_cli::formats::CbzHandler;
+    use ebook_cli::traits::EbookWriter;
+
+    let test_dir = setup_test_dir("extract_images_template_clash");
+    let cbz_path = test_dir.join("comic.cbz");
+    let out_dir = test_dir.join("out");
+
+    let mut cbz = CbzHandler::new();
+    cbz.set_metadata(ebook_cli::Metadata::new().with_title("Space Cats")).unwrap();
+    for (name, data) in [("a.png", 1), ("b.png", 2), ("c.png", 3)] {
+        cbz.add_image(name, vec![data]).unwrap();
+    }
+    cbz.write_to_file(&cbz_path).unwrap();
+
+    // Every page renders to the same name; later ones get a numeric suffix
+    let output = Command::new(get_cli_executable())
+        .args(["extract-images", cbz_path.to_str().unwrap(), out_dir.to_str().unwrap()])
+        .args(["--name-template", "{title}.{ext}"])
+        .output()
+        .unwrap();
+    let pages: Vec<_> = ["Space Cats.png", "Space Cats-2.png", "Space Cats-3.png"]
+        .iter()
+        .map(|name| fs::read(out_dir.join(name)).ok())
+        .collect();
+    let count = fs::read_dir(&out_dir).map(|entries| entries.count()).unwrap_or(0);
+    cleanup_test_dir(&test_dir);
+
+    assert!(output.status.success());
+    assert_eq!(pages, [Some(vec![1]), Some(vec![2]), Some(vec![3])]);
+    assert_eq!(count, 3);
+}
+
+#[test]
+fn test_cli_catalog_mixed_directory() {
+    use ebook_cli::formats::{EpubHandler, TxtHandler};
+    use ebook_cli::traits::EbookWriter;
+
+    let test_dir = setup_test_dir("catalog");
+    let books = test_dir.join("books");
+    fs::create_dir_all(books.join("nested")).unwrap();
+
+    let mut epub = EpubHandler::new();
+    epub.set_metadata(ebook_cli::Metadata::new().with_title("Catalog, Volume 1").with_author("Ann Author")).unwrap();
+    epub.add_chapter("Chapter 1", "<p>Text</p>").unwrap();
+    epub.write_to_file(&books.join("a.epub")).unwrap();
+
+    let mut txt = TxtHandler::new();
+    txt.set_content("Plain text book").unwrap();
+    txt.write_to_file(&books.join("b.txt")).unwrap();
+
+    fs::write(books.join("broken.cbz"), b"not a zip").unwrap();
+    fs::write(books.join("cover.jpg"), b"ignored").unwrap();
+    fs::write(books.join("nested").join("deep.txt"), "Nested book").unwrap();
+
+    let csv = Command::new(get_cli_executable()).arg("catalog").arg(&books).output().unwrap();
+    let json = Command::new(get_cli_executable())
+        .args(["catalog", "--recursive", "--json"])
+        .arg(&books)
+        .output()
+        .unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(csv.status.success(), "catalog should succeed despite the broken file");
+    let csv = String::from_utf8(csv.stdout).unwrap();
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(rows[0], "filename,format,title,authors,language,isbn,error");
+    assert_eq!(rows.len(), 4, "header plus one row per ebook: {csv}");
+    assert!(rows[1].starts_with("a.epub,epub,\"Catalog, Volume 1\",Ann Author,"), "{}", rows[1]);
+    assert!(rows[2].starts_with("b.txt,txt,"));
+    assert!(rows[3].starts_with("broken.cbz,cbz,") && !rows[3].ends_with(','), "{}", rows[3]);
+
+    assert!(json.status.success());
+    let entries: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    let filenames: Vec<&str> = entries.as_array().unwrap().iter().map(|e| e["filename"].as_str().unwrap()).collect();
+    assert_eq!(filenames.len(), 4);
+    assert!(filenames.iter().any(|name| name.ends_with("deep.txt")));
+    let broken = entries.as_array().unwrap().iter().find(|e| e["filename"] == "broken.cbz").unwrap();
+    assert!(broken["error"].is_string());
+}
+
+#[test]
+fn test_cli_convert_sample_edition() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::{EbookReader, EbookWriter};
+
+    let test_dir = setup_test_dir("sample");
+    let source_path = test_dir.join("full.epub");
+    let sample_path = test_dir.join("sample.epub");
+    let whole_path = test_dir.join("whole.epub");
+
+    let mut source = EpubHandler::new();
+    source.set_metadata(ebook_cli::Metadata::new().with_title("Five Chapters")).unwrap();
+    for n in 1..=5 {
+        source.add_chapter(&format!("Chapter {n}"), &format!("<p>Text of chapter {n}</p>")).unwrap();
+    }
+    source.write_to_file(&source_path).unwrap();
+
+    let output = Command::new(get_cli_executable())
+        .args(["convert", source_path.to_str().unwrap(), sample_path.to_str().unwrap(), "--sample", "2"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let mut sample = EpubHandler::new();
+    sample.read_from_file(&sample_path).unwrap();
+    assert_eq!(sample.get_metadata().unwrap().title.as_deref(), Some("Five Chapters (Sample)"));
+    assert!(sample.get_chapter(1).unwrap().is_some_and(|chapter| chapter.contains("chapter 2")));
+    assert!(sample.get_chapter(2).unwrap().is_none());
+
+    // A sample larger than the book keeps every chapter and says so
+    let output = Command::new(get_cli_executable())
+        .args(["convert", source_path.to_str().unwrap(), whole_path.to_str().unwrap(), "--sample", "9"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    let mut whole = EpubHandler::new();
+    whole.read_from_file(&whole_path).unwrap();
+    let fifth = whole.get_chapter(4).unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(output.status.success(), "{stderr}");
+    assert!(stderr.contains("Sample of 9 chapters covers the whole book (5 chapters)"), "{stderr}");
+    assert!(fifth.is_some());
+}
+
+#[test]
+fn test_cli_doctor_reports_missing_title() {
+    use zip::write::{FileOptions, ZipWriter};
+
+    let test_dir = setup_test_dir("doctor");
+    let epub_path = test_dir.join("untitled.epub");
+    let mut zip = ZipWriter::new(File::create(&epub_path).unwrap());
+    let stored = FileOptions::<()>::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("mimetype", stored).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", FileOptions::<()>::default()).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("content.opf", FileOptions::<()>::default()).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:language>en</dc:language></metadata>
+  <manifest><item id="ch1" href="ch1.xhtml" media-type="application/xhtml+xml"/></manifest>
+  <spine><itemref idref="ch1"/></spine>
+</package>"#).unwrap();
+    zip.start_file("ch1.xhtml", FileOptions::<()>::default()).unwrap();
+    zip.write_all(b"<html><body><p>No title here</p></body></html>").unwrap();
+    zip.finish().unwrap();
+
+    let cli = get_cli_executable();
+    let output = Command::new(&cli).arg("doctor").arg(&epub_path).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Format: epub (extension), epub (content)"), "{stdout}");
+    assert!(stdout.contains("The metadata has no title"), "{stdout}");
+    assert!(stdout.contains(&format!("ebook repair {}", epub_path.display())), "{stdout}");
+
+    // Doctor reports instead of failing, even on a file that isn't what its name says
+    let fake_path = test_dir.join("fake.epub");
+    fs::write(&fake_path, "Just some text").unwrap();
+    let output = Command::new(&cli).arg("doctor").arg(&fake_path).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("The extension says epub but the content is txt"), "{stdout}");
+    assert!(stdout.contains(&format!("mv {} {}", fake_path.display(), test_dir.join("fake.txt").display())), "{stdout}");
+
+    cleanup_test_dir(&test_dir);
+}
+
+#[test]
+fn test_cli_convert_with_forced_formats() {
+    let test_dir = setup_test_dir("forced_formats");
+    let input = test_dir.join("input");
+    fs::write(&input, "Chapter 1\n\nA story without an extension.").unwrap();
+    let output_path = test_dir.join("output.tmp");
+
+    let cli = get_cli_executable();
+    let output = Command::new(&cli).arg("convert").arg(&input).arg(&output_path).output().unwrap();
+    assert!(!output.status.success(), "detection should fail without an extension");
+
+    let output = Command::new(&cli)
+        .arg("convert")
+        .arg(&input)
+        .arg(&output_path)
+        .args(["--from", "txt", "--to", "epub"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Converting from txt to epub"));
+    assert!(fs::read(&output_path).unwrap().starts_with(b"PK"));
+
+    let output = Command::new(&cli).arg("read").arg(&output_path).args(["--as", "epub"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("A story without an extension."));
+
+    let output = Command::new(&cli).arg("info").arg(&output_path).args(["--as", "EPUB", "--json"]).output().unwrap();
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["format"], "epub");
+
+    let output = Command::new(&cli).arg("info").arg(&output_path).args(["--as", "docx"]).output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown format 'docx'"));
+}
+
+#[test]
+fn test_cli_convert_overrides_metadata() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::EbookReader;
+
+    let test_dir = setup_test_dir("metadata_override");
+    let txt_path = test_dir.join("untitled_draft.txt");
+    let epub_path = test_dir.join("book.epub");
+    fs::write(&txt_path, "Once there was a story.\n\nAnd it ended.").unwrap();
+
+    let output = Command::new(get_cli_executable())
+        .args(["convert", txt_path.to_str().unwrap(), epub_path.to_str().unwrap()])
+        .args(["--title", "My Book", "--author", "Ann Writer", "--author", "Bo Editor", "--language", "fr"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    let mut epub = EpubHandler::new();
+    let read = epub.read_from_file(&epub_path);
+    cleanup_test_dir(&test_dir);
+
+    assert!(output.status.success(), "{stderr}");
+    read.unwrap();
+    let metadata = epub.get_metadata().unwrap();
+    assert_eq!(metadata.title.as_deref(), Some("My Book"));
+    assert_eq!(metadata.authors, ["Ann Writer", "Bo Editor"]);
+    assert_eq!(metadata.language.as_deref(), Some("fr"));
+}
+
+#[test]
+fn test_cli_batch_resumes_from_manifest() {
+    let test_dir = setup_test_dir("batch_resume");
+    let books = test_dir.join("books");
+    let out = test_dir.join("out");
+    fs::create_dir_all(&books).unwrap();
+    for name in ["a", "b", "c"] {
+        fs::write(books.join(format!("{name}.txt")), format!("Book {name}\n\nSome text.")).unwrap();
+    }
+    fs::write(books.join("cover.jpg"), b"ignored").unwrap();
+
+    let batch = || Command::new(get_cli_executable()).args(["batch", "--to", "epub"]).arg(&books).arg(&out).output().unwrap();
+    let first = batch();
+    let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(out.join("batch-manifest.json")).unwrap()).unwrap();
+    fs::remove_file(out.join("b.epub")).unwrap();
+    let second = batch();
+    let after: serde_json::Value = serde_json::from_str(&fs::read_to_string(out.join("batch-manifest.json")).unwrap()).unwrap();
+    let rebuilt = out.join("b.epub").exists();
+    cleanup_test_dir(&test_dir);
+
+    assert!(first.status.success(), "{}", String::from_utf8_lossy(&first.stderr));
+    assert_eq!(manifest["finished"], true);
+    let statuses: Vec<&str> = manifest["files"].as_array().unwrap().iter().map(|f| f["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["done", "done", "done"]);
+
+    // Only the file whose output went missing is converted again
+    assert!(second.status.success(), "{}", String::from_utf8_lossy(&second.stderr));
+    let stdout = String::from_utf8_lossy(&second.stdout);
+    assert!(stdout.contains("Skipped a.txt") && stdout.contains("Skipped c.txt"), "{stdout}");
+    assert!(stdout.contains("Converted b.txt -> b.epub"), "{stdout}");
+    assert_eq!(stdout.matches("Converted ").count(), 1, "{stdout}");
+    assert!(rebuilt);
+    assert_eq!(after["files"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn test_cli_batch_keep_going_converts_valid_inputs() {
+    let test_dir = setup_test_dir("batch_keep_going");
+    let books = test_dir.join("books");
+    fs::create_dir_all(&books).unwrap();
+    fs::write(books.join("a.txt"), "Book a\n\nSome text.").unwrap();
+    fs::write(books.join("broken.cbz"), b"not a zip").unwrap();
+    fs::write(books.join("c.txt"), "Book c\n\nSome text.").unwrap();
+
+    let batch = |out: &str, keep_going: bool| {
+        let mut command = Command::new(get_cli_executable());
+        command.args(["batch", "--to", "epub"]).arg(&books).arg(test_dir.join(out));
+        if keep_going {
+            command.arg("--keep-going");
+        }
+        command.output().unwrap()
+    };
+    let stopped = batch("stopped", false);
+    let stopped_outputs = (test_dir.join("stopped/a.epub").exists(), test_dir.join("stopped/c.epub").exists());
+    let kept = batch("kept", true);
+    let kept_outputs = (test_dir.join("kept/a.epub").exists(), test_dir.join("kept/c.epub").exists());
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(test_dir.join("kept/batch-manifest.json")).unwrap()).unwrap();
+    cleanup_test_dir(&test_dir);
+
+    // Without the flag the run stops at the broken file
+    assert!(!stopped.status.success());
+    assert!(String::from_utf8_lossy(&stopped.stderr).contains("broken.cbz"));
+    assert_eq!(stopped_outputs, (true, false));
+
+    assert!(!kept.status.success(), "batch --keep-going should exit nonzero after a failure");
+    let stderr = String::from_utf8_lossy(&kept.stderr);
+    assert!(stderr.contains("1 of 3 files failed:") && stderr.contains("  broken.cbz: "), "{stderr}");
+    assert_eq!(kept_outputs, (true, true));
+    let statuses: Vec<&str> = manifest["files"].as_array().unwrap().iter().map(|f| f["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["done", "failed", "done"]);
+    assert!(manifest["files"][1]["error"].is_string());
+    assert_eq!(manifest["finished"], true);
+}
+
+#[test]
+fn test_cli_merge_keep_going() {
+    use ebook_cli::traits::EbookReader;
+
+    let test_dir = setup_test_dir("merge_keep_going");
+    let (a, broken, c) = (test_dir.join("a.txt"), test_dir.join("broken.epub"), test_dir.join("c.txt"));
+    fs::write(&a, "Title: First Book\n\nOpening text.").unwrap();
+    fs::write(&broken, b"not a zip").unwrap();
+    fs::write(&c, "Closing text.").unwrap();
+
+    let merge = |output: &PathBuf, keep_going: bool| {
+        let mut command = Command::new(get_cli_executable());
+        command.arg("merge").args([&a, &broken, &c]).arg("-o").arg(output);
+        if keep_going {
+            command.arg("--keep-going");
+        }
+        command.output().unwrap()
+    };
+    let stopped = merge(&test_dir.join("stopped.epub"), false);
+    let stopped_written = test_dir.join("stopped.epub").exists();
+    let kept = merge(&test_dir.join("kept.epub"), true);
+    let mut epub = ebook_cli::formats::EpubHandler::new();
+    epub.read_from_file(&test_dir.join("kept.epub")).unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(!stopped.status.success());
+    assert!(String::from_utf8_lossy(&stopped.stderr).contains("broken.epub"));
+    assert!(!stopped_written);
+
+    assert!(!kept.status.success(), "merge --keep-going should exit nonzero after a failure");
+    let stderr = String::from_utf8_lossy(&kept.stderr);
+    assert!(stderr.contains("1 of 3 files failed:") && stderr.contains("broken.epub: "), "{stderr}");
+    assert_eq!(epub.get_metadata().unwrap().title.as_deref(), Some("First Book"));
+    let content = epub.get_content().unwrap();
+    assert!(content.contains("Opening text.") && content.contains("Closing text."), "{content}");
+}
+
+#[test]
+fn test_cli_catalog_keep_going_summarizes_failures() {
+    let test_dir = setup_test_dir("catalog_keep_going");
+    fs::write(test_dir.join("a.txt"), "Readable book").unwrap();
+    fs::write(test_dir.join("broken.cbz"), b"not a zip").unwrap();
+
+    let output = Command::new(get_cli_executable()).args(["catalog", "--keep-going"]).arg(&test_dir).output().unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(!output.status.success(), "catalog --keep-going should exit nonzero after a failure");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("1 of 2 files failed:") && stderr.contains("  broken.cbz: "), "{stderr}");
+    let csv = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(csv.lines().count(), 3, "every book is still reported: {csv}");
+}
+
+#[test]
+fn test_cli_batch_name_template() {
+    let test_dir = setup_test_dir("batch_name_template");
+    let books = test_dir.join("books");
+    let out = test_dir.join("out");
+    fs::create_dir_all(&books).unwrap();
+    fs::write(books.join("Introduction.txt"), "Introduction\n\nWhere it starts.").unwrap();
+    fs::write(books.join("Methods.txt"), "Methods\n\nHow it was done.").unwrap();
+
+    let output = Command::new(get_cli_executable())
+        .args(["batch", "--to", "epub", "--name-template", "{index:03}-{chapter}.{ext}"])
+        .arg(&books)
+        .arg(&out)
+        .output()
+        .unwrap();
+    let first = out.join("001-Introduction.epub").is_file();
+    let second = out.join("002-Methods.epub").is_file();
+    let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(out.join("batch-manifest.json")).unwrap()).unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(first && second, "{}", String::from_utf8_lossy(&output.stdout));
+    assert_eq!(manifest["files"][0]["output"], "001-Introduction.epub");
+}
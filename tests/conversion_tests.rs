@@ -1,6 +1,8 @@
 //! Integration tests for ebook format conversion
 
-use ebook_cli::Converter;
+use ebook_cli::{Converter, ConversionOptions, Metadata};
+use ebook_cli::formats::{CbzHandler, EpubHandler, TxtHandler};
+use ebook_cli::traits::{EbookReader, EbookWriter};
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
@@ -27,7 +29,7 @@ fn create_test_txt(path: &PathBuf) {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent).unwrap();
     }
-    let mut file = File::create(&path).unwrap();
+    let mut file = File::create(path).unwrap();
     writeln!(file, "Title: Test Book").unwrap();
     writeln!(file, "Author: Test Author").unwrap();
     writeln!(file).unwrap();
@@ -231,6 +233,139 @@ fn test_conversion_with_progress() {
     assert!(exists, "Output EPUB file should exist");
 }
 
+#[test]
+fn test_epub_to_txt_with_metadata_header() {
+    let test_dir = setup_test_dir();
+    let epub_path = test_dir.join("header.epub");
+    let out_txt_path = test_dir.join("header.txt");
+
+    let mut epub = EpubHandler::new();
+    epub.set_metadata(Metadata::new().with_title("Header Book").with_author("Jane Doe")).unwrap();
+    epub.add_chapter("Chapter 1", "<h1>Chapter 1</h1><p>Body text.</p>").unwrap();
+    epub.write_to_file(&epub_path).unwrap();
+
+    let options = ConversionOptions::new().with_metadata_header(true);
+    let result = Converter::convert_with_options(&epub_path, &out_txt_path, "txt", &options, None);
+    assert!(result.is_ok(), "EPUB to TXT should succeed: {:?}", result.err());
+
+    let raw = std::fs::read_to_string(&out_txt_path).unwrap();
+    assert!(raw.starts_with("Title: Header Book\nAuthor: Jane Doe\n\n"));
+
+    let mut txt = TxtHandler::new();
+    txt.read_from_file(&out_txt_path).unwrap();
+    let metadata = txt.get_metadata().unwrap();
+    let content = txt.get_content().unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert_eq!(metadata.title, Some("Header Book".to_string()));
+    assert_eq!(metadata.author, Some("Jane Doe".to_string()));
+    assert!(!content.starts_with("Title:"));
+}
+
+#[test]
+fn test_epub_to_txt_joins_multiple_authors() {
+    let test_dir = setup_test_dir();
+    let epub_path = test_dir.join("authors.epub");
+    let out_txt_path = test_dir.join("authors.txt");
+
+    let mut epub = EpubHandler::new();
+    epub.set_metadata(Metadata::new().with_title("Joint Work").with_authors(["Ann Lee", "Bo Chan"])).unwrap();
+    epub.add_chapter("Chapter 1", "<p>Body text.</p>").unwrap();
+    epub.write_to_file(&epub_path).unwrap();
+
+    let options = ConversionOptions::new()
+        .with_metadata_header(true)
+        .with_author_separator(" & ");
+    Converter::convert_with_options(&epub_path, &out_txt_path, "txt", &options, None).unwrap();
+
+    let raw = std::fs::read_to_string(&out_txt_path).unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert!(raw.starts_with("Title: Joint Work\nAuthor: Ann Lee & Bo Chan\n\n"));
+}
+
+#[test]
+fn test_metadata_author_follows_authors() {
+    let mut legacy = Metadata::new();
+    legacy.author = Some("First Writer".to_string());
+    legacy.add_author("Second Writer");
+    assert_eq!(legacy.authors, vec!["First Writer", "Second Writer"]);
+    assert_eq!(legacy.author.as_deref(), Some("First Writer"));
+
+    let mut metadata = Metadata::new().with_authors(["Ann Lee", "Bo Chan"]);
+    metadata.set_authors(["Cy Park"]);
+    assert_eq!(metadata.author.as_deref(), Some("Cy Park"));
+    metadata.set_authors_from_joined("Di Ho; Ed Fu", ";");
+    assert_eq!(metadata.author.as_deref(), Some("Di Ho"));
+    metadata.set_authors(Vec::<String>::new());
+    assert_eq!(metadata.author, None);
+    assert!(metadata.all_authors().is_empty());
+}
+
+#[test]
+fn test_cbz_to_epub_conversion() {
+    let test_dir = setup_test_dir();
+    let cbz_path = test_dir.join("comic.cbz");
+    let epub_path = test_dir.join("comic.epub");
+
+    let mut page = Vec::new();
+    image::DynamicImage::new_rgb8(8, 8)
+        .write_to(&mut std::io::Cursor::new(&mut page), image::ImageFormat::Png)
+        .unwrap();
+
+    let mut cbz = CbzHandler::new();
+    cbz.set_metadata(Metadata::new().with_title("Comic Title").with_author("Comic Writer")).unwrap();
+    for name in ["page10.png", "page2.png", "page1.png"] {
+        cbz.add_image(name, page.clone()).unwrap();
+    }
+    cbz.write_to_file(&cbz_path).unwrap();
+
+    let result = Converter::convert(&cbz_path, &epub_path, "epub");
+    assert!(result.is_ok(), "CBZ to EPUB should succeed: {:?}", result.err());
+
+    let mut archive = zip::ZipArchive::new(File::open(&epub_path).unwrap()).unwrap();
+    let mut opf = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("OEBPS/content.opf").unwrap(), &mut opf).unwrap();
+    let mut first_page = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("OEBPS/chapter1.xhtml").unwrap(), &mut first_page).unwrap();
+
+    let mut epub = EpubHandler::new();
+    epub.read_from_file(&epub_path).unwrap();
+    let metadata = epub.get_metadata().unwrap();
+    let images = epub.extract_images().unwrap();
+    cleanup_test_dir(&test_dir);
+
+    assert_eq!(opf.matches("<itemref").count(), 3);
+    assert_eq!(images.len(), 3);
+    assert!(first_page.contains(r#"<img src="images/page0001.png""#));
+    assert_eq!(metadata.title, Some("Comic Title".to_string()));
+    assert_eq!(metadata.author, Some("Comic Writer".to_string()));
+}
+
+#[test]
+fn test_cbz_to_epub_right_to_left() {
+    let test_dir = setup_test_dir();
+    let cbz_path = test_dir.join("manga.cbz");
+    let epub_path = test_dir.join("manga.epub");
+
+    let mut page = Vec::new();
+    image::DynamicImage::new_rgb8(8, 8)
+        .write_to(&mut std::io::Cursor::new(&mut page), image::ImageFormat::Png)
+        .unwrap();
+    let mut cbz = CbzHandler::new();
+    cbz.set_metadata(Metadata::new().with_title("Manga")).unwrap();
+    cbz.add_image("page1.png", page).unwrap();
+    cbz.write_to_file(&cbz_path).unwrap();
+
+    let options = ConversionOptions::new().with_right_to_left(true);
+    Converter::convert_with_options(&cbz_path, &epub_path, "epub", &options, None).unwrap();
+
+    let mut epub = EpubHandler::new();
+    epub.read_from_file(&epub_path).unwrap();
+    cleanup_test_dir(&test_dir);
+    assert_eq!(epub.get_page_progression(), Some(ebook_cli::formats::PageProgression::RightToLeft));
+}
+
 #[test]
 fn test_unsupported_conversion() {
     let test_dir = setup_test_dir();
@@ -249,3 +384,382 @@ fn test_unsupported_conversion() {
 
     cleanup_test_dir(&test_dir);
 }
+
+#[test]
+fn test_txt_to_epub_toc_from_headings() {
+    let test_dir = setup_test_dir();
+    let txt_path = test_dir.join("headings.txt");
+    let epub_path = test_dir.join("headings.epub");
+
+    std::fs::write(&txt_path, "Chapter 1\nThe start.\n\nChapter 2\nThe end.\n").unwrap();
+
+    let options = ConversionOptions::new().with_toc_from_headings(true);
+    Converter::convert_with_options(&txt_path, &epub_path, "epub", &options, None).unwrap();
+
+    let mut epub = EpubHandler::new();
+    epub.read_from_file(&epub_path).unwrap();
+    let toc = epub.get_toc().unwrap();
+    cleanup_test_dir(&test_dir);
+
+    let titles: Vec<&str> = toc.iter().map(|entry| entry.title.as_str()).collect();
+    assert_eq!(titles, vec!["Chapter 1", "Chapter 2"]);
+}
+
+#[test]
+fn test_supported_targets_table() {
+    let txt_targets = Converter::supported_targets("txt");
+    for target in ["epub", "pdf", "mobi", "fb2"] {
+        assert!(txt_targets.contains(&target), "txt -> {target} should be supported");
+    }
+    assert!(!txt_targets.contains(&"cbz"));
+    assert_eq!(Converter::supported_targets("cbz"), vec!["epub"]);
+    assert!(Converter::all_targets().contains(&"txt"));
+
+    // Combinations missing from the table are rejected before dispatch
+    let test_dir = setup_test_dir();
+    let txt_path = test_dir.join("unsupported.txt");
+    create_test_txt(&txt_path);
+    let err = Converter::convert(&txt_path, &test_dir.join("out.cbz"), "cbz").unwrap_err();
+    cleanup_test_dir(&test_dir);
+    assert!(matches!(err, ebook_cli::EbookError::NotSupported(_)));
+}
+
+#[test]
+fn test_conversion_error_names_formats_and_stage() {
+    let test_dir = setup_test_dir();
+    let epub_path = test_dir.join("source.epub");
+    let mut epub = EpubHandler::new();
+    epub.set_metadata(Metadata::new().with_title("Unwritable")).unwrap();
+    epub.add_chapter("Chapter 1", "<p>Text</p>").unwrap();
+    epub.write_to_file(&epub_path).unwrap();
+
+    // A directory where the output file should go fails the write even when running as root,
+    // which a read-only directory would not
+    let output_path = test_dir.join("out.pdf");
+    std::fs::create_dir_all(&output_path).unwrap();
+    let err = Converter::convert(&epub_path, &output_path, "pdf").unwrap_err();
+    cleanup_test_dir(&test_dir);
+
+    match &err {
+        ebook_cli::EbookError::ConversionError { from, to, stage, source } => {
+            assert_eq!((from.as_str(), to.as_str()), ("epub", "pdf"));
+            assert_eq!(stage, "writing PDF");
+            assert!(matches!(**source, ebook_cli::EbookError::Io(_)), "{source:?}");
+        }
+        other => panic!("expected a conversion error, got {other:?}"),
+    }
+    assert!(err.to_string().starts_with("Conversion from epub to pdf failed while writing PDF: IO error"), "{err}");
+}
+
+/// Chapter titles as listed in the nav document of a written EPUB
+fn nav_titles(epub_path: &std::path::Path) -> Vec<String> {
+    use std::io::Read;
+
+    let mut archive = zip::ZipArchive::new(File::open(epub_path).unwrap()).unwrap();
+    let mut nav = String::new();
+    archive.by_name("OEBPS/nav.xhtml").unwrap().read_to_string(&mut nav).unwrap();
+    let toc = nav.split("</nav>").next().unwrap();
+    toc.split("\">")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("</a>").map(|(title, _)| title.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_txt_to_epub_chapter_title_template() {
+    let test_dir = setup_test_dir();
+    let txt_path = test_dir.join("sections.txt");
+    let epub_path = test_dir.join("sections.epub");
+    let headed_path = test_dir.join("headed.epub");
+
+    std::fs::write(&txt_path, "It began.\n\n---\n\nIt went on.\n\n---\n\nIt ended.\n").unwrap();
+
+    let options = ConversionOptions::new().with_chapter_title_template("{roman}");
+    Converter::convert_with_options(&txt_path, &epub_path, "epub", &options, None).unwrap();
+
+    assert_eq!(nav_titles(&epub_path), vec!["I", "II", "III"]);
+
+    // A section's own heading wins over the template
+    std::fs::write(&txt_path, "Prologue text.\n\n---\n\nChapter Two: Onward\nMore text.\n").unwrap();
+    let options = ConversionOptions::new().with_chapter_title_template("Part {n:02}");
+    Converter::convert_with_options(&txt_path, &headed_path, "epub", &options, None).unwrap();
+
+    let titles = nav_titles(&headed_path);
+    cleanup_test_dir(&test_dir);
+    assert_eq!(titles, vec!["Part 01", "Chapter Two: Onward"]);
+
+    assert!(ebook_cli::utils::render_chapter_title("Chapter {x}", 1).is_err());
+    assert_eq!(ebook_cli::utils::render_chapter_title("Book {roman}", 1994).unwrap(), "Book MCMXCIV");
+}
+
+#[test]
+fn test_mobi_to_epub_conversion() {
+    use ebook_cli::formats::MobiHandler;
+
+    let test_dir = setup_test_dir();
+    let mobi_path = test_dir.join("kindle.mobi");
+    let epub_path = test_dir.join("kindle.epub");
+
+    let mut mobi = MobiHandler::new();
+    mobi.set_metadata(Metadata::new().with_title("Kindle Book")).unwrap();
+    mobi.set_content(
+        "Chapter One\n\nIt began & went on.<mbp:pagebreak>Chapter Two\n\nMore text.<mbp:pagebreak>The end.",
+    )
+    .unwrap();
+    mobi.write_to_file(&mobi_path).unwrap();
+
+    let result = Converter::convert(&mobi_path, &epub_path, "epub");
+    assert!(result.is_ok(), "MOBI to EPUB should succeed: {:?}", result.err());
+
+    let mut epub = EpubHandler::new();
+    epub.read_from_file(&epub_path).unwrap();
+    let metadata = epub.get_metadata().unwrap();
+    let last_chapter = epub.get_chapter(2).unwrap();
+    let titles = nav_titles(&epub_path);
+    cleanup_test_dir(&test_dir);
+
+    assert_eq!(metadata.title, Some("Kindle Book".to_string()));
+    assert_eq!(titles, vec!["Chapter One", "Chapter Two", "Chapter 3"]);
+    assert!(last_chapter.is_some_and(|chapter| chapter.contains("The end.")));
+    assert!(epub.get_chapter(3).unwrap().is_none());
+    assert!(Converter::supported_targets("azw").contains(&"epub"));
+}
+
+#[test]
+fn test_txt_to_epub_title_page() {
+    let test_dir = setup_test_dir();
+    let txt_path = test_dir.join("Moby Dick.txt");
+    let epub_path = test_dir.join("titled.epub");
+    let titled_source_path = test_dir.join("opens-with-title.epub");
+
+    std::fs::write(&txt_path, "Call me Ishmael.\n\n---\n\nThe whale.\n").unwrap();
+    let options = ConversionOptions::new().with_title_page(true);
+    Converter::convert_with_options(&txt_path, &epub_path, "epub", &options, None).unwrap();
+
+    let mut epub = EpubHandler::new();
+    epub.read_from_file(&epub_path).unwrap();
+    let first = epub.get_chapter(0).unwrap().unwrap();
+    let second = epub.get_chapter(1).unwrap().unwrap();
+    assert!(first.contains("<h1>Moby Dick</h1>"), "{first}");
+    assert!(second.contains("Call me Ishmael."));
+
+    // PDF output gets the title page in front of the text page
+    let pdf_path = test_dir.join("titled.pdf");
+    Converter::convert_with_options(&txt_path, &pdf_path, "pdf", &options, None).unwrap();
+    assert_eq!(lopdf::Document::load(&pdf_path).unwrap().get_pages().len(), 2);
+
+    // A source whose first line is already the title gets no extra page
+    std::fs::write(&txt_path, "Moby Dick\n\nCall me Ishmael.\n").unwrap();
+    Converter::convert_with_options(&txt_path, &titled_source_path, "epub", &options, None).unwrap();
+    let mut epub = EpubHandler::new();
+    epub.read_from_file(&titled_source_path).unwrap();
+    let first = epub.get_chapter(0).unwrap().unwrap();
+    cleanup_test_dir(&test_dir);
+    assert!(!first.contains("<h1>"), "{first}");
+}
+
+#[test]
+fn test_txt_to_epub_split_on_size() {
+    let test_dir = setup_test_dir();
+    let txt_path = test_dir.join("long.txt");
+    let epub_path = test_dir.join("long.epub");
+
+    // One chapter of 40 short paragraphs, then a single 3000-byte paragraph
+    let mut paragraphs: Vec<String> = (1..=40).map(|n| format!("Paragraph {n} of the only chapter. {}", "word ".repeat(15))).collect();
+    paragraphs.push(format!("Unbroken{}", " sentence".repeat(333)));
+    std::fs::write(&txt_path, paragraphs.join("\n\n")).unwrap();
+
+    let options = ConversionOptions::new().with_max_chapter_bytes(1024);
+    Converter::convert_with_options(&txt_path, &epub_path, "epub", &options, None).unwrap();
+
+    let mut epub = EpubHandler::new();
+    epub.read_from_file(&epub_path).unwrap();
+    let toc = epub.get_toc().unwrap();
+    assert!(toc.len() > 2, "expected several spine items, got {}", toc.len());
+    let mut nav = String::new();
+    let mut archive = zip::ZipArchive::new(File::open(&epub_path).unwrap()).unwrap();
+    std::io::Read::read_to_string(&mut archive.by_name("OEBPS/nav.xhtml").unwrap(), &mut nav).unwrap();
+    assert!(nav.contains(">Chapter 1 (part 1)<") && nav.contains(">Chapter 1 (part 2)<"), "{nav}");
+
+    let texts: Vec<String> = (0..toc.len()).map(|idx| epub.get_chapter(idx).unwrap().unwrap()).collect();
+    let (last, rest) = texts.split_last().unwrap();
+    for text in rest {
+        assert!(text.contains("Paragraph") && !text.contains("Unbroken"));
+        assert!(text.trim().len() <= 1024, "part of {} bytes", text.trim().len());
+    }
+    // The oversized paragraph is a chapter of its own, whole
+    assert!(last.trim().starts_with("Unbroken") && last.trim().ends_with("sentence"), "{last}");
+    assert!(texts.iter().any(|text| text.contains("Paragraph 40 ")));
+
+    cleanup_test_dir(&test_dir);
+}
+
+#[test]
+fn test_fb2_writer_escapes_and_skips_blank_paragraphs() {
+    use ebook_cli::formats::Fb2Handler;
+
+    let test_dir = setup_test_dir();
+    let fb2_path = test_dir.join("escaped.fb2");
+
+    let mut writer = Fb2Handler::new();
+    writer.set_metadata(Metadata::new().with_title("Fish & Chips").with_author("A <B> Writer")).unwrap();
+    writer.set_content("Salt & vinegar\n\n\nx < y > z\n   \nLast line").unwrap();
+    writer.write_to_file(&fb2_path).unwrap();
+
+    let xml = std::fs::read_to_string(&fb2_path).unwrap();
+    assert!(xml.contains("<book-title>Fish &amp; Chips</book-title>"), "{xml}");
+    assert!(xml.contains("<p>Salt &amp; vinegar</p>"), "{xml}");
+    assert!(xml.contains("<p>x &lt; y &gt; z</p>"), "{xml}");
+    assert!(!xml.contains("<p></p>"), "{xml}");
+    assert_eq!(xml.matches("<p>").count(), 3);
+
+    let mut reader = Fb2Handler::new();
+    reader.read_from_file(&fb2_path).unwrap();
+    assert_eq!(reader.get_metadata().unwrap().title.as_deref(), Some("Fish & Chips"));
+    let paragraphs: Vec<String> = reader.get_content().unwrap().lines().map(|line| line.trim().to_string()).collect();
+    assert_eq!(paragraphs, vec!["Salt & vinegar", "x < y > z", "Last line"]);
+
+    // Chapters become titled sections, and survive a second round trip unchanged
+    let mut writer = Fb2Handler::new();
+    writer.set_metadata(Metadata::new().with_title("Chapters")).unwrap();
+    writer.add_chapter("One & Only", "First\n\nSecond").unwrap();
+    writer.add_chapter("Two", "Third").unwrap();
+    writer.write_to_file(&fb2_path).unwrap();
+    let first = std::fs::read_to_string(&fb2_path).unwrap();
+    assert!(first.contains("<title><p>One &amp; Only</p></title>"), "{first}");
+
+    let mut reader = Fb2Handler::new();
+    reader.read_from_file(&fb2_path).unwrap();
+    let titles: Vec<String> = reader.get_toc().unwrap().into_iter().map(|entry| entry.title).collect();
+    assert_eq!(titles, vec!["One & Only", "Two"]);
+    reader.write_to_file(&fb2_path).unwrap();
+    assert_eq!(std::fs::read_to_string(&fb2_path).unwrap(), first);
+
+    cleanup_test_dir(&test_dir);
+}
+
+#[test]
+fn test_txt_to_epub_detects_language() {
+    let test_dir = setup_test_dir();
+    let samples = [
+        ("english", "It was the best of times and it was the worst of times. The city was quiet and the river was cold.", "en"),
+        ("french", "Il était une fois une petite fille qui vivait dans un village. Elle est allée dans la forêt et le loup est venu.", "fr"),
+    ];
+    let options = ConversionOptions::new().with_language_detection(true);
+    for (name, text, expected) in samples {
+        let txt_path = test_dir.join(format!("{name}.txt"));
+        let epub_path = test_dir.join(format!("{name}.epub"));
+        std::fs::write(&txt_path, text).unwrap();
+        Converter::convert_with_options(&txt_path, &epub_path, "epub", &options, None).unwrap();
+
+        let mut epub = EpubHandler::new();
+        epub.read_from_file(&epub_path).unwrap();
+        assert_eq!(epub.get_metadata().unwrap().language.as_deref(), Some(expected));
+    }
+
+    // A language given in the options wins over detection
+    let txt_path = test_dir.join("french.txt");
+    let epub_path = test_dir.join("declared.epub");
+    let declared = ConversionOptions::new().with_language_detection(true).with_language("fr-CA");
+    Converter::convert_with_options(&txt_path, &epub_path, "epub", &declared, None).unwrap();
+    let mut epub = EpubHandler::new();
+    epub.read_from_file(&epub_path).unwrap();
+    assert_eq!(epub.get_metadata().unwrap().language.as_deref(), Some("fr-CA"));
+    cleanup_test_dir(&test_dir);
+
+    // English only narrowly ahead of French, and too little text, are too unsure to set
+    for unsure in ["The cat and the dog sleep, the end. Le chat et le chien.", "Bonjour"] {
+        let mut metadata = Metadata::new();
+        assert!(!metadata.detect_language(unsure));
+        assert_eq!(metadata.language, None);
+    }
+}
+
+#[test]
+fn test_convert_handler_reads_source_once() {
+    use ebook_cli::formats::PdfHandler;
+
+    let test_dir = setup_test_dir();
+    let epub_path = test_dir.join("once.epub");
+
+    let mut epub = EpubHandler::new();
+    epub.set_metadata(Metadata::new().with_title("Read Once").with_author("Jane Doe")).unwrap();
+    epub.add_chapter("Chapter 1", "<h1>Chapter 1</h1><p>First body text.</p>").unwrap();
+    epub.add_chapter("Chapter 2", "<h1>Chapter 2</h1><p>Second body text.</p>").unwrap();
+    epub.write_to_file(&epub_path).unwrap();
+
+    let options = ConversionOptions::new().with_metadata_header(true).with_title_page(true).with_source_format("epub");
+    let mut source = EpubHandler::new();
+    source.read_from_file(&epub_path).unwrap();
+    for format in ["txt", "pdf"] {
+        let result = Converter::convert_handler(&source, &test_dir.join(format!("loaded.{format}")), format, &options);
+        assert!(result.is_ok(), "Converting the loaded EPUB to {format} should succeed: {:?}", result.err());
+        Converter::convert_with_options(&epub_path, &test_dir.join(format!("single.{format}")), format, &options, None).unwrap();
+    }
+    let unsupported = Converter::convert_handler(&source, &test_dir.join("loaded.cbz"), "cbz", &options);
+
+    let loaded_txt = std::fs::read(test_dir.join("loaded.txt")).unwrap();
+    let single_txt = std::fs::read(test_dir.join("single.txt")).unwrap();
+    let read_pdf = |name: &str| {
+        let mut pdf = PdfHandler::new();
+        pdf.read_from_file(&test_dir.join(name)).unwrap();
+        (pdf.get_metadata().unwrap().title, pdf.get_content().unwrap())
+    };
+    let (loaded_pdf, single_pdf) = (read_pdf("loaded.pdf"), read_pdf("single.pdf"));
+    cleanup_test_dir(&test_dir);
+
+    assert_eq!(loaded_txt, single_txt);
+    assert!(String::from_utf8(loaded_txt).unwrap().contains("Second body text."));
+    assert_eq!(loaded_pdf, single_pdf);
+    assert!(loaded_pdf.1.contains("First body text."));
+    assert!(unsupported.is_err());
+}
+
+#[test]
+fn test_convert_handler_matches_file_conversion_for_pdf_and_mobi() {
+    use ebook_cli::formats::{MobiHandler, PdfHandler};
+
+    let test_dir = setup_test_dir();
+    let pdf_path = test_dir.join("two-pages.pdf");
+    let mobi_path = test_dir.join("kindle.mobi");
+
+    let mut pdf = PdfHandler::new();
+    pdf.set_metadata(Metadata::new().with_title("Two Pages")).unwrap();
+    pdf.set_title_page(vec!["Two Pages".to_string()]);
+    pdf.set_content("Body of the second page.").unwrap();
+    pdf.write_to_file(&pdf_path).unwrap();
+
+    let mut mobi = MobiHandler::new();
+    mobi.set_metadata(Metadata::new().with_title("Kindle Book")).unwrap();
+    mobi.set_content("Chapter One text.<mbp:pagebreak>Chapter Two text.<mbp:pagebreak>Chapter Three text.").unwrap();
+    mobi.write_to_file(&mobi_path).unwrap();
+
+    let mut pdf_source = PdfHandler::new();
+    pdf_source.read_from_file(&pdf_path).unwrap();
+    let mut mobi_source = MobiHandler::new();
+    mobi_source.read_from_file(&mobi_path).unwrap();
+    let sources: [(&str, &std::path::Path, &dyn EbookReader); 2] =
+        [("pdf", &pdf_path, &pdf_source), ("mobi", &mobi_path, &mobi_source)];
+
+    let mut outputs = Vec::new();
+    for (format, path, source) in sources {
+        let options = ConversionOptions::new().with_sample(2).with_source_format(format);
+        let loaded = test_dir.join(format!("loaded-{format}.txt"));
+        let single = test_dir.join(format!("single-{format}.txt"));
+        Converter::convert_handler(source, &loaded, "txt", &options).unwrap();
+        Converter::convert_with_options(path, &single, "txt", &options, None).unwrap();
+        outputs.push((format, std::fs::read_to_string(loaded).unwrap(), std::fs::read_to_string(single).unwrap()));
+    }
+    let pdf_options = ConversionOptions::new().with_source_format("pdf");
+    let pdf_to_epub = Converter::convert_handler(&pdf_source, &test_dir.join("loaded.epub"), "epub", &pdf_options);
+    let unknown_source = Converter::convert_handler(&pdf_source, &test_dir.join("loaded.txt"), "txt", &ConversionOptions::new());
+    cleanup_test_dir(&test_dir);
+
+    for (format, loaded, single) in &outputs {
+        assert_eq!(loaded, single, "{format} source converted from memory should match the file conversion");
+    }
+    assert!(outputs[0].2.contains("\n\n"), "PDF pages are joined by a blank line");
+    assert!(outputs[1].2.contains("---"), "MOBI chapters are joined by the chapter marker");
+    assert!(pdf_to_epub.is_err(), "PDF to EPUB is not a supported conversion");
+    assert!(unknown_source.is_err());
+}
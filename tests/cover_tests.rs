@@ -0,0 +1,151 @@
+//! Tests for covers and thumbnails taken from a book's first page
+
+use ebook_cli::formats::PdfHandler;
+use ebook_cli::render::PageRenderer;
+use ebook_cli::traits::{EbookReader, EbookWriter, ImageData};
+use ebook_cli::Result;
+use lopdf::{dictionary, Document, Object, Stream};
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Renders every page as the same 2x1 PNG and records the pages it was asked for
+#[derive(Default)]
+struct FakeRenderer {
+    pages: std::cell::RefCell<Vec<(u32, u32)>>,
+}
+
+impl PageRenderer for FakeRenderer {
+    fn render_page(&self, pdf: &[u8], page_number: u32, width: u32) -> Result<ImageData> {
+        assert!(pdf.starts_with(b"%PDF"));
+        self.pages.borrow_mut().push((page_number, width));
+        Ok(ImageData::new("page.png".to_string(), "image/png".to_string(), png(2, 1)))
+    }
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::from(image::GrayImage::new(width, height))
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    png.into_inner()
+}
+
+/// A one-page PDF that draws a single 8-bit grayscale image and contains no text
+fn write_scanned_pdf(path: &Path) {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let image_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => 600,
+            "Height" => 800,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 8,
+        },
+        vec![128; 600 * 800],
+    ));
+    let content_id = doc.add_object(Stream::new(dictionary! {}, b"q 612 0 0 792 0 0 cm /Im1 Do Q".to_vec()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "Resources" => dictionary! { "XObject" => dictionary! { "Im1" => image_id } },
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+    });
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 }));
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    doc.save(path).unwrap();
+}
+
+fn write_text_pdf(path: &Path) {
+    let mut writer = PdfHandler::new();
+    writer.set_content("A page of text").unwrap();
+    writer.write_to_file(path).unwrap();
+}
+
+#[test]
+fn test_scanned_first_page_is_the_cover() {
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("scan.pdf");
+    write_scanned_pdf(&pdf_path);
+
+    let mut handler = PdfHandler::new();
+    handler.read_from_file(&pdf_path).unwrap();
+    let renderer = FakeRenderer::default();
+    let cover = handler.first_page_cover(Some(&renderer)).unwrap();
+    assert_eq!(cover.name, "cover.png");
+    let decoded = image::load_from_memory(&cover.data).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (600, 800));
+    assert!(renderer.pages.borrow().is_empty());
+}
+
+#[test]
+fn test_text_first_page_is_rendered() {
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("text.pdf");
+    write_text_pdf(&pdf_path);
+
+    let mut handler = PdfHandler::new();
+    handler.read_from_file(&pdf_path).unwrap();
+    let renderer = FakeRenderer::default();
+    let cover = handler.first_page_cover(Some(&renderer)).unwrap();
+    assert_eq!(cover.name, "cover.png");
+    assert_eq!(*renderer.pages.borrow(), vec![(1, ebook_cli::render::COVER_WIDTH)]);
+}
+
+#[test]
+fn test_cli_thumbnail_cover_from_first_page() {
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("scan.pdf");
+    write_scanned_pdf(&pdf_path);
+    let thumbnail = temp_dir.path().join("thumb.jpg");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.arg("thumbnail")
+        .arg(&pdf_path)
+        .arg(&thumbnail)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("--cover-from-first-page"));
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.arg("thumbnail")
+        .arg(&pdf_path)
+        .arg(&thumbnail)
+        .args(["--cover-from-first-page", "--width", "150"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("150x200"));
+    let decoded = image::open(&thumbnail).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (150, 200));
+}
+
+#[cfg(not(feature = "pdf-render"))]
+#[test]
+fn test_rendering_needs_the_feature() {
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("text.pdf");
+    write_text_pdf(&pdf_path);
+
+    let mut handler = PdfHandler::new();
+    handler.read_from_file(&pdf_path).unwrap();
+    let error = handler.first_page_cover(None).unwrap_err();
+    assert!(error.to_string().contains("--features pdf-render"), "{error}");
+}
+
+#[cfg(feature = "pdf-render")]
+#[test]
+fn test_pdfium_renders_a_cover() {
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("text.pdf");
+    write_text_pdf(&pdf_path);
+
+    let mut handler = PdfHandler::new();
+    handler.read_from_file(&pdf_path).unwrap();
+    let cover = handler.first_page_cover(None).unwrap();
+    assert!(!cover.data.is_empty());
+    let decoded = image::load_from_memory(&cover.data).unwrap();
+    assert_eq!(decoded.width(), ebook_cli::render::COVER_WIDTH);
+}
@@ -142,3 +142,636 @@ fn test_epub3_metadata_preservation() {
     assert_eq!(read_metadata.title, Some("Metadata Test".to_string()));
     assert_eq!(read_metadata.author, Some("John Doe".to_string()));
 }
+
+#[test]
+fn test_epub3_page_list_round_trip() {
+    use ebook_cli::formats::PageMarker;
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("page_list.epub");
+    let rewritten_path = temp_dir.path().join("page_list_rewritten.epub");
+
+    let mut handler = EpubHandler::new();
+    handler.set_epub_version(EpubVersion::V3);
+    handler.set_metadata(Metadata::new().with_title("Paged Book")).unwrap();
+    handler.add_chapter("Chapter 1", "<p id=\"page1\">One</p><p id=\"page2\">Two</p>").unwrap();
+    handler.add_chapter("Chapter 2", "<p id=\"page3\">Three</p>").unwrap();
+    handler.add_page_marker("1", "chapter1.xhtml#page1");
+    handler.add_page_marker("2", "chapter1.xhtml#page2");
+    handler.add_page_marker("3", "chapter2.xhtml#page3");
+    handler.write_to_file(&epub_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&epub_path).unwrap();
+    let markers = reader.get_page_list();
+    assert_eq!(markers, vec![
+        PageMarker::new("1", "chapter1.xhtml#page1"),
+        PageMarker::new("2", "chapter1.xhtml#page2"),
+        PageMarker::new("3", "chapter2.xhtml#page3"),
+    ]);
+    // The page-list must not leak into the table of contents
+    assert_eq!(reader.get_toc().unwrap().len(), 2);
+
+    let mut writer = EpubHandler::new();
+    writer.set_metadata(Metadata::new().with_title("Paged Book")).unwrap();
+    writer.add_chapter("Chapter 1", "<p>One</p>").unwrap();
+    writer.set_page_list(markers.clone());
+    writer.write_to_file(&rewritten_path).unwrap();
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&rewritten_path).unwrap();
+    assert_eq!(reread.get_page_list(), markers);
+}
+
+#[test]
+fn test_epub3_page_list_labels_are_escaped() {
+    use ebook_cli::formats::PageMarker;
+    use std::io::Read;
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("escaped_pages.epub");
+    let rewritten_path = temp_dir.path().join("escaped_pages_rewritten.epub");
+
+    let markers = vec![
+        PageMarker::new("A&B", "chapter1.xhtml#p1"),
+        PageMarker::new("<i>", "chapter1.xhtml#p2"),
+    ];
+    let mut handler = EpubHandler::new();
+    handler.set_epub_version(EpubVersion::V3);
+    handler.set_metadata(Metadata::new().with_title("Escaped Pages")).unwrap();
+    handler.add_chapter("Chapter 1", "<p id=\"p1\">One</p><p id=\"p2\">Two</p>").unwrap();
+    handler.set_page_list(markers.clone());
+    handler.write_to_file(&epub_path).unwrap();
+
+    // Read and rewrite, then check the nav document is still well-formed XML
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&epub_path).unwrap();
+    assert_eq!(reader.get_page_list(), markers);
+    reader.write_to_file(&rewritten_path).unwrap();
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&rewritten_path).unwrap()).unwrap();
+    let nav_name = archive.file_names().find(|name| name.ends_with("nav.xhtml")).unwrap().to_string();
+    let mut nav = String::new();
+    archive.by_name(&nav_name).unwrap().read_to_string(&mut nav).unwrap();
+    let mut xml = quick_xml::Reader::from_str(&nav);
+    loop {
+        match xml.read_event() {
+            Ok(quick_xml::events::Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => panic!("nav.xhtml is not well-formed: {e}\n{nav}"),
+        }
+    }
+    assert!(nav.contains("A&amp;B") && nav.contains("&lt;i&gt;"), "{nav}");
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&rewritten_path).unwrap();
+    assert_eq!(reread.get_page_list(), markers);
+}
+
+#[test]
+fn test_epub2_guide_round_trip() {
+    use ebook_cli::formats::GuideReference;
+    use std::io::{Read, Write};
+    use zip::write::{FileOptions, ZipWriter};
+    use zip::ZipArchive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("guide_source.epub");
+    let v2_path = temp_dir.path().join("guide_v2.epub");
+    let v3_path = temp_dir.path().join("guide_v3.epub");
+
+    // A hand-written EPUB 2 with a <guide>
+    let mut zip = ZipWriter::new(fs::File::create(&source_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OPS/book.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("OPS/book.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Guided</dc:title></metadata>
+  <manifest>
+    <item id="cover" href="cover.xhtml" media-type="application/xhtml+xml"/>
+    <item id="text" href="text.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="cover"/><itemref idref="text"/></spine>
+  <guide>
+    <reference type="cover" title="Cover" href="cover.xhtml"/>
+    <reference type="text" title="Start" href="text.xhtml#start"/>
+  </guide>
+</package>"#).unwrap();
+    zip.start_file("OPS/cover.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><h1>Cover</h1></body></html>").unwrap();
+    zip.start_file("OPS/text.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><h1 id=\"start\">Start</h1></body></html>").unwrap();
+    zip.finish().unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&source_path).unwrap();
+    assert_eq!(reader.get_epub_version(), EpubVersion::V2);
+    let guide = reader.get_guide();
+    assert_eq!(guide, vec![
+        GuideReference::new("cover", "Cover", "OPS/cover.xhtml"),
+        GuideReference::new("text", "Start", "OPS/text.xhtml#start"),
+    ]);
+
+    let read_entry = |path: &std::path::Path, name: &str| {
+        let mut archive = ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+        let mut content = String::new();
+        archive.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+        content
+    };
+
+    // EPUB 2 output keeps a <guide> in the OPF, which stays in the source's package directory
+    reader.write_to_file(&v2_path).unwrap();
+    let opf = read_entry(&v2_path, "OPS/content.opf");
+    assert!(opf.contains(r#"<reference type="cover" title="Cover" href="cover.xhtml"/>"#));
+    assert!(opf.contains(r#"<reference type="text" title="Start" href="text.xhtml#start"/>"#));
+
+    // EPUB 3 output carries the same references as the landmarks nav
+    reader.set_epub_version(EpubVersion::V3);
+    reader.write_to_file(&v3_path).unwrap();
+    assert!(!read_entry(&v3_path, "OPS/content.opf").contains("<guide>"));
+    let nav = read_entry(&v3_path, "OPS/nav.xhtml");
+    assert!(nav.contains(r#"<a epub:type="cover" href="cover.xhtml">Cover</a>"#));
+    assert!(nav.contains(r#"<a epub:type="bodymatter" href="text.xhtml#start">Start</a>"#));
+
+    for path in [&v2_path, &v3_path] {
+        let mut reread = EpubHandler::new();
+        reread.read_from_file(path).unwrap();
+        let hrefs: Vec<String> = reread.get_guide().iter().map(|r| format!("{}={}", r.kind, r.href)).collect();
+        assert_eq!(hrefs, vec!["cover=OPS/cover.xhtml", "text=OPS/text.xhtml#start"]);
+    }
+}
+
+#[test]
+fn test_epub3_chapters_from_toc_anchors() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("single_file.epub");
+
+    // One spine file holding three chapters, each anchored from the nav TOC
+    let mut zip = ZipWriter::new(fs::File::create(&epub_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OPS/book.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("OPS/book.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>One File</dc:title></metadata>
+  <manifest>
+    <item id="nav" href="nav/toc.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="text" href="text/book.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="text"/></spine>
+</package>"#).unwrap();
+    zip.start_file("OPS/nav/toc.xhtml", options).unwrap();
+    zip.write_all(br#"<html xmlns:epub="http://www.idpf.org/2007/ops"><body>
+<nav epub:type="toc"><ol>
+  <li><a href="../text/book.xhtml#one">First</a></li>
+  <li><a href="../text/book.xhtml#two">Second</a></li>
+  <li><a href="../text/book.xhtml#three">Third</a></li>
+</ol></nav>
+</body></html>"#).unwrap();
+    zip.start_file("OPS/text/book.xhtml", options).unwrap();
+    zip.write_all(br#"<html><body>
+<h1 id="one">First</h1><p>Alpha text.</p>
+<h1 id="two">Second</h1><p>Beta text.</p>
+<h1 id="three">Third</h1><p>Gamma text.</p>
+</body></html>"#).unwrap();
+    zip.finish().unwrap();
+
+    let mut whole = EpubHandler::new();
+    whole.read_from_file(&epub_path).unwrap();
+    assert_eq!(whole.get_toc().unwrap().len(), 1);
+
+    let mut split = EpubHandler::new();
+    split.set_chapters_from_toc(true);
+    split.read_from_file(&epub_path).unwrap();
+
+    let toc = split.get_toc().unwrap();
+    let titles: Vec<&str> = toc.iter().map(|entry| entry.title.as_str()).collect();
+    assert_eq!(titles, vec!["First", "Second", "Third"]);
+    assert_eq!(toc[1].href.as_deref(), Some("OPS/text/book.xhtml#two"));
+
+    let second = split.get_chapter(1).unwrap().unwrap();
+    assert!(second.contains("Beta text."));
+    assert!(!second.contains("Alpha text.") && !second.contains("Gamma text."));
+    assert!(split.get_chapter(toc.len()).unwrap().is_none());
+}
+
+#[test]
+fn test_epub3_fixed_layout_round_trip() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("comic.epub");
+    let rewritten_path = temp_dir.path().join("rewritten.epub");
+
+    let mut zip = ZipWriter::new(fs::File::create(&source_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Picture Book</dc:title>
+    <meta property="rendition:layout">pre-paginated</meta>
+    <meta property="rendition:spread">landscape</meta>
+  </metadata>
+  <manifest>
+    <item id="p1" href="page1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="p2" href="page2.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="p1" properties="page-spread-right"/>
+    <itemref idref="p2" properties="page-spread-left"/>
+  </spine>
+</package>"#).unwrap();
+    zip.start_file("page1.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><h1>Page 1</h1></body></html>").unwrap();
+    zip.start_file("page2.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><h1>Page 2</h1></body></html>").unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&source_path).unwrap();
+    assert!(handler.is_fixed_layout());
+    assert_eq!(handler.rendition_property("rendition:spread"), Some("landscape"));
+    let spine_properties = vec![Some("page-spread-right".to_string()), Some("page-spread-left".to_string())];
+    assert_eq!(handler.get_spine_properties(), spine_properties);
+
+    handler.write_to_file(&rewritten_path).unwrap();
+    let mut rewritten = EpubHandler::new();
+    rewritten.read_from_file(&rewritten_path).unwrap();
+    assert!(rewritten.is_fixed_layout());
+    assert_eq!(rewritten.rendition_property("rendition:spread"), Some("landscape"));
+    assert_eq!(rewritten.get_spine_properties(), spine_properties);
+
+    // Reflowing to text would lose the page geometry
+    let err = ebook_cli::Converter::convert(&source_path, &temp_dir.path().join("comic.txt"), "txt").unwrap_err();
+    assert!(err.to_string().contains("fixed-layout"), "{err}");
+}
+
+#[test]
+fn test_epub2_nested_ncx_toc() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("ncx.epub");
+
+    let mut zip = ZipWriter::new(fs::File::create(&epub_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("OEBPS/content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>NCX Book</dc:title></metadata>
+  <manifest>
+    <item id="ncx" href="nav/toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="p1" href="text/part1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="p2" href="text/part2.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine toc="ncx"><itemref idref="p1"/><itemref idref="p2"/></spine>
+</package>"#).unwrap();
+    // The NCX sits in its own directory, so its srcs are relative to OEBPS/nav, not the OPF
+    zip.start_file("OEBPS/nav/toc.ncx", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <navMap>
+    <navPoint id="n1" playOrder="1">
+      <navLabel><text>Part One</text></navLabel>
+      <content src="../text/part1.xhtml"/>
+      <navPoint id="n2" playOrder="2">
+        <navLabel><text>Chapter 1</text></navLabel>
+        <content src="../text/part1.xhtml#ch1"/>
+      </navPoint>
+      <navPoint id="n3" playOrder="3">
+        <navLabel><text>Chapter 2</text></navLabel>
+        <content src="../text/part1.xhtml#ch2"/>
+      </navPoint>
+    </navPoint>
+    <navPoint id="n4" playOrder="4">
+      <navLabel><text>Part Two</text></navLabel>
+      <content src="../text/part2.xhtml"/>
+    </navPoint>
+  </navMap>
+  <pageList>
+    <pageTarget id="pg1" type="normal" value="1">
+      <navLabel><text>1</text></navLabel>
+      <content src="../text/part1.xhtml#page1"/>
+    </pageTarget>
+  </pageList>
+  <navList>
+    <navLabel><text>Illustrations</text></navLabel>
+    <navTarget id="fig1">
+      <navLabel><text>Figure 1</text></navLabel>
+      <content src="../text/part2.xhtml#fig1"/>
+    </navTarget>
+  </navList>
+</ncx>"#).unwrap();
+    zip.start_file("OEBPS/text/part1.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><h1 id=\"ch1\">Chapter 1</h1><p>One</p><h1 id=\"ch2\">Chapter 2</h1><p>Two</p></body></html>").unwrap();
+    zip.start_file("OEBPS/text/part2.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><h1>Part Two</h1><p id=\"fig1\">Figure</p></body></html>").unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&epub_path).unwrap();
+    assert_eq!(handler.get_epub_version(), EpubVersion::V2);
+
+    let toc = handler.get_toc().unwrap();
+    let top: Vec<(&str, Option<&str>)> = toc.iter().map(|e| (e.title.as_str(), e.href.as_deref())).collect();
+    assert_eq!(top, vec![
+        ("Part One", Some("OEBPS/text/part1.xhtml")),
+        ("Part Two", Some("OEBPS/text/part2.xhtml")),
+    ]);
+    let nested: Vec<(&str, Option<&str>, usize, u32)> = toc[0]
+        .children
+        .iter()
+        .map(|e| (e.title.as_str(), e.href.as_deref(), e.level, e.id))
+        .collect();
+    assert_eq!(nested, vec![
+        ("Chapter 1", Some("OEBPS/text/part1.xhtml#ch1"), 1, 2),
+        ("Chapter 2", Some("OEBPS/text/part1.xhtml#ch2"), 1, 3),
+    ]);
+    assert!(toc[1].children.is_empty(), "navList targets must not join the TOC");
+
+    let pages = handler.get_page_list();
+    assert_eq!(pages.len(), 1);
+    assert_eq!((pages[0].label.as_str(), pages[0].href.as_str()), ("1", "../text/part1.xhtml#page1"));
+}
+
+#[test]
+fn test_epub3_collection_and_title_refinements() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("series.epub");
+    let rewritten_path = temp_dir.path().join("rewritten.epub");
+
+    let mut zip = ZipWriter::new(fs::File::create(&source_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("content.opf", options).unwrap();
+    zip.write_all(br##"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="id">urn:isbn:9780000000002</dc:identifier>
+    <dc:title id="t2">or, There and Back Again</dc:title>
+    <meta refines="#t2" property="title-type">subtitle</meta>
+    <dc:title id="t1">The Hobbit</dc:title>
+    <meta refines="#t1" property="title-type">main</meta>
+    <meta refines="#t1" property="file-as">Hobbit, The</meta>
+    <meta property="belongs-to-collection" id="c1">Middle-earth</meta>
+    <meta refines="#c1" property="collection-type">series</meta>
+    <meta refines="#c1" property="group-position">1</meta>
+  </metadata>
+  <manifest><item id="ch1" href="ch1.xhtml" media-type="application/xhtml+xml"/></manifest>
+  <spine><itemref idref="ch1"/></spine>
+</package>"##).unwrap();
+    zip.start_file("ch1.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><p>In a hole in the ground</p></body></html>").unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&source_path).unwrap();
+    let metadata = handler.get_metadata().unwrap();
+    assert_eq!(metadata.title.as_deref(), Some("The Hobbit"));
+    assert_eq!(metadata.subtitle.as_deref(), Some("or, There and Back Again"));
+    assert_eq!(metadata.title_sort.as_deref(), Some("Hobbit, The"));
+    assert_eq!(metadata.series.as_deref(), Some("Middle-earth"));
+    assert_eq!(metadata.series_index, Some(1.0));
+
+    handler.write_to_file(&rewritten_path).unwrap();
+    let mut archive = zip::ZipArchive::new(fs::File::open(&rewritten_path).unwrap()).unwrap();
+    let mut opf = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("content.opf").unwrap(), &mut opf).unwrap();
+    assert!(opf.contains(r#"<meta property="belongs-to-collection" id="series">Middle-earth</meta>"#), "{opf}");
+    assert!(opf.contains(r##"<meta refines="#series" property="group-position">1</meta>"##), "{opf}");
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&rewritten_path).unwrap();
+    let reread = reread.get_metadata().unwrap();
+    assert_eq!(reread.title, metadata.title);
+    assert_eq!(reread.subtitle, metadata.subtitle);
+    assert_eq!(reread.title_sort, metadata.title_sort);
+    assert_eq!((reread.series, reread.series_index), (metadata.series, metadata.series_index));
+}
+
+#[test]
+fn test_epub_ncx_modes() {
+    use ebook_cli::formats::NcxMode;
+
+    let temp_dir = TempDir::new().unwrap();
+    let entries = |path: &std::path::Path| -> Vec<String> {
+        let archive = zip::ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+        archive.file_names().map(str::to_string).collect()
+    };
+    let book = |version: EpubVersion, mode: NcxMode| {
+        let mut handler = EpubHandler::new();
+        handler.set_metadata(Metadata::new().with_title("Navigation")).unwrap();
+        handler.add_chapter("One", "<h1>One</h1>").unwrap();
+        handler.set_epub_version(version);
+        handler.set_ncx_mode(mode);
+        handler
+    };
+
+    let both_path = temp_dir.path().join("both.epub");
+    book(EpubVersion::V3, NcxMode::Both).write_to_file(&both_path).unwrap();
+    let both = entries(&both_path);
+    assert!(both.contains(&"OEBPS/toc.ncx".to_string()), "{both:?}");
+    assert!(both.contains(&"OEBPS/nav.xhtml".to_string()), "{both:?}");
+
+    let nav_path = temp_dir.path().join("nav-only.epub");
+    book(EpubVersion::V3, NcxMode::Never).write_to_file(&nav_path).unwrap();
+    let nav_only = entries(&nav_path);
+    assert!(!nav_only.iter().any(|name| name.ends_with("toc.ncx")), "{nav_only:?}");
+    assert!(nav_only.contains(&"OEBPS/nav.xhtml".to_string()));
+    let report = EpubHandler::validate_detailed(&nav_path).unwrap();
+    assert!(report.is_valid(), "{:?}", report.issues);
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&nav_path).unwrap();
+    assert_eq!(reread.get_toc().unwrap().len(), 1);
+
+    // The spec needs an NCX in EPUB 2 and a nav in EPUB 3
+    assert!(book(EpubVersion::V2, NcxMode::Never).write_to_file(&temp_dir.path().join("bad2.epub")).is_err());
+    assert!(book(EpubVersion::V3, NcxMode::Only).write_to_file(&temp_dir.path().join("bad3.epub")).is_err());
+
+    // From the CLI, NCX-only means EPUB 2
+    let txt_path = temp_dir.path().join("book.txt");
+    fs::write(&txt_path, "Some text").unwrap();
+    let ncx_path = temp_dir.path().join("ncx-only.epub");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.arg("convert").arg(&txt_path).arg(&ncx_path).args(["--emit-ncx", "only"]).assert().success();
+    let ncx_only = entries(&ncx_path);
+    assert!(ncx_only.contains(&"OEBPS/toc.ncx".to_string()), "{ncx_only:?}");
+    assert!(!ncx_only.iter().any(|name| name.ends_with("nav.xhtml")), "{ncx_only:?}");
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&ncx_path).unwrap();
+    assert_eq!(reread.get_epub_version(), EpubVersion::V2);
+}
+
+#[test]
+fn test_epub3_media_overlays_survive_a_rewrite() {
+    use std::io::{Read, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("read-aloud.epub");
+    let smil = br#"<smil xmlns="http://www.w3.org/ns/SMIL" version="3.0"><body>
+  <par id="p1"><text src="../Text/ch1.xhtml#s1"/><audio src="../Audio/ch1.mp3" clipBegin="0s" clipEnd="2.5s"/></par>
+</body></smil>"#;
+    let audio: &[u8] = b"ID3\x03\x00 fake mp3 frames";
+
+    let mut zip = ZipWriter::new(fs::File::create(&source_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options.compression_method(zip::CompressionMethod::Stored)).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("OEBPS/content.opf", options).unwrap();
+    zip.write_all(br##"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Narrated</dc:title>
+    <dc:language>en</dc:language>
+    <meta property="dcterms:modified">2024-01-01T00:00:00Z</meta>
+    <meta property="media:duration">0:00:02.500</meta>
+    <meta property="media:duration" refines="#ch1-overlay">0:00:02.500</meta>
+    <meta property="media:active-class">-epub-media-overlay-active</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="ch1" href="Text/ch1.xhtml" media-type="application/xhtml+xml" media-overlay="ch1-overlay"/>
+    <item id="ch1-overlay" href="Overlays/ch1.smil" media-type="application/smil+xml"/>
+    <item id="ch1-audio" href="Audio/ch1.mp3" media-type="audio/mpeg"/>
+  </manifest>
+  <spine><itemref idref="ch1"/></spine>
+</package>"##).unwrap();
+    zip.start_file("OEBPS/nav.xhtml", options).unwrap();
+    zip.write_all(br#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"><body><nav epub:type="toc"><ol><li><a href="Text/ch1.xhtml">One</a></li></ol></nav></body></html>"#).unwrap();
+    zip.start_file("OEBPS/Text/ch1.xhtml", options).unwrap();
+    zip.write_all(br#"<html xmlns="http://www.w3.org/1999/xhtml"><body><h1>One</h1><p id="s1">Listen along.</p></body></html>"#).unwrap();
+    zip.start_file("OEBPS/Overlays/ch1.smil", options).unwrap();
+    zip.write_all(smil).unwrap();
+    zip.start_file("OEBPS/Audio/ch1.mp3", options).unwrap();
+    zip.write_all(audio).unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&source_path).unwrap();
+    assert!(handler.has_media_overlays());
+
+    let rewritten_path = temp_dir.path().join("rewritten.epub");
+    handler.write_to_file(&rewritten_path).unwrap();
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(&rewritten_path).unwrap()).unwrap();
+    let mut read_entry = |name: &str| {
+        let mut data = Vec::new();
+        archive.by_name(name).unwrap().read_to_end(&mut data).unwrap();
+        data
+    };
+    assert_eq!(read_entry("OEBPS/Overlays/ch1.smil"), smil);
+    assert_eq!(read_entry("OEBPS/Audio/ch1.mp3"), audio);
+    let opf = String::from_utf8(read_entry("OEBPS/content.opf")).unwrap();
+    assert!(opf.contains(r#"href="Overlays/ch1.smil" media-type="application/smil+xml""#), "{opf}");
+    assert!(opf.contains(r#"href="Audio/ch1.mp3" media-type="audio/mpeg""#), "{opf}");
+    assert!(opf.contains(r#"href="Text/ch1.xhtml" media-type="application/xhtml+xml" media-overlay="media-ch1-overlay""#), "{opf}");
+    assert!(opf.contains(r##"<meta refines="#media-ch1-overlay" property="media:duration">0:00:02.500</meta>"##), "{opf}");
+    assert!(opf.contains(r#"<meta property="media:active-class">-epub-media-overlay-active</meta>"#), "{opf}");
+
+    let report = EpubHandler::validate_detailed(&rewritten_path).unwrap();
+    assert!(report.is_valid(), "{:?}", report.issues);
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&rewritten_path).unwrap();
+    assert!(reread.has_media_overlays());
+
+    let mut plain = EpubHandler::new();
+    plain.add_chapter("One", "<p>Silent</p>").unwrap();
+    assert!(!plain.has_media_overlays());
+}
+
+#[test]
+fn test_guide_and_landmark_titles_are_escaped() {
+    use ebook_cli::formats::GuideReference;
+    use std::io::Read;
+
+    fn assert_well_formed(xml: &str) {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        loop {
+            match reader.read_event() {
+                Ok(quick_xml::events::Event::Eof) => break,
+                Ok(quick_xml::events::Event::Empty(e)) | Ok(quick_xml::events::Event::Start(e)) => {
+                    for attr in e.attributes() {
+                        attr.unwrap().unescape_value().unwrap_or_else(|e| panic!("bad attribute: {e}\n{xml}"));
+                    }
+                }
+                Ok(quick_xml::events::Event::Text(e)) => {
+                    e.unescape().unwrap_or_else(|e| panic!("bad text: {e}\n{xml}"));
+                }
+                Ok(_) => {}
+                Err(e) => panic!("not well-formed: {e}\n{xml}"),
+            }
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let guide = vec![GuideReference::new("notes", "Notes & Sources", "chapter1.xhtml")];
+
+    for (version, entry) in [(EpubVersion::V2, "OEBPS/content.opf"), (EpubVersion::V3, "OEBPS/nav.xhtml")] {
+        let path = temp_dir.path().join(format!("guide_{version:?}.epub"));
+        let mut handler = EpubHandler::new();
+        handler.set_epub_version(version);
+        handler.set_metadata(Metadata::new().with_title("Escaped Guide")).unwrap();
+        handler.add_chapter("Notes", "<p>Notes</p>").unwrap();
+        handler.set_guide(guide.clone());
+        handler.write_to_file(&path).unwrap();
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(&path).unwrap()).unwrap();
+        let mut xml = String::new();
+        archive.by_name(entry).unwrap().read_to_string(&mut xml).unwrap();
+        assert_well_formed(&xml);
+        assert!(xml.contains("Notes &amp; Sources"), "{xml}");
+
+        let mut reread = EpubHandler::new();
+        reread.read_from_file(&path).unwrap();
+        let titles: Vec<String> = reread.get_guide().into_iter().map(|reference| reference.title).collect();
+        assert_eq!(titles, vec!["Notes & Sources"], "{version:?}");
+    }
+}
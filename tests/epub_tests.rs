@@ -1,7 +1,6 @@
 use ebook_cli::formats::EpubHandler;
 use ebook_cli::traits::{EbookReader, EbookWriter, EbookOperator};
 use ebook_cli::Metadata;
-use std::path::PathBuf;
 use tempfile::TempDir;
 
 #[test]
@@ -31,12 +30,8 @@ fn test_epub_create_and_read() {
     assert_eq!(read_metadata.format, Some("EPUB".to_string()));
 
     let content = reader.get_content().unwrap();
-    // Content should be readable, even if empty
-    assert!(content.len() >= 0, "Content should be readable");
-    // If content is present, it should contain chapter information
-    if !content.is_empty() {
-        println!("Content length: {}", content.len());
-    }
+    assert!(content.contains("This is chapter 1 content."), "{content}");
+    assert!(content.contains("This is chapter 2 content."), "{content}");
 }
 
 #[test]
@@ -57,13 +52,8 @@ fn test_epub_toc_extraction() {
     reader.read_from_file(&epub_path).unwrap();
 
     let toc = reader.get_toc().unwrap();
-    // TOC should have entries if chapters were properly read
-    assert!(toc.len() >= 0, "TOC should be readable");
-    // If TOC has entries, verify they contain expected titles
-    if !toc.is_empty() {
-        let titles: Vec<String> = toc.iter().map(|e| e.title.clone()).collect();
-        println!("TOC entries: {:?}", titles);
-    }
+    let titles: Vec<&str> = toc.iter().map(|entry| entry.title.as_str()).collect();
+    assert_eq!(titles, ["Introduction", "Chapter 1", "Conclusion"]);
 }
 
 #[test]
@@ -120,6 +110,142 @@ fn test_epub_metadata_tags() {
     assert_eq!(read_metadata.title, Some("Tagged Book".to_string()));
 }
 
+#[test]
+fn test_epub_multiple_authors_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("two_authors.epub");
+
+    let mut handler = EpubHandler::new();
+    let metadata = Metadata::new()
+        .with_title("Good Omens")
+        .with_authors(["Terry Pratchett", "Neil Gaiman"]);
+    handler.set_metadata(metadata).unwrap();
+    handler.add_chapter("Chapter 1", "<p>In the beginning</p>").unwrap();
+    handler.write_to_file(&epub_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&epub_path).unwrap();
+
+    let read_metadata = reader.get_metadata().unwrap();
+    assert_eq!(read_metadata.authors, vec!["Terry Pratchett", "Neil Gaiman"]);
+    assert_eq!(read_metadata.author, Some("Terry Pratchett".to_string()));
+    assert_eq!(read_metadata.joined_authors(" & "), Some("Terry Pratchett & Neil Gaiman".to_string()));
+}
+
+#[test]
+fn test_epub_contributor_roles_round_trip() {
+    use ebook_cli::formats::EpubVersion;
+    use ebook_cli::Contributor;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("translated.epub");
+    let mut zip = ZipWriter::new(std::fs::File::create(&source_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:title>Crime and Punishment</dc:title>
+    <dc:creator opf:role="aut" opf:file-as="Dostoevsky, Fyodor">Fyodor Dostoevsky</dc:creator>
+    <dc:creator opf:role="trl" opf:file-as="Garnett, Constance">Constance Garnett</dc:creator>
+  </metadata>
+  <manifest><item id="ch1" href="ch1.xhtml" media-type="application/xhtml+xml"/></manifest>
+  <spine><itemref idref="ch1"/></spine>
+</package>"#).unwrap();
+    zip.start_file("ch1.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><p>On an exceptionally hot evening</p></body></html>").unwrap();
+    zip.finish().unwrap();
+
+    let author = Contributor::new("Fyodor Dostoevsky", Some("aut")).with_file_as("Dostoevsky, Fyodor");
+    let translator = Contributor::new("Constance Garnett", Some("trl")).with_file_as("Garnett, Constance");
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&source_path).unwrap();
+    let metadata = handler.get_metadata().unwrap();
+    assert_eq!(metadata.authors, vec!["Fyodor Dostoevsky"]);
+    assert_eq!(metadata.contributors, vec![author.clone(), translator.clone()]);
+    assert_eq!(metadata.contributors_with_role("trl").collect::<Vec<_>>(), vec![&translator]);
+
+    // EPUB 2 writes opf: attributes, EPUB 3 <meta refines> elements
+    for version in [EpubVersion::V2, EpubVersion::V3] {
+        let output_path = temp_dir.path().join(format!("{version:?}.epub"));
+        handler.set_epub_version(version);
+        handler.write_to_file(&output_path).unwrap();
+
+        let mut reread = EpubHandler::new();
+        reread.read_from_file(&output_path).unwrap();
+        let metadata = reread.get_metadata().unwrap();
+        assert_eq!(metadata.authors, vec!["Fyodor Dostoevsky"], "{version:?}");
+        assert_eq!(metadata.contributors, vec![author.clone(), translator.clone()], "{version:?}");
+    }
+}
+
+#[test]
+fn test_epub_without_author_has_no_placeholder_creator() {
+    use std::io::Read;
+
+    assert!(Metadata::new().is_empty());
+    let metadata = Metadata::new().with_title("Anonymous Pamphlet");
+    assert!(!metadata.is_empty());
+    assert!(metadata.has_title() && !metadata.has_author());
+    assert!(!Metadata::new().with_title("  ").has_title());
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("anonymous.epub");
+    let mut handler = EpubHandler::new();
+    handler.set_metadata(metadata).unwrap();
+    handler.add_chapter("One", "<p>Unsigned words</p>").unwrap();
+    handler.write_to_file(&epub_path).unwrap();
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&epub_path).unwrap()).unwrap();
+    let mut opf = String::new();
+    archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut opf).unwrap();
+    assert!(opf.contains("<dc:title>Anonymous Pamphlet</dc:title>"), "{opf}");
+    assert!(!opf.contains("dc:creator"), "{opf}");
+    assert!(!opf.contains("Unknown"), "{opf}");
+    assert!(opf.contains("<dc:language>"), "{opf}");
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&epub_path).unwrap();
+    assert!(!reread.get_metadata().unwrap().has_author());
+}
+
+#[test]
+fn test_epub_get_chapters_follows_the_spine() {
+    use ebook_cli::traits::ChapterView;
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("chapters.epub");
+    let mut handler = EpubHandler::new();
+    handler.set_metadata(Metadata::new().with_title("Three Parts")).unwrap();
+    for title in ["Arrival", "Departure", "Return"] {
+        handler
+            .add_chapter(title, &format!("<html><head><title>{title}</title></head><body><p>{title} text</p></body></html>"))
+            .unwrap();
+    }
+    handler.write_to_file(&epub_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&epub_path).unwrap();
+    let chapters: Vec<ChapterView> = reader.get_chapters().unwrap();
+    let titles: Vec<&str> = chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+    assert_eq!(titles, ["Arrival", "Departure", "Return"]);
+    for (idx, chapter) in chapters.iter().enumerate() {
+        assert_eq!(chapter.href.as_deref(), Some(format!("OEBPS/chapter{}.xhtml", idx + 1).as_str()));
+        assert_eq!(Some(chapter.content.clone()), reader.get_chapter(idx).unwrap());
+        assert!(chapter.content.contains(&format!("{} text", chapter.title)));
+    }
+}
+
 #[test]
 fn test_epub_validation() {
     let temp_dir = TempDir::new().unwrap();
@@ -152,3 +278,1118 @@ fn test_epub_empty_content() {
     let content = reader.get_content().unwrap();
     assert!(content.is_empty() || content.trim().is_empty());
 }
+
+#[test]
+fn test_epub_reads_latin1_and_fragment_chapters() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("latin1.epub");
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&epub_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Encodings</dc:title></metadata>
+  <manifest>
+    <item id="latin1" href="latin1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="fragment" href="fragment.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="latin1"/><itemref idref="fragment"/></spine>
+</package>"#).unwrap();
+    // "Café" with é as the single Latin-1 byte 0xE9, which is invalid UTF-8
+    zip.start_file("latin1.xhtml", options).unwrap();
+    zip.write_all(b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<html><body><h1>Caf\xe9</h1><p>cr\xe8me</p></body></html>").unwrap();
+    zip.start_file("fragment.xhtml", options).unwrap();
+    zip.write_all(b"<body><h2>Fragment</h2><p>line<br>more</p></body>").unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&epub_path).unwrap();
+
+    let titles: Vec<String> = handler.get_toc().unwrap().into_iter().map(|entry| entry.title).collect();
+    assert_eq!(titles, vec!["Café", "Fragment"]);
+    assert!(handler.get_content().unwrap().contains("crème"));
+}
+
+const STORED_EPUB_ENTRIES: &[(&str, &[u8])] = &[
+    ("mimetype", b"application/epub+zip"),
+    ("META-INF/container.xml", br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#),
+    ("OEBPS/content.opf", br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Stored Book</dc:title></metadata>
+  <manifest>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="one"/><itemref idref="two"/></spine>
+</package>"#),
+    ("OEBPS/one.xhtml", b"<html><body><h1>One</h1><p>first stored chapter</p></body></html>"),
+    ("OEBPS/two.xhtml", b"<html><body><h1>Two</h1><p>second stored chapter</p></body></html>"),
+    ("OEBPS/images/pixel.gif", b"GIF89a\x01\x00\x01\x00\x00\x00\x00;"),
+];
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Hand-built zip with every entry Stored. With `data_descriptor`, each local header has
+/// general-purpose bit 3 set and zero CRC/sizes, as streaming producers write them.
+fn write_stored_zip(path: &std::path::Path, entries: &[(&str, &[u8])], data_descriptor: bool) {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    let flags: u16 = if data_descriptor { 0x08 } else { 0 };
+
+    for (name, data) in entries {
+        let offset = out.len() as u32;
+        let crc = crc32(data);
+        let size = data.len() as u32;
+        let (header_crc, header_size) = if data_descriptor { (0, 0) } else { (crc, size) };
+
+        out.extend_from_slice(b"PK\x03\x04");
+        out.extend_from_slice(&20u16.to_le_bytes());
+        out.extend_from_slice(&flags.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes()); // stored
+        out.extend_from_slice(&[0, 0, 0x21, 0]); // time, date (1980-01-01)
+        out.extend_from_slice(&header_crc.to_le_bytes());
+        out.extend_from_slice(&header_size.to_le_bytes());
+        out.extend_from_slice(&header_size.to_le_bytes());
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+        if data_descriptor {
+            out.extend_from_slice(b"PK\x07\x08");
+            out.extend_from_slice(&crc.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+        }
+
+        central.extend_from_slice(b"PK\x01\x02");
+        central.extend_from_slice(&20u16.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes());
+        central.extend_from_slice(&flags.to_le_bytes());
+        central.extend_from_slice(&0u16.to_le_bytes());
+        central.extend_from_slice(&[0, 0, 0x21, 0]);
+        central.extend_from_slice(&crc.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        central.extend_from_slice(&[0; 8]); // extra, comment, disk, internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes());
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(b"PK\x05\x06");
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&central_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    std::fs::write(path, out).unwrap();
+}
+
+#[test]
+fn test_epub_reads_stored_archives() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let zip_writer_path = temp_dir.path().join("zip-writer.epub");
+    let mut zip = ZipWriter::new(std::fs::File::create(&zip_writer_path).unwrap());
+    let options = FileOptions::<()>::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, data) in STORED_EPUB_ENTRIES {
+        zip.start_file(*name, options).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let plain_path = temp_dir.path().join("stored.epub");
+    write_stored_zip(&plain_path, STORED_EPUB_ENTRIES, false);
+    let descriptor_path = temp_dir.path().join("stored-descriptor.epub");
+    write_stored_zip(&descriptor_path, STORED_EPUB_ENTRIES, true);
+
+    for path in [&zip_writer_path, &plain_path, &descriptor_path] {
+        let mut handler = EpubHandler::new();
+        handler.read_from_file(path).unwrap_or_else(|e| panic!("{path:?}: {e}"));
+
+        assert_eq!(handler.get_metadata().unwrap().title.as_deref(), Some("Stored Book"), "{path:?}");
+        let titles: Vec<String> = handler.get_toc().unwrap().into_iter().map(|entry| entry.title).collect();
+        assert_eq!(titles, vec!["One", "Two"], "{path:?}");
+        let content = handler.get_content().unwrap();
+        assert!(content.contains("first stored chapter") && content.contains("second stored chapter"), "{path:?}");
+        let images = handler.extract_images().unwrap();
+        assert_eq!(images.len(), 1, "{path:?}");
+        assert_eq!(images[0].data, STORED_EPUB_ENTRIES[5].1, "{path:?}");
+    }
+}
+
+#[test]
+fn test_epub_chapter_title_sources() {
+    use ebook_cli::formats::ChapterTitleSource;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("titles.epub");
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&epub_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Titles</dc:title></metadata>
+  <manifest>
+    <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="c1"/><itemref idref="c2"/></spine>
+</package>"#).unwrap();
+    // Empty <title>; the real heading has nested markup and ragged whitespace
+    zip.start_file("c1.xhtml", options).unwrap();
+    zip.write_all(b"<html><head><title>  </title></head><body><h1>\n  The <em>Long</em>\n  Night </h1></body></html>").unwrap();
+    zip.start_file("c2.xhtml", options).unwrap();
+    zip.write_all(b"<html><head><title>Running Header</title></head><body><p class=\"chapter-title big\">Dawn</p><h2>Part Two</h2></body></html>").unwrap();
+    zip.finish().unwrap();
+
+    let titles = |source: ChapterTitleSource| {
+        let mut handler = EpubHandler::new();
+        handler.set_chapter_title_source(source);
+        handler.read_from_file(&epub_path).unwrap();
+        handler.get_toc().unwrap().into_iter().map(|entry| entry.title).collect::<Vec<_>>()
+    };
+
+    assert_eq!(titles(ChapterTitleSource::Title), vec!["The Long Night", "Running Header"]);
+    assert_eq!(titles(ChapterTitleSource::Heading), vec!["The Long Night", "Part Two"]);
+    assert_eq!(titles("p.chapter-title".parse().unwrap()), vec!["The Long Night", "Dawn"]);
+}
+
+#[test]
+fn test_epub_rewrite_keeps_file_names_and_links() {
+    use std::io::{Read, Write};
+    use zip::write::{FileOptions, ZipWriter};
+    use zip::ZipArchive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("linked.epub");
+    let output_path = temp_dir.path().join("rewritten.epub");
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&source_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OPS/package.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("OPS/package.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Linked</dc:title></metadata>
+  <manifest>
+    <item id="one" href="Text/one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="Text/two.xhtml" media-type="application/xhtml+xml"/>
+    <item id="pic" href="Images/pic.png" media-type="image/png"/>
+  </manifest>
+  <spine><itemref idref="one"/><itemref idref="two"/></spine>
+</package>"#).unwrap();
+    zip.start_file("OPS/Text/one.xhtml", options).unwrap();
+    zip.write_all(br#"<html><head><title>One</title></head><body><p><a href="two.xhtml#later">Later</a><img src="../Images/pic.png"/></p></body></html>"#).unwrap();
+    zip.start_file("OPS/Text/two.xhtml", options).unwrap();
+    zip.write_all(br#"<html><head><title>Two</title></head><body><h1 id="later">Later</h1><a href="one.xhtml">Back</a></body></html>"#).unwrap();
+    zip.start_file("OPS/Images/pic.png", options).unwrap();
+    zip.write_all(b"not really a png").unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&source_path).unwrap();
+    handler.add_chapter("Three", "<html><body><p>Added</p></body></html>").unwrap();
+    handler.write_to_file(&output_path).unwrap();
+
+    let mut archive = ZipArchive::new(std::fs::File::open(&output_path).unwrap()).unwrap();
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    for expected in ["OPS/Text/one.xhtml", "OPS/Text/two.xhtml", "OPS/Images/pic.png", "OPS/chapter3.xhtml", "OPS/content.opf"] {
+        assert!(names.iter().any(|name| name == expected), "{expected} missing from {names:?}");
+    }
+
+    // Every link in the rewritten chapters still points at a file in the archive
+    let resolve = |dir: &str, href: &str| {
+        let mut parts: Vec<&str> = dir.split('/').collect();
+        for part in href.split('#').next().unwrap().split('/') {
+            match part {
+                ".." => {
+                    parts.pop();
+                }
+                part => parts.push(part),
+            }
+        }
+        parts.join("/")
+    };
+    let mut content = String::new();
+    archive.by_name("OPS/Text/one.xhtml").unwrap().read_to_string(&mut content).unwrap();
+    for href in ["two.xhtml#later", "../Images/pic.png"] {
+        assert!(content.contains(href));
+        assert!(names.contains(&resolve("OPS/Text", href)), "{href} does not resolve");
+    }
+
+    // And so does every manifest item
+    let mut opf = String::new();
+    archive.by_name("OPS/content.opf").unwrap().read_to_string(&mut opf).unwrap();
+    for href in ["Text/one.xhtml", "Text/two.xhtml", "Images/pic.png", "chapter3.xhtml"] {
+        assert!(opf.contains(&format!("href=\"{href}\"")), "{href} not in manifest");
+        assert!(names.contains(&resolve("OPS", href)));
+    }
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&output_path).unwrap();
+    let hrefs: Vec<String> = reread.get_toc().unwrap().into_iter().filter_map(|entry| entry.href).collect();
+    assert_eq!(hrefs, vec!["OPS/Text/one.xhtml", "OPS/Text/two.xhtml", "OPS/chapter3.xhtml"]);
+}
+
+#[test]
+fn test_epub_rewrite_keeps_deeply_nested_package_dir() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+    use zip::ZipArchive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("nested.epub");
+    let output_path = temp_dir.path().join("rewritten.epub");
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&source_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="book/src/OPS/package.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("book/src/OPS/package.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Nested</dc:title></metadata>
+  <manifest><item id="one" href="Text/one.xhtml" media-type="application/xhtml+xml"/></manifest>
+  <spine><itemref idref="one"/></spine>
+</package>"#).unwrap();
+    zip.start_file("book/src/OPS/Text/one.xhtml", options).unwrap();
+    zip.write_all(br#"<html><head><title>One</title></head><body><p>Deep</p></body></html>"#).unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&source_path).unwrap();
+    handler.add_chapter("Two", "<html><body><p>Added</p></body></html>").unwrap();
+    handler.write_to_file(&output_path).unwrap();
+
+    // The package directory keeps its order: book/src/OPS, not OPS/src/book
+    let archive = ZipArchive::new(std::fs::File::open(&output_path).unwrap()).unwrap();
+    let names: Vec<&str> = archive.file_names().collect();
+    for expected in ["book/src/OPS/Text/one.xhtml", "book/src/OPS/chapter2.xhtml", "book/src/OPS/content.opf"] {
+        assert!(names.contains(&expected), "{expected} missing from {names:?}");
+    }
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&output_path).unwrap();
+    let hrefs: Vec<String> = reread.get_toc().unwrap().into_iter().filter_map(|entry| entry.href).collect();
+    assert_eq!(hrefs, vec!["book/src/OPS/Text/one.xhtml", "book/src/OPS/chapter2.xhtml"]);
+}
+
+#[test]
+fn test_epub_svg_spine_page_is_kept_out_of_the_text() {
+    use std::io::{Read, Write};
+    use zip::write::{FileOptions, ZipWriter};
+    use zip::ZipArchive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("svg-page.epub");
+    let output_path = temp_dir.path().join("rewritten.epub");
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><text x="1" y="5">Map</text></svg>"#;
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&source_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OPS/package.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("OPS/package.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Maps</dc:title></metadata>
+  <manifest>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="map" href="map.svg" media-type="image/svg+xml"/>
+    <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="one"/><itemref idref="map"/><itemref idref="two"/></spine>
+</package>"#).unwrap();
+    zip.start_file("OPS/one.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><p>Before the map</p></body></html>").unwrap();
+    zip.start_file("OPS/map.svg", options).unwrap();
+    zip.write_all(svg.as_bytes()).unwrap();
+    zip.start_file("OPS/two.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><p>After the map</p></body></html>").unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&source_path).unwrap();
+    let content = handler.get_content().unwrap();
+    assert!(content.contains("Before the map") && content.contains("After the map"));
+    assert!(!content.contains("<svg"), "{content}");
+    let titles: Vec<String> = handler.get_toc().unwrap().into_iter().map(|entry| entry.title).collect();
+    assert_eq!(titles[1], "Page 2");
+    assert_eq!(handler.get_chapter(1).unwrap().as_deref(), Some(""));
+
+    handler.write_to_file(&output_path).unwrap();
+    let mut archive = ZipArchive::new(std::fs::File::open(&output_path).unwrap()).unwrap();
+    assert_eq!(archive.file_names().filter(|name| name.ends_with("map.svg")).count(), 1);
+    let mut written = String::new();
+    archive.by_name("OPS/map.svg").unwrap().read_to_string(&mut written).unwrap();
+    assert_eq!(written, svg);
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&output_path).unwrap();
+    assert_eq!(reread.get_content().unwrap(), content);
+    let titles: Vec<String> = reread.get_toc().unwrap().into_iter().map(|entry| entry.title).collect();
+    assert_eq!(titles.len(), 3);
+    assert_eq!(titles[1], "Page 2");
+}
+
+#[test]
+fn test_epub_cover_from_first_page_image() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("cover_page.epub");
+    let cover_bytes: &[u8] = b"\x89PNG\r\n\x1a\n cover";
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&epub_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    // No <meta name="cover"> and no cover-image property
+    zip.start_file("OEBPS/content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Covered</dc:title></metadata>
+  <manifest>
+    <item id="cover" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>
+    <item id="text" href="Text/text.xhtml" media-type="application/xhtml+xml"/>
+    <item id="front" href="Images/front.png" media-type="image/png"/>
+    <item id="figure" href="Images/figure.png" media-type="image/png"/>
+  </manifest>
+  <spine><itemref idref="cover"/><itemref idref="text"/></spine>
+</package>"#).unwrap();
+    zip.start_file("OEBPS/Text/cover.xhtml", options).unwrap();
+    zip.write_all(br#"<html><head><title>Cover</title></head><body><div><img src="../Images/front.png" alt="Cover"/></div></body></html>"#).unwrap();
+    zip.start_file("OEBPS/Text/text.xhtml", options).unwrap();
+    zip.write_all(br#"<html><body><p>Text</p><img src="../Images/figure.png"/></body></html>"#).unwrap();
+    zip.start_file("OEBPS/Images/figure.png", options).unwrap();
+    zip.write_all(b"\x89PNG\r\n\x1a\n figure").unwrap();
+    zip.start_file("OEBPS/Images/front.png", options).unwrap();
+    zip.write_all(cover_bytes).unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&epub_path).unwrap();
+    let cover = handler.get_cover_image().expect("cover page image should be the cover");
+    assert_eq!(cover.name, "OEBPS/Images/front.png");
+    assert_eq!(cover.data, cover_bytes);
+    assert_eq!(handler.get_metadata().unwrap().cover_image_path.as_deref(), Some("OEBPS/Images/front.png"));
+
+    // A book written without a cover page has none
+    let plain_path = temp_dir.path().join("plain.epub");
+    let mut plain = EpubHandler::new();
+    plain.add_chapter("One", "<p>Text <img src=\"a.png\"/> and <img src=\"b.png\"/></p>").unwrap();
+    plain.add_image("a.png", vec![1]).unwrap();
+    plain.add_image("b.png", vec![2]).unwrap();
+    plain.write_to_file(&plain_path).unwrap();
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&plain_path).unwrap();
+    assert!(reader.get_cover_image().is_none());
+}
+
+#[test]
+fn test_epub_list_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("entries.epub");
+
+    let mut handler = EpubHandler::new();
+    handler.set_metadata(Metadata::new().with_title("Entries")).unwrap();
+    handler.add_chapter("Chapter 1", "<h1>Chapter 1</h1><p>Some text</p>").unwrap();
+    handler.add_image("images/pic.png", vec![0x89, b'P', b'N', b'G', 1, 2, 3, 4]).unwrap();
+    assert!(handler.list_entries().unwrap().is_empty());
+    handler.write_to_file(&epub_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&epub_path).unwrap();
+    let entries = reader.list_entries().unwrap();
+
+    for name in ["mimetype", "OEBPS/content.opf", "OEBPS/toc.ncx", "OEBPS/chapter1.xhtml", "OEBPS/images/pic.png"] {
+        let entry = entries.iter().find(|entry| entry.name == name).unwrap_or_else(|| panic!("{name} not listed"));
+        assert!(entry.uncompressed_size > 0 && entry.compressed_size > 0, "{name} has no size");
+        assert_eq!(entry.is_image, name.ends_with(".png"));
+    }
+    assert_eq!(entries.iter().find(|entry| entry.name == "OEBPS/images/pic.png").unwrap().uncompressed_size, 8);
+}
+
+#[test]
+fn test_epub_writes_cover_and_dublin_core_fields() {
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("cover.epub");
+
+    let mut metadata = Metadata::new().with_title("Declared Cover");
+    metadata.publisher = Some("Smith & Sons".to_string());
+    metadata.tags = Some(vec!["Fiction".to_string()]);
+    metadata.cover_image_path = Some("cover.jpg".to_string());
+
+    let mut handler = EpubHandler::new();
+    handler.set_metadata(metadata).unwrap();
+    handler.add_chapter("Chapter 1", "<p>Text</p>").unwrap();
+    handler.add_image("image0001.jpg", vec![1, 2, 3]).unwrap();
+    handler.add_image("cover.jpg", vec![4, 5, 6]).unwrap();
+    handler.write_to_file(&epub_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&epub_path).unwrap();
+    let metadata = reader.get_metadata().unwrap();
+
+    assert_eq!(reader.get_cover_image().map(|image| image.data), Some(vec![4, 5, 6]));
+    assert_eq!(metadata.publisher, Some("Smith & Sons".to_string()));
+    assert_eq!(metadata.tags, Some(vec!["Fiction".to_string()]));
+}
+
+#[test]
+fn test_epub_rights_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("rights.epub");
+    let rewritten_path = temp_dir.path().join("rights-rewritten.epub");
+
+    let mut metadata = Metadata::new().with_title("Licensed Book");
+    metadata.rights = Some("CC BY-SA 4.0 <https://creativecommons.org/licenses/by-sa/4.0/>".to_string());
+
+    let mut handler = EpubHandler::new();
+    handler.set_metadata(metadata).unwrap();
+    handler.add_chapter("Chapter 1", "<p>Text</p>").unwrap();
+    handler.write_to_file(&epub_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&epub_path).unwrap();
+    reader.write_to_file(&rewritten_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&rewritten_path).unwrap();
+    assert_eq!(
+        reader.get_metadata().unwrap().rights.as_deref(),
+        Some("CC BY-SA 4.0 <https://creativecommons.org/licenses/by-sa/4.0/>")
+    );
+}
+
+#[test]
+fn test_epub_calibre_series_metadata_round_trip() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("calibre.epub");
+    let rewritten_path = temp_dir.path().join("calibre-rewritten.epub");
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&epub_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:title>The Two Towers</dc:title>
+    <dc:creator opf:role="aut">J. R. R. Tolkien</dc:creator>
+    <meta name="calibre:series" content="The Lord of the Rings"/>
+    <meta name="calibre:series_index" content="2.0"/>
+    <meta name="calibre:rating" content="8.0"/>
+    <meta name="calibre:author_sort" content="Tolkien, J. R. R."/>
+    <meta name="calibre:user_metadata:#shelf" content="{&quot;#value#&quot;: &quot;Fantasy&quot;}"/>
+  </metadata>
+  <manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+  <spine><itemref idref="c1"/></spine>
+</package>"#).unwrap();
+    zip.start_file("c1.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><p>Text</p></body></html>").unwrap();
+    zip.finish().unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&epub_path).unwrap();
+    let metadata = reader.get_metadata().unwrap();
+    assert_eq!(metadata.series.as_deref(), Some("The Lord of the Rings"));
+    assert_eq!(metadata.series_index, Some(2.0));
+    assert_eq!(metadata.rating, Some(8.0));
+    assert_eq!(metadata.author_sort.as_deref(), Some("Tolkien, J. R. R."));
+
+    reader.write_to_file(&rewritten_path).unwrap();
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&rewritten_path).unwrap();
+    let rewritten = reader.get_metadata().unwrap();
+    assert_eq!(rewritten.series.as_deref(), Some("The Lord of the Rings"));
+    assert_eq!(rewritten.series_index, Some(2.0));
+    assert_eq!(rewritten.rating, Some(8.0));
+    assert_eq!(rewritten.author_sort.as_deref(), Some("Tolkien, J. R. R."));
+    assert_eq!(
+        rewritten.custom_fields.get("calibre:user_metadata:#shelf").map(String::as_str),
+        Some(r##"{"#value#": "Fantasy"}"##)
+    );
+}
+
+#[test]
+fn test_epub_flatten_spine_keeps_cross_chapter_links() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("ten.epub");
+    let flat_path = temp_dir.path().join("flat.epub");
+
+    let mut handler = EpubHandler::new();
+    handler.set_metadata(Metadata::new().with_title("Ten Chapters")).unwrap();
+    for n in 1..=10 {
+        let link = if n == 2 { r##"<p><a href="chapter7.xhtml#top">See chapter 7</a></p>"## } else { "" };
+        handler
+            .add_chapter(
+                &format!("Chapter {n}"),
+                &format!(
+                    r#"<html xmlns="http://www.w3.org/1999/xhtml"><head><title>Chapter {n}</title></head><body><h1 id="top">Chapter {n}</h1><p id="p{n}">Text {n}</p>{link}</body></html>"#
+                ),
+            )
+            .unwrap();
+    }
+    handler.write_to_file(&source_path).unwrap();
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.arg("repack")
+        .arg(&source_path)
+        .arg(&flat_path)
+        .arg("--flatten-spine")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Merged 10 spine files into 1"));
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&flat_path).unwrap();
+    let chapters = reread.get_chapters().unwrap();
+    assert_eq!(chapters.len(), 1);
+    let merged = &chapters[0].content;
+    for n in 1..=10 {
+        assert!(merged.contains(&format!("Text {n}")), "{merged}");
+    }
+
+    // The clashing "top" id of chapter 7 is renamed and the link follows it
+    let link = regex::Regex::new(r#"<a href="([^"]*)">See chapter 7</a>"#).unwrap();
+    let href = &link.captures(merged).expect("link kept")[1];
+    let (file, fragment) = href.split_once('#').unwrap();
+    assert_eq!(file, "chapter1.xhtml");
+    assert!(merged.contains(&format!(r#"id="{fragment}">Chapter 7</h1>"#)), "{href} in {merged}");
+    assert_eq!(merged.matches(r#"id="top""#).count(), 1);
+
+    // Every chapter still has a TOC entry, pointing into the merged file
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&flat_path).unwrap()).unwrap();
+    let mut nav = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("OEBPS/nav.xhtml").unwrap(), &mut nav).unwrap();
+    assert_eq!(nav.matches("<li><a href=\"chapter1.xhtml").count(), 10, "{nav}");
+}
+
+#[test]
+fn test_epub_drop_empty_chapters() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("blank.epub");
+    let output_path = temp_dir.path().join("clean.epub");
+
+    let page = |title: &str, body: &str| {
+        format!(r#"<html xmlns="http://www.w3.org/1999/xhtml"><head><title>{title}</title></head><body>{body}</body></html>"#)
+    };
+    let mut handler = EpubHandler::new();
+    handler.set_metadata(Metadata::new().with_title("Blank Pages")).unwrap();
+    handler.add_chapter("One", &page("One", "<h1>One</h1>")).unwrap();
+    handler.add_chapter("Blank", &page("Blank", "<div>\n  <p>&nbsp;</p>\n</div>")).unwrap();
+    handler.add_chapter("Plate", &page("Plate", r#"<img src="plate.png" alt=""/>"#)).unwrap();
+    handler.add_chapter("Two", &page("Two", "<h1>Two</h1>")).unwrap();
+    handler.add_image("plate.png", vec![0x89, b'P', b'N', b'G']).unwrap();
+    handler.write_to_file(&source_path).unwrap();
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.arg("repack")
+        .arg(&source_path)
+        .arg(&output_path)
+        .arg("--drop-empty-chapters")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Dropped 1 empty chapters"));
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&output_path).unwrap();
+    assert_eq!(reread.get_spine_properties().len(), 3);
+    let titles: Vec<String> = reread.get_chapters().unwrap().into_iter().map(|chapter| chapter.title).collect();
+    assert_eq!(titles, vec!["One", "Plate", "Two"]);
+
+    // A blank page of a fixed-layout book is deliberate
+    let mut fixed = EpubHandler::new();
+    fixed.read_from_file(&source_path).unwrap();
+    fixed.set_rendition_property("rendition:layout", "pre-paginated");
+    assert_eq!(fixed.drop_empty_chapters(), 0);
+    assert_eq!(fixed.get_spine_properties().len(), 4);
+}
+
+#[test]
+fn test_epub_reads_backslash_entry_names() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("windows.epub");
+
+    // Written by a Windows tool: `\` separators, a leading `/`, and an OPF path to match
+    let mut zip = ZipWriter::new(std::fs::File::create(&epub_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF\\container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS\content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("/OEBPS\\content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Backslashes</dc:title></metadata>
+  <manifest>
+    <item id="ch1" href="Text\ch1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="pic" href="Images\pic.png" media-type="image/png"/>
+  </manifest>
+  <spine><itemref idref="ch1"/></spine>
+</package>"#).unwrap();
+    zip.start_file("OEBPS\\Text\\ch1.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><h1>Opening</h1><p>Read from a Windows archive</p><img src=\"../Images/pic.png\"/></body></html>").unwrap();
+    zip.start_file("OEBPS\\Images\\pic.png", options).unwrap();
+    zip.write_all(b"\x89PNG\r\n\x1a\n").unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&epub_path).unwrap();
+
+    assert_eq!(handler.get_metadata().unwrap().title, Some("Backslashes".to_string()));
+    assert!(handler.get_content().unwrap().contains("Read from a Windows archive"));
+    let toc = handler.get_toc().unwrap();
+    assert_eq!(toc[0].href.as_deref(), Some("OEBPS/Text/ch1.xhtml"));
+    let images: Vec<String> = handler.extract_images().unwrap().into_iter().map(|image| image.name).collect();
+    assert_eq!(images, vec!["OEBPS/Images/pic.png"]);
+}
+
+#[test]
+fn test_epub_cover_meta_with_content_before_name() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("reversed.epub");
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&epub_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    // `content` before `name`, and another meta after it that must not replace the cover
+    zip.start_file("OEBPS/content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Reversed</dc:title>
+    <meta content="cover-img" name="cover"/>
+    <meta name="calibre:series" content="Attributes"/>
+  </metadata>
+  <manifest>
+    <item id="text" href="text.xhtml" media-type="application/xhtml+xml"/>
+    <item id="cover-img" href="images/front.png" media-type="image/png"/>
+  </manifest>
+  <spine><itemref idref="text"/></spine>
+</package>"#).unwrap();
+    zip.start_file("OEBPS/text.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><p>Text</p></body></html>").unwrap();
+    zip.start_file("OEBPS/images/front.png", options).unwrap();
+    zip.write_all(b"\x89PNG\r\n\x1a\n front").unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&epub_path).unwrap();
+    assert_eq!(handler.get_metadata().unwrap().cover_image_path.as_deref(), Some("OEBPS/images/front.png"));
+    assert_eq!(handler.get_cover_image().unwrap().name, "OEBPS/images/front.png");
+
+    // The cover survives a rewrite
+    let rewritten_path = temp_dir.path().join("rewritten.epub");
+    handler.write_to_file(&rewritten_path).unwrap();
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&rewritten_path).unwrap();
+    assert!(reader.get_cover_image().unwrap().name.ends_with("front.png"));
+}
+
+#[test]
+fn test_epub_repeated_spine_item_round_trip() {
+    use std::io::{Read, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("repeated.epub");
+    let mut zip = ZipWriter::new(std::fs::File::create(&epub_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("OEBPS/content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Interludes</dc:title></metadata>
+  <manifest>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="break" href="break.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="one"/><itemref idref="break"/><itemref idref="two"/><itemref idref="break"/></spine>
+</package>"#).unwrap();
+    for (name, text) in [("one", "First"), ("break", "Interlude"), ("two", "Second")] {
+        zip.start_file(format!("OEBPS/{name}.xhtml"), options).unwrap();
+        zip.write_all(format!("<html><body><p>{text}</p></body></html>").as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&epub_path).unwrap();
+    assert_eq!(handler.get_chapters().unwrap().len(), 4);
+
+    let rewritten_path = temp_dir.path().join("rewritten.epub");
+    handler.write_to_file(&rewritten_path).unwrap();
+    assert!(EpubHandler::validate_detailed(&rewritten_path).unwrap().is_valid());
+
+    // One manifest item for the repeated document, referenced from both spine positions
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&rewritten_path).unwrap()).unwrap();
+    let mut opf = String::new();
+    archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut opf).unwrap();
+    assert_eq!(opf.matches(r#"href="break.xhtml""#).count(), 1, "{opf}");
+    assert_eq!(opf.matches(r#"<itemref idref="ch1"/>"#).count(), 2, "{opf}");
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&rewritten_path).unwrap();
+    let texts: Vec<String> = (0..4).map(|idx| reread.get_chapter(idx).unwrap().unwrap()).collect();
+    assert!(texts[1].contains("Interlude") && texts[3].contains("Interlude"));
+    assert!(texts[2].contains("Second"));
+}
+
+#[test]
+fn test_epub_get_resource_by_href() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("styled.epub");
+    let chapter = b"<html><head><link rel=\"stylesheet\" href=\"../styles/book.css\"/></head><body><h1>One</h1><p id=\"start\">Text</p></body></html>";
+    let stylesheet = b"p { text-indent: 1em; }";
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&epub_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("OEBPS/content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Styled</dc:title></metadata>
+  <manifest>
+    <item id="ch1" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="css" href="styles/book.css" media-type="text/css"/>
+  </manifest>
+  <spine><itemref idref="ch1"/></spine>
+</package>"#).unwrap();
+    zip.start_file("OEBPS/text/ch1.xhtml", options).unwrap();
+    zip.write_all(chapter).unwrap();
+    zip.start_file("OEBPS/styles/book.css", options).unwrap();
+    zip.write_all(stylesheet).unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&epub_path).unwrap();
+
+    // The chapter by the archive path its view reports, with a fragment
+    let href = handler.get_chapters().unwrap()[0].href.clone().unwrap();
+    let (media_type, bytes) = handler.get_resource(&format!("{href}#start")).unwrap().unwrap();
+    assert_eq!(media_type, "application/xhtml+xml");
+    assert_eq!(bytes, chapter);
+
+    // The stylesheet by its href relative to the OPF
+    let (media_type, bytes) = handler.get_resource("styles/book.css").unwrap().unwrap();
+    assert_eq!(media_type, "text/css");
+    assert_eq!(bytes, stylesheet);
+    assert_eq!(handler.get_resource("OEBPS/styles/book.css").unwrap().unwrap().1, stylesheet);
+
+    assert!(handler.get_resource("styles/missing.css").unwrap().is_none());
+    assert!(EpubHandler::new().get_resource("styles/book.css").unwrap().is_none());
+}
+
+#[test]
+fn test_epub_page_progression_direction_survives_rewrite() {
+    use ebook_cli::formats::PageProgression;
+    use std::io::{Read, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("manga.epub");
+    let mut zip = ZipWriter::new(std::fs::File::create(&source_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Manga</dc:title></metadata>
+  <manifest><item id="p1" href="p1.xhtml" media-type="application/xhtml+xml"/></manifest>
+  <spine page-progression-direction="rtl"><itemref idref="p1"/></spine>
+</package>"#).unwrap();
+    zip.start_file("p1.xhtml", options).unwrap();
+    zip.write_all(b"<html><body><p>First page</p></body></html>").unwrap();
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&source_path).unwrap();
+    assert_eq!(handler.get_page_progression(), Some(PageProgression::RightToLeft));
+
+    let rewritten_path = temp_dir.path().join("rewritten.epub");
+    handler.write_to_file(&rewritten_path).unwrap();
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&rewritten_path).unwrap()).unwrap();
+    let mut opf = String::new();
+    archive.by_name("content.opf").unwrap().read_to_string(&mut opf).unwrap();
+    assert!(opf.contains(r#"page-progression-direction="rtl""#), "{opf}");
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&rewritten_path).unwrap();
+    assert_eq!(reader.get_page_progression(), Some(PageProgression::RightToLeft));
+
+    // A book that doesn't declare a direction is written without one
+    let mut plain = EpubHandler::new();
+    plain.set_content("<p>Left to right</p>").unwrap();
+    let plain_path = temp_dir.path().join("plain.epub");
+    plain.write_to_file(&plain_path).unwrap();
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&plain_path).unwrap();
+    assert_eq!(reader.get_page_progression(), None);
+}
+
+#[test]
+fn test_epub_collapse_images_into_one_folder() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("scattered.epub");
+    let mut zip = ZipWriter::new(std::fs::File::create(&source_path).unwrap());
+    let options = FileOptions::<()>::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file("OEBPS/content.opf", options).unwrap();
+    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:title>Scattered</dc:title>
+    <meta name="cover" content="cover"/>
+  </metadata>
+  <manifest>
+    <item id="ch1" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="ch2" href="ch2.xhtml" media-type="application/xhtml+xml"/>
+    <item id="art" href="art/map.png" media-type="image/png"/>
+    <item id="pics" href="pics/map.png" media-type="image/png"/>
+    <item id="cover" href="pics/cover.png" media-type="image/png"/>
+  </manifest>
+  <spine><itemref idref="ch1"/><itemref idref="ch2"/></spine>
+</package>"#).unwrap();
+    zip.start_file("OEBPS/text/ch1.xhtml", options).unwrap();
+    zip.write_all(br#"<html><body><h1>One</h1><img src="../art/map.png"/><a href="../pics/map.png">Large</a></body></html>"#).unwrap();
+    zip.start_file("OEBPS/ch2.xhtml", options).unwrap();
+    zip.write_all(br#"<html><body><h1>Two</h1><img src='pics/map.png'/><img src="pics/cover.png"/></body></html>"#).unwrap();
+    for (name, shade) in [("OEBPS/art/map.png", 10u8), ("OEBPS/pics/map.png", 20), ("OEBPS/pics/cover.png", 30)] {
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(2, 2, image::Rgb([shade, shade, shade])))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        zip.start_file(name, options).unwrap();
+        zip.write_all(&png).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let mut handler = EpubHandler::new();
+    handler.read_from_file(&source_path).unwrap();
+    let original: Vec<Vec<u8>> = handler.extract_images().unwrap().into_iter().map(|image| image.data).collect();
+    assert_eq!(handler.collapse_images(), 3);
+    let collapsed_path = temp_dir.path().join("collapsed.epub");
+    handler.write_to_file(&collapsed_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&collapsed_path).unwrap();
+    let images = reader.extract_images().unwrap();
+    let names: Vec<&str> = images.iter().map(|image| image.name.as_str()).collect();
+    assert_eq!(names.len(), 3);
+    for name in ["OEBPS/images/map.png", "OEBPS/images/map-2.png", "OEBPS/images/cover.png"] {
+        assert!(names.contains(&name), "{name} missing from {names:?}");
+    }
+    // Same bytes under the new names, and every reference points at an image that exists
+    let data = |name: &str| images.iter().find(|image| image.name == name).unwrap().data.clone();
+    assert_eq!(data("OEBPS/images/map.png"), original[0]);
+    assert_eq!(data("OEBPS/images/map-2.png"), original[1]);
+    assert_eq!(reader.get_metadata().unwrap().cover_image_path.as_deref(), Some("OEBPS/images/cover.png"));
+    let chapters = reader.get_chapters().unwrap();
+    assert!(chapters[0].content.contains(r#"<img src="../images/map.png"/>"#), "{}", chapters[0].content);
+    assert!(chapters[0].content.contains(r#"href="../images/map-2.png""#));
+    assert!(chapters[1].content.contains("<img src='images/map-2.png'/>"), "{}", chapters[1].content);
+    assert!(chapters[1].content.contains(r#"<img src="images/cover.png"/>"#));
+}
+
+#[test]
+fn test_epub_rewrite_keeps_identifier() {
+    use ebook_cli::formats::EpubVersion;
+    use std::io::Read;
+
+    let temp_dir = TempDir::new().unwrap();
+    let first_path = temp_dir.path().join("first.epub");
+    let rewritten_path = temp_dir.path().join("rewritten.epub");
+
+    let mut metadata = Metadata::new().with_title("Identified");
+    metadata.isbn = Some("urn:isbn:9780000000002".to_string());
+    let mut handler = EpubHandler::new();
+    handler.set_epub_version(EpubVersion::V2);
+    handler.set_metadata(metadata).unwrap();
+    handler.add_chapter("Chapter 1", "<p>Text.</p>").unwrap();
+    handler.write_to_file(&first_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&first_path).unwrap();
+    reader.write_to_file(&rewritten_path).unwrap();
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&rewritten_path).unwrap()).unwrap();
+    let mut read_entry = |suffix: &str| {
+        let name = archive.file_names().find(|name| name.ends_with(suffix)).unwrap().to_string();
+        let mut text = String::new();
+        archive.by_name(&name).unwrap().read_to_string(&mut text).unwrap();
+        text
+    };
+    let opf = read_entry(".opf");
+    let ncx = read_entry(".ncx");
+    assert!(opf.contains(r#"<dc:identifier id="BookID">urn:isbn:9780000000002</dc:identifier>"#));
+    assert!(ncx.contains(r#"<meta name="dtb:uid" content="urn:isbn:9780000000002"/>"#));
+}
+
+/// A minimal EPUB with one extra deflated entry of `size` zero bytes, which expands far past
+/// the default ratio limit
+fn epub_with_zero_entry(path: &std::path::Path, name: &str, size: usize) {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let mut zip = ZipWriter::new(std::fs::File::create(path).unwrap());
+    let stored = FileOptions::<()>::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated = FileOptions::<()>::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("mimetype", stored).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", deflated).unwrap();
+    zip.write_all(br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#).unwrap();
+    zip.start_file(name, deflated).unwrap();
+    zip.write_all(&vec![0u8; size]).unwrap();
+    zip.finish().unwrap();
+}
+
+#[test]
+fn test_epub_optimize_preserving_structure_respects_extraction_limits() {
+    use ebook_cli::image_optimizer::OptimizationOptions;
+
+    let temp_dir = TempDir::new().unwrap();
+    let bomb_path = temp_dir.path().join("bomb.epub");
+    epub_with_zero_entry(&bomb_path, "OEBPS/images/huge.png", 16 * 1024 * 1024);
+
+    let output_path = temp_dir.path().join("optimized.epub");
+    let error =
+        EpubHandler::optimize_preserving_structure(&bomb_path, &output_path, OptimizationOptions::default()).unwrap_err();
+    assert!(matches!(error, ebook_cli::EbookError::ArchiveTooLarge(_)), "{error}");
+    assert!(error.to_string().contains("huge.png expands more than 200x"), "{error}");
+    assert!(!output_path.exists());
+}
+
+#[test]
+fn test_epub_recompress_archive_respects_extraction_limits() {
+    let temp_dir = TempDir::new().unwrap();
+    let bomb_path = temp_dir.path().join("bomb.epub");
+    epub_with_zero_entry(&bomb_path, "OEBPS/style.css", 16 * 1024 * 1024);
+
+    let output_path = temp_dir.path().join("recompressed.epub");
+    let error = EpubHandler::recompress_archive(&bomb_path, &output_path).unwrap_err();
+    assert!(matches!(error, ebook_cli::EbookError::ArchiveTooLarge(_)), "{error}");
+    assert!(error.to_string().contains("style.css expands more than 200x"), "{error}");
+    assert!(!output_path.exists());
+}
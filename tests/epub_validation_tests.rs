@@ -0,0 +1,269 @@
+//! Tests for the strict, epubcheck-style EPUB validator
+
+use ebook_cli::formats::{EpubHandler, EpubVersion, Severity, ValidationReport};
+use ebook_cli::traits::EbookWriter;
+use std::io::Write;
+use tempfile::TempDir;
+use zip::write::{FileOptions, ZipWriter};
+use zip::CompressionMethod;
+
+const CONTAINER: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+const METADATA: &str = r#"<dc:title>Strict</dc:title>
+    <dc:language>en</dc:language>
+    <meta property="dcterms:modified">2024-01-01T00:00:00Z</meta>"#;
+
+const MANIFEST: &str = r#"<item id="ch1" href="ch1.xhtml" media-type="application/xhtml+xml"/>"#;
+
+const SPINE: &str = r#"<itemref idref="ch1"/>"#;
+
+const CHAPTER: &str = "<html><body><p>Hello</p></body></html>";
+
+fn opf(version: &str, metadata: &str, manifest: &str, spine: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="{version}" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    {metadata}
+  </metadata>
+  <manifest>
+    {manifest}
+  </manifest>
+  <spine>
+    {spine}
+  </spine>
+</package>"#
+    )
+}
+
+/// Validate an archive of `entries`, written in order; `mimetype` is stored and the rest deflated
+fn validate_entries(entries: &[(&str, &str)]) -> ValidationReport {
+    validate_entries_with(entries, CompressionMethod::Stored)
+}
+
+fn validate_entries_with(entries: &[(&str, &str)], mimetype_compression: CompressionMethod) -> ValidationReport {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("book.epub");
+    let mut zip = ZipWriter::new(std::fs::File::create(&path).unwrap());
+    for (name, content) in entries {
+        let compression = if *name == "mimetype" { mimetype_compression } else { CompressionMethod::Deflated };
+        zip.start_file(*name, FileOptions::<()>::default().compression_method(compression)).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+    EpubHandler::validate_detailed(&path).unwrap()
+}
+
+/// A valid EPUB 3 archive with the OPF replaced by `opf`
+fn validate_opf(opf: &str) -> ValidationReport {
+    validate_entries(&[
+        ("mimetype", "application/epub+zip"),
+        ("META-INF/container.xml", CONTAINER),
+        ("OEBPS/content.opf", opf),
+        ("OEBPS/ch1.xhtml", CHAPTER),
+    ])
+}
+
+fn messages(report: &ValidationReport, severity: Severity) -> Vec<String> {
+    report
+        .issues
+        .iter()
+        .filter(|issue| issue.severity == severity)
+        .map(|issue| issue.message.clone())
+        .collect()
+}
+
+fn assert_single_error(report: &ValidationReport, expected: &str) {
+    let errors = messages(report, Severity::Error);
+    assert_eq!(errors.len(), 1, "expected one error, got {errors:?}");
+    assert!(errors[0].contains(expected), "{:?} does not mention {expected:?}", errors[0]);
+    assert!(!report.is_valid());
+}
+
+#[test]
+fn test_strict_validation_accepts_well_formed_epub() {
+    let report = validate_opf(&opf("3.0", METADATA, MANIFEST, SPINE));
+    assert!(report.issues.is_empty(), "{:?}", report.issues);
+    assert!(report.is_valid());
+}
+
+#[test]
+fn test_strict_validation_accepts_written_epubs() {
+    let temp_dir = TempDir::new().unwrap();
+    for version in [EpubVersion::V2, EpubVersion::V3] {
+        let path = temp_dir.path().join(format!("{version:?}.epub"));
+        let mut handler = EpubHandler::new();
+        handler.set_epub_version(version);
+        handler.add_chapter("One", "<p>Hello</p>").unwrap();
+        handler.add_image("cover.png", vec![0x89, b'P', b'N', b'G']).unwrap();
+        handler.write_to_file(&path).unwrap();
+
+        let report = EpubHandler::validate_detailed(&path).unwrap();
+        assert!(report.issues.is_empty(), "{version:?}: {:?}", report.issues);
+    }
+}
+
+#[test]
+fn test_strict_validation_missing_mimetype() {
+    let report = validate_entries(&[
+        ("META-INF/container.xml", CONTAINER),
+        ("OEBPS/content.opf", &opf("3.0", METADATA, MANIFEST, SPINE)),
+        ("OEBPS/ch1.xhtml", CHAPTER),
+    ]);
+    assert_single_error(&report, "mimetype file is missing");
+}
+
+#[test]
+fn test_strict_validation_compressed_mimetype() {
+    let report = validate_entries_with(
+        &[
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", CONTAINER),
+            ("OEBPS/content.opf", &opf("3.0", METADATA, MANIFEST, SPINE)),
+            ("OEBPS/ch1.xhtml", CHAPTER),
+        ],
+        CompressionMethod::Deflated,
+    );
+    assert_single_error(&report, "stored without compression");
+}
+
+#[test]
+fn test_strict_validation_mimetype_not_first() {
+    let report = validate_entries(&[
+        ("META-INF/container.xml", CONTAINER),
+        ("mimetype", "application/epub+zip"),
+        ("OEBPS/content.opf", &opf("3.0", METADATA, MANIFEST, SPINE)),
+        ("OEBPS/ch1.xhtml", CHAPTER),
+    ]);
+    assert_single_error(&report, "first entry");
+}
+
+#[test]
+fn test_strict_validation_wrong_mimetype_content() {
+    let report = validate_entries(&[
+        ("mimetype", "application/epub+zip\n"),
+        ("META-INF/container.xml", CONTAINER),
+        ("OEBPS/content.opf", &opf("3.0", METADATA, MANIFEST, SPINE)),
+        ("OEBPS/ch1.xhtml", CHAPTER),
+    ]);
+    assert_single_error(&report, "must contain exactly");
+}
+
+#[test]
+fn test_strict_validation_malformed_container() {
+    let report = validate_entries(&[
+        ("mimetype", "application/epub+zip"),
+        ("META-INF/container.xml", "<container><rootfiles><rootfile full-path=\"OEBPS/content.opf\"/></container>"),
+        ("OEBPS/content.opf", &opf("3.0", METADATA, MANIFEST, SPINE)),
+        ("OEBPS/ch1.xhtml", CHAPTER),
+    ]);
+    assert_single_error(&report, "container.xml is not well-formed");
+}
+
+#[test]
+fn test_strict_validation_container_points_to_missing_opf() {
+    let report = validate_entries(&[
+        ("mimetype", "application/epub+zip"),
+        ("META-INF/container.xml", &CONTAINER.replace("OEBPS/content.opf", "OEBPS/package.opf")),
+        ("OEBPS/content.opf", &opf("3.0", METADATA, MANIFEST, SPINE)),
+        ("OEBPS/ch1.xhtml", CHAPTER),
+    ]);
+    assert_single_error(&report, "points to OEBPS/package.opf");
+}
+
+#[test]
+fn test_strict_validation_duplicate_manifest_ids() {
+    let manifest = format!(r#"{MANIFEST}
+    <item id="ch1" href="ch2.xhtml" media-type="application/xhtml+xml"/>"#);
+    let report = validate_entries(&[
+        ("mimetype", "application/epub+zip"),
+        ("META-INF/container.xml", CONTAINER),
+        ("OEBPS/content.opf", &opf("3.0", METADATA, &manifest, SPINE)),
+        ("OEBPS/ch1.xhtml", CHAPTER),
+        ("OEBPS/ch2.xhtml", CHAPTER),
+    ]);
+    assert_single_error(&report, "\"ch1\" is used more than once");
+}
+
+#[test]
+fn test_strict_validation_unresolved_spine_idref() {
+    let spine = format!(r#"{SPINE}
+    <itemref idref="missing"/>"#);
+    let report = validate_opf(&opf("3.0", METADATA, MANIFEST, &spine));
+    assert_single_error(&report, "Spine itemref \"missing\"");
+}
+
+#[test]
+fn test_strict_validation_manifest_href_missing_from_archive() {
+    let manifest = format!(r#"{MANIFEST}
+    <item id="img" href="../images/cover.jpg" media-type="image/jpeg"/>
+    <item id="font" href="https://example.com/font.otf" media-type="font/otf"/>"#);
+    let report = validate_opf(&opf("3.0", METADATA, &manifest, SPINE));
+    assert_single_error(&report, "\"img\" points to images/cover.jpg");
+}
+
+#[test]
+fn test_strict_validation_missing_language() {
+    let metadata = METADATA.replace("<dc:language>en</dc:language>", "");
+    let report = validate_opf(&opf("3.0", &metadata, MANIFEST, SPINE));
+    assert_single_error(&report, "dc:language");
+}
+
+#[test]
+fn test_strict_validation_dcterms_modified_is_epub3_only() {
+    let metadata = METADATA.replace("<meta property=\"dcterms:modified\">2024-01-01T00:00:00Z</meta>", "");
+    let report = validate_opf(&opf("3.0", &metadata, MANIFEST, SPINE));
+    assert_single_error(&report, "dcterms:modified");
+
+    let report = validate_opf(&opf("2.0", &metadata, MANIFEST, SPINE));
+    assert!(report.issues.is_empty(), "{:?}", report.issues);
+
+    let metadata = METADATA.replace("2024-01-01T00:00:00Z", "2024-01-01");
+    let report = validate_opf(&opf("3.0", &metadata, MANIFEST, SPINE));
+    assert!(report.is_valid());
+    assert_eq!(messages(&report, Severity::Warning).len(), 1);
+}
+
+#[test]
+fn test_strict_validation_undeclared_file_is_a_warning() {
+    let report = validate_entries(&[
+        ("mimetype", "application/epub+zip"),
+        ("META-INF/container.xml", CONTAINER),
+        ("OEBPS/content.opf", &opf("3.0", METADATA, MANIFEST, SPINE)),
+        ("OEBPS/ch1.xhtml", CHAPTER),
+        ("OEBPS/notes.txt", "scratch"),
+    ]);
+    assert!(report.is_valid());
+    assert_eq!(
+        messages(&report, Severity::Warning),
+        vec!["OEBPS/notes.txt is in the archive but not declared in the manifest".to_string()]
+    );
+}
+
+#[test]
+fn test_cli_validate_strict_fails_on_errors() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("broken.epub");
+    let mut zip = ZipWriter::new(std::fs::File::create(&path).unwrap());
+    zip.start_file("mimetype", FileOptions::<()>::default()).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("META-INF/container.xml", FileOptions::<()>::default()).unwrap();
+    zip.write_all(CONTAINER.as_bytes()).unwrap();
+    zip.start_file("OEBPS/content.opf", FileOptions::<()>::default()).unwrap();
+    zip.write_all(opf("3.0", METADATA, MANIFEST, SPINE).as_bytes()).unwrap();
+    zip.start_file("OEBPS/ch1.xhtml", FileOptions::<()>::default()).unwrap();
+    zip.write_all(CHAPTER.as_bytes()).unwrap();
+    zip.finish().unwrap();
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.args(["validate", "--strict"])
+        .arg(&path)
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("error: The mimetype file must be stored without compression"));
+}
@@ -95,11 +95,13 @@ fn test_optimization_quality_settings() {
     reader.read_from_file(&cbz_path).unwrap();
 
     // Test different quality settings
+    let total_size = |reader: &CbzHandler| reader.extract_images().unwrap().iter().map(|image| image.data.len()).sum::<usize>();
+    let before = total_size(&reader);
     let options_high = OptimizationOptions::default().with_quality(95);
     let savings_high = reader.optimize_images(options_high).unwrap();
-    
-    // High quality should still provide some savings
-    assert!(savings_high >= 0);
+
+    // Savings are what the pages shrank by; a page is never replaced by a larger one
+    assert_eq!(total_size(&reader) + savings_high, before);
 }
 
 #[test]
@@ -176,3 +178,208 @@ fn test_optimization_with_write() {
     let images = final_reader.extract_images().unwrap();
     assert_eq!(images.len(), 2);
 }
+
+#[test]
+fn test_cbz_transcode_png_pages_to_jpeg() {
+    use image::{DynamicImage, ImageFormat, RgbaImage};
+    use std::io::Cursor;
+
+    let temp_dir = TempDir::new().unwrap();
+    let cbz_path = temp_dir.path().join("transcode.cbz");
+
+    // A fully transparent page must come out white, not black
+    let transparent = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, image::Rgba([0, 0, 0, 0])));
+    let mut transparent_png = Cursor::new(Vec::new());
+    transparent.write_to(&mut transparent_png, ImageFormat::Png).unwrap();
+
+    let mut handler = CbzHandler::new();
+    handler.add_image("pages/page01.png", create_large_test_image()).unwrap();
+    handler.add_image("pages/page02.png", transparent_png.into_inner()).unwrap();
+
+    let options = OptimizationOptions::default()
+        .with_quality(85)
+        .with_convert_to(ImageFormat::Jpeg);
+    handler.optimize_images(options).unwrap();
+    handler.write_to_file(&cbz_path).unwrap();
+
+    let mut reader = CbzHandler::new();
+    reader.read_from_file(&cbz_path).unwrap();
+    let pages = reader.extract_images().unwrap();
+
+    let names: Vec<&str> = pages.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, vec!["pages/page01.jpg", "pages/page02.jpg"]);
+    for page in &pages {
+        assert_eq!(page.mime_type, "image/jpeg");
+        assert_eq!(image::guess_format(&page.data).unwrap(), ImageFormat::Jpeg);
+    }
+
+    let flattened = image::load_from_memory(&pages[1].data).unwrap().to_rgb8();
+    assert!(flattened.get_pixel(4, 4).0.iter().all(|&c| c > 245));
+}
+
+#[cfg(feature = "svg")]
+#[test]
+fn test_svg_rasterization() {
+    use ebook_cli::image_optimizer::ImageOptimizer;
+
+    let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="400" height="200">
+  <rect width="400" height="200" fill="#ff0000"/>
+</svg>"##;
+    let optimizer = ImageOptimizer::new(OptimizationOptions::default().with_max_dimensions(100, 100));
+
+    let png = optimizer.optimize(svg, "image/svg+xml").unwrap();
+    assert_eq!(optimizer.output_mime_type("image/svg+xml"), "image/png");
+    assert_eq!(optimizer.output_name("images/cover.svg", "image/svg+xml"), "images/cover.png");
+
+    let raster = image::load_from_memory_with_format(&png, image::ImageFormat::Png).unwrap();
+    assert_eq!((raster.width(), raster.height()), (100, 50));
+    assert_eq!(raster.to_rgb8().get_pixel(50, 25).0, [255, 0, 0]);
+}
+
+#[test]
+fn test_epub_transcode_updates_names_and_media_types() {
+    use image::ImageFormat;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("pictures.epub");
+    let output_path = temp_dir.path().join("pictures-webp.epub");
+
+    let mut handler = EpubHandler::new();
+    handler.set_metadata(Metadata::new().with_title("Pictures")).unwrap();
+    handler.add_chapter("Chapter 1", r#"<p><img src="images/red.png"/></p>"#).unwrap();
+    handler.add_image("images/red.png", create_large_test_image()).unwrap();
+    handler.write_to_file(&source_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&source_path).unwrap();
+    reader.optimize_images(OptimizationOptions::default().with_convert_to(ImageFormat::WebP)).unwrap();
+    reader.write_to_file(&output_path).unwrap();
+
+    let mut archive = ZipArchive::new(std::fs::File::open(&output_path).unwrap()).unwrap();
+    let mut opf = String::new();
+    archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut opf).unwrap();
+    assert!(opf.contains(r#"href="images/red.webp" media-type="image/webp""#), "{opf}");
+    assert!(!opf.contains("image/png"));
+
+    let mut data = Vec::new();
+    archive.by_name("OEBPS/images/red.webp").unwrap().read_to_end(&mut data).unwrap();
+    assert_eq!(image::guess_format(&data).unwrap(), ImageFormat::WebP);
+    assert!(archive.by_name("OEBPS/images/red.png").is_err());
+
+    let mut chapter = String::new();
+    archive.by_name("OEBPS/chapter1.xhtml").unwrap().read_to_string(&mut chapter).unwrap();
+    assert!(chapter.contains(r#"src="images/red.webp""#));
+
+    let mut reread = EpubHandler::new();
+    reread.read_from_file(&output_path).unwrap();
+    let images = reread.extract_images().unwrap();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].mime_type, "image/webp");
+}
+
+#[test]
+fn test_epub_transcode_rewrites_only_the_renamed_image() {
+    use image::ImageFormat;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_path = temp_dir.path().join("fruit.epub");
+    let output_path = temp_dir.path().join("fruit-webp.epub");
+
+    // `a.png` is a suffix of `banana.png`; banana.png can't be decoded, so it keeps its name
+    let mut handler = EpubHandler::new();
+    handler.set_metadata(Metadata::new().with_title("Fruit")).unwrap();
+    handler
+        .add_chapter(
+            "Chapter 1",
+            r#"<html><head><style>h1 { background: url("images/a.png") }</style></head>
+<body><h1>Fruit</h1><p><img src="images/a.png"/><img src="images/banana.png"/></p>
+<div style="background: url(images/banana.png)"></div><div style='background: url(images/a.png)'></div></body></html>"#,
+        )
+        .unwrap();
+    handler.add_image("images/a.png", create_large_test_image()).unwrap();
+    handler.add_image("images/banana.png", b"not really a png".to_vec()).unwrap();
+    handler.write_to_file(&source_path).unwrap();
+
+    let mut reader = EpubHandler::new();
+    reader.read_from_file(&source_path).unwrap();
+    reader.optimize_images(OptimizationOptions::default().with_convert_to(ImageFormat::WebP)).unwrap();
+    reader.write_to_file(&output_path).unwrap();
+
+    let mut archive = ZipArchive::new(std::fs::File::open(&output_path).unwrap()).unwrap();
+    let mut chapter = String::new();
+    archive.by_name("OEBPS/chapter1.xhtml").unwrap().read_to_string(&mut chapter).unwrap();
+    for reference in [
+        r#"url("images/a.webp")"#,
+        r#"src="images/a.webp""#,
+        r#"src="images/banana.png""#,
+        "url(images/banana.png)",
+        "url(images/a.webp)",
+    ] {
+        assert!(chapter.contains(reference), "{reference} missing from {chapter}");
+    }
+    assert!(!chapter.contains("banana.webp") && !chapter.contains("images/a.png"), "{chapter}");
+
+    let mut opf = String::new();
+    archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut opf).unwrap();
+    assert!(opf.contains(r#"href="images/a.webp" media-type="image/webp""#), "{opf}");
+    assert!(opf.contains(r#"href="images/banana.png" media-type="image/png""#), "{opf}");
+    assert!(archive.by_name("OEBPS/images/banana.png").is_ok());
+}
+
+#[test]
+fn test_optimize_into_leaves_original_unchanged() {
+    use ebook_cli::image_optimizer::ImageOptimizer;
+    use ebook_cli::traits::ImageData;
+
+    let optimizer = ImageOptimizer::new(
+        OptimizationOptions::default()
+            .with_max_dimensions(50, 50)
+            .with_convert_to(image::ImageFormat::Jpeg),
+    );
+    let original = ImageData::new("images/page.png".to_string(), "image/png".to_string(), create_large_test_image());
+    let before = original.clone();
+
+    let optimized = optimizer.optimize_into(&original).unwrap();
+    assert_eq!(optimized.name, "images/page.jpg");
+    assert_eq!(optimized.mime_type, "image/jpeg");
+    assert_eq!(image::load_from_memory(&optimized.data).unwrap().width(), 50);
+
+    assert_eq!(original.name, before.name);
+    assert_eq!(original.mime_type, before.mime_type);
+    assert_eq!(original.data, before.data);
+
+    let again = original.with_optimized(&optimizer).unwrap();
+    assert_eq!(again.data, optimized.data);
+}
+
+#[test]
+fn test_cbz_optimize_to_size_keeps_the_chosen_pass() {
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    // Noise compresses poorly, so the archive size follows the JPEG quality
+    let noise = RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 37 + y * 11) as u8, (x * y) as u8, (x ^ y) as u8 * 3]));
+    let mut page = Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(noise).write_to(&mut page, ImageFormat::Png).unwrap();
+    let page = page.into_inner();
+    let options = OptimizationOptions::default().with_quality(90).with_convert_to(ImageFormat::Jpeg);
+
+    let mut unreachable = CbzHandler::new();
+    unreachable.add_image("page01.png", page.clone()).unwrap();
+    let result = unreachable.optimize_to_size(options, 1, 20).unwrap();
+    assert!(!result.reached);
+    assert_eq!(result.quality, 20);
+    let pages = unreachable.extract_images().unwrap();
+    assert_eq!(pages[0].name, "page01.jpg");
+
+    let mut roomy = CbzHandler::new();
+    roomy.add_image("page01.png", page).unwrap();
+    let result = roomy.optimize_to_size(options, u64::MAX, 20).unwrap();
+    assert!(result.reached);
+    assert_eq!(result.quality, 90);
+    assert_eq!(image::guess_format(&roomy.extract_images().unwrap()[0].data).unwrap(), ImageFormat::Jpeg);
+}
@@ -3,7 +3,7 @@ use std::io::{BufRead, BufReader, Write};
 use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 
 fn start_mcp() -> (Child, ChildStdin, BufReader<ChildStdout>) {
-    let bin = assert_cmd::cargo::cargo_bin("ebook");
+    let bin = assert_cmd::cargo::cargo_bin!("ebook");
     let mut child = Command::new(bin)
         .arg("mcp")
         .stdin(Stdio::piped())
@@ -18,7 +18,7 @@ fn start_mcp() -> (Child, ChildStdin, BufReader<ChildStdout>) {
 }
 
 fn send(stdin: &mut ChildStdin, req: &Value) {
-    writeln!(stdin, "{}", req.to_string()).unwrap();
+    writeln!(stdin, "{}", req).unwrap();
     stdin.flush().unwrap();
 }
 
@@ -193,3 +193,341 @@ fn test_mcp_write_read_validate_info_txt_and_azw() {
     drop(stdin);
     let _ = child.wait();
 }
+
+#[test]
+fn test_mcp_peek_ebook_truncates_text() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let txt_path = temp_dir.path().join("long.txt");
+    std::fs::write(&txt_path, "é".repeat(100) + &"x".repeat(10_000)).unwrap();
+
+    let (mut child, mut stdin, mut reader) = start_mcp();
+    let peek = |id: u64, arguments: Value| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "peek_ebook", "arguments": arguments }
+        })
+    };
+
+    send(&mut stdin, &peek(1, serde_json::json!({ "path": txt_path.to_string_lossy(), "chars": 120 })));
+    let resp = recv(&mut reader);
+    let preview: Value = serde_json::from_str(resp["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    let text = preview["text"].as_str().unwrap();
+    assert_eq!(text.chars().count(), 120);
+    assert!(text.starts_with(&"é".repeat(100)));
+    assert_eq!(preview["format"], "txt");
+    assert_eq!(preview["title"], "long");
+    assert_eq!(preview["truncated"], true);
+
+    send(&mut stdin, &peek(2, serde_json::json!({ "path": txt_path.to_string_lossy() })));
+    let resp = recv(&mut reader);
+    let preview: Value = serde_json::from_str(resp["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(preview["text"].as_str().unwrap().chars().count(), 500);
+
+    drop(stdin);
+    let _ = child.wait();
+}
+
+#[test]
+fn test_mcp_peek_ebook_reads_txt_header() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let txt_path = temp_dir.path().join("headed.txt");
+    std::fs::write(&txt_path, "Title: Header Book\nAuthor: Jane Doe; John Roe\n\n".to_string() + &"body ".repeat(100)).unwrap();
+
+    let (mut child, mut stdin, mut reader) = start_mcp();
+    send(&mut stdin, &serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": { "name": "peek_ebook", "arguments": { "path": txt_path.to_string_lossy(), "chars": 20 } }
+    }));
+    let resp = recv(&mut reader);
+    let preview: Value = serde_json::from_str(resp["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(preview["title"], "Header Book");
+    assert_eq!(preview["authors"], serde_json::json!(["Jane Doe", "John Roe"]));
+    // The header isn't body text, and doesn't eat into the requested characters
+    assert_eq!(preview["text"], "body body body body ");
+    assert_eq!(preview["truncated"], true);
+
+    drop(stdin);
+    let _ = child.wait();
+}
+
+#[test]
+fn test_mcp_optimize_images_without_images() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::EbookWriter;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("text-only.epub");
+    let mut epub = EpubHandler::new();
+    epub.add_chapter("Chapter 1", "<p>No pictures here</p>").unwrap();
+    epub.write_to_file(&epub_path).unwrap();
+    let output_path = temp_dir.path().join("out.epub");
+
+    let (mut child, mut stdin, mut reader) = start_mcp();
+    send(&mut stdin, &serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": {
+            "name": "optimize_images",
+            "arguments": {
+                "input_path": epub_path.to_string_lossy(),
+                "output_path": output_path.to_string_lossy()
+            }
+        }
+    }));
+    let resp = recv(&mut reader);
+    let text = resp["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.starts_with("Nothing to optimize"), "{text}");
+    assert!(resp["result"]["isError"].is_null());
+    assert!(!output_path.exists());
+
+    drop(stdin);
+    let _ = child.wait();
+}
+
+#[test]
+fn test_mcp_get_ebook_info_has_the_same_shape_for_every_format() {
+    use ebook_cli::formats::EpubHandler;
+    use ebook_cli::traits::EbookWriter;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let txt_path = temp_dir.path().join("info.txt");
+    std::fs::write(&txt_path, "Title: Plain\nAuthor: Writer\n\nSome text").unwrap();
+    let epub_path = temp_dir.path().join("info.epub");
+    let mut epub = EpubHandler::new();
+    epub.add_chapter("One", "<p>First</p>").unwrap();
+    epub.add_chapter("Two", "<p>Second</p>").unwrap();
+    epub.write_to_file(&epub_path).unwrap();
+
+    let (mut child, mut stdin, mut reader) = start_mcp();
+    let info = |id: u64, arguments: Value| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "get_ebook_info", "arguments": arguments }
+        })
+    };
+
+    let mut objects = Vec::new();
+    for (id, path) in [(1, &txt_path), (2, &epub_path)] {
+        send(&mut stdin, &info(id, serde_json::json!({ "path": path.to_string_lossy() })));
+        let resp = recv(&mut reader);
+        let object: Value = serde_json::from_str(resp["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+        objects.push(object);
+    }
+
+    for object in &objects {
+        let keys: Vec<&String> = object.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["format", "metadata", "path", "stats", "summary"]);
+        let stats: Vec<&String> = object["stats"].as_object().unwrap().keys().collect();
+        assert_eq!(stats, ["chapter_count", "image_count", "page_count", "size_chars"]);
+        assert!(object["metadata"].is_object());
+        assert!(object["summary"].as_str().unwrap().contains("Format: "));
+    }
+    assert_eq!(objects[0]["format"], "txt");
+    assert_eq!(objects[0]["metadata"]["title"], "Plain");
+    assert!(objects[0]["stats"]["image_count"].is_null());
+    assert_eq!(objects[1]["format"], "epub");
+    assert_eq!(objects[1]["stats"]["chapter_count"], 2);
+    assert_eq!(objects[1]["stats"]["image_count"], 0);
+    assert!(objects[1]["stats"]["page_count"].is_null());
+
+    send(&mut stdin, &info(3, serde_json::json!({ "path": txt_path.to_string_lossy(), "output_format": "text" })));
+    let resp = recv(&mut reader);
+    let summary = resp["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(summary.starts_with("File: "), "{summary}");
+    assert!(summary.contains("Authors: Writer"), "{summary}");
+
+    drop(stdin);
+    let _ = child.wait();
+}
+
+/// Handler for a made-up `.slow` format whose reads take far longer than the test's timeout
+#[derive(Default)]
+struct SlowHandler;
+
+impl ebook_cli::traits::EbookReader for SlowHandler {
+    fn read_from_file(&mut self, _path: &std::path::Path) -> ebook_cli::Result<()> {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        Ok(())
+    }
+
+    fn get_metadata(&self) -> ebook_cli::Result<ebook_cli::Metadata> {
+        Ok(ebook_cli::Metadata::new())
+    }
+
+    fn get_content(&self) -> ebook_cli::Result<String> {
+        Ok(String::new())
+    }
+
+    fn get_toc(&self) -> ebook_cli::Result<Vec<ebook_cli::traits::TocEntry>> {
+        Ok(Vec::new())
+    }
+
+    fn extract_images(&self) -> ebook_cli::Result<Vec<ebook_cli::traits::ImageData>> {
+        Ok(Vec::new())
+    }
+}
+
+impl ebook_cli::traits::EbookWriter for SlowHandler {
+    fn set_metadata(&mut self, _metadata: ebook_cli::Metadata) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+
+    fn set_content(&mut self, _content: &str) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+
+    fn add_chapter(&mut self, _title: &str, _content: &str) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+
+    fn add_image(&mut self, _name: &str, _data: Vec<u8>) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+
+    fn write_to_file(&self, _path: &std::path::Path) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+}
+
+impl ebook_cli::traits::EbookOperator for SlowHandler {
+    fn convert_to(&self, _target_format: &str, _output_path: &std::path::Path) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+
+    fn validate(&self) -> ebook_cli::Result<bool> {
+        Ok(true)
+    }
+
+    fn repair(&mut self) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_mcp_tool_call_times_out() {
+    use ebook_cli::mcp::{McpServer, TOOL_TIMEOUT_ERROR};
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let path = temp_dir.path().join("book.slow");
+    std::fs::write(&path, "").unwrap();
+    let mut registry = ebook_cli::FormatRegistry::default();
+    registry.register("slow", || Box::new(SlowHandler));
+    let server = McpServer::with_registry(registry).with_tool_timeout(std::time::Duration::from_millis(50));
+
+    let call = |id: u32, tool: &str| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": tool, "arguments": { "path": path } }
+        })
+        .to_string()
+    };
+
+    let started = std::time::Instant::now();
+    let response = server.handle_request(&call(1, "read_ebook")).await;
+    assert!(started.elapsed() < std::time::Duration::from_millis(400), "waited for the slow read");
+    let error = response.error.expect("a timed out call is a JSON-RPC error");
+    assert_eq!(error.code, TOOL_TIMEOUT_ERROR);
+    assert!(error.message.contains("read_ebook timed out"), "{}", error.message);
+    assert!(response.result.is_none());
+
+    // The server still answers calls that finish in time
+    let response = server.handle_request(&call(2, "no_such_tool")).await;
+    assert!(response.error.is_none());
+    assert_eq!(response.id, Some(serde_json::json!(2)));
+}
+
+#[test]
+fn test_mcp_errors_carry_code_and_category() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let missing = temp_dir.path().join("missing.epub");
+
+    let (mut child, mut stdin, mut reader) = start_mcp();
+    let mut call = |id: u64, name: &str, arguments: Value| {
+        send(&mut stdin, &serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": arguments }
+        }));
+        recv(&mut reader)["result"].clone()
+    };
+
+    let result = call(1, "read_ebook", serde_json::json!({ "path": missing.to_string_lossy() }));
+    assert_eq!(result["isError"], true);
+    assert!(result["content"][0]["text"].as_str().unwrap().starts_with("Error: Failed to read EPUB"));
+    assert_eq!(result["structuredContent"]["category"], "not_found");
+    assert_eq!(result["structuredContent"]["code"], "io");
+
+    let result = call(2, "read_ebook", serde_json::json!({}));
+    assert_eq!(result["structuredContent"]["category"], "invalid_arguments");
+    assert_eq!(result["structuredContent"]["code"], "missing_argument");
+
+    let not_an_archive = temp_dir.path().join("broken.epub");
+    std::fs::write(&not_an_archive, "not a zip").unwrap();
+    let result = call(3, "read_ebook", serde_json::json!({ "path": not_an_archive.to_string_lossy() }));
+    assert_eq!(result["structuredContent"]["category"], "parse_error");
+    assert_eq!(result["structuredContent"]["code"], "zip");
+
+    let result = call(4, "read_ebook", serde_json::json!({ "path": temp_dir.path().join("book.docx").to_string_lossy() }));
+    assert_eq!(result["structuredContent"]["category"], "unsupported_format");
+
+    drop(stdin);
+    let _ = child.wait();
+}
+
+#[test]
+fn test_mcp_convert_batch_returns_a_result_per_job() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let first = temp_dir.path().join("first.txt");
+    let second = temp_dir.path().join("second.txt");
+    std::fs::write(&first, "First book.").unwrap();
+    std::fs::write(&second, "Second book.").unwrap();
+    let job = |input: &std::path::Path, output: &str, target: &str| {
+        serde_json::json!({
+            "input_path": input.to_string_lossy(),
+            "output_path": temp_dir.path().join(output).to_string_lossy(),
+            "target_format": target
+        })
+    };
+
+    let (mut child, mut stdin, mut reader) = start_mcp();
+    send(&mut stdin, &serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": {
+            "name": "convert_batch",
+            "arguments": {
+                "jobs": [
+                    job(&first, "first.epub", "epub"),
+                    job(&temp_dir.path().join("missing.txt"), "missing.epub", "epub"),
+                    job(&second, "second.fb2", "fb2")
+                ],
+                "concurrency": 2
+            }
+        }
+    }));
+    let result = recv(&mut reader)["result"].clone();
+    drop(stdin);
+    let _ = child.wait();
+
+    assert_eq!(result["content"][0]["text"].as_str().unwrap().lines().next(), Some("Converted 2 of 3 files"));
+    let results = result["structuredContent"]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["success"], true);
+    assert_eq!(results[0]["output_path"], temp_dir.path().join("first.epub").to_string_lossy().as_ref());
+    assert_eq!(results[1]["success"], false);
+    assert_eq!(results[1]["error"]["category"], "not_found");
+    assert_eq!(results[2]["success"], true);
+    assert!(temp_dir.path().join("first.epub").exists());
+    assert!(temp_dir.path().join("second.fb2").exists());
+}
@@ -0,0 +1,180 @@
+//! Tests for OCR of image-only PDFs and comic pages
+
+use ebook_cli::formats::{CbzHandler, PdfHandler};
+use ebook_cli::ocr::OcrBackend;
+use ebook_cli::traits::{EbookReader, EbookWriter};
+use ebook_cli::Result;
+use lopdf::{dictionary, Document, Object, Stream};
+use std::cell::RefCell;
+use tempfile::TempDir;
+
+/// Answers every image with a fixed text and records the images it was given
+#[derive(Default)]
+struct FakeOcr {
+    seen: RefCell<Vec<(Vec<u8>, String)>>,
+}
+
+impl OcrBackend for FakeOcr {
+    fn recognize(&self, image: &[u8], language: &str) -> Result<String> {
+        let mut seen = self.seen.borrow_mut();
+        seen.push((image.to_vec(), language.to_string()));
+        Ok(format!("Recognized page {}\n", seen.len()))
+    }
+}
+
+/// A PDF whose pages each draw one 8-bit grayscale image and contain no text
+fn write_scanned_pdf(path: &std::path::Path, pages: usize) {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let mut kids: Vec<Object> = Vec::new();
+    for _ in 0..pages {
+        let image_id = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 4,
+                "Height" => 2,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            vec![0, 255, 0, 255, 255, 0, 255, 0],
+        ));
+        let content_id = doc.add_object(Stream::new(dictionary! {}, b"q 400 0 0 200 50 500 cm /Im1 Do Q".to_vec()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "Resources" => dictionary! { "XObject" => dictionary! { "Im1" => image_id } },
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        kids.push(page_id.into());
+    }
+    let count = kids.len() as i64;
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count }));
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    doc.save(path).unwrap();
+}
+
+#[test]
+fn test_ocr_replaces_missing_pdf_text_layer() {
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("scan.pdf");
+    write_scanned_pdf(&pdf_path, 2);
+
+    let mut handler = PdfHandler::new();
+    handler.read_from_file(&pdf_path).unwrap();
+    assert_eq!(handler.page_images(1).len(), 1);
+    assert_eq!(handler.page_images(1)[0].mime_type, "image/png");
+
+    let backend = FakeOcr::default();
+    assert!(handler.ocr_if_image_only(&backend, "deu").unwrap());
+
+    let seen = backend.seen.borrow();
+    assert_eq!(seen.len(), 2);
+    assert!(seen.iter().all(|(image, language)| image.starts_with(b"\x89PNG") && language == "deu"));
+    assert_eq!(handler.get_page_text(2).as_deref(), Some("Recognized page 2"));
+    let content = handler.get_content().unwrap();
+    assert!(content.contains("Recognized page 1"), "{content}");
+    assert!(content.contains("Recognized page 2"), "{content}");
+}
+
+#[test]
+fn test_ocr_skips_pdfs_with_a_text_layer() {
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("text.pdf");
+    let mut writer = PdfHandler::new();
+    writer.set_content("This page has a perfectly good text layer already.").unwrap();
+    writer.write_to_file(&pdf_path).unwrap();
+
+    let mut handler = PdfHandler::new();
+    handler.read_from_file(&pdf_path).unwrap();
+    let backend = FakeOcr::default();
+    assert!(!handler.ocr_if_image_only(&backend, "eng").unwrap());
+    assert!(backend.seen.borrow().is_empty());
+    assert!(handler.get_content().unwrap().contains("perfectly good text layer"));
+}
+
+#[test]
+fn test_ocr_cbz_pages_become_chapters() {
+    let temp_dir = TempDir::new().unwrap();
+    let cbz_path = temp_dir.path().join("comic.cbz");
+    let mut writer = CbzHandler::new();
+    writer.add_image("page01.png", vec![1]).unwrap();
+    writer.add_image("page02.png", vec![2]).unwrap();
+    writer.write_to_file(&cbz_path).unwrap();
+
+    let mut handler = CbzHandler::new();
+    handler.read_from_file(&cbz_path).unwrap();
+    assert!(handler.get_chapter(0).is_err());
+
+    handler.recognize_pages(&FakeOcr::default(), "eng").unwrap();
+    assert_eq!(handler.get_chapter(1).unwrap().as_deref(), Some("Recognized page 2"));
+    assert_eq!(handler.get_content().unwrap(), "Recognized page 1\n\nRecognized page 2");
+}
+
+#[cfg(not(feature = "ocr"))]
+#[test]
+fn test_cli_ocr_needs_the_feature() {
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("scan.pdf");
+    write_scanned_pdf(&pdf_path, 1);
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("ebook");
+    cmd.args(["read", "--ocr"])
+        .arg(&pdf_path)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("--features ocr"));
+}
+
+/// Render `text` (capitals and spaces) in a blocky 5x7 font, scaled up for Tesseract
+#[cfg(feature = "ocr")]
+fn render_text_png(text: &str) -> Vec<u8> {
+    fn glyph(c: char) -> [&'static str; 7] {
+        match c {
+            'H' => ["10001", "10001", "10001", "11111", "10001", "10001", "10001"],
+            'E' => ["11111", "10000", "10000", "11110", "10000", "10000", "11111"],
+            'L' => ["10000", "10000", "10000", "10000", "10000", "10000", "11111"],
+            'O' => ["01110", "10001", "10001", "10001", "10001", "10001", "01110"],
+            'W' => ["10001", "10001", "10001", "10101", "10101", "10101", "01010"],
+            'R' => ["11110", "10001", "10001", "11110", "10100", "10010", "10001"],
+            'D' => ["11110", "10001", "10001", "10001", "10001", "10001", "11110"],
+            _ => ["00000"; 7],
+        }
+    }
+
+    const SCALE: u32 = 8;
+    const MARGIN: u32 = 4;
+    let columns = text.chars().count() as u32 * 6 + 2 * MARGIN;
+    let mut canvas = image::GrayImage::from_pixel(columns * SCALE, (7 + 2 * MARGIN) * SCALE, image::Luma([255]));
+    for (i, c) in text.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for (col, bit) in bits.chars().enumerate() {
+                if bit != '1' {
+                    continue;
+                }
+                let x0 = (MARGIN + i as u32 * 6 + col as u32) * SCALE;
+                let y0 = (MARGIN + row as u32) * SCALE;
+                for y in y0..y0 + SCALE {
+                    for x in x0..x0 + SCALE {
+                        canvas.put_pixel(x, y, image::Luma([0]));
+                    }
+                }
+            }
+        }
+    }
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::from(canvas).write_to(&mut png, image::ImageFormat::Png).unwrap();
+    png.into_inner()
+}
+
+#[cfg(feature = "ocr")]
+#[test]
+fn test_tesseract_recognizes_rendered_text() {
+    let backend = ebook_cli::ocr::default_backend().unwrap();
+    let text = backend.recognize(&render_text_png("HELLO WORLD"), "eng").unwrap();
+    let text = text.to_uppercase();
+    assert!(text.contains("HELLO"), "{text:?}");
+    assert!(text.contains("WORLD"), "{text:?}");
+}
@@ -54,6 +54,40 @@ fn test_pdf_metadata() {
     assert_eq!(read_metadata.author, Some("Test Author".to_string()));
 }
 
+#[test]
+fn test_pdf_xmp_metadata_wins_over_info() {
+    use lopdf::{Document, Object, StringFormat};
+
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("xmp.pdf");
+
+    let mut metadata = Metadata::new().with_title("XMP Title").with_authors(["Ann Lee", "Bo Chan"]);
+    metadata.publisher = Some("Acme Press".to_string());
+    metadata.tags = Some(vec!["history".to_string(), "maps".to_string()]);
+    let mut handler = PdfHandler::new();
+    handler.set_metadata(metadata).unwrap();
+    handler.set_content("Content with an XMP packet").unwrap();
+    handler.write_to_file(&pdf_path).unwrap();
+
+    // Another tool updates only the Info dictionary
+    let mut doc = Document::load(&pdf_path).unwrap();
+    let info_id = doc.trailer.get(b"Info").unwrap().as_reference().unwrap();
+    let info = doc.get_dictionary_mut(info_id).unwrap();
+    info.set("Title", Object::String(b"Info Title".to_vec(), StringFormat::Literal));
+    info.set("Subject", Object::String(b"Old surveys of the coast".to_vec(), StringFormat::Literal));
+    doc.save(&pdf_path).unwrap();
+
+    let mut reader = PdfHandler::new();
+    reader.read_from_file(&pdf_path).unwrap();
+    let read = reader.get_metadata().unwrap();
+    assert_eq!(read.title.as_deref(), Some("XMP Title"));
+    assert_eq!(read.authors, ["Ann Lee", "Bo Chan"]);
+    assert_eq!(read.publisher.as_deref(), Some("Acme Press"));
+    assert_eq!(read.tags, Some(vec!["history".to_string(), "maps".to_string()]));
+    // The Info Subject describes the document; it isn't the publisher
+    assert_eq!(read.description.as_deref(), Some("Old surveys of the coast"));
+}
+
 #[test]
 fn test_pdf_validation() {
     let temp_dir = TempDir::new().unwrap();
@@ -84,8 +118,7 @@ fn test_pdf_empty_content() {
     reader.read_from_file(&pdf_path).unwrap();
 
     let content = reader.get_content().unwrap();
-    // Empty or minimal content is acceptable
-    assert!(content.len() >= 0);
+    assert!(content.trim().is_empty(), "{content:?}");
 }
 
 #[test]
@@ -125,3 +158,215 @@ fn test_pdf_repair() {
     let result = reader.repair();
     assert!(result.is_ok());
 }
+
+#[test]
+fn test_pdf_dehyphenation() {
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("hyphenated.pdf");
+
+    let mut handler = PdfHandler::new();
+    handler.set_metadata(Metadata::new().with_title("Hyphens")).unwrap();
+    handler.set_content("The inter-\nnational team uses well-known tools.\nSee list-\nItems stay split.").unwrap();
+    handler.write_to_file(&pdf_path).unwrap();
+
+    let mut raw = PdfHandler::new();
+    raw.read_from_file(&pdf_path).unwrap();
+    assert!(raw.get_content().unwrap().contains("inter-\nnational"));
+
+    let mut reader = PdfHandler::new();
+    reader.set_dehyphenate(true);
+    reader.read_from_file(&pdf_path).unwrap();
+    let content = reader.get_content().unwrap();
+
+    assert!(content.contains("The international\nteam uses well-known tools."));
+    assert!(content.contains("list-\nItems stay split."));
+}
+
+#[test]
+fn test_pdf_cid_font_text_through_to_unicode() {
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("cid.pdf");
+
+    // Glyph ids 1..=8 map to the letters of "Hello, CID" via the font's ToUnicode CMap
+    let cmap = b"/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def
+/CMapName /Adobe-Identity-UCS def
+/CMapType 2 def
+1 begincodespacerange
+<0000> <FFFF>
+endcodespacerange
+5 beginbfchar
+<0001> <0048>
+<0002> <0065>
+<0003> <006C>
+<0004> <006F>
+<0005> <002C>
+endbfchar
+2 beginbfrange
+<0006> <0006> <0043>
+<0007> <0008> [<0049> <0044>]
+endbfrange
+endcmap
+CMapName currentdict /CMap defineresource pop
+end
+end";
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let to_unicode = doc.add_object(Stream::new(dictionary! {}, cmap.to_vec()));
+    let descendant = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "CIDFontType2",
+        "BaseFont" => "Embedded",
+        "CIDSystemInfo" => dictionary! {
+            "Registry" => Object::string_literal("Adobe"),
+            "Ordering" => Object::string_literal("Identity"),
+            "Supplement" => 0,
+        },
+    });
+    let font = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => "Embedded",
+        "Encoding" => "Identity-H",
+        "DescendantFonts" => vec![descendant.into()],
+        "ToUnicode" => to_unicode,
+    });
+    // Hex glyph strings the plain parser can't read, with a word gap between them
+    let content = b"BT /F1 12 Tf 72 700 Td [<000100020003000300040005> -250 <000600070008>] TJ ET";
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content.to_vec()));
+    let page = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        "Contents" => content_id,
+        "Resources" => dictionary! { "Font" => dictionary! { "F1" => font } },
+    });
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![page.into()],
+        "Count" => 1,
+    }));
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    doc.save(&pdf_path).unwrap();
+
+    let mut handler = PdfHandler::new();
+    handler.read_from_file(&pdf_path).unwrap();
+    let content = handler.get_content().unwrap();
+    assert_eq!(content.lines().next().unwrap().trim(), "Hello, CID");
+    assert_eq!(handler.get_page_text(1).unwrap().trim(), "Hello, CID");
+}
+
+#[test]
+fn test_pdf_two_columns_in_reading_order() {
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("columns.pdf");
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    // The right column is drawn first and the lines of both columns interleave in the stream
+    let content = b"BT /F1 12 Tf 1 0 0 1 320 700 Tm (Right one.) Tj ET
+BT /F1 12 Tf 1 0 0 1 72 700 Tm (Left one.) Tj 0 -14 Td (Left two.) Tj ET
+q 1 0 0 1 320 686 cm BT /F1 12 Tf (Right two.) Tj 0 -14 Td (Right three.) Tj ET Q
+BT /F1 12 Tf 1 0 0 1 72 672 Tm (Left three.) Tj ET";
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content.to_vec()));
+    let page = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        "Contents" => content_id,
+        "Resources" => dictionary! { "Font" => dictionary! { "F1" => font } },
+    });
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![page.into()],
+        "Count" => 1,
+    }));
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    doc.save(&pdf_path).unwrap();
+
+    let mut handler = PdfHandler::new();
+    handler.read_from_file(&pdf_path).unwrap();
+    let lines: Vec<String> = handler.get_page_text(1).unwrap().lines().map(|line| line.trim().to_string()).collect();
+    assert_eq!(lines, ["Left one.", "Left two.", "Left three.", "Right one.", "Right two.", "Right three."]);
+}
+
+#[test]
+fn test_pdf_figure_markers_where_images_are_drawn() {
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    let temp_dir = TempDir::new().unwrap();
+    let pdf_path = temp_dir.path().join("figure.pdf");
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+    let image = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => 4,
+            "Height" => 2,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 8,
+        },
+        vec![0; 8],
+    ));
+    let mut page = |content: &[u8]| {
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.to_vec()));
+        doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! {
+                "Font" => dictionary! { "F1" => font },
+                "XObject" => dictionary! { "Im1" => image },
+            },
+        })
+    };
+    let first = page(b"BT /F1 12 Tf 1 0 0 1 72 700 Tm (Text only.) Tj ET");
+    let second = page(b"BT /F1 12 Tf 1 0 0 1 72 700 Tm (Above the chart.) Tj ET
+q 200 0 0 100 72 560 cm /Im1 Do Q
+BT /F1 12 Tf 1 0 0 1 72 540 Tm (Below the chart.) Tj ET");
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![first.into(), second.into()],
+        "Count" => 2,
+    }));
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    doc.save(&pdf_path).unwrap();
+
+    let mut plain = PdfHandler::new();
+    plain.read_from_file(&pdf_path).unwrap();
+    assert!(!plain.get_content().unwrap().contains("[Figure"));
+
+    let mut handler = PdfHandler::new();
+    handler.set_figure_markers(true);
+    handler.read_from_file(&pdf_path).unwrap();
+    let lines: Vec<String> = handler.get_page_text(2).unwrap().lines().map(|line| line.trim().to_string()).collect();
+    assert_eq!(lines, ["Above the chart.", "[Figure: page 2, 4x2]", "Below the chart."]);
+    assert!(!handler.get_page_text(1).unwrap().contains("[Figure"));
+
+    let content = handler.get_content().unwrap();
+    let marker = content.find("[Figure: page 2, 4x2]").unwrap();
+    assert!(content.find("Text only.").unwrap() < marker && marker < content.find("Below the chart.").unwrap());
+}
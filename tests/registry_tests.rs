@@ -0,0 +1,196 @@
+use ebook_cli::traits::{EbookOperator, EbookReader, EbookWriter, ImageData, TocEntry};
+use ebook_cli::{EbookError, FormatRegistry, Metadata, Result};
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Minimal handler for a made-up `.note` format: first line is the title, the rest is content
+#[derive(Default)]
+struct NoteHandler {
+    metadata: Metadata,
+    content: String,
+}
+
+impl EbookReader for NoteHandler {
+    fn read_from_file(&mut self, path: &Path) -> Result<()> {
+        let text = std::fs::read_to_string(path)?;
+        let (title, body) = text.split_once('\n').unwrap_or((&text, ""));
+        self.metadata = Metadata::new().with_title(title).with_format("NOTE");
+        self.content = body.to_string();
+        Ok(())
+    }
+
+    fn get_metadata(&self) -> Result<Metadata> {
+        Ok(self.metadata.clone())
+    }
+
+    fn get_content(&self) -> Result<String> {
+        Ok(self.content.clone())
+    }
+
+    fn get_toc(&self) -> Result<Vec<TocEntry>> {
+        Ok(Vec::new())
+    }
+
+    fn extract_images(&self) -> Result<Vec<ImageData>> {
+        Ok(Vec::new())
+    }
+}
+
+impl EbookWriter for NoteHandler {
+    fn set_metadata(&mut self, metadata: Metadata) -> Result<()> {
+        self.metadata = metadata;
+        Ok(())
+    }
+
+    fn set_content(&mut self, content: &str) -> Result<()> {
+        self.content = content.to_string();
+        Ok(())
+    }
+
+    fn add_chapter(&mut self, _title: &str, content: &str) -> Result<()> {
+        self.content.push_str(content);
+        Ok(())
+    }
+
+    fn add_image(&mut self, _name: &str, _data: Vec<u8>) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_to_file(&self, path: &Path) -> Result<()> {
+        let title = self.metadata.title.as_deref().unwrap_or("Untitled");
+        std::fs::write(path, format!("{title}\n{}", self.content))?;
+        Ok(())
+    }
+}
+
+impl EbookOperator for NoteHandler {
+    fn convert_to(&self, _target_format: &str, _output_path: &Path) -> Result<()> {
+        Err(EbookError::NotSupported("Conversion not yet implemented".to_string()))
+    }
+
+    fn validate(&self) -> Result<bool> {
+        Ok(self.metadata.title.is_some())
+    }
+
+    fn repair(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_registry_builtin_formats() {
+    let registry = FormatRegistry::default();
+    for format in ["epub", "mobi", "azw", "fb2", "cbz", "txt", "pdf"] {
+        assert!(registry.contains(format), "{format} should be registered");
+        assert!(registry.create(format).is_ok());
+    }
+    assert!(registry.create("note").is_err());
+}
+
+#[test]
+fn test_registry_dispatches_custom_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let note_path = temp_dir.path().join("shopping.note");
+    std::fs::write(&note_path, "Shopping List\nmilk\neggs").unwrap();
+
+    let mut registry = FormatRegistry::default();
+    assert!(registry.open(&note_path).is_err());
+
+    registry.register("note", || Box::new(NoteHandler::default()));
+    let handler = registry.open(&note_path).unwrap();
+
+    assert_eq!(registry.format_for_path(&note_path).unwrap(), "note");
+    assert_eq!(handler.get_metadata().unwrap().title, Some("Shopping List".to_string()));
+    assert_eq!(handler.get_content().unwrap(), "milk\neggs");
+    assert!(handler.validate().unwrap());
+}
+
+#[test]
+fn test_boxed_read_validate_repair_for_every_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let registry = FormatRegistry::default();
+
+    let mut page = Vec::new();
+    image::DynamicImage::new_rgb8(4, 4)
+        .write_to(&mut std::io::Cursor::new(&mut page), image::ImageFormat::Png)
+        .unwrap();
+
+    for format in ["epub", "mobi", "azw", "fb2", "cbz", "txt", "pdf"] {
+        let path = temp_dir.path().join(format!("sample.{format}"));
+        let repaired_path = temp_dir.path().join(format!("repaired.{format}"));
+
+        let mut writer = registry.create(format).unwrap();
+        writer.set_metadata(Metadata::new().with_title("Boxed Book")).unwrap();
+        writer.set_content("Some boxed content.").unwrap();
+        writer.add_chapter("Chapter 1", "<p>Some boxed content.</p>").unwrap();
+        writer.add_image("page1.png", page.clone()).unwrap();
+        writer.write_to_file(&path).unwrap();
+
+        let mut handler = registry.open(&path)
+            .unwrap_or_else(|e| panic!("{format} should be readable through the registry: {e}"));
+        assert!(handler.validate().unwrap(), "{format} should validate");
+        handler.repair().unwrap();
+        handler.write_to_file(&repaired_path).unwrap();
+        assert!(registry.open(&repaired_path).is_ok(), "repaired {format} should be readable");
+    }
+}
+
+#[test]
+fn test_capability_flags_match_handler_behavior() {
+    let temp_dir = TempDir::new().unwrap();
+    let registry = FormatRegistry::default();
+
+    let mut picture = Vec::new();
+    image::DynamicImage::new_rgb8(64, 64)
+        .write_to(&mut std::io::Cursor::new(&mut picture), image::ImageFormat::Png)
+        .unwrap();
+
+    for &(format, capabilities) in ebook_cli::registry::FORMAT_CAPABILITIES {
+        let path = temp_dir.path().join(format!("sample.{format}"));
+        let mut writer = registry.create(format).unwrap();
+        writer.set_metadata(Metadata::new().with_title("Capabilities")).unwrap();
+        writer.set_content("Capability check text.").unwrap();
+        writer.add_image("images/picture.png", picture.clone()).unwrap();
+        writer.write_to_file(&path).unwrap();
+
+        let handler = registry.open(&path);
+        assert_eq!(handler.is_ok(), capabilities.read, "{format}: read");
+        let handler = handler.unwrap();
+        let written = handler.get_content().unwrap().contains("Capability check text.");
+        assert_eq!(written, capabilities.write, "{format}: write");
+        let images = handler.extract_images().unwrap();
+        assert_eq!(!images.is_empty(), capabilities.extract_images, "{format}: extract_images");
+
+        let optimized = assert_cmd::cargo::cargo_bin_cmd!("ebook")
+            .args(["optimize", "--force", "--output"])
+            .arg(temp_dir.path().join(format!("optimized.{format}")))
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert_eq!(optimized.status.success(), capabilities.optimize, "{format}: optimize");
+    }
+}
+
+#[test]
+fn test_capability_matrix_marks_optimize_support() {
+    let matrix = ebook_cli::registry::capability_matrix();
+    let row = |format: &str| matrix.iter().find(|row| row.format == format).unwrap().clone();
+    assert!(row("cbz").optimize);
+    assert!(!row("pdf").optimize);
+    assert!(!row("cbz").write);
+    assert_eq!(row("cbz").convert_to, vec!["epub"]);
+    assert!(row("epub").convert_from.contains(&"txt"));
+
+    let output = assert_cmd::cargo::cargo_bin_cmd!("ebook").args(["formats", "--json"]).output().unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let optimize = |format: &str| json.as_array().unwrap().iter().find(|row| row["format"] == format).unwrap()["optimize"].clone();
+    assert_eq!(optimize("cbz"), true);
+    assert_eq!(optimize("pdf"), false);
+
+    let output = assert_cmd::cargo::cargo_bin_cmd!("ebook").arg("formats").output().unwrap();
+    let grid = String::from_utf8(output.stdout).unwrap();
+    assert!(grid.starts_with("Format"), "{grid}");
+    let pdf = grid.lines().find(|line| line.starts_with("pdf ")).unwrap();
+    assert_eq!(pdf.split_whitespace().nth(4), Some("no"), "{pdf}");
+}
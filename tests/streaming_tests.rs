@@ -20,7 +20,7 @@ fn test_txt_streaming_read() {
     
     // Read using streaming
     let mut handler = TxtHandler::new();
-    handler.read_from_file_streaming(&txt_path).unwrap();
+    handler.read_from_file_streaming(&txt_path, None).unwrap();
     
     let content = handler.get_content().unwrap();
     assert!(content.len() > 10_000_000); // Should be > 10 MB
@@ -58,7 +58,7 @@ fn test_txt_streaming_threshold() {
     drop(file);
     
     let mut handler = TxtHandler::new();
-    handler.read_from_file_streaming(&small_path).unwrap();
+    handler.read_from_file_streaming(&small_path, None).unwrap();
     
     let content = handler.get_content().unwrap();
     assert!(content.contains("Small content"));
@@ -95,7 +95,7 @@ fn test_streaming_preserves_content() {
     
     // Read with streaming
     let mut reader = TxtHandler::new();
-    reader.read_from_file_streaming(&txt_path).unwrap();
+    reader.read_from_file_streaming(&txt_path, None).unwrap();
     
     let read_content = reader.get_content().unwrap();
     assert_eq!(read_content.trim(), original_content.trim());
@@ -113,7 +113,7 @@ fn test_streaming_metadata_preservation() {
     handler.write_to_file_streaming(&txt_path).unwrap();
     
     let mut reader = TxtHandler::new();
-    reader.read_from_file_streaming(&txt_path).unwrap();
+    reader.read_from_file_streaming(&txt_path, None).unwrap();
     
     let metadata = reader.get_metadata().unwrap();
     assert_eq!(metadata.title, Some("metadata".to_string())); // Filename-based
@@ -144,10 +144,43 @@ fn test_streaming_unicode_content() {
     writer.write_to_file_streaming(&txt_path).unwrap();
     
     let mut reader = TxtHandler::new();
-    reader.read_from_file_streaming(&txt_path).unwrap();
+    reader.read_from_file_streaming(&txt_path, None).unwrap();
     
     let content = reader.get_content().unwrap();
     assert!(content.contains("世界"));
     assert!(content.contains("🌍"));
     assert!(content.contains("Привет"));
 }
+
+#[test]
+fn test_streaming_read_reports_progress_to_completion() {
+    use ebook_cli::ProgressHandler;
+    use std::sync::{Arc, Mutex};
+
+    let temp_dir = TempDir::new().unwrap();
+    let txt_path = temp_dir.path().join("progress.txt");
+
+    // Just over the 10 MB streaming threshold
+    let mut file = File::create(&txt_path).unwrap();
+    let line = "All work and no play makes Jack a dull boy. ".repeat(20);
+    while file.metadata().unwrap().len() < 11 * 1024 * 1024 {
+        writeln!(file, "{line}").unwrap();
+    }
+    drop(file);
+    let file_size = std::fs::metadata(&txt_path).unwrap().len() as usize;
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&reports);
+    let progress = ProgressHandler::with_callback(Box::new(move |current, total| {
+        recorded.lock().unwrap().push((current, total));
+    }));
+
+    let mut handler = TxtHandler::new();
+    handler.read_from_file_streaming(&txt_path, Some(&progress)).unwrap();
+
+    let reports = reports.lock().unwrap();
+    assert!(reports.len() >= 50, "only {} reports", reports.len());
+    assert!(reports.iter().all(|&(_, total)| total == file_size));
+    assert!(reports.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    assert_eq!(reports.last(), Some(&(file_size, file_size)));
+}
@@ -68,3 +68,46 @@ fn test_trait_streaming_helpers_are_concurrency_safe() {
         }
     });
 }
+
+/// Writer that creates its output file and then panics, remembering where it was asked to write
+#[derive(Default)]
+struct PanickingWriter {
+    written_to: std::sync::Mutex<Option<std::path::PathBuf>>,
+}
+
+impl EbookWriter for PanickingWriter {
+    fn set_metadata(&mut self, _metadata: ebook_cli::Metadata) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+
+    fn set_content(&mut self, _content: &str) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+
+    fn add_chapter(&mut self, _title: &str, _content: &str) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+
+    fn add_image(&mut self, _name: &str, _data: Vec<u8>) -> ebook_cli::Result<()> {
+        Ok(())
+    }
+
+    fn write_to_file(&self, path: &std::path::Path) -> ebook_cli::Result<()> {
+        std::fs::write(path, b"partial").unwrap();
+        *self.written_to.lock().unwrap() = Some(path.to_path_buf());
+        panic!("writer failed halfway");
+    }
+}
+
+#[test]
+fn test_trait_write_to_writer_removes_temp_file_on_panic() {
+    let writer = PanickingWriter::default();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        writer.write_to_writer(Vec::<u8>::new())
+    }));
+    assert!(result.is_err());
+
+    let temp_path = writer.written_to.lock().unwrap().clone().unwrap();
+    assert!(temp_path.starts_with(ebook_cli::traits::temp_dir()));
+    assert!(!temp_path.exists(), "temp file {temp_path:?} was left behind");
+}
@@ -86,3 +86,137 @@ fn test_txt_toc_detection() {
     assert_eq!(toc[0].title, "Chapter 1");
     assert_eq!(toc[1].title, "Chapter 2");
 }
+
+#[test]
+fn test_txt_repair_infers_language_and_date() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file
+        .write_all(b"It was the best of times, and it was the worst of times. The city was quiet and the river was cold.")
+        .unwrap();
+
+    let mut handler = TxtHandler::new();
+    handler.read_from_file(temp_file.path()).unwrap();
+    assert_eq!(handler.get_metadata().unwrap().language, None);
+
+    handler.repair().unwrap();
+    let repaired = handler.get_metadata().unwrap();
+    assert_eq!(repaired.language, Some("en".to_string()));
+    assert_eq!(repaired.publication_date.as_ref().map(String::len), Some("2024-01-01".len()));
+
+    // A second repair keeps what the first one set
+    handler.repair().unwrap();
+    let again = handler.get_metadata().unwrap();
+    assert_eq!(again.language, repaired.language);
+    assert_eq!(again.publication_date, repaired.publication_date);
+}
+
+#[test]
+fn test_txt_read_lines_window_from_large_file() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    {
+        let mut writer = std::io::BufWriter::new(temp_file.as_file_mut());
+        for i in 1..=2_000_000 {
+            writeln!(writer, "line {i}").unwrap();
+        }
+    }
+
+    // Lines 1,000,000 to 1,000,100 (1-based) are indices 999,999.. (0-based)
+    let window = TxtHandler::read_lines(temp_file.path(), 999_999, 101).unwrap();
+    let lines: Vec<&str> = window.lines().collect();
+    assert_eq!(lines.len(), 101);
+    assert_eq!(lines[0], "line 1000000");
+    assert_eq!(lines[100], "line 1000100");
+    // Only the window is returned, not the 20+ MB file
+    assert!(window.len() < 2_000);
+
+    let tail = TxtHandler::read_lines(temp_file.path(), 1_999_998, 10).unwrap();
+    assert_eq!(tail, "line 1999999\nline 2000000\n");
+    assert_eq!(TxtHandler::read_lines(temp_file.path(), 5_000_000, 10).unwrap(), "");
+}
+
+#[test]
+fn test_txt_read_lines_from_utf16_file() {
+    use ebook_cli::formats::TextEncoding;
+
+    // U+090A is 0A 09 in UTF-16LE: a newline byte that is not a newline
+    let text = "first \u{90A} line\nsecond line\nthird line\n";
+    let mut with_bom = NamedTempFile::new().unwrap();
+    with_bom.write_all(&TextEncoding::Utf16Le.encode(text)).unwrap();
+    let mut big_endian = NamedTempFile::new().unwrap();
+    big_endian.write_all(&text.encode_utf16().flat_map(u16::to_be_bytes).collect::<Vec<u8>>()).unwrap();
+
+    for file in [&with_bom, &big_endian] {
+        assert_eq!(TxtHandler::read_lines(file.path(), 0, 1).unwrap(), "first \u{90A} line\n");
+        assert_eq!(TxtHandler::read_lines(file.path(), 1, 5).unwrap(), "second line\nthird line\n");
+        assert_eq!(TxtHandler::read_lines(file.path(), 3, 1).unwrap(), "");
+    }
+}
+
+#[test]
+fn test_txt_get_chapters_splits_at_headings() {
+    let mut handler = TxtHandler::new();
+    handler.set_content("Chapter 1\nIt begins.\nChapter 2\nIt ends.\n").unwrap();
+    let chapters = handler.get_chapters().unwrap();
+    let titles: Vec<&str> = chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+    assert_eq!(titles, ["Chapter 1", "Chapter 2"]);
+    assert_eq!(chapters[1].content, "Chapter 2\nIt ends.\n");
+    assert!(chapters.iter().all(|chapter| chapter.href.is_none()));
+
+    handler.set_metadata(Metadata::new().with_title("No Headings")).unwrap();
+    handler.set_content("Just one stretch of text.").unwrap();
+    let chapters = handler.get_chapters().unwrap();
+    assert_eq!(chapters.len(), 1);
+    assert_eq!(chapters[0].title, "No Headings");
+    assert_eq!(chapters[0].content, "Just one stretch of text.");
+}
+
+#[test]
+fn test_txt_output_encodings_round_trip() {
+    use ebook_cli::formats::TextEncoding;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let text = "Café – 東京\nSecond line ✓\n";
+    let write = |name: &str, encoding: TextEncoding| {
+        let path = dir.path().join(name);
+        let mut writer = TxtHandler::new();
+        writer.set_output_encoding(encoding);
+        writer.set_content(text).unwrap();
+        writer.write_to_file(&path).unwrap();
+        path
+    };
+    let read_back = |path: &std::path::Path| {
+        let mut reader = TxtHandler::new();
+        reader.read_from_file(path).unwrap();
+        reader.get_content().unwrap()
+    };
+
+    let utf16 = write("utf16.txt", TextEncoding::Utf16Le);
+    let bytes = std::fs::read(&utf16).unwrap();
+    assert_eq!(&bytes[..4], &[0xFF, 0xFE, b'C', 0]);
+    assert_eq!(bytes.len(), 2 + text.encode_utf16().count() * 2);
+    assert_eq!(read_back(&utf16), text);
+
+    // Without the byte order mark the zero bytes give UTF-16 away
+    let bomless = dir.path().join("bomless.txt");
+    std::fs::write(&bomless, &bytes[2..]).unwrap();
+    assert_eq!(read_back(&bomless), text);
+
+    let utf8_bom = write("utf8bom.txt", TextEncoding::Utf8Bom);
+    assert!(std::fs::read(&utf8_bom).unwrap().starts_with(b"\xEF\xBB\xBFCaf"));
+    assert_eq!(read_back(&utf8_bom), text);
+
+    let plain = write("plain.txt", TextEncoding::default());
+    assert_eq!(std::fs::read(&plain).unwrap(), text.as_bytes());
+
+    let latin: TextEncoding = "windows-1252".parse().unwrap();
+    let path = dir.path().join("latin.txt");
+    let mut writer = TxtHandler::new();
+    writer.set_output_encoding(latin);
+    writer.set_content("Café crème").unwrap();
+    writer.write_to_file(&path).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"Caf\xe9 cr\xe8me");
+    assert_eq!(read_back(&path), "Café crème");
+
+    assert_eq!("UTF-16LE".parse::<TextEncoding>(), Ok(TextEncoding::Utf16Le));
+    assert!("utf-16be".parse::<TextEncoding>().is_err());
+}
@@ -0,0 +1,35 @@
+use ebook_cli::watch::watch_file;
+use std::ops::ControlFlow;
+use std::sync::mpsc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[test]
+fn test_watch_reruns_after_modification() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("manuscript.txt");
+    std::fs::write(&path, "Draft one").unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let watched = path.clone();
+    std::thread::spawn(move || {
+        watch_file(&watched, Duration::from_millis(50), || {
+            tx.send(std::fs::read_to_string(&watched).unwrap()).unwrap();
+            ControlFlow::Break(())
+        })
+    });
+
+    // Keep saving until the watcher (which starts asynchronously) reports a run
+    let mut rerun = None;
+    for revision in 2..40 {
+        std::fs::write(&path, format!("Draft {revision}")).unwrap();
+        if let Ok(content) = rx.recv_timeout(Duration::from_millis(250)) {
+            rerun = Some(content);
+            break;
+        }
+    }
+
+    let content = rerun.expect("watcher should re-run after the file changes");
+    assert!(content.starts_with("Draft "));
+    assert_ne!(content, "Draft one");
+}